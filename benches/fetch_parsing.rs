@@ -0,0 +1,120 @@
+//! Compares the ways of parsing a big page of fetched items, answered by a `MockTransport`.
+//!
+//! Run with `cargo bench --bench fetch_parsing --features test-util`.
+
+use deta_rust::database::{Database, FetchOptions};
+use deta_rust::testing::MockTransport;
+use deta_rust::DetaClient;
+use serde::Deserialize;
+use serde_json::value::RawValue;
+use serde_json::{json, Value};
+use std::borrow::Cow;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const ITEMS: usize = 5_000;
+const ROUNDS: u32 = 20;
+
+#[derive(Deserialize)]
+#[allow(dead_code)]
+struct Owned {
+    key: String,
+    title: String,
+    body: String,
+    tags: Vec<String>,
+    views: u64,
+}
+
+#[derive(Deserialize)]
+#[allow(dead_code)]
+struct Borrowed<'a> {
+    #[serde(borrow)]
+    key: Cow<'a, str>,
+    #[serde(borrow)]
+    title: Cow<'a, str>,
+    views: u64,
+}
+
+fn page() -> Value {
+    let items: Vec<Value> = (0..ITEMS)
+        .map(|index| {
+            json!({
+                "key": format!("item_{:05}", index),
+                "title": format!("Title of the item number {}", index),
+                "body": "Lorem ipsum dolor sit amet, consectetur adipiscing elit. ".repeat(8),
+                "tags": ["news", "rust", "deta"],
+                "views": index * 7,
+            })
+        })
+        .collect();
+    json!({ "paging": { "size": ITEMS }, "items": items })
+}
+
+// Returns the average time of a round.
+async fn measure<F, Fut>(mut round: F) -> Duration
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = usize>,
+{
+    assert_eq!(round().await, ITEMS);
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        assert_eq!(round().await, ITEMS);
+    }
+    start.elapsed() / ROUNDS
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    let transport = MockTransport::new();
+    transport.respond("POST", "/query", 200, page());
+    let database = Database::new(&DetaClient::new("project_key"), "bench")
+        .unwrap()
+        .with_transport(Arc::new(transport));
+    let database = &database;
+
+    let results = [
+        (
+            "fetch_items::<Value>",
+            measure(|| async {
+                let page = database.fetch_items::<Value>(None, None, None).await;
+                page.unwrap().len()
+            })
+            .await,
+        ),
+        (
+            "fetch_items::<Owned>",
+            measure(|| async {
+                let page = database.fetch_items::<Owned>(None, None, None).await;
+                page.unwrap().len()
+            })
+            .await,
+        ),
+        (
+            "fetch_items_borrowed + raw_items",
+            measure(|| async {
+                let page = database.fetch_items_borrowed(FetchOptions::default()).await;
+                let page = page.unwrap();
+                let items: Vec<&RawValue> = page.raw_items().unwrap().items;
+                items.len()
+            })
+            .await,
+        ),
+        (
+            "fetch_items_borrowed + items::<Borrowed>",
+            measure(|| async {
+                let page = database.fetch_items_borrowed(FetchOptions::default()).await;
+                let page = page.unwrap();
+                let items = page.items::<Borrowed>().unwrap();
+                items.len()
+            })
+            .await,
+        ),
+    ];
+
+    println!("Parsing a page of {} items, {} rounds:", ITEMS, ROUNDS);
+    for (name, time) in results {
+        println!("{:<42} {:>10.2?} per page", name, time);
+    }
+}
@@ -0,0 +1,62 @@
+//! This example illustrates the way to serve the files of a drive from an `actix-web` app,
+//! streamed to the client without keeping them whole in memory.
+//!
+//! Run it with `cargo run --example actix_files --features actix`, then e.g.:
+//!
+//! `curl -i http://127.0.0.1:3000/avatars/user_1.png`
+//! `curl -i -H "Range: bytes=0-99" http://127.0.0.1:3000/avatars/user_1.png`
+//! `curl -OJ http://127.0.0.1:3000/reports/2024-05.pdf`
+
+// This is using `actix-web`. You'll need the following dependency:
+//
+// `actix-web = "4"`
+
+use actix_web::{web, App, HttpRequest, HttpServer};
+use deta_rust::drive::actix::DriveFile;
+use deta_rust::prelude::*;
+
+struct Drives {
+    avatars: Drive,
+    reports: Drive,
+}
+
+// Passes the `Range` header on, so that browsers can resume the download.
+async fn get_avatar(
+    drives: web::Data<Drives>,
+    name: web::Path<String>,
+    request: HttpRequest,
+) -> Result<DriveFile> {
+    DriveFile::fetch_range(&drives.avatars, &name, &request).await
+}
+
+// The error is turned into a response without details, so it's logged here.
+async fn download_report(drives: web::Data<Drives>, name: web::Path<String>) -> Result<DriveFile> {
+    match DriveFile::fetch(&drives.reports, &name).await {
+        Ok(file) => Ok(file.attachment()),
+        Err(error) => {
+            eprintln!("Failed to serve the report '{}': {}", name, error);
+            Err(error)
+        }
+    }
+}
+
+#[actix_web::main]
+async fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let client = DetaClient::new("[place_your_project_key_here]");
+    let drives = web::Data::new(Drives {
+        avatars: Drive::new(&client, "avatars")?,
+        reports: Drive::new(&client, "reports")?,
+    });
+
+    HttpServer::new(move || {
+        App::new()
+            .app_data(drives.clone())
+            .route("/avatars/{name}", web::get().to(get_avatar))
+            .route("/reports/{name}", web::get().to(download_report))
+    })
+    .bind("127.0.0.1:3000")?
+    .run()
+    .await?;
+
+    Ok(())
+}
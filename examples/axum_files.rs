@@ -0,0 +1,61 @@
+//! This example illustrates the way to serve the files of a drive from an `axum` service,
+//! streamed to the client without keeping them whole in memory.
+//!
+//! Run it with `cargo run --example axum_files --features axum`, then e.g.:
+//!
+//! `curl -i http://127.0.0.1:3000/avatars/user_1.png`
+//! `curl -i -H "Range: bytes=0-99" http://127.0.0.1:3000/avatars/user_1.png`
+
+// This is using the `tokio` runtime and `axum`. You'll need the following dependencies:
+//
+// `tokio = { version = "1", features = ["full"] }`
+// `axum = "0.6"`
+
+use axum::{
+    extract::{Path, State},
+    http::HeaderMap,
+    response::Response,
+    routing::get,
+    Router,
+};
+use deta_rust::drive::axum::{serve_file, serve_file_range};
+use deta_rust::prelude::*;
+use std::sync::Arc;
+
+type Avatars = Arc<Drive>;
+
+// Passes the `Range` header on, so that browsers can resume the download.
+async fn get_avatar(
+    State(avatars): State<Avatars>,
+    Path(name): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    serve_file_range(&avatars, &name, &headers).await
+}
+
+// The error is turned into a response without details, so it's logged here.
+async fn get_default_avatar(State(avatars): State<Avatars>) -> Result<Response> {
+    serve_file(&avatars, "default.png").await.map_err(|error| {
+        eprintln!("Failed to serve the default avatar: {}", error);
+        error
+    })
+}
+
+fn app(avatars: Avatars) -> Router {
+    Router::new()
+        .route("/avatars/default", get(get_default_avatar))
+        .route("/avatars/:name", get(get_avatar))
+        .with_state(avatars)
+}
+
+#[tokio::main]
+async fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let client = DetaClient::new("[place_your_project_key_here]");
+    let avatars = Arc::new(Drive::new(&client, "avatars")?);
+
+    axum::Server::bind(&"127.0.0.1:3000".parse()?)
+        .serve(app(avatars).into_make_service())
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,64 @@
+//! This example illustrates the way to inject the database into `axum` handlers as `Arc<dyn DatabaseOps>`,
+//! so the handlers can be tested with `FakeBase` or wrapped in a decorator without changing them.
+
+// This is using the `tokio` runtime and `axum`. You'll need the following dependencies:
+//
+// `tokio = { version = "1", features = ["full"] }`
+// `axum = "0.6"`
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::{get, put},
+    Json, Router,
+};
+use deta_rust::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+#[derive(Serialize, Deserialize, Debug)]
+struct User {
+    key: String,
+    name: String,
+}
+
+// The handlers only know the trait, not the concrete database.
+type Users = Arc<dyn DatabaseOps>;
+
+async fn get_user(
+    State(users): State<Users>,
+    Path(key): Path<String>,
+) -> Result<Json<User>, StatusCode> {
+    match users.get_item::<User>(&key).await {
+        Ok(Some(user)) => Ok(Json(user)),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(_) => Err(StatusCode::BAD_GATEWAY),
+    }
+}
+
+async fn put_user(State(users): State<Users>, Json(user): Json<User>) -> StatusCode {
+    match users.put_items(&[user]).await {
+        Ok(result) if !result.has_failures() => StatusCode::NO_CONTENT,
+        _ => StatusCode::BAD_GATEWAY,
+    }
+}
+
+fn app(users: Users) -> Router {
+    Router::new()
+        .route("/users", put(put_user))
+        .route("/users/:key", get(get_user))
+        .with_state(users)
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let client = DetaClient::new("[place_your_project_key_here]");
+    // In tests, pass `Arc::new(deta_rust::testing::FakeBase::new())` instead (`test-util` feature).
+    let users: Users = Arc::new(Database::new(&client, "users")?);
+
+    axum::Server::bind(&"127.0.0.1:3000".parse()?)
+        .serve(app(users).into_make_service())
+        .await?;
+
+    Ok(())
+}
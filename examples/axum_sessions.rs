@@ -0,0 +1,80 @@
+//! This example illustrates the way to keep the sessions of an `axum` application in a base,
+//! with `DetaSessionStore` passed to the `SessionManagerLayer` of `tower-sessions`.
+
+// This is using the `tokio` runtime, `axum` and `tower-sessions`. You'll need the following dependencies:
+//
+// `tokio = { version = "1", features = ["full"] }`
+// `axum = "0.8"`
+// `tower-sessions = "0.14"`
+//
+// This crate's `axum` feature is built for `axum` 0.6, so here `axum` 0.8 is renamed to `axum_sessions`.
+
+use axum_sessions::{
+    http::StatusCode,
+    response::{IntoResponse, Redirect},
+    routing::{get, post},
+    Form, Router,
+};
+use deta_rust::prelude::*;
+use deta_rust::sessions::DetaSessionStore;
+use serde::Deserialize;
+use tower_sessions::{cookie::time::Duration, Expiry, Session, SessionManagerLayer};
+
+const USER_KEY: &str = "user";
+
+#[derive(Deserialize)]
+struct Login {
+    name: String,
+}
+
+async fn home(session: Session) -> Result<String, StatusCode> {
+    let user: Option<String> = session
+        .get(USER_KEY)
+        .await
+        .map_err(|_| StatusCode::BAD_GATEWAY)?;
+    Ok(match user {
+        Some(name) => format!("Hello, {}!", name),
+        None => "Hello, stranger! POST your name to /login.".to_owned(),
+    })
+}
+
+async fn login(session: Session, Form(login): Form<Login>) -> impl IntoResponse {
+    // A new id for the logged in user, so a session id known before the login is useless.
+    session
+        .cycle_id()
+        .await
+        .map_err(|_| StatusCode::BAD_GATEWAY)?;
+    session
+        .insert(USER_KEY, login.name)
+        .await
+        .map_err(|_| StatusCode::BAD_GATEWAY)?;
+    Ok::<_, StatusCode>(Redirect::to("/"))
+}
+
+async fn logout(session: Session) -> impl IntoResponse {
+    // Deletes the session item and the cookie.
+    session.flush().await.map_err(|_| StatusCode::BAD_GATEWAY)?;
+    Ok::<_, StatusCode>(Redirect::to("/"))
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let client = DetaClient::new("[place_your_project_key_here]");
+    // In tests, pass `deta_rust::testing::FakeBase::new()` instead (`test-util` feature).
+    let store = DetaSessionStore::new(Database::new(&client, "sessions")?);
+    // The session items expire after a week without requests, deta deletes them then.
+    let sessions = SessionManagerLayer::new(store)
+        .with_secure(false)
+        .with_expiry(Expiry::OnInactivity(Duration::weeks(1)));
+
+    let app = Router::new()
+        .route("/", get(home))
+        .route("/login", post(login))
+        .route("/logout", post(logout))
+        .layer(sessions);
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:3000").await?;
+    axum_sessions::serve(listener, app).await?;
+
+    Ok(())
+}
@@ -4,14 +4,7 @@
 //
 // `tokio = { version = "1", features = ["full"] }`
 
-use deta_rust::{
-    database::{
-        self,
-        query::{Condition, Query},
-        updates::{Action, Updates},
-    },
-    DetaClient,
-};
+use deta_rust::prelude::*;
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -27,7 +20,7 @@ struct SampleDbModel {
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let client = DetaClient::new("[place_your_project_key_here]");
-    let database = database::Database::new(&client, "sample_db");
+    let database = Database::new(&client, "sample_db")?;
 
     // Put
     let items = vec![
@@ -4,14 +4,14 @@
 //
 // `tokio = { version = "1", features = ["full"] }`
 
-use deta_rust::{drive, DetaClient};
+use deta_rust::prelude::*;
 use tokio::io::AsyncWriteExt;
 use tokio::{fs::File, io::AsyncReadExt};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let client = DetaClient::new("[place_your_project_key_here]");
-    let drive = drive::Drive::new(&client, "sample_drive");
+    let drive = Drive::new(&client, "sample_drive")?;
 
     // Upload file
     let mut file = File::open("some_file.jpg").await?;
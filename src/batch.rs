@@ -0,0 +1,994 @@
+//! Operations on several bases and drives, undone on failure.
+//!
+//! Deta has no transactions, so a [`Batch`](Batch) is best-effort: its steps run in order,
+//! and when one of them fails, the ones which already ran are compensated in reverse order,
+//! e.g. an inserted item is deleted and an overwritten one is put back.
+//! Other clients may see the intermediate state, and may change the data in the meantime,
+//! in which case the compensation overwrites their changes.
+//!
+//! The steps work with any [`DatabaseOps`](crate::ops::DatabaseOps) and [`DriveOps`](crate::ops::DriveOps)
+//! implementation, including [`Database`](crate::database::Database) and [`Drive`](crate::drive::Drive).
+//!
+#![cfg_attr(all(feature = "base", feature = "drive"), doc = "```no_run")]
+#![cfg_attr(not(all(feature = "base", feature = "drive")), doc = "```ignore")]
+//! use deta_rust::{batch::Batch, database::Database, drive::Drive, DetaClient};
+//! use serde_json::json;
+//!
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() -> deta_rust::error::Result<()> {
+//! let client = DetaClient::new("project_key");
+//! let users = Database::new(&client, "users")?;
+//! let orders = Database::new(&client, "orders")?;
+//! let invoices = Drive::new(&client, "invoices")?;
+//!
+//! let outcome = Batch::new()
+//!     .put_item(&users, &json!({ "key": "user_1", "orders": 1 }))
+//!     .insert_item(&orders, &json!({ "key": "order_1", "user": "user_1" }))
+//!     .put_file(&invoices, "order_1.txt", "Total: 10", Some("text/plain"))
+//!     .execute()
+//!     .await;
+//!
+//! if let Some(step) = outcome.failed_step() {
+//!     println!("Failed to {}", step.description);
+//!     for step in outcome.remaining_changes() {
+//!         println!("Could not undo {}", step.description);
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::error::{Error, Result};
+#[cfg(feature = "drive")]
+use crate::ops::DriveOps;
+use crate::ops::OpsFuture;
+#[cfg(feature = "base")]
+use crate::{database::updates::Updates, ops::DatabaseOps};
+#[cfg(feature = "drive")]
+use bytes::Bytes;
+#[cfg(feature = "base")]
+use serde::Serialize;
+#[cfg(feature = "base")]
+use serde_json::Value;
+
+/// An action run by a custom step or compensation.
+pub type BatchAction<'a> = Box<dyn FnOnce() -> OpsFuture<'a, ()> + Send + 'a>;
+
+/// Operations run in order, with the ones which already ran compensated in reverse order
+/// when one of them fails (see the [module documentation](self)).
+///
+/// The compensation of each step is derived from the state read right before the step runs:
+/// - putting an item with a key restores the previous item, or deletes the item if there was none,
+/// - putting an item without a key or inserting an item deletes the item under the key returned by deta,
+/// - deleting or updating an item puts the previous item back,
+/// - putting a file restores the previous content and content type, or deletes the file if there was none,
+/// - deleting a file puts the previous content back.
+///
+/// The previous content of a file is kept in memory until the batch ends.
+/// The derived compensation can be replaced with [`Batch::compensate_with`](Batch::compensate_with),
+/// which also skips reading the previous state.
+///
+/// A failed step is compensated as well if its error isn't a response of deta, e.g. a timeout,
+/// as its operation may have been applied anyway. The derived compensations are safe to run in that case,
+/// except for items without a key, whose key isn't known until deta responds.
+pub struct Batch<'a> {
+    steps: Vec<Step<'a>>,
+}
+
+struct Step<'a> {
+    description: String,
+    operation: Operation<'a>,
+    // Set by `Batch::compensate_with`, replaces the derived compensation.
+    compensation: Option<BatchAction<'a>>,
+}
+
+enum Operation<'a> {
+    #[cfg(feature = "base")]
+    PutItem {
+        database: &'a dyn DatabaseOps,
+        item: serde_json::Result<Value>,
+    },
+    #[cfg(feature = "base")]
+    InsertItem {
+        database: &'a dyn DatabaseOps,
+        item: serde_json::Result<Value>,
+    },
+    #[cfg(feature = "base")]
+    DeleteItem {
+        database: &'a dyn DatabaseOps,
+        key: String,
+    },
+    #[cfg(feature = "base")]
+    UpdateItem {
+        database: &'a dyn DatabaseOps,
+        key: String,
+        updates: Updates,
+    },
+    #[cfg(feature = "drive")]
+    PutFile {
+        drive: &'a dyn DriveOps,
+        name: String,
+        data: Bytes,
+        content_type: Option<String>,
+    },
+    #[cfg(feature = "drive")]
+    DeleteFile {
+        drive: &'a dyn DriveOps,
+        name: String,
+    },
+    Custom(BatchAction<'a>),
+}
+
+// Undoes a step which ran.
+enum Compensation<'a> {
+    // The step changed nothing, e.g. it deleted a missing item.
+    Nothing,
+    #[cfg(feature = "base")]
+    PutItem {
+        database: &'a dyn DatabaseOps,
+        item: Value,
+    },
+    #[cfg(feature = "base")]
+    DeleteItem {
+        database: &'a dyn DatabaseOps,
+        key: String,
+    },
+    #[cfg(feature = "drive")]
+    PutFile {
+        drive: &'a dyn DriveOps,
+        name: String,
+        data: Bytes,
+        content_type: Option<String>,
+    },
+    #[cfg(feature = "drive")]
+    DeleteFile {
+        drive: &'a dyn DriveOps,
+        name: String,
+    },
+    Custom(BatchAction<'a>),
+}
+
+/// Outcome of [`Batch::execute`](Batch::execute), with the outcome of every step in the order they were added.
+#[derive(Debug)]
+pub struct BatchOutcome {
+    pub steps: Vec<StepOutcome>,
+}
+
+/// Outcome of a single step of the batch.
+#[derive(Debug)]
+pub struct StepOutcome {
+    /// Description of the step, e.g. `put item 'user_1'`.
+    pub description: String,
+    pub result: StepResult,
+    pub rollback: Rollback,
+}
+
+/// Whether the step ran.
+#[derive(Debug)]
+pub enum StepResult {
+    /// The step succeeded.
+    Completed,
+    /// The step failed, including reading the state needed for its compensation.
+    Failed(Error),
+    /// The step wasn't run, as an earlier step failed.
+    Skipped,
+}
+
+/// Whether the step has been undone after a failure of the batch.
+#[derive(Debug)]
+pub enum Rollback {
+    /// There's nothing to undo: the batch succeeded, the step didn't run, or it didn't change anything.
+    NotNeeded,
+    /// The step has been undone.
+    RolledBack,
+    /// The step couldn't be undone, so its changes remain.
+    Failed(Error),
+    /// The step has no compensation, e.g. a failed put of an item without a key whose key is unknown,
+    /// so its changes may remain.
+    Unavailable,
+}
+
+impl BatchOutcome {
+    /// Checks whether all the steps succeeded.
+    pub fn is_success(&self) -> bool {
+        self.failed_step().is_none()
+    }
+
+    /// Returns the step which failed the batch.
+    pub fn failed_step(&self) -> Option<&StepOutcome> {
+        self.steps
+            .iter()
+            .find(|step| matches!(step.result, StepResult::Failed(_)))
+    }
+
+    /// Returns the error of the step which failed the batch.
+    pub fn error(&self) -> Option<&Error> {
+        match &self.failed_step()?.result {
+            StepResult::Failed(error) => Some(error),
+            _ => None,
+        }
+    }
+
+    /// Checks whether nothing is left of the batch: it either succeeded,
+    /// or all of the steps which may have changed something have been undone.
+    pub fn is_consistent(&self) -> bool {
+        self.is_success() || self.remaining_changes().next().is_none()
+    }
+
+    /// Returns the steps whose changes may remain after the failure of the batch,
+    /// because their compensation failed or isn't available.
+    pub fn remaining_changes(&self) -> impl Iterator<Item = &StepOutcome> {
+        self.steps
+            .iter()
+            .filter(|step| matches!(step.rollback, Rollback::Failed(_) | Rollback::Unavailable))
+    }
+}
+
+impl Default for Batch<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> Batch<'a> {
+    /// Creates an empty batch.
+    pub fn new() -> Self {
+        Self { steps: Vec::new() }
+    }
+
+    /// Returns the number of steps.
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    /// Checks whether the batch has no steps.
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Puts the `item` like [`Database::put_items`](crate::database::Database::put_items).
+    /// An item which can't be serialized fails the step.
+    #[cfg(feature = "base")]
+    pub fn put_item<T>(self, database: &'a dyn DatabaseOps, item: &T) -> Self
+    where
+        T: Serialize + ?Sized,
+    {
+        let item = serde_json::to_value(item);
+        let description = match item.as_ref().ok().and_then(item_key) {
+            Some(key) => format!("put item '{}'", key),
+            None => "put item".to_owned(),
+        };
+        self.push(description, Operation::PutItem { database, item })
+    }
+
+    /// Inserts the `item` like [`Database::insert_item`](crate::database::Database::insert_item),
+    /// failing if an item with its key exists.
+    #[cfg(feature = "base")]
+    pub fn insert_item<T>(self, database: &'a dyn DatabaseOps, item: &T) -> Self
+    where
+        T: Serialize + ?Sized,
+    {
+        let item = serde_json::to_value(item);
+        let description = match item.as_ref().ok().and_then(item_key) {
+            Some(key) => format!("insert item '{}'", key),
+            None => "insert item".to_owned(),
+        };
+        self.push(description, Operation::InsertItem { database, item })
+    }
+
+    /// Deletes the item under the `key`.
+    #[cfg(feature = "base")]
+    pub fn delete_item(self, database: &'a dyn DatabaseOps, key: &str) -> Self {
+        let key = key.to_owned();
+        self.push(
+            format!("delete item '{}'", key),
+            Operation::DeleteItem { database, key },
+        )
+    }
+
+    /// Updates the item under the `key` like [`Database::update_item`](crate::database::Database::update_item).
+    /// A missing item is left as it is, without failing the step.
+    #[cfg(feature = "base")]
+    pub fn update_item(self, database: &'a dyn DatabaseOps, key: &str, updates: Updates) -> Self {
+        let key = key.to_owned();
+        self.push(
+            format!("update item '{}'", key),
+            Operation::UpdateItem {
+                database,
+                key,
+                updates,
+            },
+        )
+    }
+
+    /// Puts the file like [`Drive::put_file`](crate::drive::Drive::put_file).
+    #[cfg(feature = "drive")]
+    pub fn put_file(
+        self,
+        drive: &'a dyn DriveOps,
+        name: &str,
+        data: impl Into<Bytes>,
+        content_type: Option<&str>,
+    ) -> Self {
+        self.push(
+            format!("put file '{}'", name),
+            Operation::PutFile {
+                drive,
+                name: name.to_owned(),
+                data: data.into(),
+                content_type: content_type.map(str::to_owned),
+            },
+        )
+    }
+
+    /// Deletes the file under the `name`.
+    #[cfg(feature = "drive")]
+    pub fn delete_file(self, drive: &'a dyn DriveOps, name: &str) -> Self {
+        self.push(
+            format!("delete file '{}'", name),
+            Operation::DeleteFile {
+                drive,
+                name: name.to_owned(),
+            },
+        )
+    }
+
+    /// Adds a step running the `action`, compensated by the `compensation`, if any.
+    pub fn custom<F, C>(self, description: &str, action: F, compensation: Option<C>) -> Self
+    where
+        F: FnOnce() -> OpsFuture<'a, ()> + Send + 'a,
+        C: FnOnce() -> OpsFuture<'a, ()> + Send + 'a,
+    {
+        let mut batch = self.push(description.to_owned(), Operation::Custom(Box::new(action)));
+        if let Some(compensation) = compensation {
+            batch = batch.compensate_with(compensation);
+        }
+        batch
+    }
+
+    /// Replaces the compensation of the last added step with the `compensation`.
+    /// Does nothing if the batch is empty.
+    pub fn compensate_with<C>(mut self, compensation: C) -> Self
+    where
+        C: FnOnce() -> OpsFuture<'a, ()> + Send + 'a,
+    {
+        if let Some(step) = self.steps.last_mut() {
+            step.compensation = Some(Box::new(compensation));
+        }
+        self
+    }
+
+    /// Runs the steps in order. After a failed step, the remaining ones are skipped,
+    /// and the ones which ran are compensated in reverse order. The outcome reports what happened to every step.
+    pub async fn execute(self) -> BatchOutcome {
+        let mut outcomes = Vec::with_capacity(self.steps.len());
+        // The compensations of the steps which ran, by the index of the step.
+        let mut compensations = Vec::new();
+        let mut failed = false;
+
+        for (index, step) in self.steps.into_iter().enumerate() {
+            let mut outcome = StepOutcome {
+                description: step.description,
+                result: StepResult::Skipped,
+                rollback: Rollback::NotNeeded,
+            };
+            if failed {
+                outcomes.push(outcome);
+                continue;
+            }
+            outcome.result = match run(step.operation, step.compensation).await {
+                Ok(compensation) => {
+                    compensations.push((index, compensation));
+                    StepResult::Completed
+                }
+                Err(Failure::Ran(error, compensation)) if may_have_applied(&error) => {
+                    compensations.push((index, compensation));
+                    StepResult::Failed(error)
+                }
+                Err(Failure::Ran(error, _) | Failure::NotRun(error)) => StepResult::Failed(error),
+            };
+            failed = matches!(outcome.result, StepResult::Failed(_));
+            outcomes.push(outcome);
+        }
+
+        if failed {
+            for (index, compensation) in compensations.into_iter().rev() {
+                outcomes[index].rollback = match compensation {
+                    None => Rollback::Unavailable,
+                    Some(compensation) => match compensate(compensation).await {
+                        Ok(true) => Rollback::RolledBack,
+                        Ok(false) => Rollback::NotNeeded,
+                        Err(error) => Rollback::Failed(error),
+                    },
+                };
+            }
+        }
+        BatchOutcome { steps: outcomes }
+    }
+
+    fn push(mut self, description: String, operation: Operation<'a>) -> Self {
+        self.steps.push(Step {
+            description,
+            operation,
+            compensation: None,
+        });
+        self
+    }
+}
+
+// A step failing with a response of deta hasn't changed anything.
+fn may_have_applied(error: &Error) -> bool {
+    !error.is_response()
+}
+
+// Failure of a step, along with the compensation derived before its operation ran,
+// to be used if the operation may have been applied anyway.
+enum Failure<'a> {
+    NotRun(Error),
+    Ran(Error, Option<Compensation<'a>>),
+}
+
+// Runs the operation and returns its compensation.
+async fn run<'a>(
+    operation: Operation<'a>,
+    given: Option<BatchAction<'a>>,
+) -> std::result::Result<Option<Compensation<'a>>, Failure<'a>> {
+    let given = given.map(Compensation::Custom);
+    match operation {
+        #[cfg(feature = "base")]
+        Operation::PutItem { database, item } => {
+            let item = item.map_err(|error| Failure::NotRun(error.into()))?;
+            let before = match (given, item_key(&item)) {
+                (Some(given), _) => Some(given),
+                (None, Some(key)) => Some(
+                    item_snapshot(database, key)
+                        .await
+                        .map_err(Failure::NotRun)?,
+                ),
+                (None, None) => None,
+            };
+            match put_item(database, item).await {
+                Ok(stored) => Ok(before.or_else(|| deleting(database, &stored))),
+                Err(error) => Err(Failure::Ran(error, before)),
+            }
+        }
+        #[cfg(feature = "base")]
+        Operation::InsertItem { database, item } => {
+            let item = item.map_err(|error| Failure::NotRun(error.into()))?;
+            // An existing item fails the insert, so deleting the key after a failure could remove it.
+            match database.insert_item_value(item).await {
+                Ok(stored) => Ok(given.or_else(|| deleting(database, &stored))),
+                Err(error) => Err(Failure::Ran(error, given)),
+            }
+        }
+        #[cfg(feature = "base")]
+        Operation::DeleteItem { database, key } => {
+            let before = match given {
+                Some(given) => given,
+                None => item_snapshot(database, &key)
+                    .await
+                    .map(restore_only)
+                    .map_err(Failure::NotRun)?,
+            };
+            match database.delete_item(&key).await {
+                Ok(_) => Ok(Some(before)),
+                Err(error) => Err(Failure::Ran(error, Some(before))),
+            }
+        }
+        #[cfg(feature = "base")]
+        Operation::UpdateItem {
+            database,
+            key,
+            updates,
+        } => {
+            let before = match given {
+                Some(given) => given,
+                None => item_snapshot(database, &key)
+                    .await
+                    .map(restore_only)
+                    .map_err(Failure::NotRun)?,
+            };
+            match database.update_item(&key, updates).await {
+                Ok(Some(_)) => Ok(Some(before)),
+                // A missing item isn't changed, so there's nothing to undo.
+                Ok(None) => Ok(Some(Compensation::Nothing)),
+                Err(error) => Err(Failure::Ran(error, Some(before))),
+            }
+        }
+        #[cfg(feature = "drive")]
+        Operation::PutFile {
+            drive,
+            name,
+            data,
+            content_type,
+        } => {
+            let before = match given {
+                Some(given) => given,
+                None => file_snapshot(drive, &name).await.map_err(Failure::NotRun)?,
+            };
+            match drive
+                .put_file_bytes(&name, data, content_type.as_deref())
+                .await
+            {
+                Ok(_) => Ok(Some(before)),
+                Err(error) => Err(Failure::Ran(error, Some(before))),
+            }
+        }
+        #[cfg(feature = "drive")]
+        Operation::DeleteFile { drive, name } => {
+            let before = match given {
+                Some(given) => given,
+                None => file_snapshot(drive, &name)
+                    .await
+                    .map(restore_only)
+                    .map_err(Failure::NotRun)?,
+            };
+            match delete_file(drive, &name).await {
+                Ok(()) => Ok(Some(before)),
+                Err(error) => Err(Failure::Ran(error, Some(before))),
+            }
+        }
+        Operation::Custom(action) => match action().await {
+            Ok(()) => Ok(given),
+            Err(error) => Err(Failure::Ran(error, given)),
+        },
+    }
+}
+
+// Runs the compensation, returning whether there was anything to undo.
+async fn compensate(compensation: Compensation<'_>) -> Result<bool> {
+    match compensation {
+        Compensation::Nothing => return Ok(false),
+        #[cfg(feature = "base")]
+        Compensation::PutItem { database, item } => {
+            put_item(database, item).await?;
+        }
+        #[cfg(feature = "base")]
+        Compensation::DeleteItem { database, key } => {
+            database.delete_item(&key).await?;
+        }
+        #[cfg(feature = "drive")]
+        Compensation::PutFile {
+            drive,
+            name,
+            data,
+            content_type,
+        } => {
+            drive
+                .put_file_bytes(&name, data, content_type.as_deref())
+                .await?;
+        }
+        #[cfg(feature = "drive")]
+        Compensation::DeleteFile { drive, name } => delete_file(drive, &name).await?,
+        Compensation::Custom(action) => action().await?,
+    }
+    Ok(true)
+}
+
+// Deleting a missing item or file changes nothing, so there's nothing to undo.
+fn restore_only(compensation: Compensation<'_>) -> Compensation<'_> {
+    match compensation {
+        #[cfg(feature = "base")]
+        Compensation::DeleteItem { .. } => Compensation::Nothing,
+        #[cfg(feature = "drive")]
+        Compensation::DeleteFile { .. } => Compensation::Nothing,
+        compensation => compensation,
+    }
+}
+
+#[cfg(feature = "base")]
+fn item_key(item: &Value) -> Option<&str> {
+    item.get("key")?.as_str()
+}
+
+// Returns the compensation deleting the stored item, if deta returned its key.
+#[cfg(feature = "base")]
+fn deleting<'a>(database: &'a dyn DatabaseOps, stored: &Value) -> Option<Compensation<'a>> {
+    let key = item_key(stored)?.to_owned();
+    Some(Compensation::DeleteItem { database, key })
+}
+
+// Returns the compensation bringing back the current state of the item.
+#[cfg(feature = "base")]
+async fn item_snapshot<'a>(database: &'a dyn DatabaseOps, key: &str) -> Result<Compensation<'a>> {
+    Ok(match database.get_item_value(key).await? {
+        Some(item) => Compensation::PutItem { database, item },
+        None => Compensation::DeleteItem {
+            database,
+            key: key.to_owned(),
+        },
+    })
+}
+
+// Puts a single item, failing if deta reports it as failed. Returns the stored item.
+#[cfg(feature = "base")]
+async fn put_item(database: &dyn DatabaseOps, item: Value) -> Result<Value> {
+    let result = database.put_item_values(vec![item]).await?;
+    let stored = if result.has_failures() {
+        None
+    } else {
+        result.processed.items.into_iter().next()
+    };
+    stored.ok_or_else(|| {
+        Error::from_other(
+            "The item has been reported as failed by deta".to_owned(),
+            None,
+        )
+    })
+}
+
+// Returns the compensation bringing back the current content of the file.
+#[cfg(feature = "drive")]
+async fn file_snapshot<'a>(drive: &'a dyn DriveOps, name: &str) -> Result<Compensation<'a>> {
+    let name = name.to_owned();
+    let metadata = match drive.head_file(&name).await? {
+        Some(metadata) => metadata,
+        None => return Ok(Compensation::DeleteFile { drive, name }),
+    };
+    Ok(match drive.get_file_as_buffer(&name).await? {
+        Some(data) => Compensation::PutFile {
+            drive,
+            name,
+            data,
+            content_type: metadata.content_type,
+        },
+        // Deleted in the meantime.
+        None => Compensation::DeleteFile { drive, name },
+    })
+}
+
+// Deletes a single file, failing if deta reports it as failed.
+#[cfg(feature = "drive")]
+async fn delete_file(drive: &dyn DriveOps, name: &str) -> Result<()> {
+    let names = [name.to_owned()];
+    let result = drive.delete_files(&names).await?;
+    match result.failure_reason(name) {
+        Some(reason) => Err(Error::from_other(
+            format!("Could not delete the file '{}': {}", name, reason),
+            None,
+        )),
+        None => Ok(()),
+    }
+}
+
+#[cfg(all(test, feature = "base"))]
+mod tests {
+    use super::*;
+    use crate::database::models::{DeleteItem, FetchItems, PutItems, UpdateItem};
+    use crate::database::query::Query;
+    use crate::database::updates::Action;
+    use crate::testing::FakeBase;
+    use serde_json::json;
+
+    // Delegates to the fake, failing the `failing` operation with the `error`,
+    // after applying it if `applied` is set.
+    struct Flaky {
+        fake: FakeBase,
+        failing: &'static str,
+        applied: bool,
+        error: fn() -> Error,
+    }
+
+    impl Flaky {
+        fn wrap<'a, T: Send + 'a>(
+            &'a self,
+            operation: &'static str,
+            future: OpsFuture<'a, T>,
+        ) -> OpsFuture<'a, T> {
+            if operation != self.failing {
+                return future;
+            }
+            Box::pin(async move {
+                if self.applied {
+                    future.await?;
+                }
+                Err((self.error)())
+            })
+        }
+    }
+
+    impl DatabaseOps for Flaky {
+        fn put_item_values(&self, items: Vec<Value>) -> OpsFuture<'_, PutItems<Value>> {
+            self.wrap("put", self.fake.put_item_values(items))
+        }
+
+        fn get_item_value<'a>(&'a self, key: &'a str) -> OpsFuture<'a, Option<Value>> {
+            self.wrap("get", self.fake.get_item_value(key))
+        }
+
+        fn insert_item_value(&self, item: Value) -> OpsFuture<'_, Value> {
+            self.wrap("insert", self.fake.insert_item_value(item))
+        }
+
+        fn delete_item<'a>(&'a self, key: &'a str) -> OpsFuture<'a, DeleteItem> {
+            self.wrap("delete", self.fake.delete_item(key))
+        }
+
+        fn fetch_item_values<'a>(
+            &'a self,
+            limit: Option<u32>,
+            last: Option<&'a str>,
+            query: Option<Query>,
+        ) -> OpsFuture<'a, FetchItems<Value>> {
+            self.fake.fetch_item_values(limit, last, query)
+        }
+
+        fn update_item<'a>(
+            &'a self,
+            key: &'a str,
+            updates: Updates,
+        ) -> OpsFuture<'a, Option<UpdateItem>> {
+            self.wrap("update", self.fake.update_item(key, updates))
+        }
+    }
+
+    fn bad_request() -> Error {
+        Error::from_response_data(Some(reqwest::StatusCode::BAD_REQUEST), None, None)
+    }
+
+    fn timeout() -> Error {
+        Error::from_timeout("The request timed out".to_owned())
+    }
+
+    fn failing_step(batch: Batch<'_>) -> Batch<'_> {
+        batch.custom(
+            "fail",
+            || Box::pin(async { Err(bad_request()) }),
+            None::<BatchAction>,
+        )
+    }
+
+    async fn seeded() -> FakeBase {
+        let fake = FakeBase::new();
+        fake.put_item_values(vec![
+            json!({ "key": "a", "value": 1 }),
+            json!({ "key": "c", "value": 3 }),
+            json!({ "key": "d", "value": 4 }),
+        ])
+        .await
+        .unwrap();
+        fake
+    }
+
+    #[tokio::test]
+    async fn successful_batch_keeps_changes() {
+        let fake = seeded().await;
+        let outcome = Batch::new()
+            .put_item(&fake, &json!({ "key": "a", "value": 10 }))
+            .insert_item(&fake, &json!({ "key": "b" }))
+            .delete_item(&fake, "c")
+            .execute()
+            .await;
+
+        assert!(outcome.is_success());
+        assert!(outcome.is_consistent());
+        assert!(outcome.steps.iter().all(|step| {
+            matches!(step.result, StepResult::Completed)
+                && matches!(step.rollback, Rollback::NotNeeded)
+        }));
+        let items = fake.items();
+        assert_eq!(items["a"]["value"], 10);
+        assert!(items.contains_key("b"));
+        assert!(!items.contains_key("c"));
+    }
+
+    #[tokio::test]
+    async fn failure_rolls_back_in_reverse_order() {
+        let fake = seeded().await;
+        let before = fake.items();
+        let batch = Batch::new()
+            .put_item(&fake, &json!({ "key": "a", "value": 10 }))
+            .put_item(&fake, &json!({ "key": "b" }))
+            .insert_item(&fake, &json!({ "generated": true }))
+            .delete_item(&fake, "c")
+            .delete_item(&fake, "missing")
+            .update_item(
+                &fake,
+                "missing",
+                Updates::init().add("value", Action::set(1)),
+            )
+            .update_item(
+                &fake,
+                "d",
+                Updates::init().add("value", Action::increment(1)),
+            );
+        let outcome = failing_step(batch).delete_item(&fake, "a").execute().await;
+
+        assert!(!outcome.is_success());
+        assert!(outcome.is_consistent());
+        assert_eq!(outcome.failed_step().unwrap().description, "fail");
+        assert!(outcome.error().unwrap().is_bad_request());
+        let rollbacks: Vec<_> = outcome
+            .steps
+            .iter()
+            .map(|step| (step.description.as_str(), &step.result, &step.rollback))
+            .collect();
+        assert!(matches!(
+            rollbacks[..],
+            [
+                ("put item 'a'", StepResult::Completed, Rollback::RolledBack),
+                ("put item 'b'", StepResult::Completed, Rollback::RolledBack),
+                ("insert item", StepResult::Completed, Rollback::RolledBack),
+                (
+                    "delete item 'c'",
+                    StepResult::Completed,
+                    Rollback::RolledBack
+                ),
+                (
+                    "delete item 'missing'",
+                    StepResult::Completed,
+                    Rollback::NotNeeded
+                ),
+                (
+                    "update item 'missing'",
+                    StepResult::Completed,
+                    Rollback::NotNeeded
+                ),
+                (
+                    "update item 'd'",
+                    StepResult::Completed,
+                    Rollback::RolledBack
+                ),
+                ("fail", StepResult::Failed(_), Rollback::NotNeeded),
+                ("delete item 'a'", StepResult::Skipped, Rollback::NotNeeded),
+            ]
+        ));
+        assert_eq!(fake.items(), before);
+    }
+
+    #[tokio::test]
+    async fn failed_compensation_is_reported() {
+        let flaky = Flaky {
+            fake: seeded().await,
+            failing: "delete",
+            applied: false,
+            error: timeout,
+        };
+        let batch = Batch::new()
+            .put_item(&flaky, &json!({ "key": "a", "value": 10 }))
+            .put_item(&flaky, &json!({ "key": "b" }));
+        let outcome = failing_step(batch).execute().await;
+
+        assert!(!outcome.is_consistent());
+        let remaining: Vec<_> = outcome.remaining_changes().collect();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].description, "put item 'b'");
+        assert!(matches!(&remaining[0].rollback, Rollback::Failed(error) if error.is_timeout()));
+        // The other steps are still undone.
+        assert_eq!(flaky.fake.items()["a"]["value"], 1);
+    }
+
+    #[tokio::test]
+    async fn step_which_may_have_applied_is_compensated() {
+        let flaky = Flaky {
+            fake: seeded().await,
+            failing: "update",
+            applied: true,
+            error: timeout,
+        };
+        let outcome = Batch::new()
+            .put_item(&flaky, &json!({ "key": "b" }))
+            .update_item(&flaky, "a", Updates::init().add("value", Action::set(10)))
+            .execute()
+            .await;
+
+        let failed = &outcome.steps[1];
+        assert!(matches!(failed.result, StepResult::Failed(_)));
+        assert!(matches!(failed.rollback, Rollback::RolledBack));
+        assert!(outcome.is_consistent());
+        let items = flaky.fake.items();
+        assert_eq!(items["a"]["value"], 1);
+        assert!(!items.contains_key("b"));
+    }
+
+    #[tokio::test]
+    async fn rejected_step_is_not_compensated() {
+        let flaky = Flaky {
+            fake: seeded().await,
+            failing: "put",
+            applied: false,
+            error: bad_request,
+        };
+        let outcome = Batch::new()
+            .put_item(&flaky, &json!({ "key": "a", "value": 10 }))
+            .execute()
+            .await;
+
+        assert!(matches!(outcome.steps[0].rollback, Rollback::NotNeeded));
+        assert!(outcome.is_consistent());
+    }
+
+    #[tokio::test]
+    async fn failed_snapshot_fails_step_without_running_it() {
+        let flaky = Flaky {
+            fake: seeded().await,
+            failing: "get",
+            applied: false,
+            error: timeout,
+        };
+        let outcome = Batch::new()
+            .insert_item(&flaky, &json!({ "key": "b" }))
+            .delete_item(&flaky, "a")
+            .execute()
+            .await;
+
+        assert!(matches!(outcome.steps[0].rollback, Rollback::RolledBack));
+        assert!(matches!(outcome.steps[1].result, StepResult::Failed(_)));
+        assert!(matches!(outcome.steps[1].rollback, Rollback::NotNeeded));
+        let items = flaky.fake.items();
+        assert!(items.contains_key("a"));
+        assert!(!items.contains_key("b"));
+    }
+
+    #[tokio::test]
+    async fn unknown_key_is_reported_as_unavailable() {
+        let flaky = Flaky {
+            fake: seeded().await,
+            failing: "put",
+            applied: true,
+            error: timeout,
+        };
+        let outcome = Batch::new()
+            .put_item(&flaky, &json!({ "value": 5 }))
+            .execute()
+            .await;
+
+        assert!(matches!(outcome.steps[0].rollback, Rollback::Unavailable));
+        assert_eq!(outcome.remaining_changes().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn unserializable_item_fails_step() {
+        let fake = FakeBase::new();
+        let item: std::collections::HashMap<(u8, u8), u8> = [((1, 2), 3)].into();
+        let outcome = Batch::new().put_item(&fake, &item).execute().await;
+
+        assert!(outcome.error().unwrap().is_body_deserialization());
+        assert!(matches!(outcome.steps[0].rollback, Rollback::NotNeeded));
+    }
+
+    #[tokio::test]
+    async fn given_compensation_replaces_derived_one() {
+        let fake = seeded().await;
+        let compensated = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let flag = compensated.clone();
+        let batch = Batch::new()
+            .delete_item(&fake, "a")
+            .compensate_with(move || {
+                Box::pin(async move {
+                    flag.store(true, std::sync::atomic::Ordering::SeqCst);
+                    Ok(())
+                })
+            });
+        let outcome = failing_step(batch).execute().await;
+
+        assert!(matches!(outcome.steps[0].rollback, Rollback::RolledBack));
+        assert!(compensated.load(std::sync::atomic::Ordering::SeqCst));
+        assert!(!fake.items().contains_key("a"));
+    }
+
+    #[cfg(feature = "drive")]
+    #[tokio::test]
+    async fn file_steps_are_rolled_back() {
+        use crate::ops::DriveOpsExt;
+        use crate::testing::FakeDrive;
+
+        let fake = FakeDrive::new();
+        fake.put_file("a.txt", "old", Some("text/plain"))
+            .await
+            .unwrap();
+        fake.put_file("b.txt", "kept", None).await.unwrap();
+        let before = fake.files();
+
+        let batch = Batch::new()
+            .put_file(&fake, "a.txt", "new", Some("application/json"))
+            .put_file(&fake, "c.txt", "created", None)
+            .delete_file(&fake, "b.txt");
+        let outcome = failing_step(batch).execute().await;
+
+        assert!(outcome.is_consistent());
+        assert_eq!(outcome.steps[0].description, "put file 'a.txt'");
+        assert_eq!(fake.files(), before);
+        assert_eq!(fake.content_type("a.txt").as_deref(), Some("text/plain"));
+    }
+}
@@ -0,0 +1,443 @@
+//! Content-addressed storage of blobs in a drive, indexed in a base.
+//!
+//! A [`BlobStore`](BlobStore) stores every distinct content once: the file is named after the SHA-256 hash
+//! of the content, and putting the same content again only counts another reference to it.
+//! The base paired with the drive holds an item per blob, keyed by the hash:
+//!
+//! ```json
+//! { "key": "9f86d08188...", "refcount": 2, "size": 4, "names": ["a.txt", "copy of a.txt"] }
+//! ```
+//!
+//! The references are counted by [`BlobStore::put`](BlobStore::put) and [`BlobStore::add_ref`](BlobStore::add_ref),
+//! and released by [`BlobStore::remove_ref`](BlobStore::remove_ref). Blobs without references aren't deleted
+//! right away, [`BlobStore::gc`](BlobStore::gc) deletes them all at once.
+//!
+//! ```no_run
+//! use deta_rust::blob::BlobStore;
+//! use deta_rust::prelude::*;
+//!
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() -> Result<()> {
+//! let client = DetaClient::new("project_key");
+//! let store = BlobStore::new(Drive::new(&client, "assets")?, Database::new(&client, "asset_index")?);
+//!
+//! let logo = store.put_named("logo.png", std::fs::read("logo.png")?).await?;
+//! let same = store.put_named("logo-copy.png", std::fs::read("logo.png")?).await?;
+//! assert_eq!(logo, same);
+//!
+//! store.remove_ref(&same).await?;
+//! store.remove_ref(&logo).await?;
+//! let deleted = store.gc().await?;
+//! assert_eq!(deleted, vec![logo]);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`BlobRef`](BlobRef) serializes to the hash, so it can be stored in the items referencing the blob.
+//!
+//! # Concurrency
+//!
+//! The reference count is changed with the `increment` update of deta, which is applied atomically,
+//! so concurrent calls of `put`, `add_ref` and `remove_ref` don't lose any of the changes.
+//! Two concurrent first puts of the same content both upload it, the second upload overwrites the file
+//! with the same content and the reference is counted like for an existing blob.
+//!
+//! Deta doesn't support conditional updates or deletes though, so [`BlobStore::gc`](BlobStore::gc)
+//! can't delete a blob only if it still has no references. A reference added after `gc` has found
+//! the blob without references, but before the blob is deleted, ends up pointing at a deleted blob:
+//! [`BlobStore::get`](BlobStore::get) returns `None` for it. Run `gc` when no references are added
+//! to the blobs which may have none, e.g. in a maintenance window, or only for the store of a single writer.
+//!
+//! Available with the `blob` feature.
+
+use crate::constants;
+use crate::database::query::{Condition, Query};
+use crate::database::updates::{Action, Updates};
+use crate::error::{Error, Result};
+use crate::ops::{DatabaseOps, DriveOps};
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::Digest;
+use std::fmt;
+use std::sync::Arc;
+
+const REFCOUNT_FIELD: &str = "refcount";
+const SIZE_FIELD: &str = "size";
+const NAMES_FIELD: &str = "names";
+const BLOB_CONTENT_TYPE: &str = "application/octet-stream";
+
+/// Reference to a blob of a [`BlobStore`](BlobStore): the hex encoded SHA-256 hash of its content.
+/// It's serialized as the hash, and validated when deserialized.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct BlobRef {
+    hash: String,
+}
+
+impl BlobRef {
+    /// Creates the reference from a `hash`, 64 lowercase hex digits.
+    /// Fails with an invalid argument error for any other text.
+    pub fn new(hash: &str) -> Result<Self> {
+        let is_hash = hash.len() == 64
+            && hash
+                .bytes()
+                .all(|byte| byte.is_ascii_digit() || (b'a'..=b'f').contains(&byte));
+        if !is_hash {
+            return Err(Error::from_invalid_argument(format!(
+                "'{}' isn't a SHA-256 hash in lowercase hex",
+                hash
+            )));
+        }
+        Ok(Self {
+            hash: hash.to_owned(),
+        })
+    }
+
+    /// Returns the hash of the content.
+    pub fn hash(&self) -> &str {
+        &self.hash
+    }
+
+    fn of(content: &[u8]) -> Self {
+        let hash = sha2::Sha256::digest(content)
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        Self { hash }
+    }
+}
+
+impl TryFrom<String> for BlobRef {
+    type Error = Error;
+
+    fn try_from(hash: String) -> Result<Self> {
+        Self::new(&hash)
+    }
+}
+
+impl From<BlobRef> for String {
+    fn from(blob: BlobRef) -> Self {
+        blob.hash
+    }
+}
+
+impl fmt::Display for BlobRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.hash)
+    }
+}
+
+/// Metadata of a stored blob, see [`BlobStore::info`](BlobStore::info).
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlobInfo {
+    /// Number of the references to the blob. It may be zero or below after too many
+    /// [`BlobStore::remove_ref`](BlobStore::remove_ref) calls, then the blob is deleted by the next `gc`.
+    pub refcount: i64,
+    /// Size of the content in bytes.
+    pub size: u64,
+    /// Names given to [`BlobStore::put_named`](BlobStore::put_named), once for each call.
+    pub names: Vec<String>,
+}
+
+/// A deduplicating store of blobs in a drive, counting their references in a base
+/// (see the [module documentation](self)).
+pub struct BlobStore {
+    drive: Arc<dyn DriveOps>,
+    database: Arc<dyn DatabaseOps>,
+    prefix: String,
+}
+
+impl BlobStore {
+    /// Creates the store keeping the content in the `drive` and the reference counts in the `database`.
+    /// The base should be used only by the store, as every item in it is taken for a blob.
+    pub fn new(drive: impl DriveOps + 'static, database: impl DatabaseOps + 'static) -> Self {
+        Self {
+            drive: Arc::new(drive),
+            database: Arc::new(database),
+            prefix: "blobs/".to_owned(),
+        }
+    }
+
+    /// Sets the prefix of the names of the files in the drive, `blobs/` by default.
+    pub fn with_prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_owned();
+        self
+    }
+
+    /// Stores the `content` and counts a reference to it. The content is uploaded only if it isn't stored yet.
+    pub async fn put(&self, content: impl Into<Bytes>) -> Result<BlobRef> {
+        self.put_content(content.into(), None).await
+    }
+
+    /// Works like [`BlobStore::put`](BlobStore::put), but also records the original `name` of the content,
+    /// e.g. the name of the uploaded file, in [`BlobInfo::names`](BlobInfo::names).
+    pub async fn put_named(&self, name: &str, content: impl Into<Bytes>) -> Result<BlobRef> {
+        self.put_content(content.into(), Some(name)).await
+    }
+
+    /// Returns the content of the blob, or `None` if it doesn't exist.
+    pub async fn get(&self, blob: &BlobRef) -> Result<Option<Bytes>> {
+        self.drive.get_file_as_buffer(&self.file_name(blob)).await
+    }
+
+    /// Returns the metadata of the blob, or `None` if it doesn't exist.
+    pub async fn info(&self, blob: &BlobRef) -> Result<Option<BlobInfo>> {
+        let item = match self.database.get_item_value(blob.hash()).await? {
+            Some(item) => item,
+            None => return Ok(None),
+        };
+        let number = |field| item.get(field).and_then(Value::as_f64);
+        let (refcount, size) = match (number(REFCOUNT_FIELD), number(SIZE_FIELD)) {
+            (Some(refcount), Some(size)) => (refcount as i64, size as u64),
+            _ => return Err(Error::from_failed_deserialization(Some(item.to_string()))),
+        };
+        let names = item
+            .get(NAMES_FIELD)
+            .and_then(Value::as_array)
+            .map(|names| {
+                names
+                    .iter()
+                    .filter_map(|name| name.as_str().map(str::to_owned))
+                    .collect()
+            })
+            .unwrap_or_default();
+        Ok(Some(BlobInfo {
+            refcount,
+            size,
+            names,
+        }))
+    }
+
+    /// Counts another reference to an existing blob. Fails with a not found error if the blob doesn't exist.
+    pub async fn add_ref(&self, blob: &BlobRef) -> Result<()> {
+        self.change_refcount(blob, 1, None).await
+    }
+
+    /// Releases a reference to the blob. The blob without references is deleted by [`BlobStore::gc`](BlobStore::gc).
+    /// Fails with a not found error if the blob doesn't exist.
+    pub async fn remove_ref(&self, blob: &BlobRef) -> Result<()> {
+        self.change_refcount(blob, -1, None).await
+    }
+
+    /// Deletes the blobs without references and returns them.
+    /// The files are deleted first, so a blob whose file couldn't be deleted is kept and deleted by the next call.
+    /// A file which is already missing, e.g. because an earlier call deleted it but not its item, counts as deleted.
+    ///
+    /// See the [concurrency notes](self#concurrency) before running it along with other writers.
+    pub async fn gc(&self) -> Result<Vec<BlobRef>> {
+        let mut unreferenced = vec![];
+        let mut last: Option<String> = None;
+        loop {
+            let query = Query::init().on(REFCOUNT_FIELD, Condition::less_than_or_equal(0));
+            let page = self
+                .database
+                .fetch_item_values(None, last.as_deref(), Some(query))
+                .await?;
+            for item in &page.items {
+                match item.get("key").and_then(Value::as_str) {
+                    Some(key) => unreferenced.push(BlobRef::new(key)?),
+                    None => return Err(Error::from_failed_deserialization(Some(item.to_string()))),
+                }
+            }
+            match page.paging.last {
+                Some(next) => last = Some(next),
+                None => break,
+            }
+        }
+
+        let mut deleted = vec![];
+        for blobs in unreferenced.chunks(constants::MAX_DELETE_BATCH_SIZE) {
+            let names: Vec<String> = blobs.iter().map(|blob| self.file_name(blob)).collect();
+            let result = self.drive.delete_files(&names).await?;
+            for (blob, name) in blobs.iter().zip(&names) {
+                if result.is_gone(name) {
+                    self.database.delete_item(blob.hash()).await?;
+                    deleted.push(blob.clone());
+                }
+            }
+        }
+        Ok(deleted)
+    }
+
+    async fn put_content(&self, content: Bytes, name: Option<&str>) -> Result<BlobRef> {
+        let blob = BlobRef::of(&content);
+        if self.database.get_item_value(blob.hash()).await?.is_none() {
+            let size = content.len();
+            self.drive
+                .put_file_bytes(&self.file_name(&blob), content, Some(BLOB_CONTENT_TYPE))
+                .await?;
+            let item = json!({
+                "key": blob.hash(),
+                REFCOUNT_FIELD: 1,
+                SIZE_FIELD: size,
+                NAMES_FIELD: name.into_iter().collect::<Vec<_>>(),
+            });
+            match self.database.insert_item_value(item).await {
+                Ok(_) => return Ok(blob),
+                // The same content has been put concurrently, the reference is counted like for an existing blob.
+                Err(error) if error.is_conflict() => {}
+                Err(error) => return Err(error),
+            }
+        }
+        self.change_refcount(&blob, 1, name).await?;
+        Ok(blob)
+    }
+
+    // Changes the reference count with the atomic `increment` update, recording the `name` if given.
+    async fn change_refcount(&self, blob: &BlobRef, by: i32, name: Option<&str>) -> Result<()> {
+        let mut updates = Updates::init().add(REFCOUNT_FIELD, Action::increment(by));
+        if let Some(name) = name {
+            updates = updates.add(NAMES_FIELD, Action::append(name));
+        }
+        match self.database.update_item(blob.hash(), updates).await? {
+            Some(_) => Ok(()),
+            None => Err(Error::from_missing_item()),
+        }
+    }
+
+    fn file_name(&self, blob: &BlobRef) -> String {
+        format!("{}{}", self.prefix, blob.hash())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{FakeBase, FakeDrive};
+
+    fn setup() -> (FakeBase, FakeDrive, BlobStore) {
+        let base = FakeBase::new();
+        let drive = FakeDrive::new();
+        let store = BlobStore::new(drive.clone(), base.clone());
+        (base, drive, store)
+    }
+
+    fn file_names(drive: &FakeDrive) -> Vec<String> {
+        drive.files().into_keys().collect()
+    }
+
+    #[tokio::test]
+    async fn put_and_get_round_trip() {
+        let (_, drive, store) = setup();
+
+        let blob = store.put_named("hello.txt", "hello").await.unwrap();
+
+        assert_eq!(
+            blob.hash(),
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+        assert_eq!(file_names(&drive), vec![format!("blobs/{}", blob)]);
+        assert_eq!(store.get(&blob).await.unwrap().unwrap(), "hello");
+        assert_eq!(
+            store.info(&blob).await.unwrap(),
+            Some(BlobInfo {
+                refcount: 1,
+                size: 5,
+                names: vec!["hello.txt".into()],
+            })
+        );
+
+        let missing = BlobRef::of(b"missing");
+        assert_eq!(store.get(&missing).await.unwrap(), None);
+        assert_eq!(store.info(&missing).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn put_deduplicates_content() {
+        let (base, drive, store) = setup();
+
+        let first = store.put_named("a.txt", "content").await.unwrap();
+        let second = store.put_named("b.txt", "content").await.unwrap();
+        let third = store.put("content").await.unwrap();
+        let other = store.put("other content").await.unwrap();
+
+        assert_eq!(first, second);
+        assert_eq!(first, third);
+        assert_ne!(first, other);
+        assert_eq!(drive.files().len(), 2);
+        assert_eq!(base.items().len(), 2);
+        let info = store.info(&first).await.unwrap().unwrap();
+        assert_eq!(info.refcount, 3);
+        assert_eq!(info.names, vec!["a.txt", "b.txt"]);
+    }
+
+    #[tokio::test]
+    async fn gc_deletes_unreferenced_blobs() {
+        let (base, drive, store) = setup();
+        let kept = store.put("kept").await.unwrap();
+        let shared = store.put("shared").await.unwrap();
+        store.add_ref(&shared).await.unwrap();
+        let removed = store.put("removed").await.unwrap();
+
+        store.remove_ref(&shared).await.unwrap();
+        store.remove_ref(&removed).await.unwrap();
+        let deleted = store.gc().await.unwrap();
+
+        assert_eq!(deleted, vec![removed.clone()]);
+        assert_eq!(store.get(&removed).await.unwrap(), None);
+        assert_eq!(store.info(&removed).await.unwrap(), None);
+        let mut names = file_names(&drive);
+        names.sort();
+        let mut expected = vec![format!("blobs/{}", kept), format!("blobs/{}", shared)];
+        expected.sort();
+        assert_eq!(names, expected);
+        assert_eq!(base.items().len(), 2);
+        assert_eq!(store.info(&shared).await.unwrap().unwrap().refcount, 1);
+
+        store.remove_ref(&shared).await.unwrap();
+        assert_eq!(store.gc().await.unwrap(), vec![shared]);
+        assert!(store.gc().await.unwrap().is_empty());
+
+        // Putting deleted content stores it again.
+        let again = store.put("removed").await.unwrap();
+        assert_eq!(store.get(&again).await.unwrap().unwrap(), "removed");
+        assert_eq!(store.info(&again).await.unwrap().unwrap().refcount, 1);
+    }
+
+    #[tokio::test]
+    async fn gc_collects_blobs_whose_file_is_already_gone() {
+        let (base, _, store) = setup();
+        let removed = store.put("removed").await.unwrap();
+        store.remove_ref(&removed).await.unwrap();
+
+        // Deta reports the file deleted by an earlier, interrupted collection as failed.
+        let transport = crate::testing::MockTransport::new();
+        transport.respond(
+            "DELETE",
+            "/files",
+            200,
+            json!({ "deleted": [], "failed": { format!("blobs/{}", removed): "File not found" } }),
+        );
+        let drive = crate::drive::Drive::new(&crate::DetaClient::new("project_key"), "blobs")
+            .unwrap()
+            .with_transport(Arc::new(transport));
+        let store = BlobStore::new(drive, base.clone());
+
+        assert_eq!(store.gc().await.unwrap(), vec![removed.clone()]);
+        assert_eq!(store.info(&removed).await.unwrap(), None);
+        assert!(base.items().is_empty());
+    }
+
+    #[tokio::test]
+    async fn refs_of_missing_blob_fail() {
+        let (_, _, store) = setup();
+        let missing = BlobRef::of(b"missing");
+
+        assert!(store.add_ref(&missing).await.unwrap_err().is_not_found());
+        assert!(store.remove_ref(&missing).await.unwrap_err().is_not_found());
+    }
+
+    #[test]
+    fn blob_ref_is_validated() {
+        let hash = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+        let blob: BlobRef = serde_json::from_value(json!(hash)).unwrap();
+        assert_eq!(serde_json::to_value(&blob).unwrap(), json!(hash));
+
+        assert!(BlobRef::new(&hash.to_uppercase())
+            .unwrap_err()
+            .is_invalid_argument());
+        assert!(BlobRef::new(&hash[1..]).is_err());
+        assert!(serde_json::from_value::<BlobRef>(json!("../secret")).is_err());
+    }
+}
@@ -0,0 +1,353 @@
+//! A cache of values with an expiry, kept in a base.
+//!
+//! A [`DetaCache`](DetaCache) stores serializable values under string keys, each for a given time to live.
+//! The base is shared by every process using it, e.g. the invocations of a serverless function,
+//! so values computed by one of them are reused by the others.
+//!
+//! ```no_run
+//! use deta_rust::cache::DetaCache;
+//! use deta_rust::prelude::*;
+//! use std::time::Duration;
+//!
+//! # async fn count_users() -> Result<u64> { Ok(0) }
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() -> Result<()> {
+//! let client = DetaClient::new("project_key");
+//! let cache = DetaCache::new(Database::new(&client, "cache")?);
+//!
+//! let users: u64 = cache
+//!     .get_or_compute("stats/users", Duration::from_secs(300), || count_users())
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! # Expiry
+//!
+//! A value is stored along with the `__expires` field, the time of its expiry in seconds
+//! since the Unix epoch, so deta deletes the item by itself once it expires.
+//! Deta doesn't delete it right away though, so the cache also treats an expired item as a miss.
+//! The expiry is rounded up to a whole second.
+//!
+//! # Keys
+//!
+//! Any text which isn't empty or whitespace only can be used as a key, e.g. `/api/users?page=2`.
+//! It's stored as the key of the item as it is, see [`Key`](crate::database::Key).
+//! The base should be used only by the cache, as other items may be taken for cached values.
+//!
+//! Available with the `base` feature.
+
+use crate::error::{Error, Result};
+use crate::ops::DatabaseOps;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::OnceCell;
+
+const VALUE_FIELD: &str = "value";
+const EXPIRES_FIELD: &str = "__expires";
+
+// The current time, replaced in tests.
+type Clock = Arc<dyn Fn() -> SystemTime + Send + Sync>;
+
+// The computations of the values in progress, shared by the callers waiting for the same key.
+type Flights = Mutex<HashMap<String, Arc<OnceCell<Value>>>>;
+
+/// A cache of values with an expiry, kept in a base (see the [module documentation](self)).
+pub struct DetaCache {
+    database: Arc<dyn DatabaseOps>,
+    delete_expired: bool,
+    clock: Clock,
+    flights: Flights,
+}
+
+impl DetaCache {
+    /// Creates the cache of the values kept in the `database`.
+    pub fn new(database: impl DatabaseOps + 'static) -> Self {
+        Self {
+            database: Arc::new(database),
+            delete_expired: false,
+            clock: Arc::new(SystemTime::now),
+            flights: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Deletes the expired items found by [`DetaCache::get`](DetaCache::get), rather than leaving them to deta.
+    /// It takes an extra request, but keeps the base small if deta is slow to delete them. Disabled by default.
+    ///
+    /// A value set by another process between reading the expired item and deleting it is deleted as well,
+    /// which only makes it a miss.
+    pub fn with_delete_expired(mut self, delete_expired: bool) -> Self {
+        self.delete_expired = delete_expired;
+        self
+    }
+
+    #[cfg(test)]
+    fn with_clock(mut self, clock: impl Fn() -> SystemTime + Send + Sync + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Returns the value cached under the `key`, or `None` if there's none or it has expired.
+    pub async fn get<T>(&self, key: &str) -> Result<Option<T>>
+    where
+        T: DeserializeOwned,
+    {
+        let value = self.get_value(key).await?;
+        value
+            .map(|value| serde_json::from_value(value).map_err(Error::from))
+            .transpose()
+    }
+
+    /// Caches the `value` under the `key` for the `ttl`, replacing the previous value.
+    pub async fn set<T>(&self, key: &str, value: &T, ttl: Duration) -> Result<()>
+    where
+        T: Serialize,
+    {
+        self.set_value(key, serde_json::to_value(value)?, ttl).await
+    }
+
+    /// Removes the value cached under the `key`.
+    pub async fn remove(&self, key: &str) -> Result<()> {
+        self.database.delete_item(key).await?;
+        Ok(())
+    }
+
+    /// Returns the value cached under the `key`, or computes it with `compute` and caches it for the `ttl`.
+    ///
+    /// Concurrent calls for the same key within the process share a single computation:
+    /// the first call computes the value, and the others wait for it instead of computing it again.
+    /// An error of the computation isn't cached, it's returned to the first call, and one of the waiting
+    /// calls computes the value again. Other processes may still compute the same value at the same time.
+    pub async fn get_or_compute<T, F, Fut>(&self, key: &str, ttl: Duration, compute: F) -> Result<T>
+    where
+        T: Serialize + DeserializeOwned,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let flight = self
+            .flights
+            .lock()
+            .unwrap()
+            .entry(key.to_owned())
+            .or_default()
+            .clone();
+
+        let result = flight
+            .get_or_try_init(|| async {
+                if let Some(value) = self.get_value(key).await? {
+                    return Ok(value);
+                }
+                let value = serde_json::to_value(compute().await?)?;
+                self.set_value(key, value.clone(), ttl).await?;
+                Ok::<_, Error>(value)
+            })
+            .await
+            .cloned();
+
+        // The flight is over, later calls read the cached value instead.
+        // A newer flight may have replaced it already, if this one failed.
+        let mut flights = self.flights.lock().unwrap();
+        if flights
+            .get(key)
+            .is_some_and(|current| Arc::ptr_eq(current, &flight))
+        {
+            flights.remove(key);
+        }
+        drop(flights);
+
+        Ok(serde_json::from_value(result?)?)
+    }
+
+    async fn get_value(&self, key: &str) -> Result<Option<Value>> {
+        let mut item = match self.database.get_item_value(key).await? {
+            Some(item) => item,
+            None => return Ok(None),
+        };
+        let expires = item.get(EXPIRES_FIELD).and_then(Value::as_f64);
+        if expires.is_some_and(|expires| expires <= self.now_seconds()) {
+            if self.delete_expired {
+                // The item is a miss either way, and deta deletes it eventually.
+                let _ = self.database.delete_item(key).await;
+            }
+            return Ok(None);
+        }
+        match item.get_mut(VALUE_FIELD) {
+            Some(value) => Ok(Some(value.take())),
+            None => Err(Error::from_failed_deserialization(Some(item.to_string()))),
+        }
+    }
+
+    async fn set_value(&self, key: &str, value: Value, ttl: Duration) -> Result<()> {
+        let expires = (self.now_seconds() + ttl.as_secs_f64()).ceil();
+        let item = json!({ "key": key, VALUE_FIELD: value, EXPIRES_FIELD: expires as u64 });
+        let result = self.database.put_item_values(vec![item]).await?;
+        if result.has_failures() {
+            return Err(Error::from_other(
+                format!("Deta failed to store the value cached under '{}'", key),
+                None,
+            ));
+        }
+        Ok(())
+    }
+
+    fn now_seconds(&self) -> f64 {
+        (self.clock)()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::FakeBase;
+    use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+    // A cache with a clock set to `now` seconds since the epoch, moved by the returned handle.
+    fn setup(base: &FakeBase) -> (DetaCache, Arc<AtomicU64>) {
+        let now = Arc::new(AtomicU64::new(1_700_000_000));
+        let clock = now.clone();
+        let cache = DetaCache::new(base.clone())
+            .with_clock(move || UNIX_EPOCH + Duration::from_secs(clock.load(Ordering::SeqCst)));
+        (cache, now)
+    }
+
+    #[tokio::test]
+    async fn set_and_get_until_expiry() {
+        let base = FakeBase::new();
+        let (cache, now) = setup(&base);
+
+        cache
+            .set("users/1", &vec!["anna", "tom"], Duration::from_secs(60))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            base.items()["users/1"],
+            json!({ "key": "users/1", "value": ["anna", "tom"], "__expires": 1_700_000_060u64 })
+        );
+        let value: Option<Vec<String>> = cache.get("users/1").await.unwrap();
+        assert_eq!(value.unwrap(), vec!["anna", "tom"]);
+        assert_eq!(cache.get::<u32>("users/2").await.unwrap(), None);
+
+        now.fetch_add(59, Ordering::SeqCst);
+        assert!(cache.get::<Vec<String>>("users/1").await.unwrap().is_some());
+        now.fetch_add(1, Ordering::SeqCst);
+        assert_eq!(cache.get::<Vec<String>>("users/1").await.unwrap(), None);
+        // Left to deta by default.
+        assert_eq!(base.items().len(), 1);
+
+        cache
+            .set("users/1", &1, Duration::from_secs(60))
+            .await
+            .unwrap();
+        assert_eq!(cache.get::<u32>("users/1").await.unwrap(), Some(1));
+        cache.remove("users/1").await.unwrap();
+        assert!(base.items().is_empty());
+    }
+
+    #[tokio::test]
+    async fn expired_item_is_deleted_if_enabled() {
+        let base = FakeBase::new();
+        let (cache, now) = setup(&base);
+        let cache = cache.with_delete_expired(true);
+        cache
+            .set("a", &1, Duration::from_millis(1500))
+            .await
+            .unwrap();
+        assert_eq!(base.items()["a"][EXPIRES_FIELD], 1_700_000_002u64);
+
+        now.fetch_add(1, Ordering::SeqCst);
+        assert_eq!(cache.get::<u32>("a").await.unwrap(), Some(1));
+        assert_eq!(base.items().len(), 1);
+        now.fetch_add(1, Ordering::SeqCst);
+        assert_eq!(cache.get::<u32>("a").await.unwrap(), None);
+        assert!(base.items().is_empty());
+    }
+
+    #[tokio::test]
+    async fn get_or_compute_caches_value() {
+        let base = FakeBase::new();
+        let (cache, now) = setup(&base);
+        let computed = AtomicUsize::new(0);
+        let compute = || async { Ok(computed.fetch_add(1, Ordering::SeqCst) + 10) };
+
+        let ttl = Duration::from_secs(10);
+        assert_eq!(cache.get_or_compute("a", ttl, compute).await.unwrap(), 10);
+        assert_eq!(cache.get_or_compute("a", ttl, compute).await.unwrap(), 10);
+        assert_eq!(computed.load(Ordering::SeqCst), 1);
+
+        now.fetch_add(10, Ordering::SeqCst);
+        assert_eq!(cache.get_or_compute("a", ttl, compute).await.unwrap(), 11);
+        assert_eq!(cache.get::<usize>("a").await.unwrap(), Some(11));
+    }
+
+    #[tokio::test]
+    async fn get_or_compute_does_not_cache_errors() {
+        let base = FakeBase::new();
+        let (cache, _) = setup(&base);
+        let ttl = Duration::from_secs(10);
+
+        let error = cache
+            .get_or_compute::<u32, _, _>("a", ttl, || async {
+                Err(Error::from_other("unavailable".into(), None))
+            })
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("unavailable"));
+        assert!(base.items().is_empty());
+
+        let value = cache.get_or_compute("a", ttl, || async { Ok(1) }).await;
+        assert_eq!(value.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn concurrent_get_or_compute_computes_once() {
+        let base = FakeBase::new();
+        let (cache, _) = setup(&base);
+        let cache = Arc::new(cache);
+        let computed = Arc::new(AtomicUsize::new(0));
+
+        let calls: Vec<_> = (0..5)
+            .map(|_| {
+                let cache = cache.clone();
+                let computed = computed.clone();
+                tokio::spawn(async move {
+                    cache
+                        .get_or_compute("report", Duration::from_secs(60), || async {
+                            tokio::time::sleep(Duration::from_millis(50)).await;
+                            Ok(computed.fetch_add(1, Ordering::SeqCst))
+                        })
+                        .await
+                        .unwrap()
+                })
+            })
+            .collect();
+        for call in calls {
+            assert_eq!(call.await.unwrap(), 0);
+        }
+
+        assert_eq!(computed.load(Ordering::SeqCst), 1);
+        assert!(cache.flights.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn keys_are_stored_as_they_are() {
+        let base = FakeBase::new();
+        let (cache, _) = setup(&base);
+        let ttl = Duration::from_secs(60);
+        for key in ["/api/users?page=2#top", "~2F", "100%", "zażółć"] {
+            cache.set(key, &key, ttl).await.unwrap();
+            assert_eq!(base.items()[key]["key"], key);
+            assert_eq!(cache.get::<String>(key).await.unwrap().unwrap(), key);
+            cache.remove(key).await.unwrap();
+        }
+        assert!(base.items().is_empty());
+    }
+}
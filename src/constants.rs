@@ -1,3 +1,26 @@
-pub const DRIVE_API_URL: &'static str = "https://drive.deta.sh/v1";
-pub const DATABASE_API_URL: &'static str = "https://database.deta.sh/v1";
-pub const MAX_DATA_CHUNK_SIZE: usize = 1024 * 1024 * 10;
\ No newline at end of file
+#[cfg(feature = "base")]
+pub const DATABASE_API_URL: &str = "https://database.deta.sh/v1";
+#[cfg(feature = "base")]
+pub const MAX_PUT_BATCH_SIZE: usize = 25;
+#[cfg(feature = "base")]
+pub const MAX_KEY_LENGTH: usize = 1024;
+#[cfg(feature = "base")]
+pub const MAX_ITEM_SIZE: usize = 400 * 1024;
+#[cfg(feature = "base")]
+pub const DEFAULT_PURGE_CONCURRENCY: usize = 8;
+
+#[cfg(feature = "drive")]
+pub use self::drive::*;
+
+#[cfg(feature = "drive")]
+mod drive {
+    pub const DRIVE_API_URL: &str = "https://drive.deta.sh/v1";
+    pub const MAX_DATA_CHUNK_SIZE: usize = 1024 * 1024 * 10;
+    pub const MIN_DATA_CHUNK_SIZE: usize = 1024 * 1024 * 5;
+    pub const TEXT_CONTENT_TYPE: &str = "text/plain; charset=utf-8";
+    pub const JSON_CONTENT_TYPE: &str = "application/json";
+    pub const DEFAULT_CONCURRENCY: usize = 8;
+    pub const MAX_DELETE_BATCH_SIZE: usize = 1000;
+    pub const MAX_DRIVE_NAME_LENGTH: usize = 64;
+    pub const MAX_FILE_NAME_LENGTH: usize = 1024;
+}
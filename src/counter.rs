@@ -0,0 +1,231 @@
+//! A counter split into shards, for counters incremented by many writers at once.
+//!
+//! Incrementing a single item from many concurrent writers makes it a hot key. A [`ShardedCounter`](ShardedCounter)
+//! spreads the increments over a number of shard items instead, and sums them when the value is read.
+//!
+//! ```no_run
+//! use deta_rust::counter::ShardedCounter;
+//! use deta_rust::prelude::*;
+//!
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() -> Result<()> {
+//! let client = DetaClient::new("project_key");
+//! let views = ShardedCounter::new(Database::new(&client, "counters")?, "page_views", 16);
+//!
+//! views.incr(1).await?;
+//! println!("{} views", views.value().await?);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! # Shards
+//!
+//! The shards of the counter `name` are the items with the keys `name#0`, `name#1`, ... up to the number
+//! of the shards, each holding its part of the value in the `value` field. A shard is created by the first
+//! increment which picks it. The value is read by fetching the items with the `name#` key prefix, so the base
+//! should hold only the counters, and the name of a counter shouldn't be a prefix of another one followed by `#`.
+//!
+//! The number of the shards may be changed at any time: the value is the sum of all the existing shards.
+//! A counter which is rarely incremented anymore can be [collapsed](ShardedCounter::collapse) into its first shard.
+//!
+//! Available with the `base` feature.
+
+use crate::database::query::{Condition, Query};
+use crate::database::updates::{Action, Updates};
+use crate::error::{Error, Result};
+use crate::ops::DatabaseOps;
+use serde_json::{json, Value};
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+const VALUE_FIELD: &str = "value";
+
+/// A counter split into shards (see the [module documentation](self)).
+pub struct ShardedCounter {
+    database: Arc<dyn DatabaseOps>,
+    name: String,
+    shards: u32,
+    random: RandomState,
+    increments: AtomicU64,
+}
+
+impl ShardedCounter {
+    /// Creates the counter `name` with the given number of `shards`, at least 1.
+    pub fn new(database: impl DatabaseOps + 'static, name: &str, shards: u32) -> Self {
+        Self {
+            database: Arc::new(database),
+            name: name.to_owned(),
+            shards: shards.max(1),
+            random: RandomState::new(),
+            increments: AtomicU64::new(0),
+        }
+    }
+
+    /// Adds `by` to the counter, which may be negative, in a randomly picked shard.
+    pub async fn incr(&self, by: i64) -> Result<()> {
+        let shard = self.random_shard();
+        self.add_to_shard(&self.shard_key(shard), by).await
+    }
+
+    /// Returns the value of the counter, the sum of its shards. It's 0 for a counter which has no shards yet.
+    pub async fn value(&self) -> Result<i64> {
+        let shards = self.read_shards().await?;
+        Ok(shards.iter().map(|(_, value)| value).sum())
+    }
+
+    /// Moves the values of all the shards into the first one, and deletes the others.
+    /// Returns the value of the counter.
+    ///
+    /// The values are moved with increments, so the increments made at the same time aren't lost,
+    /// except for an increment of a shard right between reading it as empty and deleting it.
+    /// Collapse the counters which aren't incremented anymore, or only rarely, e.g. with a single shard.
+    pub async fn collapse(&self) -> Result<i64> {
+        let first = self.shard_key(0);
+        for (key, value) in self.read_shards().await? {
+            if key == first || value == 0 {
+                continue;
+            }
+            self.add_to_shard(&first, value).await?;
+            self.add_to_shard(&key, -value).await?;
+        }
+
+        let mut total = 0;
+        for (key, value) in self.read_shards().await? {
+            if key != first && value == 0 {
+                self.database.delete_item(&key).await?;
+            }
+            total += value;
+        }
+        Ok(total)
+    }
+
+    // Increments the shard, creating it if it doesn't exist yet.
+    async fn add_to_shard(&self, key: &str, by: i64) -> Result<()> {
+        let updated = self.database.update_item(key, increment(by)).await?;
+        if updated.is_some() {
+            return Ok(());
+        }
+        let shard = json!({ "key": key, VALUE_FIELD: by });
+        match self.database.insert_item_value(shard).await {
+            // Created by another writer in the meantime.
+            Err(error) if error.is_conflict() => {
+                match self.database.update_item(key, increment(by)).await? {
+                    Some(_) => Ok(()),
+                    None => Err(Error::from_missing_item()),
+                }
+            }
+            result => result.map(|_| ()),
+        }
+    }
+
+    // Returns the keys and the values of the existing shards.
+    async fn read_shards(&self) -> Result<Vec<(String, i64)>> {
+        let prefix = format!("{}#", self.name);
+        let mut shards = vec![];
+        let mut last: Option<String> = None;
+        loop {
+            let query = Query::init().on("key", Condition::prefix(prefix.clone()));
+            let page = self
+                .database
+                .fetch_item_values(None, last.as_deref(), Some(query))
+                .await?;
+            for item in page.items {
+                let key = item.get("key").and_then(Value::as_str);
+                // Incremented numbers are stored by deta as floats.
+                let value = item.get(VALUE_FIELD).and_then(Value::as_f64);
+                match (key, value) {
+                    (Some(key), Some(value)) => shards.push((key.to_owned(), value.round() as i64)),
+                    _ => return Err(Error::from_failed_deserialization(Some(item.to_string()))),
+                }
+            }
+            match page.paging.last {
+                Some(next) => last = Some(next),
+                None => break,
+            }
+        }
+        Ok(shards)
+    }
+
+    fn shard_key(&self, shard: u32) -> String {
+        format!("{}#{}", self.name, shard)
+    }
+
+    // Picks a shard with a hash of a counter of the increments, seeded randomly for each counter.
+    fn random_shard(&self) -> u32 {
+        let increment = self.increments.fetch_add(1, Ordering::Relaxed);
+        (self.random.hash_one(increment) % u64::from(self.shards)) as u32
+    }
+}
+
+fn increment(by: i64) -> Updates {
+    Updates::init().add(VALUE_FIELD, Action::increment(by as f64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::FakeBase;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_increments_are_summed() {
+        let base = FakeBase::new();
+        let counter = Arc::new(ShardedCounter::new(base.clone(), "views", 4));
+
+        let writers: Vec<_> = (0..4)
+            .map(|_| {
+                let counter = counter.clone();
+                tokio::spawn(async move {
+                    for _ in 0..15 {
+                        counter.incr(1).await.unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.await.unwrap();
+        }
+
+        assert_eq!(counter.value().await.unwrap(), 60);
+        let keys: Vec<_> = base.items().into_keys().collect();
+        assert!(!keys.is_empty() && keys.len() <= 4);
+        assert!(keys.iter().all(|key| key.starts_with("views#")));
+    }
+
+    #[tokio::test]
+    async fn value_of_counters() {
+        let base = FakeBase::new();
+        let likes = ShardedCounter::new(base.clone(), "likes", 3);
+        let other = ShardedCounter::new(base.clone(), "likes_total", 3);
+
+        assert_eq!(likes.value().await.unwrap(), 0);
+        likes.incr(5).await.unwrap();
+        likes.incr(-2).await.unwrap();
+        other.incr(100).await.unwrap();
+
+        assert_eq!(likes.value().await.unwrap(), 3);
+        assert_eq!(other.value().await.unwrap(), 100);
+        // Fewer shards still count all the existing ones.
+        let single = ShardedCounter::new(base.clone(), "likes", 1);
+        assert_eq!(single.value().await.unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn collapse_moves_shards_into_first() {
+        let base = FakeBase::new();
+        let counter = ShardedCounter::new(base.clone(), "views", 8);
+        for _ in 0..16 {
+            counter.incr(2).await.unwrap();
+        }
+        assert!(base.items().len() > 1);
+
+        assert_eq!(counter.collapse().await.unwrap(), 32);
+
+        let items = base.items();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items["views#0"][VALUE_FIELD], 32.0);
+        assert_eq!(counter.value().await.unwrap(), 32);
+        assert_eq!(counter.collapse().await.unwrap(), 32);
+    }
+}
@@ -0,0 +1,690 @@
+//! Operations performed on many items at once.
+
+use super::query::{Condition, Query};
+use super::{item_key, models, Database, FetchOptions, PurgeOptions, PutOptions};
+use crate::constants;
+use crate::error::{Error, Result};
+use futures::StreamExt;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
+
+const FAILED_ITEM_REASON: &str = "The item has been reported as failed by deta";
+const MISSING_ITEM_REASON: &str = "The item is missing from the response of deta";
+
+/// Outcome of putting a single item with [`Database::put_items_indexed`](Database::put_items_indexed).
+#[derive(Debug, Clone, PartialEq)]
+pub enum ItemOutcome<T> {
+    /// The item has been stored. Contains the item returned by deta, including the generated key.
+    Stored(T),
+    /// The item couldn't be put, along with the reason.
+    Failed(String),
+}
+
+impl<T> ItemOutcome<T> {
+    /// Checks whether the item has been stored.
+    pub fn is_stored(&self) -> bool {
+        matches!(self, ItemOutcome::Stored(_))
+    }
+}
+
+/// Checkpoint of putting many items with [`Database::put_items_resumable`](Database::put_items_resumable).
+///
+/// It's returned when all the items are put, and attached to the error when a batch fails,
+/// see [`Error::bulk_progress`](Error::bulk_progress). It serializes to JSON,
+/// so it can be stored between the runs of a program and passed back to resume putting the same items.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BulkProgress {
+    /// Index of the first item which hasn't been sent yet, where putting is resumed.
+    pub next_index: usize,
+    /// Number of the items stored by deta.
+    pub stored: usize,
+    /// Number of the items reported as failed by deta, or skipped as exceeding the size limit.
+    pub failed: usize,
+}
+
+/// Outcome of purging the expired items with [`Database::purge_expired`](Database::purge_expired).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PurgeStats {
+    /// Number of the deleted items.
+    pub deleted: usize,
+    /// Keys of the expired items which couldn't be deleted.
+    pub failed: Vec<String>,
+    /// Keys of the expired items, collected only in a dry run, see [`PurgeOptions`](PurgeOptions).
+    pub expired: Vec<String>,
+}
+
+impl Database {
+    /// Deletes the items whose `field` holds a time not later than `now`, both in seconds since the Unix epoch.
+    /// It's meant for the items expiring by a field of their own, e.g. `expires_at`,
+    /// rather than by the `__expires` field, by which deta deletes the items itself.
+    /// Items without the field, or with a value other than a number, are kept.
+    ///
+    /// The expired items are fetched page by page and deleted concurrently, see [`PurgeOptions`](PurgeOptions).
+    /// A failed deletion doesn't interrupt the purge, the key of the item is reported
+    /// in [`PurgeStats::failed`](PurgeStats::failed) instead. A failed fetch is returned as an error.
+    ///
+    /// ```no_run
+    /// use deta_rust::prelude::*;
+    /// use std::time::{SystemTime, UNIX_EPOCH};
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() -> Result<()> {
+    /// let client = DetaClient::new("project_key");
+    /// let tokens = Database::new(&client, "tokens")?;
+    /// let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
+    ///
+    /// let stats = tokens.purge_expired("expires_at", now).await?;
+    /// println!("{} deleted, {} failed", stats.deleted, stats.failed.len());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn purge_expired(&self, field: &str, now: i64) -> Result<PurgeStats> {
+        self.purge_expired_with(field, now, PurgeOptions::default())
+            .await
+    }
+
+    /// Works like [`Database::purge_expired`](Database::purge_expired), customized with the `options`,
+    /// e.g. to only find the expired items with a dry run.
+    pub async fn purge_expired_with(
+        &self,
+        field: &str,
+        now: i64,
+        options: PurgeOptions,
+    ) -> Result<PurgeStats> {
+        let mut stats = PurgeStats::default();
+        let mut last = None;
+        loop {
+            let fetch_options = FetchOptions {
+                last: last.take(),
+                query: Some(
+                    Query::init().on(field.to_owned(), Condition::LessThanOrEqual(now as f64)),
+                ),
+                ..Default::default()
+            };
+            let page = self.fetch_items_with::<Value>(fetch_options).await?;
+            let keys = page
+                .items
+                .iter()
+                .map(item_key)
+                .collect::<Result<Vec<_>>>()?;
+
+            if options.dry_run {
+                stats.expired.extend(keys);
+            } else {
+                let mut deletions = futures::stream::iter(keys)
+                    .map(|key| async move { (self.delete_item(&key).await, key) })
+                    .buffer_unordered(options.concurrency.max(1));
+                while let Some((result, key)) = deletions.next().await {
+                    match result {
+                        Ok(_) => stats.deleted += 1,
+                        Err(_) => stats.failed.push(key),
+                    }
+                }
+            }
+
+            match page.paging.last {
+                Some(next) => last = Some(next),
+                None => break,
+            }
+        }
+        Ok(stats)
+    }
+
+    /// Puts the `items` in batches of 25, the limit of deta, resuming from the `progress` if given.
+    /// Returns the progress after putting all the items.
+    ///
+    /// When a batch fails, e.g. due to a lost connection, putting is interrupted and the error is returned
+    /// with the progress made so far attached, see [`Error::bulk_progress`](Error::bulk_progress).
+    /// Passing it back along with the same `items` resumes putting from the failed batch,
+    /// so no item is sent twice or skipped.
+    ///
+    /// Items reported as failed by deta, or exceeding the size limit, don't interrupt putting,
+    /// they're counted in [`BulkProgress::failed`](BulkProgress::failed) instead.
+    /// Use [`Database::put_items_indexed`](Database::put_items_indexed) to find out which items failed.
+    ///
+    /// ```no_run
+    /// use deta_rust::prelude::*;
+    /// use serde_json::{json, Value};
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() -> Result<()> {
+    /// let client = DetaClient::new("project_key");
+    /// let database = Database::new(&client, "imports")?;
+    /// let items: Vec<Value> = (0..100_000).map(|index| json!({ "index": index })).collect();
+    ///
+    /// let mut progress = None;
+    /// loop {
+    ///     match database.put_items_resumable(&items, progress).await {
+    ///         Ok(done) => break println!("{} items stored", done.stored),
+    ///         Err(error) if error.is_retryable() => progress = error.bulk_progress().copied(),
+    ///         Err(error) => return Err(error),
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn put_items_resumable<T>(
+        &self,
+        items: &[T],
+        progress: Option<BulkProgress>,
+    ) -> Result<BulkProgress>
+    where
+        T: DeserializeOwned + Serialize,
+    {
+        let mut progress = progress.unwrap_or_default();
+        let remaining = items.get(progress.next_index..).ok_or_else(|| {
+            Error::from_invalid_argument(format!(
+                "The progress starts at the item {}, but there are only {} items",
+                progress.next_index,
+                items.len()
+            ))
+        })?;
+
+        for batch in remaining.chunks(constants::MAX_PUT_BATCH_SIZE) {
+            let options = PutOptions {
+                skip_oversized: true,
+            };
+            let result = self
+                .put_items_with(batch, options)
+                .await
+                .map_err(|error| error.with_bulk_progress(progress))?;
+            let stored = result.processed.items.len().min(batch.len());
+            progress.next_index += batch.len();
+            progress.stored += stored;
+            progress.failed += batch.len() - stored;
+        }
+        Ok(progress)
+    }
+
+    /// Puts the `items` like [`Database::put_items`](Database::put_items) and returns the outcome of each of them,
+    /// in the order of the `items`. The items are sent in batches of 25, the limit of deta.
+    ///
+    /// The items returned by deta are matched to the sent ones by their keys. Items without a key are matched
+    /// by position, assuming deta returns the processed items in the order they were sent,
+    /// and a failed item is recognized by being returned unchanged.
+    ///
+    /// A failed batch doesn't interrupt the operation, its items are reported as failed with the error message.
+    /// Items exceeding the size limit, see [`Database::with_max_item_size`](Database::with_max_item_size),
+    /// are reported as failed without sending them, while the rest of their batch is put.
+    pub async fn put_items_indexed<T>(&self, items: &[T]) -> Result<Vec<ItemOutcome<T>>>
+    where
+        T: DeserializeOwned + Serialize,
+    {
+        let mut outcomes = Vec::with_capacity(items.len());
+        for batch in items.chunks(constants::MAX_PUT_BATCH_SIZE) {
+            let options = PutOptions {
+                skip_oversized: true,
+            };
+            match self.put_items_with(batch, options).await {
+                Ok(mut result) => {
+                    let oversized = std::mem::take(&mut result.oversized);
+                    let sent = batch
+                        .iter()
+                        .enumerate()
+                        .filter(|(index, _)| !oversized.iter().any(|item| item.index == *index))
+                        .map(|(_, item)| serde_json::to_value(item))
+                        .collect::<serde_json::Result<Vec<Value>>>()?;
+                    let mut matched = match_outcomes(&sent, result)?.into_iter();
+                    outcomes.extend((0..batch.len()).map(|index| {
+                        match oversized.iter().find(|item| item.index == index) {
+                            Some(item) => ItemOutcome::Failed(format!(
+                                "The item exceeds the size limit with {} bytes",
+                                item.size
+                            )),
+                            None => matched.next().expect("Every sent item has an outcome"),
+                        }
+                    }));
+                }
+                Err(error) => {
+                    let reason = error.to_string();
+                    outcomes.extend(batch.iter().map(|_| ItemOutcome::Failed(reason.clone())));
+                }
+            }
+        }
+        Ok(outcomes)
+    }
+}
+
+// An item returned by deta, along with its JSON form used for matching. `None` once matched.
+type Returned<T> = Option<(Value, T)>;
+
+// Matches the items returned by deta to the `sent` ones, see `Database::put_items_indexed`.
+fn match_outcomes<T>(sent: &[Value], result: models::PutItems<T>) -> Result<Vec<ItemOutcome<T>>>
+where
+    T: Serialize,
+{
+    let mut processed = returned(result.processed.items)?;
+    let mut failed = returned(result.failed.map(|failed| failed.items).unwrap_or_default())?;
+    let sent_keys: HashSet<&str> = sent.iter().filter_map(key).collect();
+
+    let outcomes = sent
+        .iter()
+        .map(|item| {
+            let (stored, failure) = match key(item) {
+                Some(sent_key) => {
+                    let has_key = |returned: &Value| key(returned) == Some(sent_key);
+                    (take(&mut processed, has_key), take(&mut failed, has_key))
+                }
+                None => {
+                    let failure = take(&mut failed, |returned| returned == item);
+                    let stored = failure.is_none().then(|| {
+                        take(&mut processed, |returned| {
+                            key(returned).is_none_or(|key| !sent_keys.contains(key))
+                        })
+                    });
+                    (stored.flatten(), failure)
+                }
+            };
+            match (stored, failure) {
+                (Some(stored), _) => ItemOutcome::Stored(stored),
+                (None, Some(_)) => ItemOutcome::Failed(FAILED_ITEM_REASON.to_owned()),
+                (None, None) => ItemOutcome::Failed(MISSING_ITEM_REASON.to_owned()),
+            }
+        })
+        .collect();
+    Ok(outcomes)
+}
+
+// Pairs the returned items with their JSON form.
+fn returned<T>(items: Vec<T>) -> Result<Vec<Returned<T>>>
+where
+    T: Serialize,
+{
+    items
+        .into_iter()
+        .map(|item| Ok(Some((serde_json::to_value(&item)?, item))))
+        .collect()
+}
+
+// Takes the first not yet matched item satisfying the `predicate`.
+fn take<T, P>(items: &mut [Returned<T>], predicate: P) -> Option<T>
+where
+    P: Fn(&Value) -> bool,
+{
+    let position = items
+        .iter()
+        .position(|item| matches!(item, Some((value, _)) if predicate(value)))?;
+    items[position].take().map(|(_, item)| item)
+}
+
+fn key(item: &Value) -> Option<&str> {
+    item.get("key")?.as_str()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::FakeBase;
+    use crate::transport::{Transport, TransportFuture};
+    use serde::Deserialize;
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use wiremock::matchers::{body_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    // Passes the requests to the fake base, recording the keys of the put items,
+    // until the given number of requests, after which the connection is "lost".
+    struct Interrupted {
+        fake: FakeBase,
+        remaining: AtomicUsize,
+        put_keys: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Transport for Interrupted {
+        fn execute(&self, request: reqwest::Request) -> TransportFuture<'_> {
+            let remaining = self
+                .remaining
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1));
+            if remaining.is_err() {
+                let error = Error::from_other("Connection lost".into(), None);
+                return Box::pin(async move { Err(error) });
+            }
+            let body: Value = request
+                .body()
+                .and_then(reqwest::Body::as_bytes)
+                .map(|body| serde_json::from_slice(body).unwrap())
+                .unwrap_or_default();
+            let keys = body["items"].as_array().into_iter().flatten();
+            self.put_keys
+                .lock()
+                .unwrap()
+                .extend(keys.map(|item| item["key"].as_str().unwrap().to_owned()));
+            self.fake.execute(request)
+        }
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    struct Entry {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        key: Option<String>,
+        value: u32,
+    }
+
+    fn entry(key: Option<&str>, value: u32) -> Entry {
+        Entry {
+            key: key.map(str::to_owned),
+            value,
+        }
+    }
+
+    async fn mount_put(server: &MockServer, sent: &[Entry], response: ResponseTemplate) {
+        Mock::given(method("PUT"))
+            .and(path("/items"))
+            .and(body_json(json!({ "items": sent })))
+            .respond_with(response)
+            .expect(1)
+            .mount(server)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn put_items_indexed_across_batches() {
+        let items: Vec<Entry> = (0..30)
+            .map(|value| entry(Some(&value.to_string()), value))
+            .collect();
+        let server = MockServer::start().await;
+        // Deta may return the items in any order, they're matched by keys.
+        let mut processed = items[..25].to_vec();
+        processed.retain(|item| item.value != 12);
+        processed.reverse();
+        mount_put(
+            &server,
+            &items[..25],
+            ResponseTemplate::new(207).set_body_json(json!({
+                "processed": { "items": processed },
+                "failed": { "items": [items[12]] }
+            })),
+        )
+        .await;
+        mount_put(
+            &server,
+            &items[25..],
+            ResponseTemplate::new(207).set_body_json(json!({
+                "processed": { "items": &items[25..] }
+            })),
+        )
+        .await;
+
+        let database = Database::with_base_url(&server.uri());
+        let outcomes = database.put_items_indexed(&items).await.unwrap();
+
+        assert_eq!(outcomes.len(), 30);
+        for (index, outcome) in outcomes.iter().enumerate() {
+            match outcome {
+                ItemOutcome::Stored(stored) => assert_eq!(stored, &items[index]),
+                ItemOutcome::Failed(reason) => {
+                    assert_eq!(index, 12);
+                    assert_eq!(reason, FAILED_ITEM_REASON);
+                }
+            }
+        }
+        assert!(!outcomes[12].is_stored());
+    }
+
+    #[tokio::test]
+    async fn put_items_indexed_for_generated_keys() {
+        let items = vec![
+            entry(None, 1),
+            entry(Some("b"), 2),
+            entry(None, 3),
+            entry(None, 4),
+        ];
+        let server = MockServer::start().await;
+        mount_put(
+            &server,
+            &items,
+            ResponseTemplate::new(207).set_body_json(json!({
+                "processed": { "items": [
+                    { "key": "x1", "value": 1 },
+                    { "key": "b", "value": 2 },
+                    { "key": "x4", "value": 4 }
+                ] },
+                "failed": { "items": [{ "value": 3 }] }
+            })),
+        )
+        .await;
+
+        let database = Database::with_base_url(&server.uri());
+        let outcomes = database.put_items_indexed(&items).await.unwrap();
+
+        assert_eq!(
+            outcomes,
+            vec![
+                ItemOutcome::Stored(entry(Some("x1"), 1)),
+                ItemOutcome::Stored(entry(Some("b"), 2)),
+                ItemOutcome::Failed(FAILED_ITEM_REASON.into()),
+                ItemOutcome::Stored(entry(Some("x4"), 4)),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn put_items_indexed_continues_after_failed_batch() {
+        let items: Vec<Entry> = (0..27).map(|value| entry(None, value)).collect();
+        let server = MockServer::start().await;
+        mount_put(
+            &server,
+            &items[..25],
+            ResponseTemplate::new(400).set_body_json(json!({ "errors": ["Bad item"] })),
+        )
+        .await;
+        mount_put(
+            &server,
+            &items[25..],
+            ResponseTemplate::new(207).set_body_json(json!({
+                "processed": { "items": [{ "key": "x25", "value": 25 }] }
+            })),
+        )
+        .await;
+
+        let database = Database::with_base_url(&server.uri());
+        let outcomes = database.put_items_indexed(&items).await.unwrap();
+
+        assert_eq!(outcomes.len(), 27);
+        assert!(outcomes[..25].iter().all(
+            |outcome| matches!(outcome, ItemOutcome::Failed(reason) if reason.contains("Bad item"))
+        ));
+        assert_eq!(outcomes[25], ItemOutcome::Stored(entry(Some("x25"), 25)));
+        assert_eq!(
+            outcomes[26],
+            ItemOutcome::Failed(MISSING_ITEM_REASON.into())
+        );
+    }
+
+    #[tokio::test]
+    async fn put_items_indexed_for_no_items() {
+        let database = Database::with_base_url("http://127.0.0.1:1");
+        let outcomes = database.put_items_indexed::<Entry>(&[]).await.unwrap();
+        assert!(outcomes.is_empty());
+    }
+
+    #[tokio::test]
+    async fn put_items_indexed_fails_oversized_item_only() {
+        let items = vec![
+            entry(Some("a"), 1),
+            entry(Some("oversized"), 2),
+            entry(Some("c"), 3),
+        ];
+        let sent = [items[0].clone(), items[2].clone()];
+        let server = MockServer::start().await;
+        mount_put(
+            &server,
+            &sent,
+            ResponseTemplate::new(207).set_body_json(json!({
+                "processed": { "items": sent }
+            })),
+        )
+        .await;
+
+        let database = Database::with_base_url(&server.uri()).with_max_item_size(24);
+        let outcomes = database.put_items_indexed(&items).await.unwrap();
+
+        assert_eq!(
+            outcomes,
+            vec![
+                ItemOutcome::Stored(items[0].clone()),
+                ItemOutcome::Failed("The item exceeds the size limit with 29 bytes".into()),
+                ItemOutcome::Stored(items[2].clone()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn put_items_resumable_after_interruption() {
+        let fake = FakeBase::new();
+        let put_keys = Arc::new(Mutex::new(vec![]));
+        let database = |requests| {
+            let transport = Interrupted {
+                fake: fake.clone(),
+                remaining: AtomicUsize::new(requests),
+                put_keys: put_keys.clone(),
+            };
+            Database::new(&crate::DetaClient::new("project_key"), "imports")
+                .unwrap()
+                .with_transport(Arc::new(transport))
+        };
+        let items: Vec<Entry> = (0..110)
+            .map(|value| entry(Some(&format!("item_{:03}", value)), value))
+            .collect();
+
+        let error = database(2)
+            .put_items_resumable(&items, None)
+            .await
+            .unwrap_err();
+        let progress = *error.bulk_progress().unwrap();
+        assert_eq!(
+            progress,
+            BulkProgress {
+                next_index: 50,
+                stored: 50,
+                failed: 0
+            }
+        );
+        assert!(error.to_string().contains("stopped at item 50"));
+
+        // The checkpoint is persisted between the runs.
+        let saved = serde_json::to_string(&progress).unwrap();
+        let progress: BulkProgress = serde_json::from_str(&saved).unwrap();
+        let done = database(usize::MAX)
+            .put_items_resumable(&items, Some(progress))
+            .await
+            .unwrap();
+        assert_eq!(
+            done,
+            BulkProgress {
+                next_index: 110,
+                stored: 110,
+                failed: 0
+            }
+        );
+
+        let mut put_keys = put_keys.lock().unwrap().clone();
+        put_keys.sort();
+        let expected: Vec<String> = items.iter().map(|item| item.key.clone().unwrap()).collect();
+        assert_eq!(put_keys, expected);
+        assert_eq!(fake.items().len(), 110);
+
+        let done = database(0)
+            .put_items_resumable(&items, Some(done))
+            .await
+            .unwrap();
+        assert_eq!(done.next_index, 110);
+        let error = database(0)
+            .put_items_resumable(&items[..10], Some(done))
+            .await
+            .unwrap_err();
+        assert!(error.is_invalid_argument());
+    }
+
+    async fn seeded_expiring() -> FakeBase {
+        let fake = FakeBase::new();
+        let items: Vec<Value> = (0..30)
+            .map(|index| json!({ "key": format!("token_{:02}", index), "expires_at": 100 + index * 10 }))
+            .chain([
+                json!({ "key": "no_field" }),
+                json!({ "key": "not_number", "expires_at": "soon" }),
+                json!({ "key": "nested", "session": { "expires_at": 0 } }),
+            ])
+            .collect();
+        fake.database()
+            .put_items_resumable(&items, None)
+            .await
+            .unwrap();
+        fake
+    }
+
+    #[tokio::test]
+    async fn purge_expired_deletes_only_expired_items() {
+        let fake = seeded_expiring().await;
+
+        // Items expiring at exactly `now` are expired too.
+        let stats = fake
+            .database()
+            .purge_expired("expires_at", 250)
+            .await
+            .unwrap();
+
+        assert_eq!(stats.deleted, 16);
+        assert!(stats.failed.is_empty());
+        assert!(stats.expired.is_empty());
+        let mut kept: Vec<String> = fake.items().into_keys().collect();
+        kept.sort();
+        assert_eq!(kept.len(), 17);
+        assert_eq!(kept[..3], ["nested", "no_field", "not_number"]);
+        assert_eq!(kept[3], "token_16");
+        assert_eq!(
+            fake.database()
+                .purge_expired("expires_at", 250)
+                .await
+                .unwrap()
+                .deleted,
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn purge_expired_dry_run_keeps_items() {
+        let fake = seeded_expiring().await;
+        let options = PurgeOptions {
+            dry_run: true,
+            concurrency: 2,
+        };
+
+        let stats = fake
+            .database()
+            .purge_expired_with("expires_at", 120, options)
+            .await
+            .unwrap();
+
+        assert_eq!(stats.deleted, 0);
+        assert_eq!(stats.expired, ["token_00", "token_01", "token_02"]);
+        assert_eq!(fake.items().len(), 33);
+    }
+
+    #[tokio::test]
+    async fn purge_expired_continues_after_failed_deletion() {
+        let transport = crate::testing::MockTransport::new();
+        transport
+            .respond(
+                "POST",
+                "/query",
+                200,
+                json!({ "paging": { "size": 3 }, "items": [{ "key": "a" }, { "key": "b" }, { "key": "c" }] }),
+            )
+            .respond("DELETE", "/items/a", 200, json!({ "key": "a" }))
+            .respond("DELETE", "/items/c", 200, json!({ "key": "c" }));
+        let database = Database::new(&crate::DetaClient::new("project_key"), "tokens")
+            .unwrap()
+            .with_transport(Arc::new(transport.clone()));
+
+        let stats = database.purge_expired("expires_at", 1000).await.unwrap();
+
+        assert_eq!(stats.deleted, 2);
+        assert_eq!(stats.failed, ["b"]);
+        let query = transport.requests()[0].json().unwrap();
+        assert_eq!(query["query"], json!([{ "expires_at?lte": 1000.0 }]));
+    }
+}
@@ -0,0 +1,171 @@
+//! Validated keys of the items.
+
+use crate::error::Error;
+use crate::utils;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// A key of an item, checked before it's sent to deta.
+///
+/// The methods taking a key, e.g. [`Database::get_item`](super::Database::get_item),
+/// accept both `Key` and plain strings, which are sent as they are,
+/// except for the empty or whitespace only ones, rejected by every method.
+///
+/// # Key rules
+///
+/// Deta stores any string as a key, and generates a random one for an item put without a key.
+/// The key of an existing item is sent in the URL path though, where the mistakes are hard to spot:
+/// an empty key is rejected with `400 Bad Request`, and a stray newline or space
+/// from user input makes the item impossible to find, reported as `404 Not Found`.
+/// So a `Key`:
+/// - must not be empty,
+/// - must be at most 1024 bytes long,
+/// - must not start or end with whitespace,
+/// - must not contain control characters, e.g. `\n` or `\t`.
+///
+/// The key is percent-encoded in the URL path, and decoded back by deta, so keys containing
+/// e.g. `/`, `?`, `#` or `%` are read, updated and deleted like any other.
+///
+/// ```
+/// use deta_rust::database::Key;
+///
+/// let key = Key::new("user_1").unwrap();
+/// assert_eq!(key.as_str(), "user_1");
+/// assert!(Key::new("user_1\n").is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Key(String);
+
+/// The rule broken by a key passed to [`Key::new`](Key::new).
+/// Converted into an invalid argument [`Error`](Error) with `?`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyError {
+    key: String,
+    rule: &'static str,
+}
+
+impl Key {
+    /// Creates a key, if it follows the [key rules](Key#key-rules).
+    pub fn new(key: &str) -> Result<Self, KeyError> {
+        Self::try_from(key.to_owned())
+    }
+
+    /// Returns the key as a string slice.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns the key as a string.
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
+
+impl KeyError {
+    /// Returns the rejected key.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Returns the description of the broken rule, e.g. `must not be empty`.
+    pub fn rule(&self) -> &str {
+        self.rule
+    }
+}
+
+impl TryFrom<String> for Key {
+    type Error = KeyError;
+
+    fn try_from(key: String) -> Result<Self, Self::Error> {
+        match utils::broken_key_rule(&key) {
+            Some(rule) => Err(KeyError { key, rule }),
+            None => Ok(Self(key)),
+        }
+    }
+}
+
+impl TryFrom<&str> for Key {
+    type Error = KeyError;
+
+    fn try_from(key: &str) -> Result<Self, Self::Error> {
+        Self::new(key)
+    }
+}
+
+impl FromStr for Key {
+    type Err = KeyError;
+
+    fn from_str(key: &str) -> Result<Self, Self::Err> {
+        Self::new(key)
+    }
+}
+
+impl From<Key> for String {
+    fn from(key: Key) -> Self {
+        key.0
+    }
+}
+
+impl AsRef<str> for Key {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl fmt::Display for KeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid key {:?}, it {}", self.key, self.rule)
+    }
+}
+
+impl std::error::Error for KeyError {}
+
+impl From<KeyError> for Error {
+    fn from(error: KeyError) -> Self {
+        Error::from_invalid_argument(error.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_accepts_valid_key() {
+        let key = Key::new("user 1").unwrap();
+        assert_eq!(key.as_str(), "user 1");
+        assert_eq!(key.to_string(), "user 1");
+        assert_eq!(Key::try_from("user 1").unwrap(), key);
+        assert_eq!("user 1".parse::<Key>().unwrap(), key);
+        assert_eq!(key.into_string(), "user 1");
+    }
+
+    #[test]
+    fn new_reports_broken_rule() {
+        let error = Key::new("user_1\n").unwrap_err();
+        assert_eq!(error.key(), "user_1\n");
+        assert_eq!(error.rule(), "must not start or end with whitespace");
+        assert_eq!(
+            error.to_string(),
+            r#"Invalid key "user_1\n", it must not start or end with whitespace"#
+        );
+
+        let error = Error::from(Key::new("").unwrap_err());
+        assert!(error.is_invalid_argument());
+    }
+
+    #[test]
+    fn serde_validates_key() {
+        let key: Key = serde_json::from_str(r#""user_1""#).unwrap();
+        assert_eq!(serde_json::to_string(&key).unwrap(), r#""user_1""#);
+        assert!(serde_json::from_str::<Key>(r#""""#).is_err());
+    }
+}
@@ -3,26 +3,42 @@
 
 use crate::constants;
 use crate::deta_client::DetaClient;
-use crate::error::Result;
+use crate::error::{Error, Result};
+use crate::mutation::{MutationEvent, MutationKind, MutationObserver, Observers};
+use crate::transport::{HttpTransport, Transport};
 use crate::utils;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
+use std::sync::Arc;
+mod batch;
 mod common;
+mod key;
 pub mod models;
+mod options;
 pub mod query;
 mod requests;
+mod size;
 pub mod updates;
 
+pub use batch::{BulkProgress, ItemOutcome, PurgeStats};
+pub use key::{Key, KeyError};
+pub use options::{FetchOptions, Order, PurgeOptions, PutOptions};
+
 /// Stores the necessary information and methods to
 /// work with the [deta-base](https://docs.deta.sh/docs/base/http) api.
 pub struct Database {
     base_url: String,
     x_api_key: String,
+    transport: Arc<dyn Transport>,
+    max_item_size: usize,
+    observers: Observers,
 }
 
 impl Database {
     /// Creates an `Database` instance.
-    pub fn new(client: &DetaClient, database_name: &str) -> Self {
+    /// Fails with an invalid argument error if the database name is empty or whitespace only.
+    pub fn new(client: &DetaClient, database_name: &str) -> Result<Self> {
+        utils::validate_not_blank("database name", database_name)?;
         let base_url = format!(
             "{}/{}/{}",
             constants::DATABASE_API_URL,
@@ -32,29 +48,134 @@ impl Database {
 
         let x_api_key = client.api_key().to_owned();
 
-        Self {
+        Ok(Self {
             base_url,
             x_api_key,
+            transport: Arc::new(HttpTransport::default()),
+            max_item_size: constants::MAX_ITEM_SIZE,
+            observers: Observers::default(),
+        })
+    }
+
+    /// Sends the requests through the `transport` instead of the network,
+    /// e.g. through [`MockTransport`](crate::testing::MockTransport) in tests.
+    pub fn with_transport(mut self, transport: Arc<dyn Transport>) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Sets the size limit of a single item, 400 KB by default as documented by deta.
+    /// The items are checked against it before sending, as deta rejects a whole batch
+    /// containing an oversized item without telling which one it is.
+    /// The size is measured in bytes of the item serialized to JSON.
+    pub fn with_max_item_size(mut self, size: usize) -> Self {
+        self.max_item_size = size;
+        self
+    }
+
+    /// Registers the `observer` called with the [`MutationEvent`](MutationEvent) of every change made through the base,
+    /// e.g. to invalidate a cache. See the [`mutation`](crate::mutation) module documentation.
+    pub fn on_mutation(mut self, observer: MutationObserver) -> Self {
+        self.observers.push(observer);
+        self
+    }
+
+    #[cfg(test)]
+    pub(crate) fn with_base_url(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.to_owned(),
+            x_api_key: "test_key".to_owned(),
+            transport: Arc::new(HttpTransport::default()),
+            max_item_size: constants::MAX_ITEM_SIZE,
+            observers: Observers::default(),
         }
     }
 
     /// Creates or overwrites collections of elements
     /// depending on whether a element with a given key already exists in the database or not.
+    ///
+    /// If any of the items exceeds the size limit, see [`Database::with_max_item_size`](Database::with_max_item_size),
+    /// an error listing the oversized items is returned without sending a request.
     pub async fn put_items<T>(&self, items: &[T]) -> Result<models::PutItems<T>>
     where
         T: DeserializeOwned + Serialize,
     {
-        let response = requests::put_items_request(&self.base_url, &self.x_api_key, items).await?;
-        utils::parse_response_body(response).await
+        self.put_items_with(items, PutOptions::default()).await
+    }
+
+    /// Works like [`Database::put_items`](Database::put_items),
+    /// but allows to skip the oversized items with [`PutOptions`](PutOptions).
+    /// The skipped items are reported in [`PutItems::oversized`](models::PutItems::oversized),
+    /// while the rest of them are put. No request is sent if all the items are skipped.
+    pub async fn put_items_with<T>(
+        &self,
+        items: &[T],
+        options: PutOptions,
+    ) -> Result<models::PutItems<T>>
+    where
+        T: DeserializeOwned + Serialize,
+    {
+        let result = self.send_put_items(items, options).await;
+        self.observers.notify(&result, |result| match result {
+            Ok(put) => MutationEvent::succeeded(
+                MutationKind::Put,
+                serialized_keys(put.processed.iter()),
+                serialized_keys(put.failed.iter().flat_map(models::Items::iter)),
+            ),
+            Err(error) => MutationEvent::failed(MutationKind::Put, serialized_keys(items), error),
+        });
+        result
     }
 
-    /// Returns an item with a given key.
-    pub async fn get_item<T>(&self, key: &str) -> Result<Option<T>>
+    // Puts the items without reporting the change to the observers.
+    async fn send_put_items<T>(
+        &self,
+        items: &[T],
+        options: PutOptions,
+    ) -> Result<models::PutItems<T>>
+    where
+        T: DeserializeOwned + Serialize,
+    {
+        let (sent, oversized) = size::partition(items, self.max_item_size)?;
+        if !oversized.is_empty() && !options.skip_oversized {
+            return Err(size::oversized_error(&oversized, self.max_item_size));
+        }
+
+        let mut result = if sent.is_empty() && !oversized.is_empty() {
+            models::PutItems {
+                processed: models::Items::default(),
+                failed: None,
+                oversized: vec![],
+            }
+        } else {
+            let response = requests::put_items_request(
+                self.transport.as_ref(),
+                &self.base_url,
+                &self.x_api_key,
+                &sent,
+            )
+            .await?;
+            utils::parse_response_body::<models::PutItems<T>>(response).await?
+        };
+        result.oversized = oversized;
+        Ok(result)
+    }
+
+    /// Returns an item with a given key, or `None` if the item doesn't exist.
+    /// If the base itself doesn't exist, an error is returned, see [`Error::is_target_not_found`](Error::is_target_not_found).
+    /// The key may be a validated [`Key`](Key) or a plain string.
+    pub async fn get_item<T>(&self, key: impl AsRef<str>) -> Result<Option<T>>
     where
         T: DeserializeOwned,
     {
-        let response_result =
-            requests::get_item_request(&self.base_url, &self.x_api_key, key).await;
+        utils::validate_not_blank("key", key.as_ref())?;
+        let response_result = requests::get_item_request(
+            self.transport.as_ref(),
+            &self.base_url,
+            &self.x_api_key,
+            key.as_ref(),
+        )
+        .await;
 
         if let Err(ref error) = response_result {
             if error.is_not_found() {
@@ -67,19 +188,113 @@ impl Database {
     }
 
     /// Deletes an item with a given key.
-    pub async fn delete_item(&self, key: &str) -> Result<models::DeleteItem> {
-        let response = requests::delete_item_request(&self.base_url, &self.x_api_key, key).await?;
-        utils::parse_response_body(response).await
+    /// The key may be a validated [`Key`](Key) or a plain string.
+    pub async fn delete_item(&self, key: impl AsRef<str>) -> Result<models::DeleteItem> {
+        let key = key.as_ref();
+        let result = async {
+            utils::validate_not_blank("key", key)?;
+            let response = requests::delete_item_request(
+                self.transport.as_ref(),
+                &self.base_url,
+                &self.x_api_key,
+                key,
+            )
+            .await?;
+            utils::parse_response_body(response).await
+        }
+        .await;
+        self.observers.notify(&result, |result| {
+            MutationEvent::new(MutationKind::Delete, vec![key.to_owned()], result)
+        });
+        result
     }
 
     /// Adds a new item. If the specified object contains a key that already exists in the database,
     /// the operation fails (collision error).
+    /// An item exceeding the size limit, see [`Database::with_max_item_size`](Database::with_max_item_size),
+    /// fails the operation without sending a request.
     pub async fn insert_item<T>(&self, item: &T) -> Result<T>
     where
         T: DeserializeOwned + Serialize,
     {
-        let response = requests::insert_item_request(&self.base_url, &self.x_api_key, item).await?;
-        utils::parse_response_body(response).await
+        let (sent, oversized) = size::partition(std::slice::from_ref(item), self.max_item_size)?;
+        let sent = match sent.first() {
+            Some(sent) => sent,
+            None => return Err(size::oversized_error(&oversized, self.max_item_size)),
+        };
+
+        let result = async {
+            let response = requests::insert_item_request(
+                self.transport.as_ref(),
+                &self.base_url,
+                &self.x_api_key,
+                sent,
+            )
+            .await
+            .map_err(|error| with_item_key(error, item))?;
+            utils::parse_response_body(response).await
+        }
+        .await;
+        // The stored item includes the key generated by deta.
+        self.observers.notify(&result, |result| match result {
+            Ok(stored) => {
+                MutationEvent::succeeded(MutationKind::Insert, serialized_keys([stored]), vec![])
+            }
+            Err(error) => {
+                MutationEvent::failed(MutationKind::Insert, serialized_keys([item]), error)
+            }
+        });
+        result
+    }
+
+    /// Inserts the `item` under the given `key`, or returns the stored item if the key already exists.
+    /// The `key` overrides the key field of the serialized `item`, so the item must serialize to an object.
+    pub async fn get_or_insert<T>(&self, key: &str, item: &T) -> Result<T>
+    where
+        T: DeserializeOwned + Serialize,
+    {
+        utils::validate_not_blank("key", key)?;
+        let mut value = serde_json::to_value(item)?;
+        value
+            .as_object_mut()
+            .ok_or_else(|| {
+                Error::from_invalid_argument("The item must be serialized to an object".into())
+            })?
+            .insert("key".to_owned(), key.into());
+
+        let error = match self.insert_item(&value).await {
+            Ok(inserted) => return Ok(serde_json::from_value(inserted)?),
+            Err(error) if error.is_conflict() => error,
+            Err(error) => return Err(error),
+        };
+
+        // The item may be deleted in the meantime, then the conflict is the best explanation of the failure.
+        match self.get_item(key).await? {
+            Some(stored) => Ok(stored),
+            None => Err(error),
+        }
+    }
+
+    /// Inserts the `item` without a typed model. Returns the key of the stored item,
+    /// generated by deta if the `item` has no key, along with the stored item.
+    /// The `item` must be a JSON object, otherwise an error is returned without sending a request.
+    pub async fn insert_raw(&self, item: serde_json::Value) -> Result<(String, serde_json::Value)> {
+        ensure_object(&item)?;
+        let stored: serde_json::Value = self.insert_item(&item).await?;
+        let key = item_key(&stored)?;
+        Ok((key, stored))
+    }
+
+    /// Puts the `items` without a typed model, like [`Database::put_items`](Database::put_items).
+    /// Returns the keys of the processed items in the order of the response, including the keys generated by deta.
+    /// Items which deta failed to process are skipped, use `put_items` to inspect them.
+    /// Every item must be a JSON object, otherwise an error is returned without sending a request.
+    pub async fn put_raw(&self, items: Vec<serde_json::Value>) -> Result<Vec<String>> {
+        for item in &items {
+            ensure_object(item)?;
+        }
+        let result = self.put_items(&items).await?;
+        result.processed.iter().map(item_key).collect()
     }
 
     /// Fetch items for database.
@@ -94,37 +309,854 @@ impl Database {
     where
         T: DeserializeOwned,
     {
+        self.fetch_items_with(FetchOptions {
+            limit,
+            last: last.map(str::to_owned),
+            query,
+            order: None,
+        })
+        .await
+    }
+
+    /// Works like [`Database::fetch_items`](Database::fetch_items),
+    /// but allows to set the [`Order`](Order) of the items with [`FetchOptions`](FetchOptions).
+    pub async fn fetch_items_with<T>(&self, options: FetchOptions) -> Result<models::FetchItems<T>>
+    where
+        T: DeserializeOwned,
+    {
+        let response = self.query_items_response(options).await?;
+        utils::parse_response_body(response).await
+    }
+
+    /// Works like [`Database::fetch_items_with`](Database::fetch_items_with), but keeps the body of the response
+    /// as it was received, so that the items can be deserialized borrowing from it, see [`FetchedPage`](models::FetchedPage).
+    /// It avoids copying the fields which are skipped, or which are only passed on as raw JSON.
+    pub async fn fetch_items_borrowed(&self, options: FetchOptions) -> Result<models::FetchedPage> {
+        let response = self.query_items_response(options).await?;
+        let body = utils::read_response_body(response).await?;
+        Ok(models::FetchedPage { body })
+    }
+
+    async fn query_items_response(&self, options: FetchOptions) -> Result<reqwest::Response> {
         let query_value;
-        if let Some(query) = query {
+        if let Some(query) = options.query {
             query_value = Some(query.render()?);
         } else {
             query_value = None;
         }
 
-        let response = requests::query_items_request(
+        requests::query_items_request(
+            self.transport.as_ref(),
             &self.base_url,
             &self.x_api_key,
-            limit,
-            last,
+            options.limit,
+            options.last.as_deref(),
             query_value,
+            options.order,
         )
-        .await?;
-        utils::parse_response_body(response).await
+        .await
     }
 
-    /// Updates an item with the specified key.
+    /// Updates an item with the specified key, or returns `None` if the item doesn't exist.
+    /// If the base itself doesn't exist, an error is returned, see [`Error::is_target_not_found`](Error::is_target_not_found).
     /// The updates are described by the [`Updates`](updates::Updates) type.
     /// Check [deta docs](https://docs.deta.sh/docs/base/sdk/#update-operations) for more information.
+    /// The key may be a validated [`Key`](Key) or a plain string.
+    ///
+    /// The updates of a field whose name contains a dot, named with [`Attr::literal`](updates::Attr::literal),
+    /// can't be sent to deta, so they're applied by getting the item and putting it back changed.
+    /// **This isn't atomic**, see the [`updates`](updates) module documentation.
     pub async fn update_item(
+        &self,
+        key: impl AsRef<str>,
+        updates: updates::Updates,
+    ) -> Result<Option<models::UpdateItem>> {
+        let key = key.as_ref();
+        let result = self.send_update(key, updates).await;
+        // A missing item is left as it is.
+        self.observers.notify(&result, |result| match result {
+            Ok(updated) => MutationEvent::succeeded(
+                MutationKind::Update,
+                updated.iter().map(|_| key.to_owned()).collect(),
+                vec![],
+            ),
+            Err(error) => MutationEvent::failed(MutationKind::Update, vec![key.to_owned()], error),
+        });
+        result
+    }
+
+    // Updates the item without reporting the change to the observers.
+    async fn send_update(
         &self,
         key: &str,
         updates: updates::Updates,
-    ) -> Result<models::UpdateItem> {
-        let response_result =
-            requests::update_item_request(&self.base_url, &self.x_api_key, key, updates.render()?)
-                .await;
+    ) -> Result<Option<models::UpdateItem>> {
+        utils::validate_not_blank("key", key)?;
+        if updates.has_dotted_fields() {
+            return self.update_item_by_put(key, updates).await;
+        }
+
+        let response_result = requests::update_item_request(
+            self.transport.as_ref(),
+            &self.base_url,
+            &self.x_api_key,
+            key,
+            updates.render()?,
+        )
+        .await;
+
+        if let Err(ref error) = response_result {
+            if error.is_not_found() {
+                return Ok(None);
+            }
+        }
 
         let response = response_result?;
-        utils::parse_response_body(response).await
+        utils::parse_response_body(response).await.map(Some)
+    }
+
+    /// Sends a request to an endpoint of the base not covered by the SDK, at the `path` under the url of the base,
+    /// e.g. `items/a` for `https://database.deta.sh/v1/{project_id}/{base_name}/items/a`.
+    /// The api key is attached and the errors are reported like by the other methods.
+    /// Returns the JSON body of the response, `null` if it's empty.
+    ///
+    /// The `path` can't lead outside of the url of the base, e.g. with `..` segments.
+    pub async fn raw_request(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        body: Option<serde_json::Value>,
+    ) -> Result<serde_json::Value> {
+        let response = utils::send_raw_request(
+            self.transport.as_ref(),
+            &self.base_url,
+            &self.x_api_key,
+            method,
+            path,
+            body,
+        )
+        .await?;
+        utils::parse_raw_json_response(response).await
+    }
+
+    // Applies the updates deta can't express to the item got from it, and puts the item back.
+    async fn update_item_by_put(
+        &self,
+        key: &str,
+        updates: updates::Updates,
+    ) -> Result<Option<models::UpdateItem>> {
+        let mut item: serde_json::Value = match self.get_item(key).await? {
+            Some(item) => item,
+            None => return Ok(None),
+        };
+        let mut rendered = updates.apply(&mut item)?;
+
+        // Reported as the update by the caller, not as a put.
+        let result = self.send_put_items(&[item], PutOptions::default()).await?;
+        if result.has_failures() {
+            return Err(Error::from_other(
+                format!("Deta failed to put the updated item '{}'", key),
+                None,
+            ));
+        }
+        rendered["key"] = key.into();
+        serde_json::from_value(rendered)
+            .map(Some)
+            .map_err(|error| Error::from_failed_deserialization(Some(error.to_string())))
+    }
+}
+
+// Rejects the raw items other than JSON objects before sending them.
+fn ensure_object(item: &serde_json::Value) -> Result<()> {
+    if item.is_object() {
+        Ok(())
+    } else {
+        Err(Error::from_invalid_argument(
+            "The item must be a JSON object".into(),
+        ))
+    }
+}
+
+// Reads the keys of the items serialized to objects, skipping the items without a key.
+fn serialized_keys<'a, T>(items: impl IntoIterator<Item = &'a T>) -> Vec<String>
+where
+    T: Serialize + 'a,
+{
+    items
+        .into_iter()
+        .filter_map(|item| serde_json::to_value(item).ok())
+        .filter_map(|item| item_key(&item).ok())
+        .collect()
+}
+
+// Reads the key of the item returned by deta.
+fn item_key(item: &serde_json::Value) -> Result<String> {
+    match item.get("key").and_then(serde_json::Value::as_str) {
+        Some(key) => Ok(key.to_owned()),
+        None => Err(Error::from_failed_deserialization(Some(item.to_string()))),
+    }
+}
+
+// Attaches the key of the sent item to the conflict error, since deta's messages don't name the existing key.
+fn with_item_key<T>(error: Error, item: &T) -> Error
+where
+    T: Serialize,
+{
+    if !error.is_conflict() {
+        return error;
+    }
+    let key = serde_json::to_value(item)
+        .ok()
+        .and_then(|value| value.get("key")?.as_str().map(str::to_owned));
+    match key {
+        Some(key) => error.with_conflicting_key(&key),
+        None => error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{FakeBase, MockTransport};
+    use serde_json::json;
+    use tokio::sync::mpsc;
+    use wiremock::matchers::{body_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[derive(Serialize, serde::Deserialize, Debug, PartialEq)]
+    struct Counter {
+        key: Option<String>,
+        value: u32,
+    }
+
+    // Creates a database sending the requests through the `transport`.
+    fn mocked(transport: &MockTransport) -> Database {
+        Database::new(&DetaClient::new("project_key"), "base")
+            .unwrap()
+            .with_transport(Arc::new(transport.clone()))
+    }
+
+    #[tokio::test]
+    async fn get_item_for_missing_item() {
+        let transport = MockTransport::new();
+        transport.respond("GET", "/items/a", 404, json!({ "key": "a" }));
+
+        let item = mocked(&transport).get_item::<Counter>("a").await.unwrap();
+
+        assert_eq!(item, None);
+        let requests = transport.requests();
+        assert_eq!(
+            requests[0].url,
+            format!("{}/project/base/items/a", constants::DATABASE_API_URL)
+        );
+        assert_eq!(requests[0].header("x-api-key"), None);
+    }
+
+    #[tokio::test]
+    async fn fetch_items_borrowed_from_body() {
+        #[derive(serde::Deserialize, Debug)]
+        struct Title<'a> {
+            title: &'a str,
+        }
+
+        let transport = MockTransport::new();
+        transport.respond_raw(
+            "POST",
+            "/base/query",
+            200,
+            "application/json",
+            r#"{"paging":{"size":2},"items":[{"key":"a","title":"A","body":"x"},{"key":"b","title":"B","body":"y"}]}"#,
+        );
+        transport.respond_raw(
+            "POST",
+            "/base2/query",
+            200,
+            "application/json",
+            b"{\"paging\":{\"size\":1},\"items\":[{\"title\":\"\xff\"}]}".to_vec(),
+        );
+        let database = mocked(&transport);
+
+        let page = database
+            .fetch_items_borrowed(FetchOptions::default())
+            .await
+            .unwrap();
+        let raw = page.raw_items().unwrap();
+        assert_eq!(raw.paging.size, 2);
+        assert_eq!(raw.items[1].get(), r#"{"key":"b","title":"B","body":"y"}"#);
+        let titles: Vec<&str> = page
+            .items::<Title>()
+            .unwrap()
+            .iter()
+            .map(|item| item.title)
+            .collect();
+        assert_eq!(titles, ["A", "B"]);
+
+        let invalid = Database::new(&DetaClient::new("project_key"), "base2")
+            .unwrap()
+            .with_transport(Arc::new(transport.clone()));
+        let error = invalid
+            .fetch_items_borrowed(FetchOptions::default())
+            .await
+            .unwrap()
+            .items::<Title>()
+            .unwrap_err();
+        assert!(error.is_body_deserialization());
+        assert_eq!(
+            error.get_raw_response_data(),
+            Some("{\"paging\":{\"size\":1},\"items\":[{\"title\":\"\u{fffd}\"}]}")
+        );
+    }
+
+    #[tokio::test]
+    async fn item_key_is_encoded_in_url() {
+        let transport = MockTransport::new();
+        transport.respond(
+            "GET",
+            "/items/a%2Fb%3Fc%23d%25%20",
+            200,
+            json!({ "value": 1 }),
+        );
+
+        let database = mocked(&transport);
+        database
+            .get_item::<serde_json::Value>("a/b?c#d% ")
+            .await
+            .unwrap();
+        database.delete_item("visits#3").await.unwrap_err();
+
+        let requests = transport.requests();
+        assert!(requests[0]
+            .url
+            .ends_with("/project/base/items/a%2Fb%3Fc%23d%25%20"));
+        assert!(requests[1].url.ends_with("/project/base/items/visits%233"));
+    }
+
+    #[tokio::test]
+    async fn blank_keys_and_names_are_rejected_locally() {
+        let transport = MockTransport::new();
+        let database = mocked(&transport);
+        for key in ["", "  ", "\n"] {
+            let error = database.get_item::<Counter>(key).await.unwrap_err();
+            assert!(error.is_invalid_argument());
+            assert!(error.to_string().contains("Invalid key"));
+            let error = database.delete_item(key).await.unwrap_err();
+            assert!(error.is_invalid_argument());
+            let updates = updates::Updates::init().add("value", updates::Action::increment(1));
+            let error = database.update_item(key, updates).await.unwrap_err();
+            assert!(error.is_invalid_argument());
+            let error = database
+                .get_or_insert(key, &json!({ "value": 1 }))
+                .await
+                .unwrap_err();
+            assert!(error.is_invalid_argument());
+        }
+
+        assert!(transport.requests().is_empty());
+
+        for name in ["", " ", "\t"] {
+            let error = Database::new(&DetaClient::new("project_key"), name)
+                .err()
+                .unwrap();
+            assert!(error.is_invalid_argument());
+            assert!(error.to_string().contains("Invalid database name"));
+        }
+    }
+
+    #[tokio::test]
+    async fn raw_request_to_made_up_endpoint() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/project/base/indexes/by_age"))
+            .and(wiremock::matchers::header("X-Api-Key", "test_key"))
+            .and(body_json(json!({ "field": "age" })))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!({ "created": true })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(path("/project/base/indexes/by_age"))
+            .respond_with(ResponseTemplate::new(204))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/project/base/indexes/missing"))
+            .respond_with(
+                ResponseTemplate::new(404).set_body_json(json!({ "errors": ["Index not found"] })),
+            )
+            .mount(&server)
+            .await;
+
+        let database = Database::with_base_url(&format!("{}/project/base", server.uri()));
+        let created = database
+            .raw_request(
+                reqwest::Method::POST,
+                "/indexes/by_age",
+                Some(json!({ "field": "age" })),
+            )
+            .await
+            .unwrap();
+        assert_eq!(created, json!({ "created": true }));
+        let deleted = database
+            .raw_request(reqwest::Method::DELETE, "indexes/by_age", None)
+            .await
+            .unwrap();
+        assert_eq!(deleted, serde_json::Value::Null);
+
+        let error = database
+            .raw_request(reqwest::Method::GET, "indexes/missing", None)
+            .await
+            .unwrap_err();
+        assert!(error.is_not_found());
+        assert_eq!(
+            error.server_errors(),
+            Some(&["Index not found".to_owned()][..])
+        );
+        assert_eq!(error.method(), Some("GET"));
+        let error = database
+            .raw_request(reqwest::Method::GET, "../other_base/items", None)
+            .await
+            .unwrap_err();
+        assert!(error.is_invalid_argument());
+    }
+
+    #[tokio::test]
+    async fn get_item_for_missing_base() {
+        let transport = MockTransport::new();
+        transport.respond(
+            "GET",
+            "/items/a",
+            404,
+            json!({ "errors": ["Base not found"] }),
+        );
+
+        let error = mocked(&transport)
+            .get_item::<Counter>("a")
+            .await
+            .unwrap_err();
+
+        assert!(error.is_target_not_found());
+    }
+
+    #[tokio::test]
+    async fn get_or_insert_inserts_item() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/items"))
+            .and(body_json(json!({ "item": { "key": "a", "value": 1 } })))
+            .respond_with(
+                ResponseTemplate::new(201).set_body_json(json!({ "key": "a", "value": 1 })),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let database = Database::with_base_url(&server.uri());
+        let item = Counter {
+            key: None,
+            value: 1,
+        };
+        let result = database.get_or_insert("a", &item).await.unwrap();
+
+        assert_eq!(
+            result,
+            Counter {
+                key: Some("a".into()),
+                value: 1
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn get_or_insert_returns_existing_item() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/items"))
+            .respond_with(
+                ResponseTemplate::new(409)
+                    .set_body_json(json!({ "errors": ["Key already exists"] })),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/items/a"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(json!({ "key": "a", "value": 7 })),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let database = Database::with_base_url(&server.uri());
+        let item = Counter {
+            key: None,
+            value: 1,
+        };
+        let result = database.get_or_insert("a", &item).await.unwrap();
+
+        assert_eq!(result.value, 7);
+    }
+
+    #[tokio::test]
+    async fn get_or_insert_reports_conflicting_key_for_deleted_item() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/items"))
+            .respond_with(
+                ResponseTemplate::new(409)
+                    .set_body_json(json!({ "errors": ["Key already exists"] })),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/items/a"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&server)
+            .await;
+
+        let database = Database::with_base_url(&server.uri());
+        let item = Counter {
+            key: None,
+            value: 1,
+        };
+        let error = database.get_or_insert("a", &item).await.unwrap_err();
+
+        assert!(error.is_conflict());
+        assert_eq!(error.conflicting_key(), Some("a"));
+    }
+
+    #[tokio::test]
+    async fn insert_raw_returns_generated_key() {
+        let transport = MockTransport::new();
+        transport.respond("POST", "/items", 201, json!({ "key": "x1", "value": 1 }));
+
+        let (key, stored) = mocked(&transport)
+            .insert_raw(json!({ "value": 1 }))
+            .await
+            .unwrap();
+
+        assert_eq!(key, "x1");
+        assert_eq!(stored, json!({ "key": "x1", "value": 1 }));
+        assert_eq!(
+            transport.requests()[0].json(),
+            Some(json!({ "item": { "value": 1 } }))
+        );
+    }
+
+    #[tokio::test]
+    async fn insert_raw_fails_for_response_without_key() {
+        let transport = MockTransport::new();
+        transport.respond("POST", "/items", 201, json!({ "value": 1 }));
+
+        let error = mocked(&transport)
+            .insert_raw(json!({ "value": 1 }))
+            .await
+            .unwrap_err();
+
+        assert!(error.is_body_deserialization());
+    }
+
+    #[tokio::test]
+    async fn put_raw_returns_keys_in_order() {
+        let transport = MockTransport::new();
+        transport.respond(
+            "PUT",
+            "/items",
+            207,
+            json!({
+                "processed": { "items": [{ "key": "a", "value": 1 }, { "key": "x2", "value": 2 }] }
+            }),
+        );
+
+        let items = vec![json!({ "key": "a", "value": 1 }), json!({ "value": 2 })];
+        let keys = mocked(&transport).put_raw(items.clone()).await.unwrap();
+
+        assert_eq!(keys, vec!["a".to_owned(), "x2".to_owned()]);
+        assert_eq!(
+            transport.requests()[0].json(),
+            Some(json!({ "items": items }))
+        );
+    }
+
+    #[tokio::test]
+    async fn raw_items_must_be_objects() {
+        let transport = MockTransport::new();
+        let database = mocked(&transport);
+
+        let error = database.insert_raw(json!([1, 2])).await.unwrap_err();
+        assert!(error.is_invalid_argument());
+
+        let error = database
+            .put_raw(vec![json!({ "value": 1 }), json!("text")])
+            .await
+            .unwrap_err();
+        assert!(error.is_invalid_argument());
+        assert!(transport.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn insert_item_reports_conflicting_key() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/items"))
+            .respond_with(
+                ResponseTemplate::new(409)
+                    .set_body_json(json!({ "errors": ["Key already exists"] })),
+            )
+            .mount(&server)
+            .await;
+
+        let database = Database::with_base_url(&server.uri());
+        let item = Counter {
+            key: Some("b".into()),
+            value: 1,
+        };
+        let error = database.insert_item(&item).await.unwrap_err();
+
+        assert_eq!(error.conflicting_key(), Some("b"));
+    }
+
+    #[tokio::test]
+    async fn get_or_insert_fails_for_other_errors() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/items"))
+            .respond_with(ResponseTemplate::new(401))
+            .mount(&server)
+            .await;
+
+        let database = Database::with_base_url(&server.uri());
+        let item = Counter {
+            key: None,
+            value: 1,
+        };
+        let error = database.get_or_insert("a", &item).await.unwrap_err();
+
+        assert!(error.is_unauthorized());
+        assert_eq!(server.received_requests().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn get_or_insert_rejects_non_object_item() {
+        let database = Database::with_base_url("http://localhost");
+        let error = database.get_or_insert("a", &1).await.unwrap_err();
+        assert!(error.is_invalid_argument());
+    }
+
+    // Three items, of which `b` exceeds the default size limit.
+    fn items_with_oversized() -> Vec<serde_json::Value> {
+        vec![
+            json!({ "key": "a", "text": "short" }),
+            json!({ "key": "b", "text": "x".repeat(constants::MAX_ITEM_SIZE) }),
+            json!({ "key": "c", "text": "short" }),
+        ]
+    }
+
+    #[tokio::test]
+    async fn put_items_rejects_oversized_item() {
+        let transport = MockTransport::new();
+        let items = items_with_oversized();
+
+        let error = mocked(&transport).put_items(&items).await.unwrap_err();
+
+        assert!(error.is_invalid_argument());
+        let message = error.to_string();
+        assert!(
+            message.contains("#1 (key 'b', 409621 bytes)"),
+            "{}",
+            message
+        );
+        assert!(!message.contains("'a'") && !message.contains("'c'"));
+        assert!(transport.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn put_items_with_skips_oversized_item() {
+        let transport = MockTransport::new();
+        transport.respond(
+            "PUT",
+            "/items",
+            207,
+            json!({ "processed": { "items": [
+                { "key": "a", "text": "short" },
+                { "key": "c", "text": "short" }
+            ] } }),
+        );
+        let items = items_with_oversized();
+
+        let options = PutOptions {
+            skip_oversized: true,
+        };
+        let result = mocked(&transport)
+            .put_items_with(&items, options)
+            .await
+            .unwrap();
+
+        assert_eq!(result.processed.len(), 2);
+        assert_eq!(
+            result.oversized,
+            vec![models::OversizedItem {
+                index: 1,
+                key: Some("b".into()),
+                size: 409621
+            }]
+        );
+        assert!(result.has_failures());
+        let requests = transport.requests();
+        assert_eq!(
+            requests[0].json().unwrap(),
+            json!({ "items": [&items[0], &items[2]] })
+        );
+    }
+
+    #[tokio::test]
+    async fn put_items_with_sends_nothing_if_all_items_are_skipped() {
+        let transport = MockTransport::new();
+        let database = mocked(&transport).with_max_item_size(10);
+
+        let options = PutOptions {
+            skip_oversized: true,
+        };
+        let result = database
+            .put_items_with(&[json!({ "key": "a", "text": "long" })], options)
+            .await
+            .unwrap();
+
+        assert!(result.processed.is_empty());
+        assert_eq!(result.oversized.len(), 1);
+        assert!(transport.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn insert_item_respects_max_item_size() {
+        let transport = MockTransport::new();
+        transport.respond("POST", "/items", 201, json!({ "key": "a", "value": 1 }));
+        let item = Counter {
+            key: Some("a".into()),
+            value: 1,
+        };
+
+        let error = mocked(&transport)
+            .with_max_item_size(20)
+            .insert_item(&item)
+            .await
+            .unwrap_err();
+        assert!(error.is_invalid_argument());
+        assert!(error.to_string().contains("#0 (key 'a', 21 bytes)"));
+        assert!(transport.requests().is_empty());
+
+        let inserted = mocked(&transport)
+            .with_max_item_size(21)
+            .insert_item(&item)
+            .await
+            .unwrap();
+        assert_eq!(inserted, item);
+    }
+
+    // Registers an observer passing the events on to the returned receiver.
+    fn observed(database: Database) -> (Database, mpsc::UnboundedReceiver<MutationEvent>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let database = database.on_mutation(Arc::new(move |event| {
+            let _ = sender.send(event);
+        }));
+        (database, receiver)
+    }
+
+    async fn next_event(events: &mut mpsc::UnboundedReceiver<MutationEvent>) -> MutationEvent {
+        tokio::time::timeout(std::time::Duration::from_secs(5), events.recv())
+            .await
+            .expect("The event is reported")
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn mutation_events_of_every_method() {
+        let base = FakeBase::new();
+        let (database, mut events) = observed(base.database());
+
+        let put = database
+            .put_items(&[json!({ "key": "a", "value": 1 }), json!({ "value": 2 })])
+            .await
+            .unwrap();
+        let generated = item_key(&put.processed.items[1]).unwrap();
+        assert_eq!(
+            next_event(&mut events).await,
+            MutationEvent::succeeded(MutationKind::Put, vec!["a".into(), generated], vec![])
+        );
+
+        let inserted = database.insert_item(&json!({ "value": 3 })).await.unwrap();
+        let event = next_event(&mut events).await;
+        assert_eq!(event.kind, MutationKind::Insert);
+        assert_eq!(event.keys, vec![item_key(&inserted).unwrap()]);
+
+        let updates = || updates::Updates::init().add("value", updates::Action::increment(1));
+        database.update_item("a", updates()).await.unwrap();
+        assert_eq!(
+            next_event(&mut events).await,
+            MutationEvent::succeeded(MutationKind::Update, vec!["a".into()], vec![])
+        );
+        // Applied by getting and putting the item, but still reported as an update.
+        let dotted =
+            updates::Updates::init().add(updates::Attr::literal("a.b"), updates::Action::set(1));
+        database.update_item("a", dotted).await.unwrap();
+        assert_eq!(next_event(&mut events).await.kind, MutationKind::Update);
+
+        // Nothing changed for a missing item.
+        let updated = database.update_item("missing", updates()).await.unwrap();
+        assert!(updated.is_none());
+        assert_eq!(
+            next_event(&mut events).await,
+            MutationEvent::succeeded(MutationKind::Update, vec![], vec![])
+        );
+
+        database.delete_item("a").await.unwrap();
+        assert_eq!(
+            next_event(&mut events).await,
+            MutationEvent::succeeded(MutationKind::Delete, vec!["a".into()], vec![])
+        );
+
+        // Rejected before sending a request, so nothing changed.
+        database.delete_item(" ").await.unwrap_err();
+        drop(database);
+        assert!(events.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn put_event_reports_failed_items() {
+        let transport = MockTransport::new();
+        transport.respond(
+            "PUT",
+            "/items",
+            207,
+            json!({
+                "processed": { "items": [{ "key": "a", "value": 1 }] },
+                "failed": { "items": [{ "key": "b", "value": 2 }] },
+            }),
+        );
+        let database = mocked(&transport).on_mutation(Arc::new(|_| panic!("Broken observer")));
+        let (database, mut events) = observed(database);
+
+        let items = [
+            Counter {
+                key: Some("a".into()),
+                value: 1,
+            },
+            Counter {
+                key: Some("b".into()),
+                value: 2,
+            },
+        ];
+        let result = database.put_items(&items).await.unwrap();
+        assert!(result.has_failures());
+        let event = next_event(&mut events).await;
+        assert_eq!(event.keys, vec!["a".to_owned()]);
+        assert_eq!(event.failed_keys, vec!["b".to_owned()]);
+        assert!(!event.is_success());
+
+        // The request fails without a mocked response.
+        let (database, mut events) = observed(mocked(&MockTransport::new()));
+        database.put_items(&items).await.unwrap_err();
+        let event = next_event(&mut events).await;
+        assert_eq!(event.keys, vec!["a".to_owned(), "b".to_owned()]);
+        assert!(event.error.is_some());
     }
 }
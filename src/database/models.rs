@@ -7,10 +7,91 @@ pub struct Items<T> {
     pub items: Vec<T>,
 }
 
+impl<T> Items<T> {
+    /// Returns an iterator over the items.
+    pub fn iter(&self) -> std::slice::Iter<'_, T> {
+        self.items.iter()
+    }
+
+    /// Returns the number of the items.
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Checks whether there are no items.
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+}
+
+impl<T> IntoIterator for Items<T> {
+    type Item = T;
+    type IntoIter = std::vec::IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.into_iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a Items<T> {
+    type Item = &'a T;
+    type IntoIter = std::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.iter()
+    }
+}
+
+// Implemented manually, as deriving it would require `T: Default`.
+impl<T> Default for Items<T> {
+    fn default() -> Self {
+        Self { items: vec![] }
+    }
+}
+
+/// The result of [`Database::put_items`](super::Database::put_items).
+/// Missing `processed` and `failed` sections are accepted, as deta omits them in some responses.
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct PutItems<T> {
+    // A plain `default` would require `T: Default`. A missing `failed` section is `None` anyway.
+    #[serde(default = "Items::default")]
     pub processed: Items<T>,
     pub failed: Option<Items<T>>,
+    /// Items skipped without sending them, as they exceed the size limit.
+    /// Filled only by [`Database::put_items_with`](super::Database::put_items_with)
+    /// with [`PutOptions::skip_oversized`](super::PutOptions::skip_oversized) set.
+    #[serde(skip)]
+    pub oversized: Vec<OversizedItem>,
+}
+
+impl<T> PutItems<T> {
+    /// Checks whether any of the items couldn't be put, including the skipped [`oversized`](PutItems::oversized) ones.
+    pub fn has_failures(&self) -> bool {
+        self.failed
+            .as_ref()
+            .is_some_and(|failed| !failed.is_empty())
+            || !self.oversized.is_empty()
+    }
+}
+
+/// An item exceeding the size limit of deta, see [`Database::with_max_item_size`](super::Database::with_max_item_size).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OversizedItem {
+    /// Position of the item in the given items.
+    pub index: usize,
+    /// Key of the item, if it has one.
+    pub key: Option<String>,
+    /// Size of the serialized item in bytes.
+    pub size: usize,
+}
+
+impl std::fmt::Display for OversizedItem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.key {
+            Some(key) => write!(f, "#{} (key '{}', {} bytes)", self.index, key, self.size),
+            None => write!(f, "#{} ({} bytes)", self.index, self.size),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -18,12 +99,129 @@ pub struct DeleteItem {
     pub key: String
 }
 
+/// A page of the fetched items. It can be iterated over directly:
+///
+/// ```no_run
+/// # async fn run(database: deta_rust::database::Database) -> deta_rust::error::Result<()> {
+/// let fetched = database.fetch_items::<serde_json::Value>(None, None, None).await?;
+/// for item in &fetched {
+///     println!("{}", item);
+/// }
+/// let items: Vec<serde_json::Value> = fetched.into_iter().collect();
+/// # Ok(())
+/// # }
+/// ```
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct FetchItems<T> {
     pub paging: FetchItemsPaging,
     pub items: Vec<T>
 }
 
+impl<T> FetchItems<T> {
+    /// Returns an iterator over the fetched items.
+    pub fn iter(&self) -> std::slice::Iter<'_, T> {
+        self.items.iter()
+    }
+
+    /// Returns the number of the fetched items.
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Checks whether there are no fetched items.
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Appends the items of the `next` page, which is expected to follow this one.
+    /// The paging of the `next` page is kept, with the size counting all the items.
+    pub fn merge(mut self, next: FetchItems<T>) -> FetchItems<T> {
+        self.extend_from(next);
+        self
+    }
+
+    /// Appends the items of the `next` page in place, see [`FetchItems::merge`].
+    pub fn extend_from(&mut self, mut next: FetchItems<T>) {
+        self.items.append(&mut next.items);
+        self.paging = FetchItemsPaging {
+            size: self.items.len(),
+            last: next.paging.last,
+        };
+    }
+}
+
+impl<T> IntoIterator for FetchItems<T> {
+    type Item = T;
+    type IntoIter = std::vec::IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.into_iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a FetchItems<T> {
+    type Item = &'a T;
+    type IntoIter = std::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.iter()
+    }
+}
+
+/// The body of a fetch response kept as it was received, returned by
+/// [`Database::fetch_items_borrowed`](super::Database::fetch_items_borrowed).
+///
+/// The items are deserialized from the body on demand, into models which may borrow from it,
+/// so the fields which aren't needed are never copied. For example, with `&RawValue` items
+/// only the boundaries of the items are found, and each item can be parsed later, or passed on as it is:
+///
+/// ```no_run
+/// use deta_rust::database::FetchOptions;
+/// use serde::Deserialize;
+/// use std::borrow::Cow;
+///
+/// #[derive(Deserialize)]
+/// struct Title<'a> {
+///     #[serde(borrow)]
+///     title: Cow<'a, str>,
+/// }
+///
+/// # async fn run(database: deta_rust::database::Database) -> deta_rust::error::Result<()> {
+/// let page = database.fetch_items_borrowed(FetchOptions::default()).await?;
+/// for raw in page.raw_items()?.iter() {
+///     println!("{}", raw.get());
+/// }
+/// // Only the titles are read, other fields are skipped without allocating.
+/// let titles = page.items::<Title>()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct FetchedPage {
+    pub(crate) body: bytes::Bytes,
+}
+
+impl FetchedPage {
+    /// Deserializes the items, borrowing from the body where the model allows it.
+    /// On failure, the error keeps a lossy UTF-8 copy of the body as its raw response data.
+    pub fn items<'a, T>(&'a self) -> crate::error::Result<FetchItems<T>>
+    where
+        T: Deserialize<'a>,
+    {
+        crate::utils::parse_borrowed(&self.body)
+    }
+
+    /// Returns the items as raw JSON, without parsing their fields.
+    pub fn raw_items(&self) -> crate::error::Result<FetchItems<&serde_json::value::RawValue>> {
+        self.items()
+    }
+
+    /// Returns the body as it was received.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.body
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct FetchItemsPaging {
     pub size: usize,
@@ -39,3 +237,140 @@ pub struct UpdateItem {
     pub prepend: Option<serde_json::Value>,
     pub delete: Option<serde_json::Value>
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fetched(items: Vec<u32>) -> FetchItems<u32> {
+        FetchItems {
+            paging: FetchItemsPaging {
+                size: items.len(),
+                last: None,
+            },
+            items,
+        }
+    }
+
+    fn page(items: Vec<u32>, last: Option<&str>) -> FetchItems<u32> {
+        let mut page = fetched(items);
+        page.paging.last = last.map(str::to_owned);
+        page
+    }
+
+    #[test]
+    fn merge_fetch_items() {
+        let merged = page(vec![1, 2], Some("2")).merge(page(vec![3], Some("3")));
+
+        assert_eq!(merged.items, vec![1, 2, 3]);
+        assert_eq!(merged.paging.size, 3);
+        assert_eq!(merged.paging.last.as_deref(), Some("3"));
+
+        let merged = merged.merge(page(vec![4], None));
+        assert_eq!(merged.items, vec![1, 2, 3, 4]);
+        assert_eq!(merged.paging.size, 4);
+        assert_eq!(merged.paging.last, None);
+    }
+
+    #[test]
+    fn merge_empty_fetch_items() {
+        let merged = page(vec![1, 2], Some("2")).merge(page(vec![], None));
+        assert_eq!(merged.items, vec![1, 2]);
+        assert_eq!(merged.paging.size, 2);
+        assert_eq!(merged.paging.last, None);
+
+        let mut items = page(vec![], Some("0"));
+        items.extend_from(page(vec![1], None));
+        assert_eq!(items.items, vec![1]);
+        assert_eq!(items.paging.size, 1);
+        assert_eq!(items.paging.last, None);
+    }
+
+    #[test]
+    fn iterate_fetch_items() {
+        let fetched = fetched(vec![1, 2, 3]);
+
+        assert_eq!(fetched.len(), 3);
+        assert!(!fetched.is_empty());
+        assert_eq!(fetched.iter().sum::<u32>(), 6);
+        assert_eq!((&fetched).into_iter().max(), Some(&3));
+        assert_eq!(fetched.into_iter().collect::<Vec<_>>(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn iterate_empty_fetch_items() {
+        let fetched = fetched(vec![]);
+
+        assert_eq!(fetched.len(), 0);
+        assert!(fetched.is_empty());
+        assert_eq!(fetched.iter().next(), None);
+        assert_eq!(fetched.into_iter().next(), None);
+    }
+
+    fn put_items(body: serde_json::Value) -> PutItems<u32> {
+        serde_json::from_value(body).unwrap()
+    }
+
+    #[test]
+    fn put_items_with_failed_section() {
+        let result = put_items(serde_json::json!({
+            "processed": { "items": [1] },
+            "failed": { "items": [2] }
+        }));
+
+        assert_eq!(result.processed.items, vec![1]);
+        assert_eq!(result.failed.as_ref().unwrap().items, vec![2]);
+        assert!(result.has_failures());
+    }
+
+    #[test]
+    fn put_items_with_null_failed_section() {
+        let result = put_items(serde_json::json!({
+            "processed": { "items": [1] },
+            "failed": null
+        }));
+
+        assert_eq!(result.processed.items, vec![1]);
+        assert!(result.failed.is_none());
+        assert!(!result.has_failures());
+    }
+
+    #[test]
+    fn put_items_without_failed_section() {
+        let result = put_items(serde_json::json!({ "processed": { "items": [1] } }));
+
+        assert_eq!(result.processed.items, vec![1]);
+        assert!(!result.has_failures());
+    }
+
+    #[test]
+    fn put_items_with_empty_failed_section() {
+        let result = put_items(serde_json::json!({
+            "processed": { "items": [1] },
+            "failed": { "items": [] }
+        }));
+
+        assert!(!result.has_failures());
+    }
+
+    #[test]
+    fn put_items_for_empty_body() {
+        let result = put_items(serde_json::json!({}));
+
+        assert!(result.processed.is_empty());
+        assert!(!result.has_failures());
+    }
+
+    #[test]
+    fn iterate_items() {
+        let items = Items { items: vec!["a", "b"] };
+        let mut names = vec![];
+        for item in &items {
+            names.push(*item);
+        }
+
+        assert_eq!(names, vec!["a", "b"]);
+        assert_eq!(items.len(), 2);
+        assert!(Items::<u32> { items: vec![] }.is_empty());
+    }
+}
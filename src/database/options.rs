@@ -0,0 +1,103 @@
+//! Options customizing the behavior of base operations.
+
+use super::query::Query;
+use crate::constants;
+use serde::Serialize;
+
+/// Options for [`Database::fetch_items_with`](super::Database::fetch_items_with).
+///
+/// ```
+/// use deta_rust::database::{query::{Condition, Query}, FetchOptions, Order};
+///
+/// let options = FetchOptions {
+///     limit: Some(20),
+///     query: Some(Query::init().on("age", Condition::greater_than(18))),
+///     order: Some(Order::Desc),
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Default)]
+pub struct FetchOptions {
+    /// Maximum number of items to scan. Defaults to `None`, which means the limit of deta.
+    pub limit: Option<u32>,
+    /// Key of the last item of the previous page, see [`FetchItemsPaging::last`](super::models::FetchItemsPaging::last).
+    /// Defaults to `None`, which means the first page.
+    pub last: Option<String>,
+    /// Conditions the items must satisfy. Defaults to `None`, which means all the items.
+    pub query: Option<Query>,
+    /// Order of the items by key. Defaults to `None`, which leaves it to deta (ascending).
+    pub order: Option<Order>,
+}
+
+/// Options for [`Database::put_items_with`](super::Database::put_items_with).
+///
+/// ```
+/// use deta_rust::database::PutOptions;
+///
+/// let options = PutOptions {
+///     skip_oversized: true,
+/// };
+/// ```
+#[derive(Default)]
+pub struct PutOptions {
+    /// Whether to put the rest of the items when some exceed the size limit,
+    /// reporting them in [`PutItems::oversized`](super::models::PutItems::oversized).
+    /// Defaults to `false`, which means such items fail the whole operation without sending a request.
+    pub skip_oversized: bool,
+}
+
+/// Options for [`Database::purge_expired_with`](super::Database::purge_expired_with).
+///
+/// ```
+/// use deta_rust::database::PurgeOptions;
+///
+/// let options = PurgeOptions {
+///     dry_run: true,
+///     ..Default::default()
+/// };
+/// ```
+pub struct PurgeOptions {
+    /// Whether to only collect the keys of the expired items, in [`PurgeStats::expired`](super::PurgeStats::expired),
+    /// without deleting them. Defaults to `false`.
+    pub dry_run: bool,
+    /// Maximum number of the items deleted at once. Defaults to 8.
+    pub concurrency: usize,
+}
+
+impl Default for PurgeOptions {
+    fn default() -> Self {
+        Self {
+            dry_run: false,
+            concurrency: constants::DEFAULT_PURGE_CONCURRENCY,
+        }
+    }
+}
+
+/// Order of the fetched items by key.
+///
+/// It's sent as the `sort` field of the query request only when it's set,
+/// so the requests without it are the same as before.
+///
+/// # Backends ignoring the order
+///
+/// Some deployments of deta base don't support the `sort` field and always return the items
+/// in ascending order of the keys. To get the newest items first from them, store the items
+/// under inverted keys instead, e.g. the time of creation subtracted from a constant
+/// and padded with zeros to a fixed width:
+///
+/// ```
+/// // Sorts ascending from the newest to the oldest item.
+/// fn inverted_key(created_at_millis: u64) -> String {
+///     format!("{:013}", 9_999_999_999_999 - created_at_millis)
+/// }
+///
+/// assert!(inverted_key(1714566600250) < inverted_key(1714566600000));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Order {
+    /// From the lowest key.
+    Asc,
+    /// From the highest key.
+    Desc,
+}
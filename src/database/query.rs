@@ -1,6 +1,9 @@
 //! Tools for defining the query to be used when fetching items from the database.
 
 use super::common::{JsonValue, StringValue};
+use crate::error::{Error, Result};
+#[cfg(feature = "chrono")]
+use crate::serde_helpers::{ts_millis, ts_seconds};
 use serde::Serialize;
 use std::borrow::Borrow;
 use std::convert::Into;
@@ -21,6 +24,7 @@ pub enum Condition {
     NotContains(StringValue),
 }
 
+// The attribute names are checked by `Query::on`, so the postfix is the only `?` in the key.
 fn set_postfix(key: StringValue, postfix: &str) -> StringValue {
     format!("{}?{}", key, postfix).into()
 }
@@ -117,6 +121,58 @@ impl Condition {
     }
 }
 
+/// Time conditions for the fields stored with [`ts_millis`](crate::serde_helpers::ts_millis)
+/// and [`ts_seconds`](crate::serde_helpers::ts_seconds). Available with the `chrono` feature.
+#[cfg(feature = "chrono")]
+impl Condition {
+    /// Matches the times after the `time`, for a field stored with [`ts_millis`](crate::serde_helpers::ts_millis).
+    pub fn after_millis(time: chrono::DateTime<chrono::Utc>) -> Condition {
+        Self::GreaterThan(ts_millis::encode(&time) as f64)
+    }
+
+    /// Matches the times before the `time`, for a field stored with [`ts_millis`](crate::serde_helpers::ts_millis).
+    pub fn before_millis(time: chrono::DateTime<chrono::Utc>) -> Condition {
+        Self::LessThan(ts_millis::encode(&time) as f64)
+    }
+
+    /// Matches the times from the `start` to the `end`, both inclusive,
+    /// for a field stored with [`ts_millis`](crate::serde_helpers::ts_millis).
+    pub fn between_millis(
+        start: chrono::DateTime<chrono::Utc>,
+        end: chrono::DateTime<chrono::Utc>,
+    ) -> Condition {
+        Self::Range(
+            ts_millis::encode(&start) as f64,
+            ts_millis::encode(&end) as f64,
+        )
+    }
+
+    /// Matches the times after the `time`, for a field stored with [`ts_seconds`](crate::serde_helpers::ts_seconds).
+    /// The fraction of a second is dropped, like when storing.
+    pub fn after_seconds(time: chrono::DateTime<chrono::Utc>) -> Condition {
+        Self::GreaterThan(ts_seconds::encode(&time) as f64)
+    }
+
+    /// Matches the times before the `time`, for a field stored with [`ts_seconds`](crate::serde_helpers::ts_seconds).
+    /// The fraction of a second is dropped, like when storing.
+    pub fn before_seconds(time: chrono::DateTime<chrono::Utc>) -> Condition {
+        Self::LessThan(ts_seconds::encode(&time) as f64)
+    }
+
+    /// Matches the times from the `start` to the `end`, both inclusive,
+    /// for a field stored with [`ts_seconds`](crate::serde_helpers::ts_seconds).
+    /// The fraction of a second is dropped, like when storing.
+    pub fn between_seconds(
+        start: chrono::DateTime<chrono::Utc>,
+        end: chrono::DateTime<chrono::Utc>,
+    ) -> Condition {
+        Self::Range(
+            ts_seconds::encode(&start) as f64,
+            ts_seconds::encode(&end) as f64,
+        )
+    }
+}
+
 /// Useful conversion to wrap an Condition type value to [`serde_json::Result`](serde_json::Result)
 /// for standardization purposes inside the `Query` type.
 impl From<Condition> for serde_json::Result<Condition> {
@@ -129,7 +185,7 @@ impl From<Condition> for serde_json::Result<Condition> {
 pub struct Query {
     // Each element in the list makes up an OR.
     // A single element represents an AND expression.
-    conditions: Vec<Vec<(StringValue, serde_json::Result<Condition>)>>,
+    conditions: Vec<Vec<(StringValue, Result<Condition>)>>,
 }
 
 impl Query {
@@ -139,16 +195,26 @@ impl Query {
     }
 
     /// Adds a new condition that the item must satisfy.
+    /// Nested attributes are separated with dots, e.g. `profile.age`.
+    ///
+    /// Deta reads the operator of a condition from the attribute name, after a `?`, e.g. `age?gt`,
+    /// and doesn't support escaping it. So an attribute name containing `?` is rejected, as well as an empty one.
+    /// The error is returned when the query is used.
     pub fn on<K, V>(mut self, key: K, condition: V) -> Self
     where
         K: Into<StringValue>,
         V: Into<serde_json::Result<Condition>>,
     {
-        if let None = self.conditions.last() {
+        let key = key.into();
+        let condition = match check_attribute(&key) {
+            Ok(()) => condition.into().map_err(Error::from),
+            Err(error) => Err(error),
+        };
+        if self.conditions.last().is_none() {
             self.conditions.push(vec![]);
         }
         if let Some(and) = self.conditions.last_mut() {
-            and.push((key.into(), condition.into()));
+            and.push((key, condition));
         }
         self
     }
@@ -156,14 +222,14 @@ impl Query {
     /// Separates alternative conditions (or statement).
     pub fn either(mut self) -> Self {
         if let Some(and) = self.conditions.last_mut() {
-            if and.len() > 0 {
+            if !and.is_empty() {
                 self.conditions.push(vec![]);
             }
         }
         self
     }
 
-    pub(crate) fn render(self) -> serde_json::Result<JsonValue> {
+    pub(crate) fn render(self) -> Result<JsonValue> {
         let mut target = vec![];
         for condition in self.conditions {
             let mut target_obj = serde_json::json!({});
@@ -176,8 +242,24 @@ impl Query {
             }
             target.push(target_obj);
         }
-        serde_json::to_value(target)
+        Ok(serde_json::to_value(target)?)
+    }
+}
+
+// Rejects the attribute names which would change the meaning of the query.
+fn check_attribute(key: &str) -> Result<()> {
+    if key.is_empty() {
+        return Err(Error::from_invalid_argument(
+            "The query attribute name cannot be empty".into(),
+        ));
+    }
+    if key.contains('?') {
+        return Err(Error::from_invalid_argument(format!(
+            "The query attribute name '{}' cannot contain '?', which separates the operator of the condition",
+            key
+        )));
     }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -219,6 +301,37 @@ mod tests {
         assert_eq!(query, target_query);
     }
 
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn render_time_conditions() {
+        use chrono::TimeZone;
+        let start = chrono::Utc.with_ymd_and_hms(2024, 5, 1, 0, 0, 0).unwrap();
+        let end = start + chrono::Duration::milliseconds(1500);
+
+        let query = Query::init()
+            .on("created_at", Condition::after_millis(start))
+            .on("updated_at", Condition::before_millis(end))
+            .on("published_at", Condition::between_millis(start, end))
+            .on("day", Condition::after_seconds(start))
+            .on("hour", Condition::before_seconds(end))
+            .on("minute", Condition::between_seconds(start, end))
+            .render()
+            .unwrap();
+
+        let target_query = serde_json::json!([
+            {
+                "created_at?gt": 1714521600000.,
+                "updated_at?lt": 1714521601500.,
+                "published_at?r": [1714521600000., 1714521601500.],
+                "day?gt": 1714521600.,
+                "hour?lt": 1714521601.,
+                "minute?r": [1714521600., 1714521601.]
+            },
+        ]);
+
+        assert_eq!(query, target_query);
+    }
+
     #[test]
     fn render_with_either_statements() {
         let query = Query::init()
@@ -307,4 +420,29 @@ mod tests {
 
         assert_eq!(query, target_query);
     }
+
+    #[test]
+    fn render_rejects_invalid_attributes() {
+        // Previously rendered as `{"age?gt": 18}`, which deta reads as a `greater than` condition.
+        let error = Query::init()
+            .on("age?gt", Condition::equal(18))
+            .render()
+            .unwrap_err();
+        assert!(error.is_invalid_argument());
+        assert!(error.to_string().contains("age?gt"));
+
+        let error = Query::init()
+            .on("name", Condition::equal("Anna"))
+            .either()
+            .on("", Condition::less_than(3))
+            .render()
+            .unwrap_err();
+        assert!(error.is_invalid_argument());
+
+        let error = Query::init()
+            .on("profile.what?", Condition::prefix("x"))
+            .render()
+            .unwrap_err();
+        assert!(error.is_invalid_argument());
+    }
 }
@@ -1,9 +1,12 @@
+use super::Order;
 use crate::error::Result;
-use crate::utils::send_request;
+use crate::transport::Transport;
+use crate::utils::send_request_via;
 use serde::Serialize;
 use serde_json::json;
 
 pub async fn put_items_request<T>(
+    transport: &dyn Transport,
     base_url: &str,
     x_api_key: &str,
     items: &[T],
@@ -16,34 +19,37 @@ where
         .header("X-Api-Key", x_api_key)
         .json(&json!({ "items": &items }));
 
-    send_request(request).await
+    send_request_via(transport, request).await
 }
 
 pub async fn get_item_request(
+    transport: &dyn Transport,
     base_url: &str,
     x_api_key: &str,
     key: &str,
 ) -> Result<reqwest::Response> {
     let request = reqwest::Client::new()
-        .get(format!("{}/items/{}", base_url, key))
+        .get(item_url(base_url, key))
         .header("X-Api-Key", x_api_key);
 
-    send_request(request).await
+    send_request_via(transport, request).await
 }
 
 pub async fn delete_item_request(
+    transport: &dyn Transport,
     base_url: &str,
     x_api_key: &str,
     key: &str,
 ) -> Result<reqwest::Response> {
     let request = reqwest::Client::new()
-        .delete(format!("{}/items/{}", base_url, key))
+        .delete(item_url(base_url, key))
         .header("X-Api-Key", x_api_key);
 
-    send_request(request).await
+    send_request_via(transport, request).await
 }
 
 pub async fn insert_item_request<T>(
+    transport: &dyn Transport,
     base_url: &str,
     x_api_key: &str,
     item: &T,
@@ -56,38 +62,60 @@ where
         .header("X-Api-Key", x_api_key)
         .json(&json!({ "item": item }));
 
-    send_request(request).await
+    send_request_via(transport, request).await
 }
 
 pub async fn query_items_request(
+    transport: &dyn Transport,
     base_url: &str,
     x_api_key: &str,
     limit: Option<u32>,
     last: Option<&str>,
     query: Option<serde_json::Value>,
+    order: Option<Order>,
 ) -> Result<reqwest::Response> {
+    let mut body = json!({
+        "limit": limit,
+        "last": last,
+        "query": query
+    });
+    // Sent only when it's set, for the deployments which don't know the field.
+    if let Some(order) = order {
+        body["sort"] = json!(order);
+    }
     let request = reqwest::Client::new()
         .post(format!("{}/query", base_url))
         .header("X-Api-Key", x_api_key)
-        .json(&json!({
-            "limit": limit,
-            "last": last,
-            "query": query
-        }));
+        .json(&body);
 
-    send_request(request).await
+    send_request_via(transport, request).await
 }
 
 pub async fn update_item_request(
+    transport: &dyn Transport,
     base_url: &str,
     x_api_key: &str,
     key: &str,
     updates: serde_json::Value,
 ) -> Result<reqwest::Response> {
     let request = reqwest::Client::new()
-        .patch(format!("{}/items/{}", base_url, key))
+        .patch(item_url(base_url, key))
         .header("X-Api-Key", x_api_key)
         .json(&updates);
 
-    send_request(request).await
+    send_request_via(transport, request).await
+}
+
+// Returns the URL of the item with the `key`, which is percent-encoded as a path segment,
+// so that e.g. `/`, `?` and `#` are a part of the key rather than of the URL.
+fn item_url(base_url: &str, key: &str) -> String {
+    let mut url = format!("{}/items/", base_url);
+    for byte in key.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+            url.push(byte as char);
+        } else {
+            url.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    url
 }
@@ -0,0 +1,92 @@
+// Checking the size of the items against the limit of deta before sending them.
+// Each item is serialized once, the serialized form is both measured and sent.
+
+use super::models::OversizedItem;
+use crate::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+
+// The key of a serialized item, read to describe the oversized ones.
+#[derive(Deserialize)]
+struct Keyed {
+    key: Option<String>,
+}
+
+// Serializes the `items`, splitting them into the ones within the `limit` and the oversized ones.
+pub(super) fn partition<T>(
+    items: &[T],
+    limit: usize,
+) -> Result<(Vec<Box<RawValue>>, Vec<OversizedItem>)>
+where
+    T: Serialize,
+{
+    let mut within = Vec::with_capacity(items.len());
+    let mut oversized = vec![];
+    for (index, item) in items.iter().enumerate() {
+        let serialized = serde_json::value::to_raw_value(item)?;
+        let size = serialized.get().len();
+        if size <= limit {
+            within.push(serialized);
+        } else {
+            let key = serde_json::from_str::<Keyed>(serialized.get())
+                .ok()
+                .and_then(|keyed| keyed.key);
+            oversized.push(OversizedItem { index, key, size });
+        }
+    }
+    Ok((within, oversized))
+}
+
+// Describes the `oversized` items failing the operation.
+pub(super) fn oversized_error(oversized: &[OversizedItem], limit: usize) -> Error {
+    let listed: Vec<String> = oversized.iter().map(ToString::to_string).collect();
+    Error::from_invalid_argument(format!(
+        "The items exceed the size limit of {} bytes: {}",
+        limit,
+        listed.join(", ")
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn partition_separates_oversized_items() {
+        let items = [
+            json!({ "key": "a", "text": "short" }),
+            json!({ "key": "b", "text": "x".repeat(100) }),
+            json!({ "text": "y".repeat(100) }),
+            json!({ "key": "c" }),
+        ];
+
+        let (within, oversized) = partition(&items, 50).unwrap();
+
+        let within: Vec<_> = within.iter().map(|item| item.get()).collect();
+        assert_eq!(
+            within,
+            vec![r#"{"key":"a","text":"short"}"#, r#"{"key":"c"}"#]
+        );
+        assert_eq!(
+            oversized,
+            vec![
+                OversizedItem {
+                    index: 1,
+                    key: Some("b".into()),
+                    size: 121
+                },
+                OversizedItem {
+                    index: 2,
+                    key: None,
+                    size: 111
+                },
+            ]
+        );
+        assert_eq!(
+            oversized_error(&oversized, 50).to_string(),
+            "Invalid argument exception. Reason: 'The items exceed the size limit of 50 bytes: \
+             #1 (key 'b', 121 bytes), #2 (111 bytes)'."
+        );
+    }
+}
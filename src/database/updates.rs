@@ -1,6 +1,29 @@
 //! Tools for defining updates to be performed on an item in the database.
+//!
+//! # Dots in attribute names
+//!
+//! Deta reads the dots in the name of an updated attribute as nesting, so `profile.age` is the `age` field
+//! of the `profile` object, and it doesn't support any escaping of the dots. A plain string passed to
+//! [`Updates::add`](Updates::add) is read the same way. A field whose name itself contains a dot,
+//! e.g. `config.v1`, is named with [`Attr::literal`](Attr::literal) instead:
+//!
+//! ```
+//! use deta_rust::database::updates::{Action, Attr, Updates};
+//!
+//! let updates = Updates::init()
+//!     // The `enabled` field of the `config` object, in the `v1` object.
+//!     .add("config.v1.enabled", Action::set(true))
+//!     // The `enabled` field of the `config.v1` object.
+//!     .add(Attr::literal("config.v1").field("enabled"), Action::set(true));
+//! ```
+//!
+//! Such updates can't be sent to deta, so [`Database::update_item`](super::Database::update_item) applies them
+//! by getting the item, changing it and putting it back. **This isn't atomic:** a change made to the item
+//! by someone else in the meantime is overwritten. All the updates of the call are applied this way
+//! if any of their attributes contains a dot in a field name.
 
 use super::common::{JsonValue, StringValue};
+use crate::error::{Error, Result};
 use serde::Serialize;
 use std::collections::HashMap;
 use std::convert::Into;
@@ -21,7 +44,7 @@ pub(crate) struct UpdatesSchema {
     delete: Option<UpdatesSchemaDelete>,
 }
 
-impl<'a> UpdatesSchema {
+impl UpdatesSchema {
     fn new() -> Self {
         Self {
             set: None,
@@ -33,6 +56,89 @@ impl<'a> UpdatesSchema {
     }
 }
 
+/// The name of an attribute to update, a path of the fields leading to it from the top of the item.
+///
+/// A plain string, e.g. `"profile.age"`, is converted with [`Attr::path`](Attr::path), splitting it at the dots.
+/// A field whose name contains a dot is named with [`Attr::literal`](Attr::literal) and [`Attr::field`](Attr::field),
+/// see the [module documentation](self).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Attr {
+    fields: Vec<StringValue>,
+}
+
+impl Attr {
+    /// The attribute at the `path` of fields separated by dots, e.g. `profile.age`.
+    pub fn path<T>(path: T) -> Self
+    where
+        T: Into<StringValue>,
+    {
+        let path = path.into();
+        let fields = if path.contains('.') {
+            path.split('.')
+                .map(|field| StringValue::Owned(field.to_owned()))
+                .collect()
+        } else {
+            vec![path]
+        };
+        Self { fields }
+    }
+
+    /// The top-level field with the given `name`, taken literally even if it contains dots, e.g. `config.v1`.
+    pub fn literal<T>(name: T) -> Self
+    where
+        T: Into<StringValue>,
+    {
+        Self {
+            fields: vec![name.into()],
+        }
+    }
+
+    /// The field with the given `name` nested in this attribute, taken literally even if it contains dots.
+    pub fn field<T>(mut self, name: T) -> Self
+    where
+        T: Into<StringValue>,
+    {
+        self.fields.push(name.into());
+        self
+    }
+
+    // Checks whether any field name contains a dot, which deta would read as nesting.
+    pub(crate) fn has_dotted_field(&self) -> bool {
+        self.fields.iter().any(|field| field.contains('.'))
+    }
+
+    // The top-level field containing the attribute.
+    pub(crate) fn root(&self) -> &str {
+        &self.fields[0]
+    }
+
+    // The attribute written the way deta reads it, with the fields joined by dots.
+    fn to_path(&self) -> StringValue {
+        match self.fields.as_slice() {
+            [field] => field.clone(),
+            fields => fields.join(".").into(),
+        }
+    }
+}
+
+impl From<&'static str> for Attr {
+    fn from(path: &'static str) -> Self {
+        Self::path(path)
+    }
+}
+
+impl From<String> for Attr {
+    fn from(path: String) -> Self {
+        Self::path(path)
+    }
+}
+
+impl From<StringValue> for Attr {
+    fn from(path: StringValue) -> Self {
+        Self::path(path)
+    }
+}
+
 /// Enum specifying the variants of actions to be performed when updating the item.
 /// The type contains factory methods to facilitate the construction of variants.
 /// Check [deta docs](https://docs.deta.sh/docs/base/http#update-item) for more information.
@@ -119,15 +225,26 @@ impl Action {
         Self::Delete
     }
 
+    // The order in which deta applies the kinds of actions.
+    fn order(&self) -> u8 {
+        match self {
+            Self::Set(_) => 0,
+            Self::Increment(_) => 1,
+            Self::Append(_) => 2,
+            Self::Prepend(_) => 3,
+            Self::Delete => 4,
+        }
+    }
+
     // Consumes the specified action variant and inserts this value of type `UpdatesSchema`.
-    pub(crate) fn render<'a>(
+    pub(crate) fn render(
         self,
         key: StringValue,
         mut target: UpdatesSchema,
     ) -> serde_json::Result<UpdatesSchema> {
         match self {
             Self::Set(set_value) => {
-                if let None = target.set {
+                if target.set.is_none() {
                     target.set = Some(HashMap::new());
                 }
                 if let Some(value) = &mut target.set {
@@ -135,7 +252,7 @@ impl Action {
                 }
             }
             Self::Increment(increment_value) => {
-                if let None = target.increment {
+                if target.increment.is_none() {
                     target.increment = Some(HashMap::new());
                 }
                 if let Some(value) = &mut target.increment {
@@ -143,7 +260,7 @@ impl Action {
                 }
             }
             Self::Append(append_value) => {
-                if let None = target.append {
+                if target.append.is_none() {
                     target.append = Some(HashMap::new());
                 }
                 if let Some(value) = &mut target.append {
@@ -151,7 +268,7 @@ impl Action {
                 }
             }
             Self::Prepend(prepend_value) => {
-                if let None = target.prepend {
+                if target.prepend.is_none() {
                     target.prepend = Some(HashMap::new());
                 }
                 if let Some(value) = &mut target.prepend {
@@ -159,7 +276,7 @@ impl Action {
                 }
             }
             Self::Delete => {
-                if let None = target.delete {
+                if target.delete.is_none() {
                     target.delete = Some(vec![]);
                 }
                 if let Some(value) = &mut target.delete {
@@ -180,7 +297,7 @@ impl From<Action> for serde_json::Result<Action> {
     }
 }
 
-type PartialActions = Vec<(StringValue, serde_json::Result<Action>)>;
+type PartialActions = Vec<(Attr, serde_json::Result<Action>)>;
 
 /// Builder type to build a list of updates to perform.
 pub struct Updates {
@@ -196,6 +313,7 @@ impl Updates {
     }
 
     /// Adds a new action to be performed during an update.
+    /// A plain string `attr` is a path of fields separated by dots, see [`Attr`](Attr) for the names containing dots.
     /// Both `Action` and `serde_json::Result<Action>` types can be specified as `action` parameters.
     /// This allows the deserialisation error handling to be postponed.
     ///
@@ -209,22 +327,190 @@ impl Updates {
     /// methods generate a common action variants: [`Action::Append`](Action::Append) and [`Action::Prepend`](Action::Prepend).
     pub fn add<T, D>(mut self, attr: T, action: D) -> Self
     where
-        T: Into<StringValue>,
+        T: Into<Attr>,
         D: Into<serde_json::Result<Action>>,
     {
         self.actions.push((attr.into(), action.into()));
         self
     }
 
+    // Returns the attributes changed by the actions.
+    #[cfg(feature = "drive")]
+    pub(crate) fn attributes(&self) -> impl Iterator<Item = &Attr> {
+        self.actions.iter().map(|(attr, _)| attr)
+    }
+
+    // Checks whether the updates can't be sent to deta, as a field name contains a dot.
+    pub(crate) fn has_dotted_fields(&self) -> bool {
+        self.actions.iter().any(|(attr, _)| attr.has_dotted_field())
+    }
+
     pub(crate) fn render(self) -> serde_json::Result<JsonValue> {
         let mut target = UpdatesSchema::new();
         for (k, v) in self.actions {
-            target = v?.render(k, target)?;
+            target = v?.render(k.to_path(), target)?;
         }
 
         let target_json = serde_json::to_value(target)?;
         Ok(target_json)
     }
+
+    // Applies the updates to the `item` the way deta does, for the updates which can't be sent to it.
+    // Returns the rendered updates, which deta would return in the response.
+    pub(crate) fn apply(self, item: &mut JsonValue) -> Result<JsonValue> {
+        let mut actions: Vec<(Attr, Action)> = vec![];
+        for (attr, action) in self.actions {
+            let action = action?;
+            if attr.root() == "key" {
+                return Err(Error::from_invalid_argument(
+                    "The key cannot be updated".into(),
+                ));
+            }
+            // The new action overwrites the old one of the same type, as in the rendered updates.
+            actions.retain(|(other, other_action)| {
+                other != &attr || other_action.order() != action.order()
+            });
+            actions.push((attr, action));
+        }
+        actions.sort_by_key(|(_, action)| action.order());
+
+        let mut target = UpdatesSchema::new();
+        for (attr, action) in &actions {
+            target = action.clone().render(attr.to_path(), target)?;
+        }
+        for (attr, action) in actions {
+            apply_action(item, &attr.fields, action).map_err(Error::from_invalid_argument)?;
+        }
+        Ok(serde_json::to_value(target)?)
+    }
+}
+
+// Performs the action on the attribute at the path of `fields` in the `item`, the way deta does.
+// Shared with the fake base, so both apply the updates the same way.
+pub(crate) fn apply_action<F>(
+    item: &mut JsonValue,
+    fields: &[F],
+    action: Action,
+) -> std::result::Result<(), String>
+where
+    F: AsRef<str>,
+{
+    match action {
+        Action::Set(value) => set_field(item, fields, value),
+        Action::Increment(by) => {
+            let current = match get_field(item, fields) {
+                None | Some(JsonValue::Null) => None,
+                Some(JsonValue::Number(current)) => Some(current),
+                Some(_) => {
+                    return Err(format!(
+                        "Cannot increment non-number field '{}'",
+                        join_fields(fields)
+                    ))
+                }
+            };
+            let incremented = add(current, by)
+                .ok_or_else(|| format!("Cannot increment '{}' by {}", join_fields(fields), by))?;
+            set_field(item, fields, incremented);
+        }
+        Action::Append(values) => extend_list(item, fields, values, false)?,
+        Action::Prepend(values) => extend_list(item, fields, values, true)?,
+        Action::Delete => remove_field(item, fields),
+    }
+    Ok(())
+}
+
+fn extend_list<F>(
+    item: &mut JsonValue,
+    fields: &[F],
+    values: Vec<JsonValue>,
+    prepend: bool,
+) -> std::result::Result<(), String>
+where
+    F: AsRef<str>,
+{
+    let mut list = match get_field(item, fields) {
+        None | Some(JsonValue::Null) => vec![],
+        Some(JsonValue::Array(list)) => list.clone(),
+        Some(_) => {
+            let name = if prepend { "prepend" } else { "append" };
+            return Err(format!(
+                "Cannot {} to non-list field '{}'",
+                name,
+                join_fields(fields)
+            ));
+        }
+    };
+    if prepend {
+        list.splice(0..0, values);
+    } else {
+        list.extend(values);
+    }
+    set_field(item, fields, JsonValue::Array(list));
+    Ok(())
+}
+
+fn join_fields<F>(fields: &[F]) -> String
+where
+    F: AsRef<str>,
+{
+    fields
+        .iter()
+        .map(AsRef::as_ref)
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+fn get_field<'a, F>(item: &'a JsonValue, fields: &[F]) -> Option<&'a JsonValue>
+where
+    F: AsRef<str>,
+{
+    fields
+        .iter()
+        .try_fold(item, |value, field| value.get(field.as_ref()))
+}
+
+// Sets the field, creating the missing parent objects.
+fn set_field<F>(item: &mut JsonValue, fields: &[F], value: JsonValue)
+where
+    F: AsRef<str>,
+{
+    let mut target = item;
+    for field in fields {
+        if !target.is_object() {
+            *target = JsonValue::Object(serde_json::Map::new());
+        }
+        target = target
+            .as_object_mut()
+            .expect("The target is an object")
+            .entry(field.as_ref())
+            .or_insert(JsonValue::Null);
+    }
+    *target = value;
+}
+
+fn remove_field<F>(item: &mut JsonValue, fields: &[F])
+where
+    F: AsRef<str>,
+{
+    if let Some((field, parents)) = fields.split_last() {
+        if let Some(JsonValue::Object(parent)) = parents
+            .iter()
+            .try_fold(item, |value, parent| value.get_mut(parent.as_ref()))
+        {
+            parent.remove(field.as_ref());
+        }
+    }
+}
+
+// Adds the numbers the way the service does: increments are sent as floats,
+// but a whole result is stored as an integer, e.g. `30 + 1.0` gives `31`.
+fn add(current: Option<&serde_json::Number>, by: f64) -> Option<JsonValue> {
+    let sum = current.map_or(Some(0.0), serde_json::Number::as_f64)? + by;
+    if sum.fract() == 0.0 && sum.abs() < i64::MAX as f64 {
+        Some((sum as i64).into())
+    } else {
+        serde_json::Number::from_f64(sum).map(JsonValue::Number)
+    }
 }
 
 #[cfg(test)]
@@ -303,4 +589,52 @@ mod tests {
 
         assert_eq!(target, expected_target);
     }
+
+    #[test]
+    fn attributes_with_dots() {
+        assert_eq!(
+            Attr::from("profile.age"),
+            Attr::literal("profile").field("age")
+        );
+        assert!(!Attr::from("profile.age").has_dotted_field());
+        assert!(Attr::literal("config.v1")
+            .field("enabled")
+            .has_dotted_field());
+
+        let updates = Updates::init().add("profile.age", Action::set(33)).add(
+            Attr::literal("config.v1").field("enabled"),
+            Action::set(true),
+        );
+        assert!(updates.has_dotted_fields());
+        let target = updates.render().expect("Render failed");
+        assert_eq!(
+            target["set"],
+            serde_json::json!({ "profile.age": 33, "config.v1.enabled": true })
+        );
+    }
+
+    #[test]
+    fn apply_in_service_order() {
+        let mut item = serde_json::json!({ "key": "a", "count": 1, "likes": ["tom"] });
+        let target = Updates::init()
+            .add("likes", Action::append("julie"))
+            .add("count", Action::increment(1))
+            .add(Attr::literal("count"), Action::set(10))
+            .add("likes", Action::append("adam"))
+            .add("profile.age", Action::delete())
+            .apply(&mut item)
+            .expect("Apply failed");
+
+        assert_eq!(
+            item,
+            serde_json::json!({ "key": "a", "count": 11, "likes": ["tom", "adam"] })
+        );
+        assert_eq!(target["append"], serde_json::json!({ "likes": ["adam"] }));
+
+        let error = Updates::init()
+            .add("key", Action::set("b"))
+            .apply(&mut item)
+            .unwrap_err();
+        assert!(error.is_invalid_argument());
+    }
 }
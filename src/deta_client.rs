@@ -1,7 +1,31 @@
+#[cfg(feature = "drive")]
+use crate::constants;
+#[cfg(feature = "drive")]
+use crate::utils::{Limiter, Throttle};
+
+/// Bandwidth limits of drive transfers, in bytes per second.
+/// The limits are shared by all transfers made through the same client (see [`DetaClient::with_transfer_limit`](DetaClient::with_transfer_limit)).
+///
+/// Available with the `drive` feature.
+#[cfg(feature = "drive")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TransferLimit {
+    /// Maximum upload speed. Defaults to `None`, which means no limit.
+    pub max_upload_bps: Option<u64>,
+    /// Maximum download speed. Defaults to `None`, which means no limit.
+    pub max_download_bps: Option<u64>,
+}
+
 /// Stores the necessary information for deta integration.
 /// Check [deta docs](https://docs.deta.sh/docs/home/) for more information.
 pub struct DetaClient {
     api_key: String,
+    #[cfg(feature = "drive")]
+    limiter: Limiter,
+    #[cfg(feature = "drive")]
+    upload_throttle: Option<Throttle>,
+    #[cfg(feature = "drive")]
+    download_throttle: Option<Throttle>,
 }
 
 impl DetaClient {
@@ -9,9 +33,38 @@ impl DetaClient {
     pub fn new(api_key: &str) -> Self {
         Self {
             api_key: api_key.to_owned(),
+            #[cfg(feature = "drive")]
+            limiter: Limiter::new(constants::DEFAULT_CONCURRENCY),
+            #[cfg(feature = "drive")]
+            upload_throttle: None,
+            #[cfg(feature = "drive")]
+            download_throttle: None,
         }
     }
 
+    /// Sets the maximum number of requests performed at the same time by bulk operations
+    /// (e.g. [`Drive::copy_to`](crate::drive::Drive::copy_to)).
+    /// The limit is shared by all services created from this client. Defaults to 8.
+    ///
+    /// Available with the `drive` feature.
+    #[cfg(feature = "drive")]
+    pub fn with_concurrency(mut self, limit: usize) -> Self {
+        self.limiter = Limiter::new(limit);
+        self
+    }
+
+    /// Limits the bandwidth of drive transfers. The limits are shared by all services created from this client,
+    /// so concurrent transfers together don't exceed them. Uploads are paced part by part
+    /// and downloads chunk by chunk, so the speed is kept on average rather than at every moment.
+    ///
+    /// Available with the `drive` feature.
+    #[cfg(feature = "drive")]
+    pub fn with_transfer_limit(mut self, limit: TransferLimit) -> Self {
+        self.upload_throttle = limit.max_upload_bps.map(Throttle::new);
+        self.download_throttle = limit.max_download_bps.map(Throttle::new);
+        self
+    }
+
     /// Returns api key.
     pub fn api_key(&self) -> &str {
         &self.api_key
@@ -19,6 +72,21 @@ impl DetaClient {
 
     /// Returns project id.
     pub fn project_id(&self) -> &str {
-        &self.api_key.split('_').next().unwrap()
+        self.api_key.split('_').next().unwrap()
+    }
+
+    #[cfg(feature = "drive")]
+    pub(crate) fn limiter(&self) -> &Limiter {
+        &self.limiter
+    }
+
+    #[cfg(feature = "drive")]
+    pub(crate) fn upload_throttle(&self) -> Option<&Throttle> {
+        self.upload_throttle.as_ref()
+    }
+
+    #[cfg(feature = "drive")]
+    pub(crate) fn download_throttle(&self) -> Option<&Throttle> {
+        self.download_throttle.as_ref()
     }
 }
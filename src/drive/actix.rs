@@ -0,0 +1,262 @@
+//! Serving the files of a drive from [actix-web](https://docs.rs/actix-web/4) handlers.
+//!
+//! [`DriveFile`](DriveFile) implements [`Responder`](Responder), streaming the content to the client
+//! as it's downloaded, without keeping the file whole in memory, along with the `Content-Type`,
+//! `Content-Length` and `Last-Modified` headers sent by deta. A missing file is served as `404 Not Found`.
+//!
+//! Other failures are returned as [`Error`](Error), which implements [`ResponseError`](ResponseError)
+//! with the same statuses and messages as the [`axum`](super::axum) integration:
+//! - `400 Bad Request` for an invalid argument, e.g. an invalid file name taken from the path,
+//! - `404 Not Found` for a missing item or file,
+//! - `416 Range Not Satisfiable` if deta rejects the requested range,
+//! - `502 Bad Gateway` for any other failure.
+//!
+//! ```no_run
+//! use actix_web::{web, App, HttpRequest};
+//! use deta_rust::drive::actix::DriveFile;
+//! use deta_rust::prelude::*;
+//!
+//! async fn report(drive: web::Data<Drive>, name: web::Path<String>) -> Result<DriveFile> {
+//!     Ok(DriveFile::fetch(&drive, &name).await?.attachment())
+//! }
+//!
+//! async fn video(
+//!     drive: web::Data<Drive>,
+//!     name: web::Path<String>,
+//!     request: HttpRequest,
+//! ) -> Result<DriveFile> {
+//!     DriveFile::fetch_range(&drive, &name, &request).await
+//! }
+//!
+//! # fn run() -> Result<()> {
+//! let drive = web::Data::new(Drive::new(&DetaClient::new("project_key"), "files")?);
+//! let app = App::new()
+//!     .app_data(drive)
+//!     .route("/reports/{name}", web::get().to(report))
+//!     .route("/videos/{name}", web::get().to(video));
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Available with the `actix` feature.
+
+use super::{serve, Drive};
+use crate::error::{Error, Result};
+use actix_web::body::{BodyStream, BoxBody, SizedStream};
+use actix_web::http::header::{self, HeaderName, HeaderValue};
+use actix_web::http::StatusCode;
+use actix_web::{HttpRequest, HttpResponse, Responder, ResponseError};
+use futures::stream::BoxStream;
+
+/// A file of a drive, served as the response by actix-web.
+pub struct DriveFile {
+    name: String,
+    status: StatusCode,
+    headers: Vec<(HeaderName, HeaderValue)>,
+    // `None` if the file doesn't exist.
+    body: Option<BoxStream<'static, Result<bytes::Bytes>>>,
+}
+
+impl DriveFile {
+    /// Starts the download of the file. The content is received while it's sent to the client.
+    /// A missing file is served as `404 Not Found`, see [`DriveFile::is_found`](DriveFile::is_found).
+    pub async fn fetch(drive: &Drive, name: &str) -> Result<Self> {
+        let response = drive.get_file_response(name).await?;
+        Ok(Self::from_response(drive, name, response))
+    }
+
+    /// Works like [`DriveFile::fetch`](DriveFile::fetch), but passes the `Range` header of the `request` on to deta,
+    /// so that the client can download a part of the file, e.g. to resume a download or seek in a video.
+    /// The part is served as `206 Partial Content` along with the `Content-Range` header.
+    ///
+    /// Without the `Range` header in the `request`, the whole file is served.
+    pub async fn fetch_range(drive: &Drive, name: &str, request: &HttpRequest) -> Result<Self> {
+        let range = match serve::range(request.headers().get(header::RANGE))? {
+            Some(range) => range,
+            None => return Self::fetch(drive, name).await,
+        };
+        let response = drive.get_file_range_response(name, range).await?;
+        Ok(Self::from_response(drive, name, response))
+    }
+
+    /// Makes browsers save the file instead of displaying it, with the `Content-Disposition: attachment` header.
+    /// The file is saved under the last segment of its name, e.g. `report.pdf` for `2024/05/report.pdf`.
+    pub fn attachment(mut self) -> Self {
+        if self.is_found() {
+            self.headers.push(serve::attachment(&self.name));
+        }
+        self
+    }
+
+    /// Checks whether the file exists, otherwise it's served as `404 Not Found`.
+    pub fn is_found(&self) -> bool {
+        self.body.is_some()
+    }
+
+    fn from_response(drive: &Drive, name: &str, response: Option<reqwest::Response>) -> Self {
+        match response {
+            Some(response) => Self {
+                name: name.to_owned(),
+                status: response.status(),
+                headers: serve::file_headers(response.headers()),
+                body: Some(Box::pin(drive.body_stream(response))),
+            },
+            None => Self {
+                name: name.to_owned(),
+                status: StatusCode::NOT_FOUND,
+                headers: vec![],
+                body: None,
+            },
+        }
+    }
+}
+
+impl Responder for DriveFile {
+    type Body = BoxBody;
+
+    fn respond_to(self, _request: &HttpRequest) -> HttpResponse {
+        let body = match self.body {
+            Some(body) => body,
+            None => return status_response(self.status),
+        };
+
+        let mut response = HttpResponse::build(self.status);
+        let mut length = None;
+        for (name, value) in self.headers {
+            // Actix sets the length itself, it's given along with the body instead.
+            if name == header::CONTENT_LENGTH {
+                length = value.to_str().ok().and_then(|value| value.parse().ok());
+            } else {
+                response.insert_header((name, value));
+            }
+        }
+        match length {
+            Some(length) => response.body(SizedStream::new(length, body)),
+            None => response.body(BodyStream::new(body)),
+        }
+    }
+}
+
+impl ResponseError for Error {
+    fn status_code(&self) -> StatusCode {
+        serve::error_status(self)
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        status_response(self.status_code())
+    }
+}
+
+fn status_response(status: StatusCode) -> HttpResponse {
+    HttpResponse::build(status).body(serve::status_message(status))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::drive::serve::tests::{Storage, CONTENT};
+    use crate::DetaClient;
+    use actix_web::body::{BodySize, MessageBody};
+    use actix_web::{test, web, App};
+    use std::sync::Arc;
+
+    async fn avatar(
+        drive: web::Data<Drive>,
+        name: web::Path<String>,
+        request: HttpRequest,
+    ) -> Result<DriveFile> {
+        DriveFile::fetch_range(&drive, &name, &request).await
+    }
+
+    async fn download(drive: web::Data<Drive>, name: web::Path<String>) -> Result<DriveFile> {
+        Ok(DriveFile::fetch(&drive, &name).await?.attachment())
+    }
+
+    async fn get(storage: &Storage, uri: &str, range: Option<&str>) -> HttpResponse {
+        let drive = Drive::new(&DetaClient::new("project_key"), "avatars")
+            .unwrap()
+            .with_transport(Arc::new(storage.clone()));
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(drive))
+                .route("/avatars/{name}", web::get().to(avatar))
+                .route("/downloads/{name}", web::get().to(download)),
+        )
+        .await;
+
+        let mut request = test::TestRequest::get().uri(uri);
+        if let Some(range) = range {
+            request = request.insert_header((header::RANGE, range));
+        }
+        test::call_service(&app, request.to_request())
+            .await
+            .into_parts()
+            .1
+            .map_into_boxed_body()
+    }
+
+    async fn read_body(response: HttpResponse) -> bytes::Bytes {
+        actix_web::body::to_bytes(response.into_body())
+            .await
+            .unwrap()
+    }
+
+    #[actix_web::test]
+    async fn fetch_serves_file_with_headers() {
+        let storage = Storage::default();
+        let response = get(&storage, "/avatars/avatar.png", None).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let headers = response.headers();
+        assert_eq!(headers.get(header::CONTENT_TYPE).unwrap(), "image/png");
+        assert_eq!(
+            headers.get(header::LAST_MODIFIED).unwrap(),
+            "Wed, 01 May 2024 12:30:00 GMT"
+        );
+        assert!(!headers.contains_key(header::CONTENT_DISPOSITION));
+        assert_eq!(response.body().size(), BodySize::Sized(10));
+        assert_eq!(read_body(response).await, CONTENT);
+        assert_eq!(*storage.ranges.lock().unwrap(), vec![None]);
+    }
+
+    #[actix_web::test]
+    async fn fetch_range_passes_range_through() {
+        let storage = Storage::default();
+        let response = get(&storage, "/avatars/avatar.png", Some("bytes=2-5")).await;
+
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(
+            response.headers().get(header::CONTENT_RANGE).unwrap(),
+            "bytes 2-5/10"
+        );
+        assert_eq!(read_body(response).await, &CONTENT[2..6]);
+
+        let response = get(&storage, "/avatars/avatar.png", Some("bytes=20-")).await;
+        assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+    }
+
+    #[actix_web::test]
+    async fn attachment_sets_content_disposition() {
+        let storage = Storage::default();
+        let response = get(&storage, "/downloads/avatar.png", None).await;
+
+        assert_eq!(
+            response.headers().get(header::CONTENT_DISPOSITION).unwrap(),
+            r#"attachment; filename="avatar.png""#
+        );
+
+        let response = get(&storage, "/downloads/missing.png", None).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert!(!response.headers().contains_key(header::CONTENT_DISPOSITION));
+        assert_eq!(read_body(response).await, "Not Found");
+    }
+
+    #[actix_web::test]
+    async fn failure_is_served_without_details() {
+        let storage = Storage::default();
+        let response = get(&storage, "/avatars/broken.png", None).await;
+
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+        assert_eq!(read_body(response).await, "Bad Gateway");
+    }
+}
@@ -0,0 +1,199 @@
+//! Serving the files of a drive from [axum](https://docs.rs/axum/0.6) handlers.
+//!
+//! The content is streamed to the client as it's downloaded, without keeping the file whole in memory,
+//! along with the `Content-Type`, `Content-Length` and `Last-Modified` headers sent by deta.
+//! A missing file is served as `404 Not Found`.
+//!
+//! Other failures are returned as [`Error`](Error), which implements [`IntoResponse`](IntoResponse),
+//! so a handler can return them as they are, or log them first. The response doesn't reveal
+//! the details of the failure, only its status and the reason phrase:
+//! - `400 Bad Request` for an invalid argument, e.g. an invalid file name taken from the path,
+//! - `404 Not Found` for a missing item or file,
+//! - `416 Range Not Satisfiable` if deta rejects the requested range,
+//! - `502 Bad Gateway` for any other failure.
+//!
+//! ```no_run
+//! use axum::{
+//!     extract::{Path, State},
+//!     http::HeaderMap,
+//!     response::Response,
+//!     routing::get,
+//!     Router,
+//! };
+//! use deta_rust::drive::axum::serve_file_range;
+//! use deta_rust::prelude::*;
+//! use std::sync::Arc;
+//!
+//! async fn avatar(
+//!     State(drive): State<Arc<Drive>>,
+//!     Path(name): Path<String>,
+//!     headers: HeaderMap,
+//! ) -> Result<Response> {
+//!     serve_file_range(&drive, &name, &headers).await
+//! }
+//!
+//! # fn run() -> Result<()> {
+//! let drive = Drive::new(&DetaClient::new("project_key"), "avatars")?;
+//! let app: Router = Router::new()
+//!     .route("/avatars/:name", get(avatar))
+//!     .with_state(Arc::new(drive));
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Available with the `axum` feature.
+
+use super::{serve, Drive};
+use crate::error::{Error, Result};
+use axum::body::{self, StreamBody};
+use axum::http::header::{self, HeaderMap};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+
+/// Returns a response streaming the content of the file, or `404 Not Found` if the file doesn't exist.
+pub async fn serve_file(drive: &Drive, name: &str) -> Result<Response> {
+    let response = drive.get_file_response(name).await?;
+    Ok(file_response(drive, response))
+}
+
+/// Works like [`serve_file`](serve_file), but passes the `Range` header of the request on to deta,
+/// so that the client can download a part of the file, e.g. to resume a download or seek in a video.
+/// The part is served as `206 Partial Content` along with the `Content-Range` header.
+///
+/// Without the `Range` header in the `headers`, the whole file is served.
+pub async fn serve_file_range(drive: &Drive, name: &str, headers: &HeaderMap) -> Result<Response> {
+    let range = match serve::range(headers.get(header::RANGE))? {
+        Some(range) => range,
+        None => return serve_file(drive, name).await,
+    };
+    let response = drive.get_file_range_response(name, range).await?;
+    Ok(file_response(drive, response))
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        status_response(serve::error_status(&self))
+    }
+}
+
+// Streams the body of the download `response` with its headers, or responds with 404 if there's no file.
+fn file_response(drive: &Drive, response: Option<reqwest::Response>) -> Response {
+    let response = match response {
+        Some(response) => response,
+        None => return status_response(StatusCode::NOT_FOUND),
+    };
+
+    let mut builder = Response::builder().status(response.status());
+    for (name, value) in serve::file_headers(response.headers()) {
+        builder = builder.header(name, value);
+    }
+    let body = body::boxed(StreamBody::new(drive.body_stream(response)));
+    builder
+        .body(body)
+        .expect("The headers are copied from a valid response")
+}
+
+fn status_response(status: StatusCode) -> Response {
+    (status, serve::status_message(status)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::drive::serve::tests::{Storage, CONTENT};
+    use crate::DetaClient;
+    use axum::extract::{Path, State};
+    use axum::http::Request;
+    use axum::routing::get;
+    use axum::Router;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    async fn avatar(
+        State(drive): State<Arc<Drive>>,
+        Path(name): Path<String>,
+        headers: HeaderMap,
+    ) -> Result<Response> {
+        serve_file_range(&drive, &name, &headers).await
+    }
+
+    fn app(storage: &Storage) -> Router {
+        let drive = Drive::new(&DetaClient::new("project_key"), "avatars")
+            .unwrap()
+            .with_transport(Arc::new(storage.clone()));
+        Router::new()
+            .route("/avatars/:name", get(avatar))
+            .with_state(Arc::new(drive))
+    }
+
+    async fn get_avatar(storage: &Storage, name: &str, range: Option<&str>) -> Response {
+        let mut request = Request::get(format!("/avatars/{}", name));
+        if let Some(range) = range {
+            request = request.header(header::RANGE, range);
+        }
+        let request = request.body(axum::body::Body::empty()).unwrap();
+        app(storage).oneshot(request).await.unwrap()
+    }
+
+    async fn read_body(response: Response) -> bytes::Bytes {
+        hyper::body::to_bytes(response.into_body()).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn serve_file_with_headers() {
+        let storage = Storage::default();
+        let response = get_avatar(&storage, "avatar.png", None).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let headers = response.headers();
+        assert_eq!(headers[header::CONTENT_TYPE], "image/png");
+        assert_eq!(headers[header::CONTENT_LENGTH], "10");
+        assert_eq!(
+            headers[header::LAST_MODIFIED],
+            "Wed, 01 May 2024 12:30:00 GMT"
+        );
+        assert!(!headers.contains_key(header::CONTENT_RANGE));
+        assert_eq!(read_body(response).await, CONTENT);
+        assert_eq!(*storage.ranges.lock().unwrap(), vec![None]);
+    }
+
+    #[tokio::test]
+    async fn serve_file_range_passes_range_through() {
+        let storage = Storage::default();
+        let response = get_avatar(&storage, "avatar.png", Some("bytes=2-5")).await;
+
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers()[header::CONTENT_RANGE], "bytes 2-5/10");
+        assert_eq!(response.headers()[header::CONTENT_LENGTH], "4");
+        assert_eq!(read_body(response).await, &CONTENT[2..6]);
+        assert_eq!(
+            *storage.ranges.lock().unwrap(),
+            vec![Some("bytes=2-5".to_owned())]
+        );
+
+        let response = get_avatar(&storage, "avatar.png", Some("bytes=20-")).await;
+        assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+    }
+
+    #[tokio::test]
+    async fn serve_missing_file() {
+        let storage = Storage::default();
+        let response = get_avatar(&storage, "missing.png", None).await;
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(read_body(response).await, "Not Found");
+    }
+
+    #[tokio::test]
+    async fn failure_is_served_without_details() {
+        let storage = Storage::default();
+        let response = get_avatar(&storage, "broken.png", None).await;
+
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+        assert_eq!(read_body(response).await, "Bad Gateway");
+
+        let response = get_avatar(&storage, "%20", None).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(storage.ranges.lock().unwrap().len(), 1);
+    }
+}
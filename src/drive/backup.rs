@@ -0,0 +1,231 @@
+use super::Drive;
+use crate::error::{Error, Result};
+use futures::StreamExt;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+const BLOCK_SIZE: usize = 512;
+
+/// Summary of [`Drive::backup_to_tar`](Drive::backup_to_tar).
+#[derive(Debug, Clone, Default)]
+pub struct BackupStats {
+    /// Number of the archived files.
+    pub files: usize,
+    /// Total size of the archived files' content.
+    pub bytes: u64,
+}
+
+impl Drive {
+    /// Writes all files matching the `prefix` (or all files of the drive) into a tar archive.
+    /// Each entry is named after the file name in the drive.
+    ///
+    /// The size of each file is read from its metadata first, so that the content can be streamed
+    /// straight into the archive. Only files whose size is unknown are buffered in memory.
+    /// Files removed while the backup is in progress are left out.
+    ///
+    /// Available with the `backup` feature.
+    pub async fn backup_to_tar<W>(&self, prefix: Option<&str>, mut writer: W) -> Result<BackupStats>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let mut stats = BackupStats::default();
+        let names = self.list_all_files(prefix).await?.names;
+
+        for name in names {
+            let size = match self.head_file(&name).await {
+                Ok(Some(metadata)) => metadata.content_length,
+                Ok(None) => continue,
+                // The metadata is only an optimization, the file can still be buffered.
+                Err(_) => None,
+            };
+
+            let written = match size {
+                Some(size) => self.append_streamed(&name, size, &mut writer).await?,
+                None => self.append_buffered(&name, &mut writer).await?,
+            };
+
+            if let Some(written) = written {
+                stats.files += 1;
+                stats.bytes += written;
+            }
+        }
+
+        writer.write_all(&[0; 2 * BLOCK_SIZE]).await?;
+        writer.flush().await?;
+        Ok(stats)
+    }
+
+    // Appends the entry of a known size, streaming the content. Returns `None` if the file no longer exists.
+    async fn append_streamed<W>(&self, name: &str, size: u64, writer: &mut W) -> Result<Option<u64>>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let stream = match self.get_file_as_stream(name).await? {
+            Some(stream) => stream,
+            None => return Ok(None),
+        };
+        futures::pin_mut!(stream);
+
+        writer.write_all(&entry_header(name, size)?).await?;
+        let mut written = 0;
+        while let Some(chunk) = stream.next().await.transpose()? {
+            written += chunk.len() as u64;
+            if written > size {
+                break;
+            }
+            writer.write_all(&chunk).await?;
+        }
+
+        // The header is already written, so the archive would be corrupted by content of a different size.
+        if written != size {
+            return Err(Error::from_other(
+                format!(
+                    "Size of '{}' changed during the backup, expected {} bytes",
+                    name, size
+                ),
+                None,
+            ));
+        }
+
+        writer.write_all(&padding(size)).await?;
+        Ok(Some(size))
+    }
+
+    // Appends the entry of an unknown size, buffering the whole content. Returns `None` if the file no longer exists.
+    async fn append_buffered<W>(&self, name: &str, writer: &mut W) -> Result<Option<u64>>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let data = match self.get_file_as_buffer(name).await? {
+            Some(data) => data,
+            None => return Ok(None),
+        };
+
+        let size = data.len() as u64;
+        writer.write_all(&entry_header(name, size)?).await?;
+        writer.write_all(&data).await?;
+        writer.write_all(&padding(size)).await?;
+        Ok(Some(size))
+    }
+}
+
+// Builds the header blocks of the entry, including the extension blocks required by long names.
+fn entry_header(name: &str, size: u64) -> Result<Vec<u8>> {
+    let mut header = tar::Header::new_gnu();
+    header.set_entry_type(tar::EntryType::Regular);
+    header.set_size(size);
+    header.set_mode(0o644);
+
+    // With empty content, the builder writes nothing but the header blocks.
+    let mut builder = tar::Builder::new(Vec::new());
+    builder.append_data(&mut header, name, std::io::empty())?;
+    Ok(std::mem::take(builder.get_mut()))
+}
+
+fn padding(size: u64) -> Vec<u8> {
+    let remainder = (size % BLOCK_SIZE as u64) as usize;
+    vec![0; (BLOCK_SIZE - remainder) % BLOCK_SIZE]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::io::Read;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    async fn mount_file(server: &MockServer, name: &str, data: &[u8], with_size: bool) {
+        let head_response = if with_size {
+            ResponseTemplate::new(200)
+                .insert_header("Content-Length", data.len().to_string().as_str())
+        } else {
+            ResponseTemplate::new(405)
+        };
+        Mock::given(method("HEAD"))
+            .and(path("/files/download"))
+            .and(query_param("name", name))
+            .respond_with(head_response)
+            .mount(server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/files/download"))
+            .and(query_param("name", name))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(data))
+            .mount(server)
+            .await;
+    }
+
+    fn read_archive(archive: &[u8]) -> Vec<(String, Vec<u8>)> {
+        let mut archive = tar::Archive::new(archive);
+        archive
+            .entries()
+            .unwrap()
+            .map(|entry| {
+                let mut entry = entry.unwrap();
+                let name = entry.path().unwrap().to_string_lossy().into_owned();
+                let mut data = vec![];
+                entry.read_to_end(&mut data).unwrap();
+                (name, data)
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn backup_to_tar() {
+        let server = MockServer::start().await;
+        let long_name = format!("docs/{}.txt", "long".repeat(40));
+        Mock::given(method("GET"))
+            .and(path("/files"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "names": ["docs/a.txt", "docs/b.bin", long_name, "docs/removed.txt"]
+            })))
+            .mount(&server)
+            .await;
+        mount_file(&server, "docs/a.txt", b"Hello", true).await;
+        mount_file(&server, "docs/b.bin", &[7; 1500], false).await;
+        mount_file(&server, &long_name, b"", true).await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let mut archive = vec![];
+        let stats = drive
+            .backup_to_tar(Some("docs/"), &mut archive)
+            .await
+            .unwrap();
+
+        assert_eq!(stats.files, 3);
+        assert_eq!(stats.bytes, 1505);
+        assert_eq!(archive.len() % BLOCK_SIZE, 0);
+        assert_eq!(
+            read_archive(&archive),
+            vec![
+                ("docs/a.txt".to_owned(), b"Hello".to_vec()),
+                ("docs/b.bin".to_owned(), vec![7; 1500]),
+                (long_name, vec![]),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn backup_to_tar_for_changed_size() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/files"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "names": ["a.txt"] })))
+            .mount(&server)
+            .await;
+        Mock::given(method("HEAD"))
+            .and(path("/files/download"))
+            .respond_with(ResponseTemplate::new(200).insert_header("Content-Length", "3"))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/files/download"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(b"Hello".to_vec()))
+            .mount(&server)
+            .await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let error = drive.backup_to_tar(None, vec![]).await.unwrap_err();
+        assert!(error.to_string().contains("changed during the backup"));
+    }
+}
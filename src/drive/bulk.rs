@@ -0,0 +1,502 @@
+//! Operations performed on many files at once.
+
+use super::{models, Drive, PutFileResult};
+use crate::constants;
+use crate::error::{Error, Result};
+use futures::{StreamExt, TryStreamExt};
+use std::collections::{HashMap, HashSet};
+
+/// Summary of the [`Drive::copy_to`](Drive::copy_to) operation.
+#[derive(Debug, Default)]
+pub struct CopyStats {
+    /// Names of the copied files.
+    pub copied: Vec<String>,
+    /// Names of the files skipped because they already exist in the destination drive.
+    pub skipped: Vec<String>,
+    /// Names of the files that could not be copied, along with the cause.
+    pub failed: Vec<(String, Error)>,
+}
+
+/// Progress of the [`Drive::copy_to_with_progress`](Drive::copy_to_with_progress) operation,
+/// reported after each processed file.
+#[derive(Debug, Clone, Copy)]
+pub struct CopyProgress<'a> {
+    /// Name of the file just processed.
+    pub name: &'a str,
+    /// Number of files processed so far, including skipped and failed ones.
+    pub completed: usize,
+    /// Number of files to process.
+    pub total: usize,
+}
+
+/// Summary of the [`Drive::put_files`](Drive::put_files) operation.
+#[derive(Debug, Default)]
+pub struct PutFilesReport {
+    /// Results of the successful uploads.
+    pub uploaded: Vec<PutFileResult>,
+    /// Names of the files that could not be uploaded, along with the cause.
+    pub failed: Vec<(String, Error)>,
+}
+
+enum CopyOutcome {
+    Copied,
+    Skipped,
+    Failed(Error),
+}
+
+impl Drive {
+    /// Copies files into the `dest` drive, which may belong to another project.
+    /// Only files whose names start with `prefix` are copied, if specified.
+    ///
+    /// If `overwrite` is `false`, files already existing in the destination drive are skipped.
+    /// Failure to copy a single file doesn't interrupt the operation, it's reported in the
+    /// [`CopyStats::failed`](CopyStats::failed) field instead.
+    /// Files are copied concurrently, within the limit set by [`DetaClient::with_concurrency`](crate::DetaClient::with_concurrency).
+    pub async fn copy_to(
+        &self,
+        dest: &Drive,
+        prefix: Option<&str>,
+        overwrite: bool,
+    ) -> Result<CopyStats> {
+        self.copy_to_with_progress(dest, prefix, overwrite, |_| {})
+            .await
+    }
+
+    /// Works like [`Drive::copy_to`](Drive::copy_to),
+    /// but calls `on_progress` each time a file is processed.
+    pub async fn copy_to_with_progress<F>(
+        &self,
+        dest: &Drive,
+        prefix: Option<&str>,
+        overwrite: bool,
+        on_progress: F,
+    ) -> Result<CopyStats>
+    where
+        F: Fn(CopyProgress),
+    {
+        let names = self.list_all_files(prefix).await?.names;
+        let existing: HashSet<String> = if overwrite {
+            HashSet::new()
+        } else {
+            dest.list_all_files(prefix)
+                .await?
+                .names
+                .into_iter()
+                .collect()
+        };
+
+        let total = names.len();
+        let mut outcomes = futures::stream::iter(names)
+            .map(|name| {
+                let skip = existing.contains(&name);
+                async move {
+                    if skip {
+                        return (name, CopyOutcome::Skipped);
+                    }
+                    let _permit = self.limiter.acquire().await;
+                    match self.copy_file(&name, dest, &name).await {
+                        Ok(()) => (name, CopyOutcome::Copied),
+                        Err(error) => (name, CopyOutcome::Failed(error)),
+                    }
+                }
+            })
+            .buffer_unordered(self.limiter.limit());
+
+        let mut stats = CopyStats::default();
+        let mut completed = 0;
+        while let Some((name, outcome)) = outcomes.next().await {
+            completed += 1;
+            on_progress(CopyProgress {
+                name: &name,
+                completed,
+                total,
+            });
+            match outcome {
+                CopyOutcome::Copied => stats.copied.push(name),
+                CopyOutcome::Skipped => stats.skipped.push(name),
+                CopyOutcome::Failed(error) => stats.failed.push((name, error)),
+            }
+        }
+
+        Ok(stats)
+    }
+
+    /// Uploads many files concurrently, within the limit set by [`DetaClient::with_concurrency`](crate::DetaClient::with_concurrency).
+    /// Each file is described by its name, data and optional content type.
+    /// Like with [`Drive::put_file`](Drive::put_file), files greater than 10MB are uploaded in chunks.
+    ///
+    /// A failed upload doesn't interrupt the operation, it's reported in the
+    /// [`PutFilesReport::failed`](PutFilesReport::failed) field instead.
+    /// Duplicated names are rejected before sending any request.
+    pub async fn put_files<D>(
+        &self,
+        files: Vec<(String, D, Option<String>)>,
+    ) -> Result<PutFilesReport>
+    where
+        D: Into<bytes::Bytes>,
+    {
+        let mut names = HashSet::new();
+        if let Some((name, _, _)) = files.iter().find(|(name, _, _)| !names.insert(name)) {
+            return Err(Error::from_invalid_argument(format!(
+                "File name '{}' is duplicated",
+                name
+            )));
+        }
+
+        let mut results = futures::stream::iter(files)
+            .map(|(name, data, content_type)| async move {
+                let _permit = self.limiter.acquire().await;
+                let result = self.put_file(&name, data, content_type.as_deref()).await;
+                (name, result)
+            })
+            .buffer_unordered(self.limiter.limit());
+
+        let mut report = PutFilesReport::default();
+        while let Some((name, result)) = results.next().await {
+            match result {
+                Ok(result) => report.uploaded.push(result),
+                Err(error) => report.failed.push((name, error)),
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Downloads many files concurrently, within the limit set by [`DetaClient::with_concurrency`](crate::DetaClient::with_concurrency).
+    /// Returns the data of each file by its name, or `None` if the file doesn't exist.
+    /// The first failure other than a missing file interrupts the operation and is returned as an error.
+    ///
+    /// **NOTE:** All files are kept in memory. To process big files,
+    /// use [`Drive::get_file_as_stream`](Drive::get_file_as_stream) instead.
+    pub async fn get_files(&self, names: &[&str]) -> Result<HashMap<String, Option<bytes::Bytes>>> {
+        futures::stream::iter(names)
+            .map(|name| async move {
+                let _permit = self.limiter.acquire().await;
+                let data = self.get_file_as_buffer(name).await?;
+                Ok((name.to_string(), data))
+            })
+            .buffer_unordered(self.limiter.limit())
+            .try_collect()
+            .await
+    }
+
+    /// Deletes all files whose names start with `prefix`.
+    /// The listing is processed page by page, and each page is deleted with a single request.
+    ///
+    /// A failed deletion doesn't interrupt the operation, the affected names are reported
+    /// in the [`DeleteFiles::failed`](models::DeleteFiles::failed) field instead.
+    ///
+    /// An empty `prefix` is rejected to avoid accidental wiping of the drive,
+    /// use [`Drive::delete_all`](Drive::delete_all) for that.
+    pub async fn delete_by_prefix(&self, prefix: &str) -> Result<models::DeleteFiles> {
+        if prefix.is_empty() {
+            return Err(Error::from_invalid_argument(
+                "Prefix must not be empty, use `Drive::delete_all` to delete all files".into(),
+            ));
+        }
+        self.delete_matching(Some(prefix)).await
+    }
+
+    /// Deletes all files from the drive.
+    /// Works like [`Drive::delete_by_prefix`](Drive::delete_by_prefix).
+    pub async fn delete_all(&self) -> Result<models::DeleteFiles> {
+        self.delete_matching(None).await
+    }
+
+    async fn delete_matching(&self, prefix: Option<&str>) -> Result<models::DeleteFiles> {
+        let mut deleted = vec![];
+        let mut failed = HashMap::new();
+        let mut cursor: Option<models::Cursor> = None;
+
+        loop {
+            let files = self
+                .list_files(
+                    Some(constants::MAX_DELETE_BATCH_SIZE as u32),
+                    prefix,
+                    cursor.as_ref().map(models::Cursor::as_str),
+                )
+                .await?;
+            cursor = files.next_cursor();
+
+            if !files.names.is_empty() {
+                match self.delete_files(&files.names).await {
+                    Ok(mut result) => {
+                        failed.extend(result.failed.take().unwrap_or_default());
+                        deleted.append(&mut result.deleted);
+                    }
+                    Err(error) => {
+                        let reason = serde_json::Value::from(error.to_string());
+                        failed.extend(files.names.into_iter().map(|name| (name, reason.clone())));
+                    }
+                }
+            }
+
+            if cursor.is_none() {
+                break;
+            }
+        }
+
+        Ok(models::DeleteFiles {
+            deleted,
+            failed: if failed.is_empty() {
+                None
+            } else {
+                Some(failed)
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::time::{Duration, Instant};
+    use wiremock::matchers::{body_json, method, path, query_param, query_param_is_missing};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn delete_by_prefix_for_many_pages() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/files"))
+            .and(query_param("prefix", "thumbs/"))
+            .and(query_param_is_missing("last"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "paging": { "size": 2, "last": "thumbs/b" },
+                "names": ["thumbs/a", "thumbs/b"]
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/files"))
+            .and(query_param("prefix", "thumbs/"))
+            .and(query_param("last", "thumbs/b"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "names": ["thumbs/c"]
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(path("/files"))
+            .and(body_json(json!({ "names": ["thumbs/a", "thumbs/b"] })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "deleted": ["thumbs/a", "thumbs/b"]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(path("/files"))
+            .and(body_json(json!({ "names": ["thumbs/c"] })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "deleted": [],
+                "failed": { "thumbs/c": "Internal error" }
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let result = drive.delete_by_prefix("thumbs/").await.unwrap();
+
+        assert_eq!(result.deleted, vec!["thumbs/a", "thumbs/b"]);
+        let failed = result.failed.unwrap();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed["thumbs/c"], "Internal error");
+    }
+
+    #[tokio::test]
+    async fn delete_by_prefix_continues_after_failed_batch() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/files"))
+            .and(query_param_is_missing("last"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "paging": { "size": 1, "last": "thumbs/a" },
+                "names": ["thumbs/a"]
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/files"))
+            .and(query_param("last", "thumbs/a"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "names": ["thumbs/b"]
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(path("/files"))
+            .and(body_json(json!({ "names": ["thumbs/a"] })))
+            .respond_with(ResponseTemplate::new(500))
+            .mount(&server)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(path("/files"))
+            .and(body_json(json!({ "names": ["thumbs/b"] })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "deleted": ["thumbs/b"]
+            })))
+            .mount(&server)
+            .await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let result = drive.delete_by_prefix("thumbs/").await.unwrap();
+
+        assert_eq!(result.deleted, vec!["thumbs/b"]);
+        assert!(result.failed.unwrap().contains_key("thumbs/a"));
+    }
+
+    fn put_file_response(name: &str) -> ResponseTemplate {
+        ResponseTemplate::new(201).set_body_json(json!({
+            "name": name,
+            "project_id": "project",
+            "drive_name": "drive"
+        }))
+    }
+
+    #[tokio::test]
+    async fn put_files_with_failures() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/files"))
+            .and(query_param("name", "a.txt"))
+            .respond_with(put_file_response("a.txt"))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/files"))
+            .and(query_param("name", "b.txt"))
+            .respond_with(ResponseTemplate::new(400).set_body_json(json!({
+                "errors": ["Bad request"]
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/files"))
+            .and(query_param("name", "c.txt"))
+            .respond_with(put_file_response("c.txt"))
+            .mount(&server)
+            .await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let files = vec![
+            ("a.txt".to_owned(), b"a".to_vec(), None),
+            (
+                "b.txt".to_owned(),
+                b"b".to_vec(),
+                Some("text/plain".to_owned()),
+            ),
+            ("c.txt".to_owned(), b"c".to_vec(), None),
+        ];
+        let report = drive.put_files(files).await.unwrap();
+
+        let mut uploaded: Vec<&str> = report.uploaded.iter().map(|result| result.name()).collect();
+        uploaded.sort_unstable();
+        assert_eq!(uploaded, vec!["a.txt", "c.txt"]);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].0, "b.txt");
+        assert!(report.failed[0].1.is_bad_request());
+    }
+
+    #[tokio::test]
+    async fn put_files_respects_concurrency_limit() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/files"))
+            .respond_with(put_file_response("file.txt").set_delay(Duration::from_millis(200)))
+            .mount(&server)
+            .await;
+
+        let mut drive = Drive::with_base_url(&server.uri());
+        drive.limiter = crate::utils::Limiter::new(2);
+        let files = (0..6)
+            .map(|idx| (format!("{}.txt", idx), vec![idx as u8], None))
+            .collect();
+
+        let start = Instant::now();
+        let report = drive.put_files(files).await.unwrap();
+
+        // 6 requests, at most 2 at a time, each taking 200ms
+        assert!(start.elapsed() >= Duration::from_millis(550));
+        assert_eq!(report.uploaded.len(), 6);
+    }
+
+    #[tokio::test]
+    async fn put_files_rejects_duplicated_names() {
+        let server = MockServer::start().await;
+        Mock::given(wiremock::matchers::any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let files = vec![
+            ("a.txt".to_owned(), b"a".to_vec(), None),
+            ("a.txt".to_owned(), b"b".to_vec(), None),
+        ];
+        let error = drive.put_files(files).await.unwrap_err();
+        assert!(error.is_invalid_argument());
+    }
+
+    #[tokio::test]
+    async fn get_files_with_missing_names() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/files/download"))
+            .and(query_param("name", "a.txt"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("a"))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/files/download"))
+            .and(query_param("name", "b.txt"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&server)
+            .await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let files = drive.get_files(&["a.txt", "b.txt"]).await.unwrap();
+
+        assert_eq!(files.len(), 2);
+        assert_eq!(files["a.txt"].as_deref(), Some(&b"a"[..]));
+        assert_eq!(files["b.txt"], None);
+    }
+
+    #[tokio::test]
+    async fn get_files_with_failure() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/files/download"))
+            .and(query_param("name", "a.txt"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("a"))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/files/download"))
+            .and(query_param("name", "b.txt"))
+            .respond_with(ResponseTemplate::new(500))
+            .mount(&server)
+            .await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let error = drive.get_files(&["a.txt", "b.txt"]).await.unwrap_err();
+        assert!(error.is_response());
+    }
+
+    #[tokio::test]
+    async fn delete_by_prefix_rejects_empty_prefix() {
+        let server = MockServer::start().await;
+        Mock::given(wiremock::matchers::any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let error = drive.delete_by_prefix("").await.unwrap_err();
+        assert!(error.is_invalid_argument());
+    }
+}
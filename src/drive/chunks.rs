@@ -0,0 +1,83 @@
+use bytes::{Bytes, BytesMut};
+
+// Collects the incoming data into parts of exactly `chunk_size` bytes.
+// Deta requires every part of the chunked upload except the last one to be at least 5MB,
+// so the data coming in smaller pieces (e.g. from a reader) must be coalesced before sending.
+pub struct ChunkCoalescer {
+    chunk_size: usize,
+    buffer: BytesMut,
+}
+
+impl ChunkCoalescer {
+    pub fn new(chunk_size: usize) -> Self {
+        Self {
+            chunk_size,
+            buffer: BytesMut::new(),
+        }
+    }
+
+    pub fn push(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    // Returns the next full part, if enough data has been collected.
+    pub fn next_part(&mut self) -> Option<Bytes> {
+        if self.buffer.len() < self.chunk_size {
+            return None;
+        }
+        Some(self.buffer.split_to(self.chunk_size).freeze())
+    }
+
+    // Returns the remaining data, which makes up the last part. It may be empty.
+    pub fn finish(self) -> Bytes {
+        self.buffer.freeze()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MB: usize = 1024 * 1024;
+
+    // Pushes pieces of the given sizes and returns the sizes of the produced parts, including the last one.
+    fn coalesce(chunk_size: usize, pieces: &[usize]) -> Vec<usize> {
+        let mut coalescer = ChunkCoalescer::new(chunk_size);
+        let mut parts = vec![];
+        for piece in pieces {
+            coalescer.push(&vec![0; *piece]);
+            while let Some(part) = coalescer.next_part() {
+                parts.push(part.len());
+            }
+        }
+        parts.push(coalescer.finish().len());
+        parts
+    }
+
+    #[test]
+    fn coalesce_small_pieces() {
+        assert_eq!(coalesce(5 * MB, &[MB; 12]), vec![5 * MB, 5 * MB, 2 * MB]);
+    }
+
+    #[test]
+    fn coalesce_pieces_not_aligned_with_chunk_size() {
+        assert_eq!(
+            coalesce(5 * MB, &[3 * MB, 3 * MB, 3 * MB, 3 * MB]),
+            vec![5 * MB, 5 * MB, 2 * MB]
+        );
+        assert_eq!(
+            coalesce(5 * MB, &[MB + 7, 4 * MB, 17, 2 * MB]),
+            vec![5 * MB, 2 * MB + 24]
+        );
+    }
+
+    #[test]
+    fn coalesce_piece_greater_than_chunk_size() {
+        assert_eq!(coalesce(5 * MB, &[12 * MB]), vec![5 * MB, 5 * MB, 2 * MB]);
+    }
+
+    #[test]
+    fn coalesce_pieces_filling_whole_parts() {
+        assert_eq!(coalesce(5 * MB, &[5 * MB, 5 * MB]), vec![5 * MB, 5 * MB, 0]);
+    }
+}
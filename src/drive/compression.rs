@@ -0,0 +1,309 @@
+use super::{Drive, GetFileOptions, PutFileOptions};
+use crate::error::Result;
+use bytes::Bytes;
+use flate2::write::{GzDecoder, GzEncoder};
+use futures::future::Either;
+use futures::{Stream, StreamExt};
+use std::io::Write;
+
+const GZIP_SUFFIX: &str = ".gz";
+const GZIP_CONTENT_TYPE: &str = "application/gzip";
+
+/// Compression of the uploaded content, set with [`PutFileOptions::compression`](PutFileOptions::compression).
+///
+/// Compressed files follow a simple convention, so that clients other than this SDK can read them too:
+/// the content is stored in the gzip format under the given name with the `.gz` suffix appended
+/// (unless the name already ends with it), e.g. `report.json` is stored as `report.json.gz`.
+/// The content type is set to `application/gzip`, but deta ignores it for chunked uploads,
+/// so the suffix is the only reliable marker.
+///
+/// Available with the `compression` feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Compression {
+    /// The content is uploaded as it is.
+    #[default]
+    None,
+    /// The content is compressed with gzip.
+    Gzip,
+}
+
+impl Drive {
+    /// Returns a raw data like [`Drive::get_file_as_buffer`](Drive::get_file_as_buffer),
+    /// decompressing it if requested in the `options`.
+    pub async fn get_file_as_buffer_with(
+        &self,
+        name: &str,
+        options: GetFileOptions,
+    ) -> Result<Option<Bytes>> {
+        let data = self.get_file_as_buffer(name).await?;
+        match data {
+            Some(data) if should_decompress(name, &options) => {
+                let mut decoder = GzDecoder::new(Vec::new());
+                decoder.write_all(&data)?;
+                Ok(Some(decoder.finish()?.into()))
+            }
+            data => Ok(data),
+        }
+    }
+
+    /// Returns a raw data as a stream like [`Drive::get_file_as_stream`](Drive::get_file_as_stream),
+    /// decompressing it on the fly if requested in the `options`.
+    pub async fn get_file_as_stream_with(
+        &self,
+        name: &str,
+        options: GetFileOptions,
+    ) -> Result<Option<impl Stream<Item = Result<Bytes>>>> {
+        let decompress = should_decompress(name, &options);
+        let stream = self.get_file_as_stream(name).await?;
+        Ok(stream.map(|stream| {
+            if decompress {
+                Either::Right(transcode(stream, GzDecoder::new(Vec::new())))
+            } else {
+                Either::Left(stream)
+            }
+        }))
+    }
+}
+
+// Compresses the data to upload if requested, returning the name and options adjusted to the convention.
+pub(super) fn prepare_bytes(
+    name: &str,
+    data: Bytes,
+    options: PutFileOptions,
+) -> Result<(String, Bytes, PutFileOptions)> {
+    match options.compression {
+        Compression::None => Ok((name.to_owned(), data, options)),
+        Compression::Gzip => {
+            let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(&data)?;
+            let data = encoder.finish()?.into();
+            Ok((gzip_name(name), data, gzip_options(options)))
+        }
+    }
+}
+
+// Compresses the stream to upload on the fly if requested, returning the name and options adjusted to the convention.
+pub(super) fn prepare_stream<S>(
+    name: &str,
+    stream: S,
+    options: PutFileOptions,
+) -> (String, impl Stream<Item = Result<Bytes>>, PutFileOptions)
+where
+    S: Stream<Item = Result<Bytes>>,
+{
+    match options.compression {
+        Compression::None => (name.to_owned(), Either::Left(stream), options),
+        Compression::Gzip => {
+            let encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+            let stream = transcode(stream, encoder);
+            (
+                gzip_name(name),
+                Either::Right(stream),
+                gzip_options(options),
+            )
+        }
+    }
+}
+
+fn gzip_name(name: &str) -> String {
+    if name.ends_with(GZIP_SUFFIX) {
+        name.to_owned()
+    } else {
+        format!("{}{}", name, GZIP_SUFFIX)
+    }
+}
+
+fn gzip_options(options: PutFileOptions) -> PutFileOptions {
+    PutFileOptions {
+        content_type: Some(GZIP_CONTENT_TYPE.to_owned()),
+        compression: Compression::None,
+        ..options
+    }
+}
+
+fn should_decompress(name: &str, options: &GetFileOptions) -> bool {
+    options.decompress && name.ends_with(GZIP_SUFFIX)
+}
+
+// Encoder or decoder writing its output into a vector, which can be drained between writes.
+trait Coder: Write {
+    fn output(&mut self) -> &mut Vec<u8>;
+    fn finish_output(self) -> std::io::Result<Vec<u8>>;
+}
+
+impl Coder for GzEncoder<Vec<u8>> {
+    fn output(&mut self) -> &mut Vec<u8> {
+        self.get_mut()
+    }
+
+    fn finish_output(self) -> std::io::Result<Vec<u8>> {
+        self.finish()
+    }
+}
+
+impl Coder for GzDecoder<Vec<u8>> {
+    fn output(&mut self) -> &mut Vec<u8> {
+        self.get_mut()
+    }
+
+    fn finish_output(self) -> std::io::Result<Vec<u8>> {
+        self.finish()
+    }
+}
+
+// Passes every chunk of the stream through the coder, yielding the output as soon as it's available.
+fn transcode<S, C>(stream: S, coder: C) -> impl Stream<Item = Result<Bytes>>
+where
+    S: Stream<Item = Result<Bytes>>,
+    C: Coder,
+{
+    let state = (Box::pin(stream), Some(coder));
+    futures::stream::try_unfold(state, |(mut stream, mut coder)| async move {
+        while let Some(active_coder) = coder.as_mut() {
+            match stream.next().await.transpose()? {
+                Some(data) => {
+                    active_coder.write_all(&data)?;
+                    if !active_coder.output().is_empty() {
+                        let output = std::mem::take(active_coder.output());
+                        return Ok(Some((output.into(), (stream, coder))));
+                    }
+                }
+                None => {
+                    let output = coder.take().unwrap().finish_output()?;
+                    if !output.is_empty() {
+                        return Ok(Some((output.into(), (stream, None))));
+                    }
+                }
+            }
+        }
+        Ok(None)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn compressible_data() -> Vec<u8> {
+        "{\"level\": \"info\", \"message\": \"Request handled\"}\n"
+            .repeat(2000)
+            .into_bytes()
+    }
+
+    // Data which doesn't compress, like the content of images or archives.
+    fn incompressible_data() -> Vec<u8> {
+        let mut seed: u32 = 42;
+        (0..64 * 1024)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                (seed >> 16) as u8
+            })
+            .collect()
+    }
+
+    async fn mount_put(server: &MockServer) {
+        Mock::given(method("POST"))
+            .and(path("/files"))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!({
+                "name": "file",
+                "project_id": "project",
+                "drive_name": "drive"
+            })))
+            .mount(server)
+            .await;
+    }
+
+    async fn mount_download(server: &MockServer, name: &str, data: Vec<u8>) {
+        Mock::given(method("GET"))
+            .and(path("/files/download"))
+            .and(query_param("name", name))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(data))
+            .mount(server)
+            .await;
+    }
+
+    fn gzip_options() -> PutFileOptions {
+        PutFileOptions {
+            compression: Compression::Gzip,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn put_file_with_gzip_round_trip() {
+        let server = MockServer::start().await;
+        mount_put(&server).await;
+        let drive = Drive::with_base_url(&server.uri());
+        let data = compressible_data();
+
+        drive
+            .put_file_with("logs.jsonl", data.clone(), gzip_options())
+            .await
+            .unwrap();
+
+        let requests = server.received_requests().await.unwrap();
+        let request = &requests[0];
+        assert_eq!(request.url.query(), Some("name=logs.jsonl.gz"));
+        assert_eq!(request.headers[&"content-type".into()], GZIP_CONTENT_TYPE);
+        assert!(request.body.len() * 10 < data.len());
+
+        mount_download(&server, "logs.jsonl.gz", request.body.clone()).await;
+        let options = GetFileOptions { decompress: true };
+        let downloaded = drive
+            .get_file_as_buffer_with("logs.jsonl.gz", options)
+            .await
+            .unwrap();
+        assert_eq!(downloaded.as_deref(), Some(&data[..]));
+
+        let raw = drive
+            .get_file_as_buffer_with("logs.jsonl.gz", GetFileOptions::default())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(&raw[..], &request.body[..]);
+    }
+
+    #[tokio::test]
+    async fn put_file_from_reader_with_gzip_round_trip() {
+        let server = MockServer::start().await;
+        mount_put(&server).await;
+        let drive = Drive::with_base_url(&server.uri());
+        let data = incompressible_data();
+
+        drive
+            .put_file_from_reader("photo.jpg.gz", &data[..], gzip_options())
+            .await
+            .unwrap();
+
+        let requests = server.received_requests().await.unwrap();
+        let request = &requests[0];
+        assert_eq!(request.url.query(), Some("name=photo.jpg.gz"));
+
+        mount_download(&server, "photo.jpg.gz", request.body.clone()).await;
+        let options = GetFileOptions { decompress: true };
+        let stream = drive
+            .get_file_as_stream_with("photo.jpg.gz", options)
+            .await
+            .unwrap()
+            .unwrap();
+        let chunks: Vec<Bytes> = futures::TryStreamExt::try_collect(stream).await.unwrap();
+        assert_eq!(chunks.concat(), data);
+    }
+
+    #[tokio::test]
+    async fn get_file_with_decompress_for_plain_file() {
+        let server = MockServer::start().await;
+        mount_download(&server, "plain.txt", b"plain".to_vec()).await;
+        let drive = Drive::with_base_url(&server.uri());
+
+        let options = GetFileOptions { decompress: true };
+        let downloaded = drive
+            .get_file_as_buffer_with("plain.txt", options)
+            .await
+            .unwrap();
+        assert_eq!(downloaded.as_deref(), Some(&b"plain"[..]));
+    }
+}
@@ -0,0 +1,295 @@
+use super::PutFileOptions;
+use crate::error::{Error, Result};
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+#[cfg(feature = "checksum")]
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+/// Statistics of the upload performed with [`Drive::put_file_with_stats`](super::Drive::put_file_with_stats).
+/// A file uploaded with a single put is reported as a single part.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UploadStats {
+    /// Number of uploaded parts.
+    pub parts: usize,
+    /// Number of uploaded bytes.
+    pub bytes: u64,
+    /// Number of parts sent more than once (see [`PutFileOptions::part_retries`](PutFileOptions::part_retries)).
+    pub retried_parts: usize,
+    /// Time of the whole upload.
+    pub elapsed: Duration,
+    /// Hex encoded SHA-256 digest of the uploaded content, computed while the content is sent.
+    /// For a compressed file, it's the digest of the compressed content.
+    ///
+    /// Available with the `checksum` feature.
+    #[cfg(feature = "checksum")]
+    pub sha256: String,
+}
+
+// Limits of a single upload: the timeout of each request, the deadline of the whole operation,
+// the cancellation by the caller and the number of retries of each part.
+// The deadline is fixed when the upload starts, so nothing performed later can extend it,
+// and it covers reading the content from the source as well as the requests.
+// It's measured with the tokio clock, so a paused runtime pauses it too.
+// The progress of the upload is counted along the way, to be reported in errors and statistics.
+pub(super) struct UploadLimits {
+    part_timeout: Option<Duration>,
+    deadline: Option<(tokio::time::Instant, Duration)>,
+    cancellation: Option<CancellationToken>,
+    part_retries: usize,
+    started: Instant,
+    completed_parts: AtomicUsize,
+    uploaded_bytes: AtomicU64,
+    retried_parts: AtomicUsize,
+    #[cfg(feature = "checksum")]
+    digest: Mutex<sha2::Sha256>,
+}
+
+impl UploadLimits {
+    pub fn start(options: &PutFileOptions) -> Self {
+        Self {
+            part_timeout: options.part_timeout,
+            deadline: options
+                .deadline
+                .map(|deadline| (tokio::time::Instant::now() + deadline, deadline)),
+            cancellation: options.cancellation.clone(),
+            part_retries: options.part_retries,
+            started: Instant::now(),
+            completed_parts: AtomicUsize::new(0),
+            uploaded_bytes: AtomicU64::new(0),
+            retried_parts: AtomicUsize::new(0),
+            #[cfg(feature = "checksum")]
+            digest: Mutex::new(sha2::Digest::new()),
+        }
+    }
+
+    pub fn part_completed(&self, size: usize) {
+        self.completed_parts.fetch_add(1, Ordering::Relaxed);
+        self.uploaded_bytes
+            .fetch_add(size as u64, Ordering::Relaxed);
+    }
+
+    pub fn stats(&self) -> UploadStats {
+        UploadStats {
+            parts: self.completed_parts.load(Ordering::Relaxed),
+            bytes: self.uploaded_bytes.load(Ordering::Relaxed),
+            retried_parts: self.retried_parts.load(Ordering::Relaxed),
+            elapsed: self.started.elapsed(),
+            #[cfg(feature = "checksum")]
+            sha256: {
+                let digest = self
+                    .digest
+                    .lock()
+                    .expect("The digest lock is never poisoned");
+                sha2::Digest::finalize(digest.clone())
+                    .iter()
+                    .map(|byte| format!("{:02x}", byte))
+                    .collect()
+            },
+        }
+    }
+
+    // Feeds the uploaded content into the checksum. It must be called with the content in the order of the file.
+    #[cfg(feature = "checksum")]
+    pub fn hash(&self, data: &[u8]) {
+        sha2::Digest::update(
+            &mut *self
+                .digest
+                .lock()
+                .expect("The digest lock is never poisoned"),
+            data,
+        );
+    }
+
+    // Runs the request of a single part, sending it again after a retryable failure,
+    // as long as the retries are not used up. The `request` is called once per attempt.
+    pub async fn run_part<F, Fut, T>(&self, name: &str, request: F) -> Result<T>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut attempt = 0;
+        loop {
+            match self.run(name, request()).await {
+                Err(error) if attempt < self.part_retries && error.is_retryable() => {
+                    if attempt == 0 {
+                        self.retried_parts.fetch_add(1, Ordering::Relaxed);
+                    }
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    // Runs the request of the upload, failing with a timeout error if any of the limits is exceeded.
+    // Nothing is sent if the upload has already been cancelled.
+    pub async fn run<F, T>(&self, name: &str, request: F) -> Result<T>
+    where
+        F: Future<Output = Result<T>>,
+    {
+        if let Some(cancellation) = &self.cancellation {
+            if cancellation.is_cancelled() {
+                return Err(Error::from_cancellation(
+                    self.completed_parts.load(Ordering::Relaxed),
+                ));
+            }
+        }
+
+        let (limit, is_deadline) = match (self.part_timeout, self.remaining()) {
+            (None, None) => return request.await,
+            (Some(part_timeout), Some(remaining)) if remaining < part_timeout => (remaining, true),
+            (Some(part_timeout), _) => (part_timeout, false),
+            (None, Some(remaining)) => (remaining, true),
+        };
+
+        match tokio::time::timeout(limit, request).await {
+            Ok(result) => result,
+            Err(_) => Err(self.timeout_error(name, is_deadline)),
+        }
+    }
+
+    // Reads the next piece of the content from the source, failing with a timeout error if the deadline passes first.
+    // The part timeout only applies to the requests, a slow source doesn't make them slow.
+    pub async fn read<F, T>(&self, name: &str, read: F) -> Result<T>
+    where
+        F: Future<Output = T>,
+    {
+        match self.remaining() {
+            None => Ok(read.await),
+            Some(remaining) => tokio::time::timeout(remaining, read)
+                .await
+                .map_err(|_| self.timeout_error(name, true)),
+        }
+    }
+
+    fn remaining(&self) -> Option<Duration> {
+        self.deadline
+            .map(|(deadline, _)| deadline.saturating_duration_since(tokio::time::Instant::now()))
+    }
+
+    fn timeout_error(&self, name: &str, is_deadline: bool) -> Error {
+        let limit = if is_deadline {
+            format!("Upload deadline of {:?}", self.deadline.unwrap().1)
+        } else {
+            format!("Part timeout of {:?}", self.part_timeout.unwrap())
+        };
+        Error::from_timeout(format!(
+            "{} exceeded while uploading '{}', {} part(s) completed",
+            limit,
+            name,
+            self.completed_parts.load(Ordering::Relaxed)
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(part_timeout: Option<u64>, deadline: Option<u64>) -> UploadLimits {
+        UploadLimits::start(&PutFileOptions {
+            part_timeout: part_timeout.map(Duration::from_millis),
+            deadline: deadline.map(Duration::from_millis),
+            ..Default::default()
+        })
+    }
+
+    async fn stalled_request() -> Result<()> {
+        tokio::time::sleep(Duration::from_secs(10)).await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn run_without_limits() {
+        let limits = limits(None, None);
+        assert_eq!(limits.run("file", async { Ok(1) }).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn run_for_exceeded_part_timeout() {
+        let limits = limits(Some(10), Some(10_000));
+        limits.part_completed(1);
+        let error = limits.run("file", stalled_request()).await.unwrap_err();
+        assert!(error.is_timeout());
+        assert!(error.to_string().contains("Part timeout of 10ms"));
+        assert!(error.to_string().contains("1 part(s) completed"));
+    }
+
+    #[tokio::test]
+    async fn run_for_cancelled_upload() {
+        let cancellation = CancellationToken::new();
+        let limits = UploadLimits::start(&PutFileOptions {
+            cancellation: Some(cancellation.clone()),
+            ..Default::default()
+        });
+        limits.run("file", async { Ok(()) }).await.unwrap();
+        limits.part_completed(1);
+
+        cancellation.cancel();
+        let error = limits.run("file", async { Ok(()) }).await.unwrap_err();
+        assert!(error.is_cancelled());
+        assert!(matches!(
+            error.get_kind(),
+            crate::error::Kind::Cancelled { uploaded_parts: 1 }
+        ));
+    }
+
+    #[tokio::test]
+    async fn run_part_retries_transient_failures() {
+        let limits = UploadLimits::start(&PutFileOptions {
+            part_retries: 2,
+            ..Default::default()
+        });
+        let attempts = AtomicUsize::new(0);
+        let result = limits
+            .run_part("file", || async {
+                match attempts.fetch_add(1, Ordering::Relaxed) {
+                    0 | 1 => Err(Error::from_response_data(
+                        Some(reqwest::StatusCode::INTERNAL_SERVER_ERROR),
+                        None,
+                        None,
+                    )),
+                    _ => Ok(()),
+                }
+            })
+            .await;
+
+        assert!(result.is_ok());
+        assert_eq!(attempts.load(Ordering::Relaxed), 3);
+        assert_eq!(limits.stats().retried_parts, 1);
+    }
+
+    #[tokio::test]
+    async fn run_part_does_not_retry_client_errors() {
+        let limits = UploadLimits::start(&PutFileOptions {
+            part_retries: 2,
+            ..Default::default()
+        });
+        let attempts = AtomicUsize::new(0);
+        let error = limits
+            .run_part("file", || async {
+                attempts.fetch_add(1, Ordering::Relaxed);
+                Err::<(), _>(Error::from_response_data(
+                    Some(reqwest::StatusCode::BAD_REQUEST),
+                    None,
+                    None,
+                ))
+            })
+            .await
+            .unwrap_err();
+
+        assert!(error.is_bad_request());
+        assert_eq!(attempts.load(Ordering::Relaxed), 1);
+        assert_eq!(limits.stats().retried_parts, 0);
+    }
+
+    #[tokio::test]
+    async fn run_for_exceeded_deadline() {
+        let limits = limits(Some(10_000), Some(10));
+        let error = limits.run("file", stalled_request()).await.unwrap_err();
+        assert!(error.to_string().contains("Upload deadline of 10ms"));
+        assert!(error.to_string().contains("0 part(s) completed"));
+    }
+}
@@ -0,0 +1,204 @@
+use super::{Drive, PutFileResult, PutFromUrlOptions};
+use crate::error::{Error, Result};
+use futures::{Stream, StreamExt};
+
+impl Drive {
+    /// Uploads the content of a remote `url`, streaming it straight into the drive,
+    /// so sources of any size can be mirrored without loading them whole into memory.
+    /// The content type sent by the source is kept, unless it's set in the options.
+    ///
+    /// Failures of the source are reported as [`Error::is_source_fetch`](crate::error::Error::is_source_fetch) errors,
+    /// including exceeding the [`max_bytes`](PutFromUrlOptions::max_bytes) limit,
+    /// which aborts the upload. Other errors come from the upload itself.
+    pub async fn put_from_url(
+        &self,
+        name: &str,
+        url: &str,
+        options: PutFromUrlOptions,
+    ) -> Result<PutFileResult> {
+        let response = reqwest::Client::new()
+            .get(url)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(|error| {
+                Error::from_source_fetch(format!("Cannot fetch '{}'", url), Some(error.into()))
+            })?;
+
+        let max_bytes = options.max_bytes;
+        if let (Some(max_bytes), Some(length)) = (max_bytes, response.content_length()) {
+            if length > max_bytes {
+                return Err(size_limit_error(url, max_bytes));
+            }
+        }
+
+        let mut put_options = options.put_options;
+        if put_options.content_type.is_none() {
+            put_options.content_type = response
+                .headers()
+                .get(reqwest::header::CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_owned);
+        }
+
+        let stream = response.bytes_stream().map(move |chunk| {
+            chunk.map_err(|error| {
+                Error::from_source_fetch(format!("Cannot read '{}'", url), Some(error.into()))
+            })
+        });
+        let stream = limit_size(stream, url, max_bytes);
+
+        let (result, _) = self.put_stream(name, stream, put_options).await?;
+        Ok(result)
+    }
+}
+
+// Fails the stream once it exceeds `max_bytes`. The limit is checked for every chunk,
+// as the length declared by the source may be missing or wrong.
+fn limit_size<'a, S>(
+    stream: S,
+    url: &'a str,
+    max_bytes: Option<u64>,
+) -> impl Stream<Item = Result<bytes::Bytes>> + 'a
+where
+    S: Stream<Item = Result<bytes::Bytes>> + 'a,
+{
+    let mut received = 0;
+    stream.map(move |chunk| {
+        let chunk = chunk?;
+        received += chunk.len() as u64;
+        match max_bytes {
+            Some(max_bytes) if received > max_bytes => Err(size_limit_error(url, max_bytes)),
+            _ => Ok(chunk),
+        }
+    })
+}
+
+fn size_limit_error(url: &str, max_bytes: u64) -> Error {
+    Error::from_source_fetch(
+        format!("Source '{}' exceeds the limit of {} bytes", url, max_bytes),
+        None,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use serde_json::json;
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    async fn mount_source(server: &MockServer, data: Vec<u8>) {
+        Mock::given(method("GET"))
+            .and(path("/image.png"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(data, "image/png"))
+            .mount(server)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn put_from_url() {
+        let source = MockServer::start().await;
+        mount_source(&source, vec![1; 1024]).await;
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/files"))
+            .and(header("content-type", "image/png"))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!({
+                "name": "image.png",
+                "project_id": "project",
+                "drive_name": "drive"
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let url = format!("{}/image.png", source.uri());
+        let result = drive
+            .put_from_url("image.png", &url, PutFromUrlOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(result.name(), "image.png");
+    }
+
+    #[tokio::test]
+    async fn put_from_url_for_failed_source() {
+        let source = MockServer::start().await;
+        let server = MockServer::start().await;
+        Mock::given(wiremock::matchers::any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let url = format!("{}/missing.png", source.uri());
+        let error = drive
+            .put_from_url("image.png", &url, PutFromUrlOptions::default())
+            .await
+            .unwrap_err();
+        assert!(error.is_source_fetch());
+    }
+
+    #[tokio::test]
+    async fn put_from_url_for_failed_upload() {
+        let source = MockServer::start().await;
+        mount_source(&source, vec![1; 1024]).await;
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/files"))
+            .respond_with(ResponseTemplate::new(401))
+            .mount(&server)
+            .await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let url = format!("{}/image.png", source.uri());
+        let error = drive
+            .put_from_url("image.png", &url, PutFromUrlOptions::default())
+            .await
+            .unwrap_err();
+        assert!(!error.is_source_fetch());
+        assert!(error.is_response());
+    }
+
+    #[tokio::test]
+    async fn put_from_url_exceeding_max_bytes() {
+        let source = MockServer::start().await;
+        mount_source(&source, vec![1; 2048]).await;
+        let server = MockServer::start().await;
+        Mock::given(wiremock::matchers::any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let url = format!("{}/image.png", source.uri());
+        let options = PutFromUrlOptions {
+            max_bytes: Some(1024),
+            ..Default::default()
+        };
+        let error = drive
+            .put_from_url("image.png", &url, options)
+            .await
+            .unwrap_err();
+        assert!(error.is_source_fetch());
+        assert!(error.to_string().contains("limit of 1024 bytes"));
+    }
+
+    #[tokio::test]
+    async fn limit_size_for_exceeding_stream() {
+        let chunks = vec![Ok(Bytes::from(vec![1; 512])), Ok(Bytes::from(vec![2; 512]))];
+        let stream = limit_size(futures::stream::iter(chunks), "url", Some(1000));
+        let results: Vec<Result<Bytes>> = stream.collect().await;
+        assert!(results[0].is_ok());
+        assert!(results[1].as_ref().unwrap_err().is_source_fetch());
+
+        let chunks = vec![Ok(Bytes::from(vec![1; 512])), Ok(Bytes::from(vec![2; 512]))];
+        let stream = limit_size(futures::stream::iter(chunks), "url", Some(1024));
+        let results: Vec<Result<Bytes>> = stream.collect().await;
+        assert!(results.iter().all(Result::is_ok));
+    }
+}
@@ -1,23 +1,77 @@
 //! Deta-drive service SDK.
 //! Check [deta docs](https://docs.deta.sh/docs/drive/http) for more information.
+//!
+//! File names are validated before any request is sent. A name must not be empty or consist only of whitespace,
+//! must not start with `/` and must be at most 1024 bytes long, otherwise an invalid argument error is returned.
 
 use crate::deta_client::DetaClient;
+#[cfg(feature = "actix")]
+pub mod actix;
+#[cfg(feature = "axum")]
+pub mod axum;
+#[cfg(feature = "backup")]
+mod backup;
+mod bulk;
+mod chunks;
+#[cfg(feature = "compression")]
+mod compression;
+mod limits;
+mod mirror;
 pub mod models;
+#[cfg(feature = "object_store")]
+pub mod object_store;
+mod options;
 mod requests;
+mod resume;
+#[cfg(any(feature = "axum", feature = "actix"))]
+mod serve;
+mod sync;
+mod verify;
+mod watch;
 use crate::constants;
-use crate::error::Result;
+use crate::error::{Error, Result, UploadErrorContext};
+use crate::mutation::{MutationEvent, MutationKind, MutationObserver, Observers};
+use crate::transport::{HttpTransport, Transport};
 use crate::utils;
+use futures::{Stream, StreamExt, TryStreamExt};
+use limits::UploadLimits;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::sync::Arc;
+
+#[cfg(feature = "backup")]
+pub use backup::BackupStats;
+pub use bulk::{CopyProgress, CopyStats, PutFilesReport};
+#[cfg(feature = "compression")]
+pub use compression::Compression;
+pub use limits::UploadStats;
+#[cfg(feature = "compression")]
+pub use options::GetFileOptions;
+pub use options::{
+    AppendOptions, DownloadDirOptions, DownloadOptions, PutFileOptions, PutFromUrlOptions,
+    UploadDirOptions,
+};
+pub use sync::SyncStats;
+pub use watch::DriveEvent;
 
 /// Stores the necessary information and methods to
 /// work with the [deta-drive](https://docs.deta.sh/docs/drive/http) API.
 pub struct Drive {
     base_url: String,
     x_api_key: String,
+    limiter: utils::Limiter,
+    upload_throttle: Option<utils::Throttle>,
+    download_throttle: Option<utils::Throttle>,
+    transport: Arc<dyn Transport>,
+    observers: Observers,
 }
 
 impl Drive {
     /// Creates an `Drive` instance.
-    pub fn new(client: &DetaClient, drive_name: &str) -> Self {
+    /// Fails with an invalid argument error if the drive name is empty, longer than 64 characters,
+    /// or contains characters other than ASCII letters, digits, `_` and `-`.
+    pub fn new(client: &DetaClient, drive_name: &str) -> Result<Self> {
+        utils::validate_drive_name(drive_name)?;
         let base_url = format!(
             "{}/{}/{}",
             constants::DRIVE_API_URL,
@@ -27,9 +81,76 @@ impl Drive {
 
         let x_api_key = client.api_key().to_owned();
 
-        Self {
+        Ok(Self {
             base_url,
             x_api_key,
+            limiter: client.limiter().clone(),
+            upload_throttle: client.upload_throttle().cloned(),
+            download_throttle: client.download_throttle().cloned(),
+            transport: Arc::new(HttpTransport::default()),
+            observers: Observers::default(),
+        })
+    }
+
+    /// Sends the requests through the `transport` instead of the network,
+    /// e.g. through [`FakeDrive`](crate::testing::FakeDrive) in tests.
+    pub fn with_transport(mut self, transport: Arc<dyn Transport>) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Registers the `observer` called with the [`MutationEvent`](MutationEvent) of every upload and deletion
+    /// made through the drive, e.g. to invalidate a cache. See the [`mutation`](crate::mutation) module documentation.
+    pub fn on_mutation(mut self, observer: MutationObserver) -> Self {
+        self.observers.push(observer);
+        self
+    }
+
+    #[cfg(test)]
+    pub(crate) fn with_base_url(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.to_owned(),
+            x_api_key: "test_key".to_owned(),
+            limiter: utils::Limiter::new(constants::DEFAULT_CONCURRENCY),
+            upload_throttle: None,
+            download_throttle: None,
+            transport: Arc::new(HttpTransport::default()),
+            observers: Observers::default(),
+        }
+    }
+
+    // Returns the body of the download response, paced to the download limit of the client.
+    fn body_stream(
+        &self,
+        response: reqwest::Response,
+    ) -> impl Stream<Item = Result<bytes::Bytes>> + Send + 'static {
+        let stream = response.bytes_stream().map_err(Error::from);
+        match self.download_throttle.clone() {
+            Some(throttle) => futures::future::Either::Left(throttle.pace(stream)),
+            None => futures::future::Either::Right(stream),
+        }
+    }
+
+    // Returns the whole body of the download response, received at the pace of the download limit of the client.
+    async fn body_bytes(&self, response: reqwest::Response) -> Result<bytes::Bytes> {
+        if self.download_throttle.is_none() {
+            return Ok(response.bytes().await?);
+        }
+        let chunks: Vec<bytes::Bytes> = self.body_stream(response).try_collect().await?;
+        Ok(chunks.concat().into())
+    }
+
+    // Reports the finished upload of the file to the observers.
+    fn notify_put<T>(&self, name: &str, result: &Result<T>) {
+        self.observers.notify(result, |result| {
+            MutationEvent::new(MutationKind::Put, vec![name.to_owned()], result)
+        });
+    }
+
+    // Waits until uploading the `bytes` fits within the upload limit of the client.
+    async fn pace_upload(&self, bytes: usize) {
+        if let Some(throttle) = &self.upload_throttle {
+            throttle.consume(bytes).await;
         }
     }
 
@@ -37,64 +158,323 @@ impl Drive {
         &self,
         name: &str,
     ) -> Result<models::InitializeChunkedUpload> {
-        let response =
-            requests::initialize_chunked_upload_request(&self.base_url, &self.x_api_key, name)
-                .await?;
-        Ok(utils::parse_response_body(response).await?)
+        let response = requests::initialize_chunked_upload_request(
+            self.transport.as_ref(),
+            &self.base_url,
+            &self.x_api_key,
+            name,
+        )
+        .await?;
+        utils::parse_response_body(response).await
+    }
+
+    async fn end_chunked_upload(
+        &self,
+        name: &str,
+        upload_id: &str,
+    ) -> Result<models::EndChunkedUpload> {
+        let response = requests::end_chunked_upload_request(
+            self.transport.as_ref(),
+            &self.base_url,
+            &self.x_api_key,
+            name,
+            upload_id,
+        )
+        .await?;
+        utils::parse_response_body(response).await
     }
 
     async fn perform_chunked_upload(
         &self,
         name: &str,
-        data: Vec<u8>,
+        bytes: bytes::Bytes,
+        options: &PutFileOptions,
+        limits: &UploadLimits,
     ) -> Result<models::EndChunkedUpload> {
-        let bytes: bytes::Bytes = data.into();
-        let upload_id = self.get_chunked_upload_object(name).await?.upload_id;
+        let upload_id = limits
+            .run(name, self.get_chunked_upload_object(name))
+            .await?
+            .upload_id;
+
+        let result = async {
+            self.upload_parts(name, &upload_id, &bytes, options, limits)
+                .await?;
+            limits
+                .run(name, self.end_chunked_upload(name, &upload_id))
+                .await
+        }
+        .await;
+
+        match result {
+            Ok(result) => Ok(result),
+            Err(error) => Err(self.abort_after_failure(name, &upload_id, error).await),
+        }
+    }
+
+    // Aborts the chunked upload after the `error`. The returned error is always the original one,
+    // with the upload context and the abort failure attached, so that the orphaned upload can be aborted later.
+    async fn abort_after_failure(&self, name: &str, upload_id: &str, error: Error) -> Error {
+        let error = error.with_upload_context(UploadErrorContext {
+            upload_id: upload_id.to_owned(),
+            part: None,
+            total_parts: None,
+        });
+        match self.abort_chunked_upload(name, upload_id).await {
+            Ok(()) => error,
+            Err(abort_error) => error.with_cleanup_failure(upload_id, abort_error),
+        }
+    }
+
+    async fn upload_parts(
+        &self,
+        name: &str,
+        upload_id: &str,
+        bytes: &bytes::Bytes,
+        options: &PutFileOptions,
+        limits: &UploadLimits,
+    ) -> Result<()> {
         let content_length = bytes.len();
-        let chunk_size = constants::MAX_DATA_CHUNK_SIZE;
-        let mut part = 1;
+        let chunk_size = options.chunk_size;
+        if content_length == 0 {
+            return Err(empty_chunked_upload_error(name));
+        }
+
+        let total_parts = Some(content_length.div_ceil(chunk_size));
+        futures::stream::iter((1..).zip((0..content_length).step_by(chunk_size)))
+            .map(|(part, idx)| {
+                let chunk = bytes.slice(idx..content_length.min(idx + chunk_size));
+                self.upload_part(name, upload_id, part, total_parts, chunk, limits)
+            })
+            .buffer_unordered(options.concurrency)
+            .try_for_each(|_| async { Ok(()) })
+            .await
+    }
 
-        for idx in (0..content_length).step_by(chunk_size) {
-            let end = content_length.min(idx + chunk_size);
-            let chunk = bytes.slice(idx..end);
-            let upload_result = requests::upload_chunk_request(
+    // Uploads the `part` out of `total_parts`. The failure carries the upload context with the part number.
+    async fn upload_part(
+        &self,
+        name: &str,
+        upload_id: &str,
+        part: usize,
+        total_parts: Option<usize>,
+        chunk: bytes::Bytes,
+        limits: &UploadLimits,
+    ) -> Result<()> {
+        let size = chunk.len();
+        let request = || {
+            requests::upload_chunk_request(
+                self.transport.as_ref(),
                 &self.base_url,
                 &self.x_api_key,
                 name,
-                &upload_id,
+                upload_id,
                 part,
-                chunk,
+                chunk.clone(),
             )
-            .await;
-            if let Err(error) = upload_result {
-                requests::abort_chunked_upload_request(
-                    &self.base_url,
-                    &self.x_api_key,
-                    name,
-                    &upload_id,
-                )
+        };
+        self.pace_upload(size).await;
+        limits.run_part(name, request).await.map_err(|error| {
+            error.with_upload_context(UploadErrorContext {
+                upload_id: upload_id.to_owned(),
+                part: Some(part),
+                total_parts,
+            })
+        })?;
+        limits.part_completed(size);
+        Ok(())
+    }
+
+    // Uploads data coming from the stream, collecting it into parts of `options.chunk_size` bytes.
+    // Parts are sent one by one, so that the whole content never has to be kept in memory.
+    async fn perform_streamed_upload<S>(
+        &self,
+        name: &str,
+        stream: S,
+        options: &PutFileOptions,
+        limits: &UploadLimits,
+    ) -> Result<models::EndChunkedUpload>
+    where
+        S: Stream<Item = Result<bytes::Bytes>>,
+    {
+        let upload_id = limits
+            .run(name, self.get_chunked_upload_object(name))
+            .await?
+            .upload_id;
+
+        let result = async {
+            self.upload_stream_parts(name, &upload_id, stream, options.chunk_size, limits)
                 .await?;
-                return Err(error);
+            limits
+                .run(name, self.end_chunked_upload(name, &upload_id))
+                .await
+        }
+        .await;
+
+        match result {
+            Ok(result) => Ok(result),
+            Err(error) => Err(self.abort_after_failure(name, &upload_id, error).await),
+        }
+    }
+
+    async fn upload_stream_parts<S>(
+        &self,
+        name: &str,
+        upload_id: &str,
+        stream: S,
+        chunk_size: usize,
+        limits: &UploadLimits,
+    ) -> Result<()>
+    where
+        S: Stream<Item = Result<bytes::Bytes>>,
+    {
+        futures::pin_mut!(stream);
+        let mut coalescer = chunks::ChunkCoalescer::new(chunk_size);
+        let mut part = 1;
+
+        while let Some(data) = limits.read(name, stream.next()).await?.transpose()? {
+            coalescer.push(&data);
+            while let Some(chunk) = coalescer.next_part() {
+                self.upload_part(name, upload_id, part, None, chunk, limits)
+                    .await?;
+                part += 1;
             }
-            part += 1;
         }
 
-        let response =
-            requests::end_chunked_upload_request(&self.base_url, &self.x_api_key, name, &upload_id)
+        // The last part may be smaller than `chunk_size`.
+        let chunk = coalescer.finish();
+        if chunk.is_empty() && part == 1 {
+            return Err(empty_chunked_upload_error(name));
+        }
+        if !chunk.is_empty() {
+            self.upload_part(name, upload_id, part, None, chunk, limits)
                 .await?;
-        Ok(utils::parse_response_body(response).await?)
+        }
+
+        Ok(())
     }
 
-    /// Uploads the file to the server.
-    /// If the amount of data to be uploaded exceeds 10MB, chunked uploading will be used.
-    pub async fn put_file(
+    // Uploads the data coming from the stream. If the stream ends before exceeding the chunked upload threshold,
+    // the data is sent with a single request, otherwise it's streamed into a chunked upload.
+    async fn put_stream<S>(
+        &self,
+        name: &str,
+        stream: S,
+        options: PutFileOptions,
+    ) -> Result<(PutFileResult, UploadStats)>
+    where
+        S: Stream<Item = Result<bytes::Bytes>>,
+    {
+        #[cfg(feature = "compression")]
+        let (name, stream, options) = compression::prepare_stream(name, stream, options);
+        #[cfg(feature = "compression")]
+        let name = name.as_str();
+
+        let result = self.upload_stream(name, stream, options).await;
+        self.notify_put(name, &result);
+        result
+    }
+
+    async fn upload_stream<S>(
+        &self,
+        name: &str,
+        stream: S,
+        options: PutFileOptions,
+    ) -> Result<(PutFileResult, UploadStats)>
+    where
+        S: Stream<Item = Result<bytes::Bytes>>,
+    {
+        utils::validate_file_name(name)?;
+        options.validate()?;
+        let limits = UploadLimits::start(&options);
+        futures::pin_mut!(stream);
+
+        // Even a forced chunked upload needs some data, an empty stream always ends with a single put.
+        let mut head = bytes::BytesMut::new();
+        while head.is_empty() || (!options.force_chunked && head.len() <= options.chunked_threshold)
+        {
+            match limits.read(name, stream.next()).await?.transpose()? {
+                Some(data) => head.extend_from_slice(&data),
+                None => {
+                    let size = head.len() as u64;
+                    #[cfg(feature = "checksum")]
+                    limits.hash(&head);
+                    let result = self
+                        .single_put_with_fallback(name, head.freeze(), &options, &limits)
+                        .await?;
+                    let stats = limits.stats();
+                    self.verify_if_requested(name, size, &options).await?;
+                    return Ok((result, stats));
+                }
+            }
+        }
+
+        let mut size = 0;
+        let stream = futures::stream::once(async { Ok(head.freeze()) })
+            .chain(stream)
+            .inspect_ok(|data| {
+                size += data.len() as u64;
+                #[cfg(feature = "checksum")]
+                limits.hash(data);
+            });
+        let result = self
+            .perform_streamed_upload(name, stream, &options, &limits)
+            .await?;
+        let stats = limits.stats();
+        self.verify_if_requested(name, size, &options).await?;
+        Ok((PutFileResult::ChunkedUpload(result), stats))
+    }
+
+    // Copies the file into `dest` drive. Content is streamed into a chunked upload,
+    // unless it turns out to be small enough for a single put.
+    async fn copy_file(&self, from: &str, dest: &Drive, to: &str) -> Result<()> {
+        utils::validate_file_name(from)?;
+        let response = requests::get_file_request(
+            self.transport.as_ref(),
+            &self.base_url,
+            &self.x_api_key,
+            from,
+        )
+        .await?;
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_owned);
+
+        let small_enough = matches!(
+            response.content_length(),
+            Some(length) if length as usize <= constants::MAX_DATA_CHUNK_SIZE
+        );
+
+        if small_enough {
+            let data = self.body_bytes(response).await?;
+            dest.put_file(to, data, content_type.as_deref()).await?;
+            return Ok(());
+        }
+
+        let stream = self.body_stream(response);
+        let options = PutFileOptions {
+            content_type,
+            ..Default::default()
+        };
+        dest.put_stream(to, stream, options).await?;
+        Ok(())
+    }
+
+    // Uploads the data with a single request. Deta may reject empty files,
+    // which is reported as an invalid argument rather than a bare bad request.
+    async fn single_put(
         &self,
         name: &str,
-        data: Vec<u8>,
+        data: bytes::Bytes,
         content_type: Option<&str>,
+        limits: &UploadLimits,
     ) -> Result<PutFileResult> {
-        if data.len() <= constants::MAX_DATA_CHUNK_SIZE {
+        let size = data.len();
+        self.pace_upload(size).await;
+        let request = async {
             let response = requests::put_file_request(
+                self.transport.as_ref(),
                 &self.base_url,
                 &self.x_api_key,
                 name,
@@ -102,20 +482,386 @@ impl Drive {
                 content_type,
             )
             .await?;
-            return Ok(PutFileResult::SinglePut(
-                utils::parse_response_body(response).await?,
-            ));
+            utils::parse_response_body(response).await
+        };
+        let result = limits.run(name, request).await;
+
+        match result {
+            Ok(result) => {
+                limits.part_completed(size);
+                Ok(PutFileResult::SinglePut(result))
+            }
+            Err(error) if size == 0 && error.is_bad_request() => Err(Error::from_invalid_argument(
+                format!("Empty file '{}' was rejected by deta", name),
+            )),
+            Err(error) => Err(error),
+        }
+    }
+
+    // Uploads the data with a single put. If deta rejects the payload as too large,
+    // the same data is uploaded in chunks of the minimum size, unless the fallback is disabled.
+    async fn single_put_with_fallback(
+        &self,
+        name: &str,
+        data: bytes::Bytes,
+        options: &PutFileOptions,
+        limits: &UploadLimits,
+    ) -> Result<PutFileResult> {
+        let result = self
+            .single_put(name, data.clone(), options.content_type.as_deref(), limits)
+            .await;
+
+        match result {
+            Err(error)
+                if options.chunked_fallback && error.is_payload_too_large() && !data.is_empty() =>
+            {
+                let options = PutFileOptions {
+                    chunk_size: constants::MIN_DATA_CHUNK_SIZE,
+                    ..options.clone()
+                };
+                let result = self
+                    .perform_chunked_upload(name, data, &options, limits)
+                    .await?;
+                Ok(PutFileResult::ChunkedUpload(result))
+            }
+            result => result,
+        }
+    }
+
+    // Checks whether a file with exactly the given name exists, by listing the files sharing its prefix.
+    async fn file_exists(&self, name: &str) -> Result<bool> {
+        let mut last_name: Option<String> = None;
+        loop {
+            let files = self
+                .list_files(None, Some(name), last_name.as_deref())
+                .await?;
+            if files.names.iter().any(|file_name| file_name == name) {
+                return Ok(true);
+            }
+            last_name = files.paging.and_then(|paging| paging.last);
+            if last_name.is_none() {
+                return Ok(false);
+            }
+        }
+    }
+
+    /// Uploads the file to the server.
+    /// If the amount of data to be uploaded exceeds 10MB, chunked uploading will be used.
+    /// Chunked uploading is also used when deta rejects a smaller file as too large
+    /// (see [`PutFileOptions::chunked_fallback`](PutFileOptions::chunked_fallback)).
+    ///
+    /// The `data` can be anything convertible into [`bytes::Bytes`](bytes::Bytes),
+    /// e.g. `Vec<u8>`, `&'static [u8]` or `Bytes` itself, which is uploaded without copying.
+    pub async fn put_file(
+        &self,
+        name: &str,
+        data: impl Into<bytes::Bytes>,
+        content_type: Option<&str>,
+    ) -> Result<PutFileResult> {
+        let options = PutFileOptions {
+            content_type: content_type.map(str::to_owned),
+            ..Default::default()
+        };
+        self.put_file_with(name, data, options).await
+    }
+
+    /// Uploads the file to the server, like [`Drive::put_file`](Drive::put_file),
+    /// but allows to adjust the chunked uploading with [`PutFileOptions`](PutFileOptions).
+    /// Invalid options are rejected before sending any request.
+    pub async fn put_file_with(
+        &self,
+        name: &str,
+        data: impl Into<bytes::Bytes>,
+        options: PutFileOptions,
+    ) -> Result<PutFileResult> {
+        let (result, _) = self.put_file_with_stats(name, data, options).await?;
+        Ok(result)
+    }
+
+    /// Works like [`Drive::put_file_with`](Drive::put_file_with),
+    /// but also returns the [`UploadStats`](UploadStats) of the upload.
+    pub async fn put_file_with_stats(
+        &self,
+        name: &str,
+        data: impl Into<bytes::Bytes>,
+        options: PutFileOptions,
+    ) -> Result<(PutFileResult, UploadStats)> {
+        let data = data.into();
+        #[cfg(feature = "compression")]
+        let (name, data, options) = compression::prepare_bytes(name, data, options)?;
+        #[cfg(feature = "compression")]
+        let name = name.as_str();
+
+        let result = self.upload_bytes(name, data, options).await;
+        self.notify_put(name, &result);
+        result
+    }
+
+    async fn upload_bytes(
+        &self,
+        name: &str,
+        data: bytes::Bytes,
+        options: PutFileOptions,
+    ) -> Result<(PutFileResult, UploadStats)> {
+        utils::validate_file_name(name)?;
+        options.validate()?;
+        let limits = UploadLimits::start(&options);
+        let size = data.len() as u64;
+        #[cfg(feature = "checksum")]
+        limits.hash(&data);
+
+        // Empty files are always uploaded with a single put, as a chunked upload needs at least one part.
+        let result = if data.is_empty()
+            || (!options.force_chunked && data.len() <= options.chunked_threshold)
+        {
+            self.single_put_with_fallback(name, data, &options, &limits)
+                .await?
+        } else {
+            PutFileResult::ChunkedUpload(
+                self.perform_chunked_upload(name, data, &options, &limits)
+                    .await?,
+            )
+        };
+
+        let stats = limits.stats();
+        self.verify_if_requested(name, size, &options).await?;
+        Ok((result, stats))
+    }
+
+    /// Uploads the file only if no file with the same name exists yet.
+    /// The existence is checked with [`Drive::head_file`](Drive::head_file), so no content is downloaded.
+    ///
+    /// **NOTE:** Deta drive doesn't support conditional writes, so a file uploaded by someone else
+    /// between the check and the upload is overwritten.
+    pub async fn put_file_if_absent(
+        &self,
+        name: &str,
+        data: impl Into<bytes::Bytes>,
+        content_type: Option<&str>,
+    ) -> Result<PutIfAbsentOutcome> {
+        if self.head_file(name).await?.is_some() {
+            return Ok(PutIfAbsentOutcome::AlreadyExists);
+        }
+        let result = self.put_file(name, data, content_type).await?;
+        Ok(PutIfAbsentOutcome::Uploaded(result))
+    }
+
+    /// Appends the `data` to the end of the file, creating the file if it doesn't exist.
+    /// Deta drive doesn't support appending, so the whole file is downloaded, extended and uploaded again
+    /// (in chunks if it exceeds 10MB). The content type of the existing file is kept.
+    ///
+    /// **NOTE:** Every call transfers the whole file twice, so the cost grows with the file size.
+    /// Appends performed concurrently (e.g. by different processes) may overwrite each other,
+    /// as nothing prevents the file from changing between the download and the upload.
+    pub async fn append_to_file(&self, name: &str, data: &[u8]) -> Result<PutFileResult> {
+        self.append_to_file_with(name, data, AppendOptions::default())
+            .await
+    }
+
+    /// Appends the `data` to the end of the file, like [`Drive::append_to_file`](Drive::append_to_file),
+    /// but allows to limit the size of the file with [`AppendOptions`](AppendOptions).
+    pub async fn append_to_file_with(
+        &self,
+        name: &str,
+        data: &[u8],
+        options: AppendOptions,
+    ) -> Result<PutFileResult> {
+        let existing = self.get_file_detailed(name).await?;
+        let (existing_data, content_type) = match existing {
+            Some(file) => (file.data, file.content_type),
+            None => (bytes::Bytes::new(), None),
+        };
+
+        let size = (existing_data.len() + data.len()) as u64;
+        if let Some(max_size) = options.max_size {
+            if size > max_size {
+                return Err(Error::from_other(
+                    format!(
+                        "Cannot append to '{}', its size would be {} bytes, exceeding the limit of {} bytes",
+                        name, size, max_size
+                    ),
+                    None,
+                ));
+            }
         }
 
-        Ok(PutFileResult::ChunkedUpload(
-            self.perform_chunked_upload(name, data).await?,
-        ))
+        let mut combined = bytes::BytesMut::with_capacity(size as usize);
+        combined.extend_from_slice(&existing_data);
+        combined.extend_from_slice(data);
+        self.put_file(name, combined.freeze(), content_type.as_deref())
+            .await
+    }
+
+    /// Uploads the content read from the `reader`, without loading it whole into memory.
+    /// If the content exceeds the chunked upload threshold, it's uploaded in chunks sent one after another
+    /// (the [`concurrency`](PutFileOptions::concurrency) option has no effect here).
+    /// Data read in small pieces is collected into parts of the configured chunk size,
+    /// as deta requires each part except the last one to be at least 5MB.
+    pub async fn put_file_from_reader<R>(
+        &self,
+        name: &str,
+        reader: R,
+        options: PutFileOptions,
+    ) -> Result<PutFileResult>
+    where
+        R: tokio::io::AsyncRead,
+    {
+        let (result, _) = self
+            .put_file_from_reader_with_stats(name, reader, options)
+            .await?;
+        Ok(result)
+    }
+
+    /// Works like [`Drive::put_file_from_reader`](Drive::put_file_from_reader),
+    /// but also returns the [`UploadStats`](UploadStats) of the upload.
+    pub async fn put_file_from_reader_with_stats<R>(
+        &self,
+        name: &str,
+        reader: R,
+        options: PutFileOptions,
+    ) -> Result<(PutFileResult, UploadStats)>
+    where
+        R: tokio::io::AsyncRead,
+    {
+        let stream = tokio_util::io::ReaderStream::new(reader).map_err(Error::from);
+        self.put_stream(name, stream, options).await
     }
 
-    /// Returns a raw data as type [`bytes::Bytes`](bytes::Bytes).
+    /// Returns a raw data as type [`bytes::Bytes`](bytes::Bytes), or `None` if the file doesn't exist.
+    /// If the drive itself doesn't exist, an error is returned, see [`Error::is_target_not_found`](Error::is_target_not_found).
     pub async fn get_file_as_buffer(&self, name: &str) -> Result<Option<bytes::Bytes>> {
-        let response_result =
-            requests::get_file_request(&self.base_url, &self.x_api_key, name).await;
+        let response = self.get_file_response(name).await?;
+        if response.is_none() {
+            return Ok(None);
+        }
+        let bytes = self.body_bytes(response.unwrap()).await?;
+        Ok(Some(bytes))
+    }
+
+    /// Returns a raw data along with the content type, length and last modification date
+    /// sent in the response headers, e.g. to serve the file further with the same headers.
+    pub async fn get_file_detailed(&self, name: &str) -> Result<Option<models::FileDownload>> {
+        let response = self.get_file_response(name).await?;
+        if response.is_none() {
+            return Ok(None);
+        }
+        let response = response.unwrap();
+
+        let metadata = models::FileMetadata::from_headers(response.headers());
+        let data = self.body_bytes(response).await?;
+        Ok(Some(models::FileDownload {
+            data,
+            content_type: metadata.content_type,
+            content_length: metadata.content_length,
+            last_modified: metadata.last_modified,
+        }))
+    }
+
+    /// Returns a raw data as a stream of [`bytes::Bytes`](bytes::Bytes) chunks,
+    /// so that the file doesn't have to be kept whole in memory.
+    pub async fn get_file_as_stream(
+        &self,
+        name: &str,
+    ) -> Result<Option<impl Stream<Item = Result<bytes::Bytes>>>> {
+        let response = self.get_file_response(name).await?;
+        Ok(response.map(|response| self.body_stream(response)))
+    }
+
+    /// Downloads the file into the `writer`, without keeping it whole in memory.
+    /// Returns the number of written bytes, or `None` if the file doesn't exist (nothing is written then).
+    ///
+    /// An interrupted download is resumed from the first missing byte, up to 3 times
+    /// (see [`DownloadOptions`](DownloadOptions)). If the number of written bytes differs
+    /// from the `Content-Length` of the file, an error is returned.
+    pub async fn download_to_writer<W>(&self, name: &str, writer: &mut W) -> Result<Option<u64>>
+    where
+        W: tokio::io::AsyncWrite + Unpin,
+    {
+        self.download_to_writer_with(name, writer, DownloadOptions::default())
+            .await
+    }
+
+    /// Works like [`Drive::download_to_writer`](Drive::download_to_writer),
+    /// but allows to adjust resuming with [`DownloadOptions`](DownloadOptions).
+    pub async fn download_to_writer_with<W>(
+        &self,
+        name: &str,
+        writer: &mut W,
+        options: DownloadOptions,
+    ) -> Result<Option<u64>>
+    where
+        W: tokio::io::AsyncWrite + Unpin,
+    {
+        let response = self.get_file_response(name).await?;
+        if response.is_none() {
+            return Ok(None);
+        }
+        let written = self
+            .write_resumable(name, response.unwrap(), writer, &options)
+            .await?;
+        Ok(Some(written))
+    }
+
+    /// Downloads the file into a local file at `path`, creating or truncating it.
+    /// Returns the number of written bytes, or `None` if the file doesn't exist (the local file is not created then).
+    ///
+    /// An interrupted download is resumed like in [`Drive::download_to_writer`](Drive::download_to_writer).
+    pub async fn download_to_file(
+        &self,
+        name: &str,
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<Option<u64>> {
+        self.download_to_file_with(name, path, DownloadOptions::default())
+            .await
+    }
+
+    /// Works like [`Drive::download_to_file`](Drive::download_to_file),
+    /// but allows to adjust resuming with [`DownloadOptions`](DownloadOptions).
+    pub async fn download_to_file_with(
+        &self,
+        name: &str,
+        path: impl AsRef<std::path::Path>,
+        options: DownloadOptions,
+    ) -> Result<Option<u64>> {
+        let response = self.get_file_response(name).await?;
+        if response.is_none() {
+            return Ok(None);
+        }
+        let mut file = tokio::fs::File::create(path).await?;
+        let written = self
+            .write_resumable(name, response.unwrap(), &mut file, &options)
+            .await?;
+        Ok(Some(written))
+    }
+
+    /// Returns the metadata of the file, read from the response headers without downloading the content,
+    /// or `None` if the file doesn't exist.
+    pub async fn head_file(&self, name: &str) -> Result<Option<models::FileMetadata>> {
+        utils::validate_file_name(name)?;
+        match requests::head_file_request(
+            self.transport.as_ref(),
+            &self.base_url,
+            &self.x_api_key,
+            name,
+        )
+        .await
+        {
+            Ok(response) => Ok(Some(models::FileMetadata::from_headers(response.headers()))),
+            Err(error) if error.is_not_found() => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+
+    // Returns the download response, or `None` if the file doesn't exist.
+    async fn get_file_response(&self, name: &str) -> Result<Option<reqwest::Response>> {
+        utils::validate_file_name(name)?;
+        let response_result = requests::get_file_request(
+            self.transport.as_ref(),
+            &self.base_url,
+            &self.x_api_key,
+            name,
+        )
+        .await;
 
         if let Err(ref error) = response_result {
             if error.is_not_found() {
@@ -123,19 +869,128 @@ impl Drive {
             }
         }
 
-        let response = response_result?;
-        let bytes = response.bytes().await?;
-        Ok(Some(bytes))
+        Ok(Some(response_result?))
+    }
+
+    // Returns the download response for the value of the `Range` header, e.g. `bytes=0-99`,
+    // or `None` if the file doesn't exist.
+    #[cfg(any(feature = "axum", feature = "actix", feature = "object_store"))]
+    async fn get_file_range_response(
+        &self,
+        name: &str,
+        range: &str,
+    ) -> Result<Option<reqwest::Response>> {
+        utils::validate_file_name(name)?;
+        match requests::get_file_range_request(
+            self.transport.as_ref(),
+            &self.base_url,
+            &self.x_api_key,
+            name,
+            range,
+        )
+        .await
+        {
+            Ok(response) => Ok(Some(response)),
+            Err(error) if error.is_not_found() => Ok(None),
+            Err(error) => Err(error),
+        }
     }
 
     /// Returns a raw data as type `Vec<u8>`.
+    /// To avoid allocating a new vector for every download, use [`Drive::get_file_into`](Drive::get_file_into).
     pub async fn get_file_as_u8_vec(&self, name: &str) -> Result<Option<Vec<u8>>> {
+        let mut data = vec![];
+        let written = self.get_file_into(name, &mut data).await?;
+        Ok(written.map(|_| data))
+    }
+
+    /// Downloads the file into the `buf`, reusing its allocation. The buffer is cleared first,
+    /// and its capacity is reserved up front when the response declares the content length.
+    /// Returns the number of written bytes, or `None` if the file doesn't exist (the buffer is left untouched then).
+    pub async fn get_file_into(&self, name: &str, buf: &mut Vec<u8>) -> Result<Option<usize>> {
+        let response = self.get_file_response(name).await?;
+        if response.is_none() {
+            return Ok(None);
+        }
+        let response = response.unwrap();
+
+        buf.clear();
+        if let Some(length) = response.content_length() {
+            buf.reserve(length as usize);
+        }
+
+        let stream = self.body_stream(response);
+        futures::pin_mut!(stream);
+        while let Some(chunk) = stream.next().await.transpose()? {
+            buf.extend_from_slice(&chunk);
+        }
+        Ok(Some(buf.len()))
+    }
+
+    /// Returns a raw data as type `String`.
+    /// If the data isn't valid UTF-8, an error naming the file and the offset of the first invalid byte is returned
+    /// (see [`Error::is_invalid_utf8`](crate::error::Error::is_invalid_utf8)).
+    pub async fn get_file_as_string(&self, name: &str) -> Result<Option<String>> {
+        let bytes = self.get_file_as_u8_vec(name).await?;
+        if bytes.is_none() {
+            return Ok(None);
+        }
+        let text = String::from_utf8(bytes.unwrap())
+            .map_err(|error| Error::from_invalid_utf8(name, error))?;
+        Ok(Some(text))
+    }
+
+    /// Uploads the text, with the `text/plain; charset=utf-8` content type.
+    pub async fn put_text(&self, name: &str, text: &str) -> Result<PutFileResult> {
+        self.put_text_with(name, text, PutFileOptions::default())
+            .await
+    }
+
+    /// Uploads the text like [`Drive::put_text`](Drive::put_text).
+    /// The content type can be overridden with the [`PutFileOptions::content_type`](PutFileOptions::content_type) field.
+    pub async fn put_text_with(
+        &self,
+        name: &str,
+        text: &str,
+        mut options: PutFileOptions,
+    ) -> Result<PutFileResult> {
+        if options.content_type.is_none() {
+            options.content_type = Some(constants::TEXT_CONTENT_TYPE.into());
+        }
+        self.put_file_with(name, text.as_bytes().to_vec(), options)
+            .await
+    }
+
+    /// Serializes the value and uploads it as JSON, with the `application/json` content type.
+    pub async fn put_json<T>(&self, name: &str, value: &T) -> Result<PutFileResult>
+    where
+        T: Serialize,
+    {
+        let data = serde_json::to_vec(value)?;
+        let options = PutFileOptions {
+            content_type: Some(constants::JSON_CONTENT_TYPE.into()),
+            ..Default::default()
+        };
+        self.put_file_with(name, data, options).await
+    }
+
+    /// Downloads the file and deserializes its JSON content.
+    /// If the deserialization fails, the downloaded content is available
+    /// through [`Error::get_raw_response_data`](crate::error::Error::get_raw_response_data).
+    pub async fn get_json<T>(&self, name: &str) -> Result<Option<T>>
+    where
+        T: DeserializeOwned,
+    {
         let bytes = self.get_file_as_buffer(name).await?;
         if bytes.is_none() {
             return Ok(None);
         }
-        let bytes = bytes.unwrap();
-        Ok(Some(bytes.to_vec()))
+        Ok(Some(utils::parse_raw_data(&bytes.unwrap())?))
+    }
+
+    /// Returns the file content as text. It's an alias of [`Drive::get_file_as_string`](Drive::get_file_as_string).
+    pub async fn get_text(&self, name: &str) -> Result<Option<String>> {
+        self.get_file_as_string(name).await
     }
 
     /// Lists file names.
@@ -145,26 +1000,1932 @@ impl Drive {
         prefix: Option<&str>,
         last_name: Option<&str>,
     ) -> Result<models::ListFiles> {
-        let response =
-            requests::list_files_request(&self.base_url, &self.x_api_key, limit, prefix, last_name)
+        let response = requests::list_files_request(
+            self.transport.as_ref(),
+            &self.base_url,
+            &self.x_api_key,
+            limit,
+            prefix,
+            last_name,
+        )
+        .await?;
+        utils::parse_response_body(response).await
+    }
+
+    /// Lists file names following the [`Cursor`](models::Cursor) returned with the previous page
+    /// (see [`ListFiles::next_cursor`](models::ListFiles::next_cursor)).
+    ///
+    /// ```no_run
+    /// # async fn run(drive: deta_rust::drive::Drive) -> deta_rust::error::Result<()> {
+    /// let mut files = drive.list_files(Some(100), None, None).await?;
+    /// while let Some(cursor) = files.next_cursor() {
+    ///     files = drive.list_files_after(&cursor, Some(100), None).await?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn list_files_after(
+        &self,
+        cursor: &models::Cursor,
+        limit: Option<u32>,
+        prefix: Option<&str>,
+    ) -> Result<models::ListFiles> {
+        self.list_files(limit, prefix, Some(cursor.as_str())).await
+    }
+
+    /// Lists the names of all files, going through every page of the listing.
+    /// The returned value has no paging information.
+    pub async fn list_all_files(&self, prefix: Option<&str>) -> Result<models::ListFiles> {
+        let mut files = self.list_files(None, prefix, None).await?;
+        while let Some(cursor) = files.next_cursor() {
+            let next_files = self.list_files_after(&cursor, None, prefix).await?;
+            files = files.merge(next_files);
+        }
+        files.paging = None;
+        Ok(files)
+    }
+
+    /// Lists the names of all files matching the `glob` pattern, optionally narrowed down with the `prefix` on the server side.
+    /// The pattern is applied to each page separately, so only matching names are kept in memory.
+    /// The returned value has no paging information.
+    ///
+    /// Supported wildcards: `*` matches any sequence of characters except `/`, `**` matches any sequence
+    /// of characters including `/`, `?` matches a single character except `/`.
+    /// All other characters, including dots, are matched literally.
+    pub async fn list_matching(
+        &self,
+        prefix: Option<&str>,
+        glob: &str,
+    ) -> Result<models::ListFiles> {
+        let mut names = vec![];
+        let mut cursor: Option<models::Cursor> = None;
+        loop {
+            let files = self
+                .list_files(None, prefix, cursor.as_ref().map(models::Cursor::as_str))
                 .await?;
-        return Ok(utils::parse_response_body(response).await?);
+            cursor = files.next_cursor();
+            names.extend(
+                files
+                    .names
+                    .into_iter()
+                    .filter(|name| utils::glob_match(glob, name)),
+            );
+            if cursor.is_none() {
+                return Ok(models::ListFiles {
+                    paging: None,
+                    names,
+                });
+            }
+        }
     }
 
     /// Deletes files by the names specified in the slice.
     pub async fn delete_files(&self, names: &[String]) -> Result<models::DeleteFiles> {
-        let response =
-            requests::delete_files_request(&self.base_url, &self.x_api_key, names).await?;
-        return Ok(utils::parse_response_body(response).await?);
+        let result = async {
+            for name in names {
+                utils::validate_file_name(name)?;
+            }
+            let response = requests::delete_files_request(
+                self.transport.as_ref(),
+                &self.base_url,
+                &self.x_api_key,
+                names,
+            )
+            .await?;
+            utils::parse_response_body::<models::DeleteFiles>(response).await
+        }
+        .await;
+        self.observers.notify(&result, |result| match result {
+            Ok(deleted) => MutationEvent::succeeded(
+                MutationKind::Delete,
+                deleted.deleted.clone(),
+                deleted.failed_names().map(str::to_owned).collect(),
+            ),
+            Err(error) => MutationEvent::failed(MutationKind::Delete, names.to_vec(), error),
+        });
+        result
     }
-}
 
-/// Positive response variants to file upload.
+    /// Sends a request to an endpoint of the drive not covered by the SDK, at the `path` under the url of the drive,
+    /// e.g. `files?limit=5` for `https://drive.deta.sh/v1/{project_id}/{drive_name}/files?limit=5`.
+    /// The api key is attached and the errors are reported like by the other methods.
+    /// Returns the JSON body of the response, `null` if it's empty.
+    ///
+    /// The `path` can't lead outside of the url of the drive, e.g. with `..` segments.
+    pub async fn raw_request(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        body: Option<serde_json::Value>,
+    ) -> Result<serde_json::Value> {
+        let response = self.send_raw_request(method, path, body).await?;
+        utils::parse_raw_json_response(response).await
+    }
 
-#[derive(Debug, Clone)]
-pub enum PutFileResult {
-    /// File size less than or equal to 10MB.
-    SinglePut(models::PutFile),
+    /// Works like [`Drive::raw_request`](Drive::raw_request), but returns the body of the response as it is,
+    /// e.g. for an endpoint returning the content of a file.
+    pub async fn raw_request_bytes(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        body: Option<serde_json::Value>,
+    ) -> Result<bytes::Bytes> {
+        let response = self.send_raw_request(method, path, body).await?;
+        self.body_bytes(response).await
+    }
+
+    async fn send_raw_request(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        body: Option<serde_json::Value>,
+    ) -> Result<reqwest::Response> {
+        utils::send_raw_request(
+            self.transport.as_ref(),
+            &self.base_url,
+            &self.x_api_key,
+            method,
+            path,
+            body,
+        )
+        .await
+    }
+
+    /// Aborts the unfinished chunked upload. It's useful when a failed upload couldn't be aborted automatically,
+    /// in which case its id is available in [`Error::cleanup_failure`](crate::error::Error::cleanup_failure).
+    pub async fn abort_chunked_upload(&self, name: &str, upload_id: &str) -> Result<()> {
+        utils::validate_file_name(name)?;
+        utils::validate_not_blank("upload id", upload_id)?;
+        requests::abort_chunked_upload_request(
+            self.transport.as_ref(),
+            &self.base_url,
+            &self.x_api_key,
+            name,
+            upload_id,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Deletes a single file.
+    /// Returns `true` if the file was deleted and `false` if it didn't exist.
+    /// If deta reports a failure other than a missing file, an error is returned.
+    pub async fn delete_file(&self, name: &str) -> Result<bool> {
+        let result = self.delete_files(&[name.to_owned()]).await?;
+        if result.deleted.iter().any(|deleted| deleted == name) {
+            return Ok(true);
+        }
+
+        match result.failure_reason(name) {
+            Some(reason) if !is_not_found_reason(&reason) => Err(Error::from_other(
+                format!("Cannot delete file '{}'. Reason: '{}'", name, reason),
+                None,
+            )),
+            _ => Ok(false),
+        }
+    }
+
+    /// Renames the file. Deta drive doesn't support renaming, so the content of the `from` file
+    /// is copied under the `to` name and the original is deleted once the new file is confirmed to exist.
+    /// Files greater than 10MB are streamed into a chunked upload, so they are never fully loaded into memory.
+    ///
+    /// If `overwrite` is `false` and the `to` file already exists, the operation fails before anything is copied.
+    ///
+    /// **NOTE:** If the copy succeeds but the original file cannot be deleted,
+    /// the returned error states that both files exist.
+    pub async fn rename(&self, from: &str, to: &str, overwrite: bool) -> Result<()> {
+        if from == to {
+            return Ok(());
+        }
+
+        if !overwrite && self.file_exists(to).await? {
+            return Err(Error::from_other(
+                format!("Cannot rename '{}', file '{}' already exists", from, to),
+                None,
+            ));
+        }
+
+        self.copy_file(from, self, to).await?;
+
+        if !self.file_exists(to).await? {
+            return Err(Error::from_other(
+                format!(
+                    "Cannot rename '{}', file '{}' is missing after the copy",
+                    from, to
+                ),
+                None,
+            ));
+        }
+
+        let incomplete_rename = |source: Option<Error>| {
+            Error::from_other(
+                format!(
+                    "Renaming '{}' is incomplete, file was copied to '{}' but the original could not be deleted, both files exist",
+                    from, to
+                ),
+                source.map(Into::into),
+            )
+        };
+
+        match self.delete_files(&[from.to_owned()]).await {
+            Ok(result) if result.deleted.iter().any(|name| name == from) => Ok(()),
+            Ok(_) => Err(incomplete_rename(None)),
+            Err(error) => Err(incomplete_rename(Some(error))),
+        }
+    }
+}
+
+// Deta reports both missing files and real failures in the `failed` map of the delete response,
+// so the reason has to be inspected to tell them apart.
+fn is_not_found_reason(reason: &str) -> bool {
+    let reason = reason.to_lowercase();
+    reason.contains("not found") || reason.contains("does not exist")
+}
+
+fn empty_chunked_upload_error(name: &str) -> Error {
+    Error::from_invalid_argument(format!(
+        "Cannot upload empty file '{}' in chunks, a single put must be used",
+        name
+    ))
+}
+
+// Writes the response body into the `writer` chunk by chunk, returning the number of written bytes.
+async fn write_stream<S, W>(stream: S, writer: &mut W) -> Result<u64>
+where
+    S: Stream<Item = Result<bytes::Bytes>>,
+    W: tokio::io::AsyncWrite + Unpin,
+{
+    use tokio::io::AsyncWriteExt;
+
+    futures::pin_mut!(stream);
+    let mut written = 0;
+    while let Some(chunk) = stream.next().await.transpose()? {
+        writer.write_all(&chunk).await?;
+        written += chunk.len() as u64;
+    }
+    writer.flush().await?;
+    Ok(written)
+}
+
+/// Positive response variants to file upload.
+
+#[derive(Debug, Clone)]
+pub enum PutFileResult {
+    /// File size less than or equal to 10MB.
+    SinglePut(models::PutFile),
     /// File size greater than 10MB.
     ChunkedUpload(models::EndChunkedUpload),
 }
+
+/// Accessors for the fields common to both variants.
+impl PutFileResult {
+    /// Returns the name of the stored file.
+    ///
+    /// ```
+    /// use deta_rust::drive::{models::PutFile, PutFileResult};
+    ///
+    /// let result = PutFileResult::SinglePut(PutFile {
+    ///     name: "photo.jpg".into(),
+    ///     project_id: "a0abcyxz".into(),
+    ///     drive_name: "photos".into(),
+    /// });
+    ///
+    /// assert_eq!(result.name(), "photo.jpg");
+    /// assert_eq!(result.drive_name(), "photos");
+    /// assert!(!result.was_chunked());
+    /// ```
+    pub fn name(&self) -> &str {
+        match self {
+            Self::SinglePut(result) => &result.name,
+            Self::ChunkedUpload(result) => &result.name,
+        }
+    }
+
+    /// Returns the name of the drive the file is stored in.
+    pub fn drive_name(&self) -> &str {
+        match self {
+            Self::SinglePut(result) => &result.drive_name,
+            Self::ChunkedUpload(result) => &result.drive_name,
+        }
+    }
+
+    /// Returns the project id.
+    pub fn project_id(&self) -> &str {
+        match self {
+            Self::SinglePut(result) => &result.project_id,
+            Self::ChunkedUpload(result) => &result.project_id,
+        }
+    }
+
+    /// Checks whether the file has been uploaded in chunks.
+    pub fn was_chunked(&self) -> bool {
+        matches!(self, Self::ChunkedUpload(_))
+    }
+}
+
+/// Outcome of [`Drive::put_file_if_absent`](Drive::put_file_if_absent).
+#[derive(Debug, Clone)]
+pub enum PutIfAbsentOutcome {
+    /// The file didn't exist and has been uploaded.
+    Uploaded(PutFileResult),
+    /// The file already exists, nothing has been uploaded.
+    AlreadyExists,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tokio::sync::mpsc;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const MB: usize = 1024 * 1024;
+
+    async fn mount_chunked_upload(server: &MockServer) {
+        Mock::given(method("POST"))
+            .and(path("/uploads"))
+            .respond_with(
+                ResponseTemplate::new(201).set_body_json(json!({ "upload_id": "upload_id" })),
+            )
+            .mount(server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/uploads/upload_id/parts"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(server)
+            .await;
+        Mock::given(method("PATCH"))
+            .and(path("/uploads/upload_id"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "name": "file.dat",
+                "upload_id": "upload_id",
+                "project_id": "project",
+                "drive_name": "drive"
+            })))
+            .mount(server)
+            .await;
+    }
+
+    // Returns the sizes of the uploaded parts, ordered by the part number.
+    async fn uploaded_part_sizes(server: &MockServer) -> Vec<usize> {
+        let mut parts: Vec<(usize, usize)> = server
+            .received_requests()
+            .await
+            .unwrap()
+            .into_iter()
+            .filter(|request| request.url.path().ends_with("/parts"))
+            .map(|request| {
+                let part = request
+                    .url
+                    .query_pairs()
+                    .find(|(key, _)| key == "part")
+                    .map(|(_, value)| value.parse().unwrap())
+                    .unwrap();
+                (part, request.body.len())
+            })
+            .collect();
+        parts.sort_unstable();
+        parts.into_iter().map(|(_, size)| size).collect()
+    }
+
+    #[tokio::test]
+    async fn put_file_with_custom_chunk_size() {
+        let server = MockServer::start().await;
+        mount_chunked_upload(&server).await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let options = PutFileOptions {
+            chunk_size: 5 * MB,
+            concurrency: 2,
+            ..Default::default()
+        };
+        let result = drive
+            .put_file_with("file.dat", vec![0; 12 * MB], options)
+            .await
+            .unwrap();
+
+        assert!(result.was_chunked());
+        assert_eq!(
+            uploaded_part_sizes(&server).await,
+            vec![5 * MB, 5 * MB, 2 * MB]
+        );
+    }
+
+    #[tokio::test]
+    async fn put_file_with_custom_chunked_threshold() {
+        let server = MockServer::start().await;
+        mount_chunked_upload(&server).await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let options = PutFileOptions {
+            chunked_threshold: 512,
+            ..Default::default()
+        };
+        let result = drive
+            .put_file_with("file.dat", vec![0; 1024], options)
+            .await
+            .unwrap();
+
+        assert!(result.was_chunked());
+        assert_eq!(uploaded_part_sizes(&server).await, vec![1024]);
+    }
+
+    #[tokio::test]
+    async fn put_file_with_bytes_slice() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/files"))
+            .and(wiremock::matchers::body_bytes(b"middle".to_vec()))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!({
+                "name": "file.txt",
+                "project_id": "project",
+                "drive_name": "drive"
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let buffer = bytes::Bytes::from_static(b"start middle end");
+        let drive = Drive::with_base_url(&server.uri());
+        let result = drive
+            .put_file("file.txt", buffer.slice(6..12), None)
+            .await
+            .unwrap();
+
+        assert_eq!(result.name(), "file.txt");
+    }
+
+    #[tokio::test]
+    async fn put_file_with_force_chunked() {
+        let server = MockServer::start().await;
+        mount_chunked_upload(&server).await;
+        Mock::given(method("POST"))
+            .and(path("/files"))
+            .respond_with(ResponseTemplate::new(201))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let options = PutFileOptions {
+            force_chunked: true,
+            ..Default::default()
+        };
+        let result = drive
+            .put_file_with("file.dat", vec![0; 1024], options)
+            .await
+            .unwrap();
+
+        assert!(result.was_chunked());
+        assert_eq!(uploaded_part_sizes(&server).await, vec![1024]);
+    }
+
+    #[tokio::test]
+    async fn put_file_from_reader_with_force_chunked() {
+        let server = MockServer::start().await;
+        mount_chunked_upload(&server).await;
+
+        let data = vec![0; 1024];
+        let drive = Drive::with_base_url(&server.uri());
+        let options = PutFileOptions {
+            force_chunked: true,
+            ..Default::default()
+        };
+        let result = drive
+            .put_file_from_reader("file.dat", &data[..], options)
+            .await
+            .unwrap();
+
+        assert!(result.was_chunked());
+        assert_eq!(uploaded_part_sizes(&server).await, vec![1024]);
+    }
+
+    #[tokio::test]
+    async fn put_file_from_reader_in_chunks() {
+        let server = MockServer::start().await;
+        mount_chunked_upload(&server).await;
+
+        let data = vec![0; 12 * MB];
+        let drive = Drive::with_base_url(&server.uri());
+        let options = PutFileOptions {
+            chunk_size: 5 * MB,
+            chunked_threshold: 5 * MB,
+            ..Default::default()
+        };
+        let result = drive
+            .put_file_from_reader("file.dat", &data[..], options)
+            .await
+            .unwrap();
+
+        assert!(result.was_chunked());
+        assert_eq!(
+            uploaded_part_sizes(&server).await,
+            vec![5 * MB, 5 * MB, 2 * MB]
+        );
+    }
+
+    #[tokio::test]
+    async fn put_file_from_reader_below_threshold() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/files"))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!({
+                "name": "file.txt",
+                "project_id": "project",
+                "drive_name": "drive"
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let data = vec![0; 1024];
+        let drive = Drive::with_base_url(&server.uri());
+        let result = drive
+            .put_file_from_reader("file.txt", &data[..], PutFileOptions::default())
+            .await
+            .unwrap();
+
+        assert!(!result.was_chunked());
+    }
+
+    #[tokio::test]
+    async fn put_text_with_content_type() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/files"))
+            .and(wiremock::matchers::header(
+                "Content-Type",
+                "text/plain; charset=utf-8",
+            ))
+            .and(wiremock::matchers::body_string("Zażółć gęślą jaźń"))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!({
+                "name": "file.txt",
+                "project_id": "project",
+                "drive_name": "drive"
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        drive
+            .put_text("file.txt", "Zażółć gęślą jaźń")
+            .await
+            .unwrap();
+    }
+
+    async fn mount_download(server: &MockServer, data: &[u8]) {
+        Mock::given(method("GET"))
+            .and(path("/files/download"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(data))
+            .mount(server)
+            .await;
+    }
+
+    async fn mount_download_not_found(server: &MockServer, message: &str) {
+        Mock::given(method("GET"))
+            .and(path("/files/download"))
+            .respond_with(
+                ResponseTemplate::new(404)
+                    .set_body_json(serde_json::json!({ "errors": [message] })),
+            )
+            .mount(server)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn get_file_as_buffer_for_missing_file() {
+        let server = MockServer::start().await;
+        mount_download_not_found(&server, "File not found").await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        assert!(drive
+            .get_file_as_buffer("file.dat")
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn get_file_as_buffer_for_missing_drive() {
+        let server = MockServer::start().await;
+        mount_download_not_found(&server, "Drive not found").await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let error = drive.get_file_as_buffer("file.dat").await.unwrap_err();
+        assert!(error.is_target_not_found());
+
+        let mut data = vec![];
+        let error = drive
+            .get_file_into("file.dat", &mut data)
+            .await
+            .unwrap_err();
+        assert!(error.is_target_not_found());
+    }
+
+    #[tokio::test]
+    async fn get_text_for_invalid_utf8() {
+        let server = MockServer::start().await;
+        mount_download(&server, &[b'o', b'k', 0xc3, 0x28]).await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let error = drive.get_text("file.txt").await.unwrap_err();
+        assert!(error.is_invalid_utf8());
+    }
+
+    #[tokio::test]
+    async fn get_file_as_string_for_valid_utf8() {
+        let server = MockServer::start().await;
+        mount_download(&server, "Źdźbło".as_bytes()).await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let text = drive.get_file_as_string("file.txt").await.unwrap();
+        assert_eq!(text.as_deref(), Some("Źdźbło"));
+    }
+
+    #[tokio::test]
+    async fn get_file_as_string_for_invalid_utf8() {
+        let server = MockServer::start().await;
+        mount_download(&server, &[b'a', b'b', b'c', 0xff, b'd']).await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let error = drive.get_file_as_string("file.txt").await.unwrap_err();
+        assert!(error.is_invalid_utf8());
+        let message = error.to_string();
+        assert!(message.contains("'file.txt'"), "{}", message);
+        assert!(message.contains("byte 3"), "{}", message);
+    }
+
+    #[tokio::test]
+    async fn get_file_into_reuses_buffer() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/files/download"))
+            .and(wiremock::matchers::query_param("name", "big.dat"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(vec![1; 4096]))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/files/download"))
+            .and(wiremock::matchers::query_param("name", "small.dat"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(vec![2; 16]))
+            .mount(&server)
+            .await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let mut buf = vec![];
+        let written = drive.get_file_into("big.dat", &mut buf).await.unwrap();
+        assert_eq!(written, Some(4096));
+        assert_eq!(buf, vec![1; 4096]);
+        let capacity = buf.capacity();
+
+        let written = drive.get_file_into("small.dat", &mut buf).await.unwrap();
+        assert_eq!(written, Some(16));
+        assert_eq!(buf, vec![2; 16]);
+        assert_eq!(buf.capacity(), capacity);
+
+        let written = drive.get_file_into("missing.dat", &mut buf).await.unwrap();
+        assert_eq!(written, None);
+        assert_eq!(buf, vec![2; 16]);
+    }
+
+    #[tokio::test]
+    async fn get_file_detailed_with_headers() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/files/download"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_raw(b"<h1>Hello</h1>".to_vec(), "text/html")
+                    .insert_header("Last-Modified", "Wed, 21 Oct 2015 07:28:00 GMT"),
+            )
+            .mount(&server)
+            .await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let file = drive
+            .get_file_detailed("index.html")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(&file.data[..], b"<h1>Hello</h1>");
+        assert_eq!(file.content_type.as_deref(), Some("text/html"));
+        assert_eq!(file.content_length, Some(14));
+        assert_eq!(
+            file.last_modified.as_deref(),
+            Some("Wed, 21 Oct 2015 07:28:00 GMT")
+        );
+    }
+
+    #[tokio::test]
+    async fn get_file_detailed_without_headers() {
+        let server = MockServer::start().await;
+        mount_download(&server, b"data").await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let file = drive.get_file_detailed("file.dat").await.unwrap().unwrap();
+        assert_eq!(&file.data[..], b"data");
+        assert_eq!(file.content_length, Some(4));
+        assert_eq!(file.last_modified, None);
+
+        let server = MockServer::start().await;
+        let drive = Drive::with_base_url(&server.uri());
+        assert!(drive.get_file_detailed("file.dat").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn get_file_as_stream() {
+        let server = MockServer::start().await;
+        mount_download(&server, &[1; 1024]).await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let stream = drive.get_file_as_stream("file.dat").await.unwrap().unwrap();
+        let chunks: Vec<bytes::Bytes> = stream.try_collect().await.unwrap();
+        assert_eq!(chunks.concat(), vec![1; 1024]);
+    }
+
+    #[tokio::test]
+    async fn download_to_file() {
+        let server = MockServer::start().await;
+        mount_download(&server, b"content").await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("file.txt");
+        let drive = Drive::with_base_url(&server.uri());
+        let written = drive.download_to_file("file.txt", &path).await.unwrap();
+
+        assert_eq!(written, Some(7));
+        assert_eq!(std::fs::read(&path).unwrap(), b"content");
+    }
+
+    #[tokio::test]
+    async fn download_to_file_for_missing_file() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/files/download"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("file.txt");
+        let drive = Drive::with_base_url(&server.uri());
+        let written = drive.download_to_file("file.txt", &path).await.unwrap();
+
+        assert!(written.is_none());
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn get_file_as_string_for_missing_file() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/files/download"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&server)
+            .await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let text = drive.get_file_as_string("file.txt").await.unwrap();
+        assert!(text.is_none());
+    }
+
+    #[derive(Serialize, serde::Deserialize, Debug, PartialEq)]
+    struct Document {
+        title: String,
+        tags: Vec<String>,
+        author: Author,
+    }
+
+    #[derive(Serialize, serde::Deserialize, Debug, PartialEq)]
+    struct Author {
+        name: String,
+        age: u8,
+    }
+
+    #[tokio::test]
+    async fn put_json_and_get_json() {
+        let document = Document {
+            title: "Report".into(),
+            tags: vec!["yearly".into(), "finance".into()],
+            author: Author {
+                name: "Anna".into(),
+                age: 31,
+            },
+        };
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/files"))
+            .and(wiremock::matchers::header(
+                "Content-Type",
+                "application/json",
+            ))
+            .and(wiremock::matchers::body_json(&document))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!({
+                "name": "document.json",
+                "project_id": "project",
+                "drive_name": "drive"
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/files/download"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(&document))
+            .mount(&server)
+            .await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        drive.put_json("document.json", &document).await.unwrap();
+        let downloaded = drive.get_json::<Document>("document.json").await.unwrap();
+        assert_eq!(downloaded, Some(document));
+    }
+
+    #[tokio::test]
+    async fn get_json_for_corrupted_payload() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/files/download"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(r#"{ "title": "Rep"#))
+            .mount(&server)
+            .await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let error = drive
+            .get_json::<Document>("document.json")
+            .await
+            .unwrap_err();
+        assert!(error.is_body_deserialization());
+        assert_eq!(error.get_raw_response_data(), Some(r#"{ "title": "Rep"#));
+    }
+
+    async fn mount_empty_put(server: &MockServer, status: u16) {
+        Mock::given(method("POST"))
+            .and(path("/files"))
+            .and(wiremock::matchers::header("content-length", "0"))
+            .respond_with(ResponseTemplate::new(status).set_body_json(json!({
+                "name": "empty.txt",
+                "project_id": "project",
+                "drive_name": "drive"
+            })))
+            .expect(1)
+            .mount(server)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn put_file_with_empty_data() {
+        let server = MockServer::start().await;
+        mount_empty_put(&server, 201).await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let result = drive.put_file("empty.txt", vec![], None).await.unwrap();
+        assert!(!result.was_chunked());
+    }
+
+    #[tokio::test]
+    async fn put_file_with_empty_data_and_force_chunked() {
+        let server = MockServer::start().await;
+        mount_empty_put(&server, 201).await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let options = PutFileOptions {
+            force_chunked: true,
+            ..Default::default()
+        };
+        let result = drive
+            .put_file_with("empty.txt", vec![], options)
+            .await
+            .unwrap();
+        assert!(!result.was_chunked());
+    }
+
+    #[tokio::test]
+    async fn put_file_from_empty_reader() {
+        let server = MockServer::start().await;
+        mount_empty_put(&server, 201).await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let options = PutFileOptions {
+            force_chunked: true,
+            ..Default::default()
+        };
+        let result = drive
+            .put_file_from_reader("empty.txt", tokio::io::empty(), options)
+            .await
+            .unwrap();
+        assert!(!result.was_chunked());
+    }
+
+    #[tokio::test]
+    async fn put_file_with_empty_data_rejected() {
+        let server = MockServer::start().await;
+        mount_empty_put(&server, 400).await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let error = drive.put_file("empty.txt", vec![], None).await.unwrap_err();
+        assert!(error.is_invalid_argument());
+    }
+
+    #[tokio::test]
+    async fn put_file_keeps_chunk_error_when_abort_fails() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/uploads"))
+            .respond_with(
+                ResponseTemplate::new(201).set_body_json(json!({ "upload_id": "upload_id" })),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/uploads/upload_id/parts"))
+            .respond_with(ResponseTemplate::new(413))
+            .mount(&server)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(path("/uploads/upload_id"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let options = PutFileOptions {
+            force_chunked: true,
+            ..Default::default()
+        };
+        let error = drive
+            .put_file_with("file.dat", vec![0; 1024], options)
+            .await
+            .unwrap_err();
+
+        assert_eq!(
+            error.status_kind(),
+            Some(&crate::error::ResponseStatusKind::PayloadTooLarge)
+        );
+        let cleanup_failure = error.cleanup_failure().unwrap();
+        assert_eq!(cleanup_failure.upload_id, "upload_id");
+        assert_eq!(
+            cleanup_failure.error.status_kind(),
+            Some(&crate::error::ResponseStatusKind::InternalServerError)
+        );
+    }
+
+    // Mounts a chunked upload in which the second part is rejected.
+    async fn mount_chunked_upload_failing_at_part_2(server: &MockServer) {
+        Mock::given(method("POST"))
+            .and(path("/uploads/upload_id/parts"))
+            .and(wiremock::matchers::query_param("part", "2"))
+            .respond_with(ResponseTemplate::new(400))
+            .mount(server)
+            .await;
+        mount_chunked_upload(server).await;
+        Mock::given(method("DELETE"))
+            .and(path("/uploads/upload_id"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(server)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn put_file_failure_carries_upload_context() {
+        let server = MockServer::start().await;
+        mount_chunked_upload_failing_at_part_2(&server).await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let options = PutFileOptions {
+            chunk_size: 5 * MB,
+            concurrency: 1,
+            ..Default::default()
+        };
+        let error = drive
+            .put_file_with("file.dat", vec![0; 12 * MB], options)
+            .await
+            .unwrap_err();
+
+        assert!(error.is_bad_request());
+        assert_eq!(
+            error.upload_context(),
+            Some(&UploadErrorContext {
+                upload_id: "upload_id".into(),
+                part: Some(2),
+                total_parts: Some(3),
+            })
+        );
+        assert!(error
+            .to_string()
+            .contains("Upload: 'upload_id', part 2 of 3."));
+        assert!(error.cleanup_failure().is_none());
+    }
+
+    #[tokio::test]
+    async fn put_file_from_reader_failure_carries_upload_context() {
+        let server = MockServer::start().await;
+        mount_chunked_upload_failing_at_part_2(&server).await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let options = PutFileOptions {
+            chunk_size: 5 * MB,
+            force_chunked: true,
+            ..Default::default()
+        };
+        let data = vec![0; 12 * MB];
+        let error = drive
+            .put_file_from_reader("file.dat", &data[..], options)
+            .await
+            .unwrap_err();
+
+        let context = error.upload_context().unwrap();
+        assert_eq!(context.part, Some(2));
+        assert_eq!(context.total_parts, None);
+    }
+
+    #[tokio::test]
+    async fn put_file_end_failure_carries_upload_context() {
+        let server = MockServer::start().await;
+        Mock::given(method("PATCH"))
+            .and(path("/uploads/upload_id"))
+            .respond_with(ResponseTemplate::new(500))
+            .mount(&server)
+            .await;
+        mount_chunked_upload_failing_at_part_2(&server).await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let options = PutFileOptions {
+            force_chunked: true,
+            ..Default::default()
+        };
+        let error = drive
+            .put_file_with("file.dat", vec![0; 1024], options)
+            .await
+            .unwrap_err();
+
+        let context = error.upload_context().unwrap();
+        assert_eq!(context.upload_id, "upload_id");
+        assert_eq!(context.part, None);
+    }
+
+    // Mounts a chunked upload in which the second part stalls.
+    async fn mount_stalled_chunked_upload(server: &MockServer) {
+        Mock::given(method("POST"))
+            .and(path("/uploads/upload_id/parts"))
+            .and(wiremock::matchers::query_param("part", "2"))
+            .respond_with(ResponseTemplate::new(200).set_delay(std::time::Duration::from_secs(5)))
+            .mount(server)
+            .await;
+        mount_chunked_upload(server).await;
+        Mock::given(method("DELETE"))
+            .and(path("/uploads/upload_id"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(server)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn put_file_with_exceeded_part_timeout() {
+        let server = MockServer::start().await;
+        mount_stalled_chunked_upload(&server).await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let options = PutFileOptions {
+            chunk_size: 5 * MB,
+            part_timeout: Some(std::time::Duration::from_millis(500)),
+            ..Default::default()
+        };
+        let error = drive
+            .put_file_with("file.dat", vec![0; 11 * MB], options)
+            .await
+            .unwrap_err();
+
+        let message = error.to_string();
+        assert!(message.contains("Part timeout of 500ms"), "{}", message);
+        assert!(message.contains("1 part(s) completed"), "{}", message);
+        assert!(error.cleanup_failure().is_none());
+    }
+
+    #[tokio::test]
+    async fn put_file_from_reader_with_exceeded_deadline() {
+        let server = MockServer::start().await;
+        mount_stalled_chunked_upload(&server).await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let options = PutFileOptions {
+            chunk_size: 5 * MB,
+            part_timeout: Some(std::time::Duration::from_secs(60)),
+            deadline: Some(std::time::Duration::from_secs(1)),
+            ..Default::default()
+        };
+        let data = vec![0; 11 * MB];
+        let error = drive
+            .put_file_from_reader("file.dat", &data[..], options)
+            .await
+            .unwrap_err();
+
+        let message = error.to_string();
+        assert!(message.contains("Upload deadline of 1s"), "{}", message);
+        assert!(message.contains("1 part(s) completed"), "{}", message);
+    }
+
+    // With the clock paused, the deadline passes as soon as the upload waits, so only the first request may stall.
+    #[tokio::test(start_paused = true)]
+    async fn put_file_with_stalled_response_and_exceeded_deadline() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/files"))
+            .respond_with(ResponseTemplate::new(201).set_delay(std::time::Duration::from_secs(60)))
+            .mount(&server)
+            .await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let options = PutFileOptions {
+            deadline: Some(std::time::Duration::from_secs(30)),
+            ..Default::default()
+        };
+        let error = drive
+            .put_file_with("file.dat", vec![0; 1024], options)
+            .await
+            .unwrap_err();
+
+        assert!(error.is_timeout());
+        let message = error.to_string();
+        assert!(message.contains("Upload deadline of 30s"), "{}", message);
+        assert!(message.contains("0 part(s) completed"), "{}", message);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn put_file_from_stalled_reader_with_exceeded_deadline() {
+        use tokio::io::AsyncWriteExt;
+
+        let server = MockServer::start().await;
+        let drive = Drive::with_base_url(&server.uri());
+        let options = PutFileOptions {
+            deadline: Some(std::time::Duration::from_secs(30)),
+            ..Default::default()
+        };
+        // The writer is kept open, so the reader waits for more content forever.
+        let (reader, mut writer) = tokio::io::duplex(64);
+        writer.write_all(b"content").await.unwrap();
+        let error = drive
+            .put_file_from_reader("file.dat", reader, options)
+            .await
+            .unwrap_err();
+
+        assert!(error.is_timeout());
+        let message = error.to_string();
+        assert!(message.contains("Upload deadline of 30s"), "{}", message);
+        assert!(server.received_requests().await.unwrap().is_empty());
+        drop(writer);
+    }
+
+    // Cancels the token when the request is received.
+    struct CancelOnRequest(tokio_util::sync::CancellationToken);
+
+    impl wiremock::Respond for CancelOnRequest {
+        fn respond(&self, _: &wiremock::Request) -> ResponseTemplate {
+            self.0.cancel();
+            ResponseTemplate::new(200)
+        }
+    }
+
+    #[tokio::test]
+    async fn put_file_cancelled_after_first_part() {
+        let server = MockServer::start().await;
+        let cancellation = tokio_util::sync::CancellationToken::new();
+        Mock::given(method("POST"))
+            .and(path("/uploads/upload_id/parts"))
+            .and(wiremock::matchers::query_param("part", "1"))
+            .respond_with(CancelOnRequest(cancellation.clone()))
+            .mount(&server)
+            .await;
+        mount_chunked_upload(&server).await;
+        Mock::given(method("DELETE"))
+            .and(path("/uploads/upload_id"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let options = PutFileOptions {
+            chunk_size: 5 * MB,
+            cancellation: Some(cancellation),
+            ..Default::default()
+        };
+        let data = vec![0; 11 * MB];
+        let error = drive
+            .put_file_from_reader("file.dat", &data[..], options)
+            .await
+            .unwrap_err();
+
+        assert!(error.is_cancelled());
+        assert!(matches!(
+            error.get_kind(),
+            crate::error::Kind::Cancelled { uploaded_parts: 1 }
+        ));
+        assert_eq!(uploaded_part_sizes(&server).await, vec![5 * MB]);
+    }
+
+    #[tokio::test]
+    async fn put_file_falls_back_to_chunked_upload_on_413() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/files"))
+            .respond_with(ResponseTemplate::new(413))
+            .expect(1)
+            .mount(&server)
+            .await;
+        mount_chunked_upload(&server).await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let result = drive
+            .put_file("file.dat", vec![0; 6 * MB], None)
+            .await
+            .unwrap();
+
+        assert!(matches!(result, PutFileResult::ChunkedUpload(_)));
+        assert_eq!(uploaded_part_sizes(&server).await, vec![5 * MB, MB]);
+    }
+
+    #[tokio::test]
+    async fn put_file_without_chunked_fallback() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/files"))
+            .respond_with(ResponseTemplate::new(413))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/uploads"))
+            .respond_with(ResponseTemplate::new(201))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let options = PutFileOptions {
+            chunked_fallback: false,
+            ..Default::default()
+        };
+        let error = drive
+            .put_file_with("file.dat", vec![0; 6 * MB], options)
+            .await
+            .unwrap_err();
+
+        assert!(error.is_payload_too_large());
+    }
+
+    #[tokio::test]
+    async fn put_file_with_stats_for_retried_part() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/uploads/upload_id/parts"))
+            .and(wiremock::matchers::query_param("part", "2"))
+            .respond_with(ResponseTemplate::new(500))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        mount_chunked_upload(&server).await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let options = PutFileOptions {
+            part_retries: 1,
+            ..Default::default()
+        };
+        let (result, stats) = drive
+            .put_file_with_stats("file.dat", vec![0; 25 * MB], options)
+            .await
+            .unwrap();
+
+        assert!(result.was_chunked());
+        assert_eq!(stats.parts, 3);
+        assert_eq!(stats.bytes, 25 * MB as u64);
+        assert_eq!(stats.retried_parts, 1);
+        assert_eq!(
+            uploaded_part_sizes(&server).await,
+            vec![10 * MB, 10 * MB, 10 * MB, 5 * MB]
+        );
+    }
+
+    #[tokio::test]
+    async fn put_file_with_stats_for_single_put() {
+        let server = MockServer::start().await;
+        mount_put_expecting(&server, 1).await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let (result, stats) = drive
+            .put_file_with_stats("file.txt", vec![0; 1024], PutFileOptions::default())
+            .await
+            .unwrap();
+
+        assert!(!result.was_chunked());
+        assert_eq!(stats.parts, 1);
+        assert_eq!(stats.bytes, 1024);
+        assert_eq!(stats.retried_parts, 0);
+    }
+
+    #[tokio::test]
+    async fn put_file_with_invalid_options() {
+        let server = MockServer::start().await;
+        Mock::given(wiremock::matchers::any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let options = PutFileOptions {
+            chunk_size: MB,
+            ..Default::default()
+        };
+        let error = drive
+            .put_file_with("file.dat", vec![0; 1024], options)
+            .await
+            .unwrap_err();
+
+        assert!(error.is_invalid_argument());
+    }
+
+    async fn mount_delete(server: &MockServer, response: serde_json::Value) {
+        Mock::given(method("DELETE"))
+            .and(path("/files"))
+            .and(wiremock::matchers::body_json(
+                json!({ "names": ["file.txt"] }),
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(response))
+            .mount(server)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn delete_file_for_existing_file() {
+        let server = MockServer::start().await;
+        mount_delete(&server, json!({ "deleted": ["file.txt"] })).await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        assert!(drive.delete_file("file.txt").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn delete_file_for_missing_file() {
+        let server = MockServer::start().await;
+        mount_delete(
+            &server,
+            json!({ "deleted": [], "failed": { "file.txt": "File not found" } }),
+        )
+        .await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        assert!(!drive.delete_file("file.txt").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn delete_file_for_failure() {
+        let server = MockServer::start().await;
+        mount_delete(
+            &server,
+            json!({ "deleted": [], "failed": { "file.txt": "Internal error" } }),
+        )
+        .await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let error = drive.delete_file("file.txt").await.unwrap_err();
+        assert!(error.to_string().contains("Internal error"));
+    }
+
+    #[tokio::test]
+    async fn delete_files_for_nested_failure() {
+        let server = MockServer::start().await;
+        mount_delete(
+            &server,
+            json!({
+                "deleted": [],
+                "failed": { "file.txt": { "error": { "code": "forbidden", "message": "Forbidden" } } }
+            }),
+        )
+        .await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let result = drive.delete_files(&["file.txt".into()]).await.unwrap();
+        assert!(!result.all_succeeded());
+        assert_eq!(
+            result.failure_reason("file.txt").as_deref(),
+            Some("Forbidden")
+        );
+
+        let error = drive.delete_file("file.txt").await.unwrap_err();
+        assert!(error.to_string().contains("Forbidden"));
+    }
+
+    #[test]
+    fn is_not_found_reason_for_reasons() {
+        assert!(is_not_found_reason("File not found"));
+        assert!(is_not_found_reason("file does not exist"));
+        assert!(!is_not_found_reason("Internal error"));
+    }
+
+    #[tokio::test]
+    async fn head_file() {
+        let server = MockServer::start().await;
+        Mock::given(method("HEAD"))
+            .and(path("/files/download"))
+            .and(wiremock::matchers::query_param("name", "file.txt"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("Content-Length", "42")
+                    .insert_header("Content-Type", "text/plain")
+                    .insert_header("Last-Modified", "Wed, 21 Oct 2015 07:28:00 GMT"),
+            )
+            .mount(&server)
+            .await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let metadata = drive.head_file("file.txt").await.unwrap().unwrap();
+        assert_eq!(metadata.content_length, Some(42));
+        assert_eq!(metadata.content_type.as_deref(), Some("text/plain"));
+        assert_eq!(
+            metadata.last_modified.as_deref(),
+            Some("Wed, 21 Oct 2015 07:28:00 GMT")
+        );
+        assert!(drive.head_file("missing.txt").await.unwrap().is_none());
+    }
+
+    async fn mount_put_expecting(server: &MockServer, calls: u64) {
+        Mock::given(method("POST"))
+            .and(path("/files"))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!({
+                "name": "file.txt",
+                "project_id": "project",
+                "drive_name": "drive"
+            })))
+            .expect(calls)
+            .mount(server)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn put_file_if_absent_for_missing_file() {
+        let server = MockServer::start().await;
+        mount_put_expecting(&server, 1).await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let outcome = drive
+            .put_file_if_absent("file.txt", vec![1], None)
+            .await
+            .unwrap();
+        assert!(matches!(outcome, PutIfAbsentOutcome::Uploaded(_)));
+    }
+
+    #[tokio::test]
+    async fn put_file_if_absent_for_existing_file() {
+        let server = MockServer::start().await;
+        mount_put_expecting(&server, 0).await;
+        Mock::given(method("HEAD"))
+            .and(path("/files/download"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let outcome = drive
+            .put_file_if_absent("file.txt", vec![1], None)
+            .await
+            .unwrap();
+        assert!(matches!(outcome, PutIfAbsentOutcome::AlreadyExists));
+    }
+
+    #[tokio::test]
+    async fn put_file_if_absent_for_failed_check() {
+        let server = MockServer::start().await;
+        mount_put_expecting(&server, 0).await;
+        Mock::given(method("HEAD"))
+            .and(path("/files/download"))
+            .respond_with(ResponseTemplate::new(500))
+            .mount(&server)
+            .await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let error = drive
+            .put_file_if_absent("file.txt", vec![1], None)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.status_kind(),
+            Some(&crate::error::ResponseStatusKind::InternalServerError)
+        );
+    }
+
+    #[tokio::test]
+    async fn append_to_missing_file() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/files"))
+            .and(wiremock::matchers::body_bytes(b"first line\n".to_vec()))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!({
+                "name": "log.txt",
+                "project_id": "project",
+                "drive_name": "drive"
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        drive
+            .append_to_file("log.txt", b"first line\n")
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn append_to_existing_file() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/files/download"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_raw(b"first line\n".to_vec(), "text/plain"),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/files"))
+            .and(wiremock::matchers::body_bytes(
+                b"first line\nsecond line\n".to_vec(),
+            ))
+            .and(wiremock::matchers::header("content-type", "text/plain"))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!({
+                "name": "log.txt",
+                "project_id": "project",
+                "drive_name": "drive"
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        drive
+            .append_to_file("log.txt", b"second line\n")
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn append_to_file_exceeding_max_size() {
+        let server = MockServer::start().await;
+        mount_download(&server, &[0; 100]).await;
+        mount_put_expecting(&server, 0).await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let options = AppendOptions {
+            max_size: Some(120),
+        };
+        let error = drive
+            .append_to_file_with("log.txt", &[1; 21], options)
+            .await
+            .unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("exceeding the limit of 120 bytes"));
+    }
+
+    #[tokio::test]
+    async fn raw_request_to_made_up_endpoint() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/project/drive/thumbnails"))
+            .and(wiremock::matchers::query_param("name", "photo.jpg"))
+            .and(wiremock::matchers::header("X-Api-Key", "test_key"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(vec![1, 2, 3]))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/project/drive/usage"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "bytes": 42 })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/project/drive/broken"))
+            .respond_with(ResponseTemplate::new(500).set_body_raw("<html></html>", "text/html"))
+            .mount(&server)
+            .await;
+
+        let drive = Drive::with_base_url(&format!("{}/project/drive", server.uri()));
+        let thumbnail = drive
+            .raw_request_bytes(reqwest::Method::GET, "thumbnails?name=photo.jpg", None)
+            .await
+            .unwrap();
+        assert_eq!(thumbnail.as_ref(), [1, 2, 3]);
+        let usage = drive
+            .raw_request(reqwest::Method::GET, "/usage", None)
+            .await
+            .unwrap();
+        assert_eq!(usage, json!({ "bytes": 42 }));
+
+        let error = drive
+            .raw_request(reqwest::Method::GET, "broken", None)
+            .await
+            .unwrap_err();
+        assert!(error.is_unexpected_content_type());
+        assert!(error.is_retryable());
+        let error = drive
+            .raw_request_bytes(reqwest::Method::GET, "%2e%2e/other_drive/files", None)
+            .await
+            .unwrap_err();
+        assert!(error.is_invalid_argument());
+    }
+
+    #[test]
+    fn new_with_invalid_drive_name() {
+        let client = DetaClient::new("project_key");
+        assert!(Drive::new(&client, "drive_name").is_ok());
+        let error = Drive::new(&client, "drive name").err().unwrap();
+        assert!(error.is_invalid_argument());
+    }
+
+    #[tokio::test]
+    async fn invalid_file_names_are_rejected_locally() {
+        let server = MockServer::start().await;
+        Mock::given(wiremock::matchers::any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let error = drive
+            .put_file("/file.txt", vec![1], None)
+            .await
+            .unwrap_err();
+        assert!(error.is_invalid_argument());
+        let error = drive
+            .put_file_from_reader(" ", tokio::io::empty(), PutFileOptions::default())
+            .await
+            .unwrap_err();
+        assert!(error.is_invalid_argument());
+        let error = drive.get_file_as_buffer("").await.unwrap_err();
+        assert!(error.is_invalid_argument());
+        let error = drive.delete_file("/file.txt").await.unwrap_err();
+        assert!(error.is_invalid_argument());
+        let error = drive.head_file(" \t").await.unwrap_err();
+        assert!(error.is_invalid_argument());
+        let error = drive
+            .abort_chunked_upload("", "upload_id")
+            .await
+            .unwrap_err();
+        assert!(error.is_invalid_argument());
+        let error = drive
+            .abort_chunked_upload("file.txt", " ")
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("upload id"));
+    }
+
+    fn single_put_result() -> PutFileResult {
+        PutFileResult::SinglePut(models::PutFile {
+            name: "file.txt".into(),
+            project_id: "project".into(),
+            drive_name: "drive".into(),
+        })
+    }
+
+    fn chunked_upload_result() -> PutFileResult {
+        PutFileResult::ChunkedUpload(models::EndChunkedUpload {
+            name: "big_file.dat".into(),
+            upload_id: "upload".into(),
+            project_id: "another_project".into(),
+            drive_name: "another_drive".into(),
+        })
+    }
+
+    #[test]
+    fn put_file_result_accessors_for_single_put() {
+        let result = single_put_result();
+        assert_eq!(result.name(), "file.txt");
+        assert_eq!(result.project_id(), "project");
+        assert_eq!(result.drive_name(), "drive");
+        assert!(!result.was_chunked());
+    }
+
+    #[test]
+    fn put_file_result_accessors_for_chunked_upload() {
+        let result = chunked_upload_result();
+        assert_eq!(result.name(), "big_file.dat");
+        assert_eq!(result.project_id(), "another_project");
+        assert_eq!(result.drive_name(), "another_drive");
+        assert!(result.was_chunked());
+    }
+
+    #[tokio::test]
+    async fn list_files_after_cursor() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/files"))
+            .and(wiremock::matchers::query_param("last", "a.txt"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "paging": { "size": 1 },
+                "names": ["b.txt"]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/files"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "paging": { "size": 1, "last": "a.txt" },
+                "names": ["a.txt"]
+            })))
+            .mount(&server)
+            .await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let files = drive.list_files(Some(1), None, None).await.unwrap();
+        let cursor = files.next_cursor().unwrap();
+        let files = drive
+            .list_files_after(&cursor, Some(1), None)
+            .await
+            .unwrap();
+
+        assert_eq!(files.names, vec!["b.txt".to_owned()]);
+        assert_eq!(files.next_cursor(), None);
+    }
+
+    #[tokio::test]
+    async fn list_matching_goes_through_pages() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/files"))
+            .and(wiremock::matchers::query_param("last", "uploads/b.png"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "paging": { "size": 2 },
+                "names": ["uploads/c.jpg", "uploads/nested/d.jpg"]
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/files"))
+            .and(wiremock::matchers::query_param("prefix", "uploads/"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "paging": { "size": 2, "last": "uploads/b.png" },
+                "names": ["uploads/a.jpg", "uploads/b.png"]
+            })))
+            .mount(&server)
+            .await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let files = drive
+            .list_matching(Some("uploads/"), "uploads/*.jpg")
+            .await
+            .unwrap();
+
+        assert_eq!(
+            files.names,
+            vec!["uploads/a.jpg".to_owned(), "uploads/c.jpg".to_owned()]
+        );
+        assert!(files.paging.is_none());
+    }
+
+    #[test]
+    fn new_takes_transfer_limit_from_client() {
+        let client = DetaClient::new("project_key").with_transfer_limit(crate::TransferLimit {
+            max_upload_bps: Some(1024),
+            max_download_bps: None,
+        });
+        let drive = Drive::new(&client, "drive").unwrap();
+        assert!(drive.upload_throttle.is_some());
+        assert!(drive.download_throttle.is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn download_paced_to_transfer_limit() {
+        let server = MockServer::start().await;
+        mount_download(&server, &[1; 64 * 1024]).await;
+
+        let mut drive = Drive::with_base_url(&server.uri());
+        drive.download_throttle = Some(utils::Throttle::new(128 * 1024));
+        let started = tokio::time::Instant::now();
+        let data = drive.get_file_as_u8_vec("file.dat").await.unwrap().unwrap();
+
+        assert_eq!(data.len(), 64 * 1024);
+        assert!(started.elapsed() >= std::time::Duration::from_millis(450));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn upload_paced_to_transfer_limit() {
+        let server = MockServer::start().await;
+        mount_put_expecting(&server, 1).await;
+
+        let mut drive = Drive::with_base_url(&server.uri());
+        drive.upload_throttle = Some(utils::Throttle::new(128 * 1024));
+        let started = tokio::time::Instant::now();
+        drive
+            .put_file("file.txt", vec![0; 64 * 1024], None)
+            .await
+            .unwrap();
+
+        assert!(started.elapsed() >= std::time::Duration::from_millis(450));
+    }
+
+    #[cfg(feature = "checksum")]
+    fn sha256_hex(data: &[u8]) -> String {
+        use sha2::Digest;
+        sha2::Sha256::digest(data)
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    #[cfg(feature = "checksum")]
+    #[tokio::test]
+    async fn put_file_with_stats_reports_checksum() {
+        let server = MockServer::start().await;
+        mount_put_expecting(&server, 1).await;
+        mount_chunked_upload(&server).await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let small: Vec<u8> = (0..1024).map(|idx| idx as u8).collect();
+        let (_, stats) = drive
+            .put_file_with_stats("file.txt", small.clone(), PutFileOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(stats.sha256, sha256_hex(&small));
+
+        let big: Vec<u8> = (0..11 * MB).map(|idx| (idx % 251) as u8).collect();
+        let (result, stats) = drive
+            .put_file_with_stats("file.dat", big.clone(), PutFileOptions::default())
+            .await
+            .unwrap();
+        assert!(result.was_chunked());
+        assert_eq!(stats.sha256, sha256_hex(&big));
+    }
+
+    #[cfg(feature = "checksum")]
+    #[tokio::test]
+    async fn put_file_from_reader_with_stats_reports_checksum() {
+        let server = MockServer::start().await;
+        mount_put_expecting(&server, 1).await;
+        mount_chunked_upload(&server).await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let small = b"small content".to_vec();
+        let (_, stats) = drive
+            .put_file_from_reader_with_stats("file.txt", &small[..], PutFileOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(stats.sha256, sha256_hex(&small));
+
+        let big: Vec<u8> = (0..11 * MB).map(|idx| (idx % 251) as u8).collect();
+        let (result, stats) = drive
+            .put_file_from_reader_with_stats("file.dat", &big[..], PutFileOptions::default())
+            .await
+            .unwrap();
+        assert!(result.was_chunked());
+        assert_eq!(stats.parts, 2);
+        assert_eq!(stats.sha256, sha256_hex(&big));
+    }
+
+    #[tokio::test]
+    async fn mutation_events_of_uploads_and_deletions() {
+        let fake = crate::testing::FakeDrive::new();
+        let (sender, mut events) = mpsc::unbounded_channel();
+        let drive = fake.drive().on_mutation(Arc::new(move |event| {
+            let _ = sender.send(event);
+        }));
+        async fn next_event(events: &mut mpsc::UnboundedReceiver<MutationEvent>) -> MutationEvent {
+            tokio::time::timeout(std::time::Duration::from_secs(5), events.recv())
+                .await
+                .expect("The event is reported")
+                .unwrap()
+        }
+        let put =
+            |name: &str| MutationEvent::succeeded(MutationKind::Put, vec![name.into()], vec![]);
+
+        drive.put_file("a.txt", "a", None).await.unwrap();
+        assert_eq!(next_event(&mut events).await, put("a.txt"));
+        let chunked = PutFileOptions {
+            force_chunked: true,
+            ..Default::default()
+        };
+        drive
+            .put_file_from_reader("b.txt", &b"b"[..], chunked)
+            .await
+            .unwrap();
+        assert_eq!(next_event(&mut events).await, put("b.txt"));
+
+        // Copied under the new name, then the original is deleted.
+        drive.rename("a.txt", "c.txt", false).await.unwrap();
+        assert_eq!(next_event(&mut events).await, put("c.txt"));
+        let deleted = next_event(&mut events).await;
+        assert_eq!(deleted.kind, MutationKind::Delete);
+        assert_eq!(deleted.keys, vec!["a.txt".to_owned()]);
+
+        let names = vec!["b.txt".to_owned(), "c.txt".to_owned()];
+        drive.delete_files(&names).await.unwrap();
+        assert_eq!(
+            next_event(&mut events).await,
+            MutationEvent::succeeded(MutationKind::Delete, names, vec![])
+        );
+        assert!(fake.files().is_empty());
+    }
+}
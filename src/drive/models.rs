@@ -1,7 +1,7 @@
 //! Structures corresponding to the responses of the deta drive API.
 
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct PutFile {
@@ -23,20 +23,523 @@ pub struct EndChunkedUpload {
     pub drive_name: String,
 }
 
+/// A page of the listed file names. It can be iterated over directly:
+///
+/// ```no_run
+/// # async fn run(drive: deta_rust::drive::Drive) -> deta_rust::error::Result<()> {
+/// for name in &drive.list_files(None, None, None).await? {
+///     println!("{}", name);
+/// }
+/// # Ok(())
+/// # }
+/// ```
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct ListFiles {
     pub paging: Option<ListFilesPaging>,
     pub names: Vec<String>,
 }
 
+impl ListFiles {
+    /// Returns the cursor pointing at the next page of the listing, or `None` if this is the last page.
+    pub fn next_cursor(&self) -> Option<Cursor> {
+        self.paging.as_ref().and_then(ListFilesPaging::cursor)
+    }
+
+    /// Checks whether there are no more files to list after this page.
+    pub fn is_last_page(&self) -> bool {
+        self.next_cursor().is_none()
+    }
+
+    /// Returns the files whose names end with the `suffix`, keeping the paging of the listing.
+    pub fn filter_suffix(&self, suffix: &str) -> ListFiles {
+        ListFiles {
+            paging: self.paging.clone(),
+            names: self
+                .names
+                .iter()
+                .filter(|name| name.ends_with(suffix))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Groups the names into one level of pseudo-folders, splitting them on the first `delimiter`.
+    /// Each folder maps to the rest of the names of its files, e.g. `uploads/2022/photo.jpg`
+    /// is listed as `2022/photo.jpg` in the `uploads` folder.
+    /// Names without the delimiter are listed under the empty folder name.
+    pub fn group_by_folder(&self, delimiter: char) -> BTreeMap<String, Vec<String>> {
+        let mut folders: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for name in &self.names {
+            let (folder, child) = name.split_once(delimiter).unwrap_or(("", name));
+            folders
+                .entry(folder.to_owned())
+                .or_default()
+                .push(child.to_owned());
+        }
+        folders
+    }
+
+    /// Appends the names of the `other` page, which is expected to follow this one.
+    /// The paging of the `other` page is kept.
+    pub fn merge(mut self, mut other: ListFiles) -> ListFiles {
+        self.names.append(&mut other.names);
+        ListFiles {
+            paging: other.paging,
+            names: self.names,
+        }
+    }
+
+    /// Appends the names of the `other` page in place, see [`ListFiles::merge`].
+    pub fn extend_from(&mut self, mut other: ListFiles) {
+        self.names.append(&mut other.names);
+        self.paging = other.paging;
+    }
+
+    /// Returns an iterator over the file names.
+    pub fn iter(&self) -> std::slice::Iter<'_, String> {
+        self.names.iter()
+    }
+
+    /// Returns the number of the file names.
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    /// Checks whether there are no file names.
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
+impl IntoIterator for ListFiles {
+    type Item = String;
+    type IntoIter = std::vec::IntoIter<String>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.names.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a ListFiles {
+    type Item = &'a String;
+    type IntoIter = std::slice::Iter<'a, String>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.names.iter()
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct ListFilesPaging {
     pub size: usize,
     pub last: Option<String>,
 }
 
+impl ListFilesPaging {
+    /// Returns the [`last`](ListFilesPaging::last) file name as a [`Cursor`](Cursor).
+    pub fn cursor(&self) -> Option<Cursor> {
+        self.last.clone().map(Cursor)
+    }
+}
+
+/// Position in the file listing, allowing to continue it with
+/// [`Drive::list_files_after`](super::Drive::list_files_after).
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Cursor(String);
+
+impl Cursor {
+    /// Creates the cursor pointing after the file with the given name.
+    pub fn new(last_name: impl Into<String>) -> Self {
+        Self(last_name.into())
+    }
+
+    /// Returns the name of the last listed file.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct DeleteFiles {
     pub deleted: Vec<String>,
-    pub failed: Option<HashMap<String, String>>,
+    /// The files that could not be deleted, along with the failure details sent by deta.
+    /// The details are usually a string, but any JSON value is accepted, see [`DeleteFiles::failure_reason`].
+    pub failed: Option<HashMap<String, serde_json::Value>>,
+}
+
+impl DeleteFiles {
+    /// Checks whether every file has been deleted.
+    pub fn all_succeeded(&self) -> bool {
+        self.failed_names().next().is_none()
+    }
+
+    /// Returns the names of the files that could not be deleted.
+    pub fn failed_names(&self) -> impl Iterator<Item = &str> {
+        self.failed.iter().flatten().map(|(name, _)| name.as_str())
+    }
+
+    /// Returns the reason why the file could not be deleted, or `None` if its deletion didn't fail.
+    /// A string is returned as it is. For an object, its `reason`, `message` or `error` string field is returned,
+    /// any other value is returned as JSON.
+    pub fn failure_reason(&self, name: &str) -> Option<String> {
+        self.failed.as_ref()?.get(name).map(failure_reason)
+    }
+
+    /// Checks whether the file no longer exists after the deletion: either it wasn't reported as failed,
+    /// or deta reported it as missing, e.g. because it had already been deleted.
+    pub fn is_gone(&self, name: &str) -> bool {
+        match self.failure_reason(name) {
+            Some(reason) => super::is_not_found_reason(&reason),
+            None => true,
+        }
+    }
+
+    /// Returns the names of the files that could not be deleted, along with the reason strings
+    /// found in the details as [`DeleteFiles::failure_reason`] finds them. The reason is empty
+    /// if the details contain no string, use [`DeleteFiles::failure_reason`] to get them as JSON.
+    pub fn failures(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.failed
+            .iter()
+            .flatten()
+            .map(|(name, details)| (name.as_str(), nested_reason(details).unwrap_or_default()))
+    }
+}
+
+// Reads the reason from the failure details, whatever shape they have.
+fn failure_reason(details: &serde_json::Value) -> String {
+    nested_reason(details)
+        .map(str::to_owned)
+        .unwrap_or_else(|| details.to_string())
+}
+
+// Finds the reason string in the details, following the `reason`, `message` and `error` fields
+// of the nested objects, e.g. `{ "error": { "message": "Forbidden" } }`.
+fn nested_reason(details: &serde_json::Value) -> Option<&str> {
+    if let Some(reason) = details.as_str() {
+        return Some(reason);
+    }
+    ["reason", "message", "error"]
+        .iter()
+        .find_map(|field| nested_reason(details.get(field)?))
+}
+
+/// Metadata of the file, read from the headers of the download response.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct FileMetadata {
+    pub content_length: Option<u64>,
+    pub content_type: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+impl FileMetadata {
+    pub(crate) fn from_headers(headers: &reqwest::header::HeaderMap) -> Self {
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_owned)
+        };
+
+        Self {
+            // The header is read directly, as `Response::content_length` is always zero for HEAD requests.
+            content_length: header(reqwest::header::CONTENT_LENGTH)
+                .and_then(|value| value.parse().ok()),
+            content_type: header(reqwest::header::CONTENT_TYPE),
+            last_modified: header(reqwest::header::LAST_MODIFIED),
+        }
+    }
+}
+
+/// Downloaded file along with the metadata read from the response headers.
+#[derive(Clone, Debug)]
+pub struct FileDownload {
+    pub data: bytes::Bytes,
+    pub content_type: Option<String>,
+    pub content_length: Option<u64>,
+    pub last_modified: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::{HeaderMap, HeaderValue, CONTENT_LENGTH, CONTENT_TYPE};
+
+    #[test]
+    fn file_metadata_from_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_LENGTH, HeaderValue::from_static("42"));
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("image/png"));
+
+        let metadata = FileMetadata::from_headers(&headers);
+        assert_eq!(metadata.content_length, Some(42));
+        assert_eq!(metadata.content_type.as_deref(), Some("image/png"));
+        assert_eq!(metadata.last_modified, None);
+    }
+
+    fn delete_files(body: serde_json::Value) -> DeleteFiles {
+        serde_json::from_value(body).unwrap()
+    }
+
+    #[test]
+    fn delete_files_with_string_reasons() {
+        let result = delete_files(serde_json::json!({
+            "deleted": ["a.txt"],
+            "failed": { "b.txt": "Internal error" }
+        }));
+
+        assert!(!result.all_succeeded());
+        assert_eq!(result.failed_names().collect::<Vec<_>>(), vec!["b.txt"]);
+        assert_eq!(
+            result.failure_reason("b.txt").as_deref(),
+            Some("Internal error")
+        );
+        assert_eq!(result.failure_reason("a.txt"), None);
+    }
+
+    #[test]
+    fn delete_files_with_object_reasons() {
+        let result = delete_files(serde_json::json!({
+            "deleted": [],
+            "failed": {
+                "a.txt": { "message": "Internal error", "code": 500 },
+                "b.txt": { "code": 500 },
+                "c.txt": 42
+            }
+        }));
+
+        assert!(!result.all_succeeded());
+        assert_eq!(
+            result.failure_reason("a.txt").as_deref(),
+            Some("Internal error")
+        );
+        assert_eq!(
+            result.failure_reason("b.txt").as_deref(),
+            Some(r#"{"code":500}"#)
+        );
+        assert_eq!(result.failure_reason("c.txt").as_deref(), Some("42"));
+        let mut failures: Vec<_> = result.failures().collect();
+        failures.sort();
+        assert_eq!(
+            failures,
+            vec![("a.txt", "Internal error"), ("b.txt", ""), ("c.txt", "")]
+        );
+    }
+
+    #[test]
+    fn delete_files_with_nested_reasons() {
+        let result = delete_files(serde_json::json!({
+            "deleted": ["a.txt"],
+            "failed": {
+                "b.txt": {
+                    "error": {
+                        "code": "forbidden",
+                        "message": "Key is not permitted to delete the file",
+                        "details": { "scopes": ["read"] }
+                    }
+                },
+                "c.txt": { "error": { "code": 403 } }
+            }
+        }));
+
+        assert_eq!(result.deleted, vec!["a.txt".to_owned()]);
+        assert_eq!(
+            result.failure_reason("b.txt").as_deref(),
+            Some("Key is not permitted to delete the file")
+        );
+        assert_eq!(
+            result.failure_reason("c.txt").as_deref(),
+            Some(r#"{"error":{"code":403}}"#)
+        );
+    }
+
+    #[test]
+    fn delete_files_without_failed_section() {
+        let result = delete_files(serde_json::json!({ "deleted": ["a.txt"] }));
+
+        assert!(result.all_succeeded());
+        assert_eq!(result.failed_names().next(), None);
+        assert_eq!(result.failure_reason("a.txt"), None);
+    }
+
+    #[test]
+    fn iterate_list_files() {
+        let files = ListFiles {
+            paging: None,
+            names: vec!["a.txt".into(), "b.txt".into()],
+        };
+
+        assert_eq!(files.len(), 2);
+        assert!(!files.is_empty());
+        assert_eq!(files.iter().last().map(String::as_str), Some("b.txt"));
+        let mut names = vec![];
+        for name in &files {
+            names.push(name.as_str());
+        }
+        assert_eq!(names, vec!["a.txt", "b.txt"]);
+        assert_eq!(
+            files.into_iter().collect::<Vec<_>>(),
+            vec!["a.txt", "b.txt"]
+        );
+    }
+
+    #[test]
+    fn iterate_empty_list_files() {
+        let files = ListFiles {
+            paging: None,
+            names: vec![],
+        };
+
+        assert!(files.is_empty());
+        assert_eq!(files.iter().next(), None);
+        assert_eq!(files.into_iter().next(), None);
+    }
+
+    #[test]
+    fn next_cursor_of_middle_page() {
+        let files = ListFiles {
+            paging: Some(ListFilesPaging {
+                size: 1,
+                last: Some("a.txt".into()),
+            }),
+            names: vec!["a.txt".into()],
+        };
+        assert_eq!(files.next_cursor(), Some(Cursor::new("a.txt")));
+    }
+
+    #[test]
+    fn next_cursor_of_last_page() {
+        let files = ListFiles {
+            paging: None,
+            names: vec!["a.txt".into()],
+        };
+        assert_eq!(files.next_cursor(), None);
+
+        let paging = ListFilesPaging {
+            size: 1,
+            last: None,
+        };
+        assert_eq!(paging.cursor(), None);
+    }
+
+    #[test]
+    fn list_files_merge_keeps_later_paging() {
+        let first = ListFiles {
+            paging: Some(ListFilesPaging {
+                size: 1,
+                last: Some("a.txt".into()),
+            }),
+            names: vec!["a.txt".into()],
+        };
+        let second = ListFiles {
+            paging: None,
+            names: vec!["b.txt".into()],
+        };
+        assert!(!first.is_last_page());
+
+        let merged = first.merge(second);
+        assert_eq!(merged.names, vec!["a.txt".to_owned(), "b.txt".to_owned()]);
+        assert!(merged.paging.is_none());
+        assert!(merged.is_last_page());
+    }
+
+    #[test]
+    fn list_files_extend_from_empty_page() {
+        let mut files = ListFiles {
+            paging: Some(ListFilesPaging {
+                size: 1,
+                last: Some("a.txt".into()),
+            }),
+            names: vec!["a.txt".into()],
+        };
+
+        files.extend_from(listing(&[]));
+
+        assert_eq!(files.names, vec!["a.txt".to_owned()]);
+        assert!(files.is_last_page());
+    }
+
+    fn listing(names: &[&str]) -> ListFiles {
+        ListFiles {
+            paging: None,
+            names: names.iter().map(|name| name.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn list_files_filter_suffix() {
+        let files = listing(&["a.jpg", "b.png", "uploads/c.jpg", "jpg"]);
+        assert_eq!(
+            files.filter_suffix(".jpg").names,
+            vec!["a.jpg".to_owned(), "uploads/c.jpg".to_owned()]
+        );
+    }
+
+    #[test]
+    fn list_files_group_by_folder() {
+        let files = listing(&[
+            "root.txt",
+            "uploads/a.jpg",
+            "uploads/2022/b.jpg",
+            "docs/c.md",
+        ]);
+        let folders = files.group_by_folder('/');
+
+        assert_eq!(
+            folders.keys().collect::<Vec<_>>(),
+            vec!["", "docs", "uploads"]
+        );
+        assert_eq!(folders[""], vec!["root.txt".to_owned()]);
+        assert_eq!(folders["docs"], vec!["c.md".to_owned()]);
+        assert_eq!(
+            folders["uploads"],
+            vec!["a.jpg".to_owned(), "2022/b.jpg".to_owned()]
+        );
+    }
+
+    #[test]
+    fn delete_files_without_failures() {
+        let result = DeleteFiles {
+            deleted: vec!["a.txt".into()],
+            failed: None,
+        };
+        assert!(result.all_succeeded());
+        assert_eq!(result.failures().count(), 0);
+
+        let result = DeleteFiles {
+            deleted: vec!["a.txt".into()],
+            failed: Some(HashMap::new()),
+        };
+        assert!(result.all_succeeded());
+    }
+
+    #[test]
+    fn delete_files_is_gone_for_missing_files() {
+        let result = delete_files(serde_json::json!({
+            "deleted": ["a.txt"],
+            "failed": { "b.txt": "File not found", "c.txt": "Internal error" }
+        }));
+
+        assert!(result.is_gone("a.txt"));
+        assert!(result.is_gone("b.txt"));
+        assert!(!result.is_gone("c.txt"));
+    }
+
+    #[test]
+    fn delete_files_with_failures() {
+        let result = DeleteFiles {
+            deleted: vec![],
+            failed: Some(HashMap::from([("a.txt".into(), "Internal error".into())])),
+        };
+        assert!(!result.all_succeeded());
+        assert_eq!(
+            result.failures().collect::<Vec<_>>(),
+            vec![("a.txt", "Internal error")]
+        );
+    }
+
+    #[test]
+    fn file_metadata_from_missing_headers() {
+        let metadata = FileMetadata::from_headers(&HeaderMap::new());
+        assert_eq!(metadata, FileMetadata::default());
+    }
 }
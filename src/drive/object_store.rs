@@ -0,0 +1,573 @@
+//! The [`ObjectStore`](ObjectStore) trait of the [object_store](https://docs.rs/object_store/0.12) crate,
+//! implemented for a drive.
+//!
+//! Many tools of the Rust data ecosystem, e.g. DataFusion, delta-rs or the parquet readers,
+//! read and write files through the `ObjectStore` trait, so they can be pointed at a drive
+//! wrapped in a [`DriveObjectStore`](DriveObjectStore).
+//!
+//! ```no_run
+//! use deta_rust::drive::object_store::DriveObjectStore;
+//! use deta_rust::prelude::*;
+//! use object_store::{path::Path, ObjectStore};
+//! use std::sync::Arc;
+//!
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+//! let drive = Drive::new(&DetaClient::new("project_key"), "tables")?;
+//! let store: Arc<dyn ObjectStore> = Arc::new(DriveObjectStore::new(drive));
+//!
+//! let footer = store.get_range(&Path::from("events/part-0.parquet"), 0..8).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! # Mapping
+//!
+//! The paths are used as the file names, as they are. The names of the drive which aren't valid paths,
+//! e.g. with empty segments like `a//b`, are skipped by the listings.
+//!
+//! Deta drive lists only the names of the files, so the size and the last modification date of every listed
+//! file are read with a `HEAD` request, several at once. A `get` reads them the same way before downloading
+//! the content, so that the result has the size of the whole file even when only a range is downloaded.
+//!
+//! The multipart uploads are the chunked uploads of deta, whose parts must have between 5MB and 10MB,
+//! except the last one. [`WriteMultipart`](object_store::WriteMultipart) sends parts of 5MB by default.
+//!
+//! Deta drive doesn't support copying, conditional or versioned writes, so:
+//! - [`copy`](ObjectStore::copy), [`rename`](ObjectStore::rename), [`copy_if_not_exists`](ObjectStore::copy_if_not_exists)
+//!   and [`rename_if_not_exists`](ObjectStore::rename_if_not_exists) return [`NotImplemented`](object_store::Error::NotImplemented),
+//! - [`PutMode::Create`](PutMode::Create) checks that the file doesn't exist first, like
+//!   [`Drive::put_file_if_absent`](Drive::put_file_if_absent), and [`PutMode::Update`](PutMode::Update) isn't supported,
+//! - the files have no ETags or versions, the other preconditions of a `get` are checked against the last modification date.
+//!
+//! Available with the `object_store` feature.
+
+use super::{requests, Drive};
+use crate::constants;
+use crate::error::Error;
+use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures::stream::{self, BoxStream, StreamExt, TryStreamExt};
+use object_store::path::Path;
+use object_store::{
+    Attribute, Attributes, GetOptions, GetRange, GetResult, GetResultPayload, ListResult,
+    MultipartUpload, ObjectMeta, ObjectStore, PutMode, PutMultipartOptions, PutOptions, PutPayload,
+    PutResult, UploadPart,
+};
+use std::collections::BTreeSet;
+use std::fmt;
+use std::sync::Arc;
+
+const STORE: &str = "DetaDrive";
+const DELIMITER: char = '/';
+
+/// A drive as an [`ObjectStore`](ObjectStore) (see the [module documentation](self)).
+#[derive(Clone)]
+pub struct DriveObjectStore {
+    drive: Arc<Drive>,
+    drive_name: String,
+}
+
+impl DriveObjectStore {
+    /// Creates the store of the files of the `drive`.
+    pub fn new(drive: Drive) -> Self {
+        // The base URL of the drive ends with its name.
+        let drive_name = drive
+            .base_url
+            .rsplit('/')
+            .next()
+            .unwrap_or_default()
+            .to_owned();
+        Self {
+            drive: Arc::new(drive),
+            drive_name,
+        }
+    }
+
+    // Returns the metadata of every listed file with a name starting with the `prefix`, page by page.
+    fn list_names(&self, prefix: String) -> BoxStream<'static, object_store::Result<ObjectMeta>> {
+        let drive = self.drive.clone();
+        let pages = stream::try_unfold(Some(None), move |last: Option<Option<String>>| {
+            let drive = drive.clone();
+            let prefix = prefix.clone();
+            async move {
+                let last = match last {
+                    Some(last) => last,
+                    None => return Ok::<_, object_store::Error>(None),
+                };
+                let page = drive
+                    .list_files(None, Some(&prefix), last.as_deref())
+                    .await
+                    .map_err(|error| store_error(&prefix, error))?;
+                let next = page.paging.and_then(|paging| paging.last).map(Some);
+                Ok(Some((stream::iter(page.names).map(Ok), next)))
+            }
+        });
+
+        let drive = self.drive.clone();
+        pages
+            .try_flatten()
+            .map_ok(move |name| head_listed(drive.clone(), name))
+            .try_buffered(constants::DEFAULT_CONCURRENCY)
+            .try_filter_map(|meta| async move { Ok(meta) })
+            .boxed()
+    }
+}
+
+impl fmt::Debug for DriveObjectStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DriveObjectStore")
+            .field("drive", &self.drive_name)
+            .finish()
+    }
+}
+
+impl fmt::Display for DriveObjectStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "DetaDrive({})", self.drive_name)
+    }
+}
+
+#[async_trait]
+impl ObjectStore for DriveObjectStore {
+    async fn put_opts(
+        &self,
+        location: &Path,
+        payload: PutPayload,
+        options: PutOptions,
+    ) -> object_store::Result<PutResult> {
+        let name = location.as_ref();
+        let content_type = options.attributes.get(&Attribute::ContentType);
+        let data = Bytes::from(payload);
+        match options.mode {
+            PutMode::Overwrite => {}
+            PutMode::Create => {
+                let head = self.drive.head_file(name).await;
+                if head.map_err(|error| store_error(name, error))?.is_some() {
+                    return Err(object_store::Error::AlreadyExists {
+                        path: name.to_owned(),
+                        source: "The file already exists".into(),
+                    });
+                }
+            }
+            PutMode::Update(_) => return Err(object_store::Error::NotImplemented),
+        }
+        self.drive
+            .put_file(name, data, content_type.map(|value| value.as_ref()))
+            .await
+            .map_err(|error| store_error(name, error))?;
+        Ok(PutResult {
+            e_tag: None,
+            version: None,
+        })
+    }
+
+    async fn put_multipart_opts(
+        &self,
+        location: &Path,
+        _options: PutMultipartOptions,
+    ) -> object_store::Result<Box<dyn MultipartUpload>> {
+        let name = location.to_string();
+        let upload_id = self
+            .drive
+            .get_chunked_upload_object(&name)
+            .await
+            .map_err(|error| store_error(&name, error))?
+            .upload_id;
+        Ok(Box::new(DriveUpload {
+            drive: self.drive.clone(),
+            name,
+            upload_id,
+            parts: 0,
+        }))
+    }
+
+    async fn get_opts(
+        &self,
+        location: &Path,
+        options: GetOptions,
+    ) -> object_store::Result<GetResult> {
+        let name = location.as_ref();
+        if options.version.is_some() {
+            return Err(object_store::Error::NotSupported {
+                source: "Deta drive doesn't keep the versions of the files".into(),
+            });
+        }
+        let (meta, attributes) = match self.drive.head_file(name).await {
+            Ok(Some(metadata)) => {
+                let attributes = content_type_attributes(metadata.content_type.clone());
+                (object_meta(location.clone(), &metadata), attributes)
+            }
+            Ok(None) => return Err(not_found(name)),
+            Err(error) => return Err(store_error(name, error)),
+        };
+        options.check_preconditions(&meta)?;
+
+        let range = match &options.range {
+            Some(range) => {
+                range
+                    .as_range(meta.size)
+                    .map_err(|error| object_store::Error::Generic {
+                        store: STORE,
+                        source: Box::new(error),
+                    })?
+            }
+            None => 0..meta.size,
+        };
+        if options.head {
+            return Ok(GetResult {
+                payload: GetResultPayload::Stream(stream::empty().boxed()),
+                meta,
+                range,
+                attributes,
+            });
+        }
+
+        let response = match options.range {
+            Some(_) => {
+                let header = GetRange::Bounded(range.clone()).to_string();
+                self.drive.get_file_range_response(name, &header).await
+            }
+            None => self.drive.get_file_response(name).await,
+        };
+        let response = match response {
+            Ok(Some(response)) => response,
+            Ok(None) => return Err(not_found(name)),
+            Err(error) => return Err(store_error(name, error)),
+        };
+        let path = name.to_owned();
+        let stream = self
+            .drive
+            .body_stream(response)
+            .map_err(move |error| store_error(&path, error));
+        Ok(GetResult {
+            payload: GetResultPayload::Stream(stream.boxed()),
+            meta,
+            range,
+            attributes,
+        })
+    }
+
+    async fn delete(&self, location: &Path) -> object_store::Result<()> {
+        let name = location.as_ref();
+        self.drive
+            .delete_files(&[name.to_owned()])
+            .await
+            .map_err(|error| store_error(name, error))?;
+        Ok(())
+    }
+
+    fn list(&self, prefix: Option<&Path>) -> BoxStream<'static, object_store::Result<ObjectMeta>> {
+        self.list_names(directory(prefix))
+    }
+
+    async fn list_with_delimiter(&self, prefix: Option<&Path>) -> object_store::Result<ListResult> {
+        let directory = directory(prefix);
+        let mut common_prefixes = BTreeSet::new();
+        let mut files = vec![];
+        let mut last: Option<String> = None;
+        loop {
+            let page = self
+                .drive
+                .list_files(None, Some(&directory), last.as_deref())
+                .await
+                .map_err(|error| store_error(&directory, error))?;
+            for name in page.names {
+                match name[directory.len()..].split_once(DELIMITER) {
+                    Some((child, _)) => {
+                        common_prefixes.insert(format!("{}{}", directory, child));
+                    }
+                    None => files.push(name),
+                }
+            }
+            match page.paging.and_then(|paging| paging.last) {
+                Some(next) => last = Some(next),
+                None => break,
+            }
+        }
+
+        let objects = stream::iter(files)
+            .map(|name| head_listed(self.drive.clone(), name))
+            .buffered(constants::DEFAULT_CONCURRENCY)
+            .try_filter_map(|meta| async move { Ok(meta) })
+            .try_collect()
+            .await?;
+        Ok(ListResult {
+            common_prefixes: common_prefixes
+                .into_iter()
+                .filter_map(|prefix| Path::parse(prefix).ok())
+                .collect(),
+            objects,
+        })
+    }
+
+    async fn copy(&self, _from: &Path, _to: &Path) -> object_store::Result<()> {
+        Err(object_store::Error::NotImplemented)
+    }
+
+    async fn copy_if_not_exists(&self, _from: &Path, _to: &Path) -> object_store::Result<()> {
+        Err(object_store::Error::NotImplemented)
+    }
+}
+
+// A chunked upload of deta. The parts are numbered in the order of `put_part`, and may be sent concurrently.
+struct DriveUpload {
+    drive: Arc<Drive>,
+    name: String,
+    upload_id: String,
+    parts: usize,
+}
+
+impl fmt::Debug for DriveUpload {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DriveUpload")
+            .field("name", &self.name)
+            .field("upload_id", &self.upload_id)
+            .field("parts", &self.parts)
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl MultipartUpload for DriveUpload {
+    fn put_part(&mut self, data: PutPayload) -> UploadPart {
+        self.parts += 1;
+        let part = self.parts;
+        let drive = self.drive.clone();
+        let name = self.name.clone();
+        let upload_id = self.upload_id.clone();
+        Box::pin(async move {
+            let data = Bytes::from(data);
+            drive.pace_upload(data.len()).await;
+            requests::upload_chunk_request(
+                drive.transport.as_ref(),
+                &drive.base_url,
+                &drive.x_api_key,
+                &name,
+                &upload_id,
+                part,
+                data,
+            )
+            .await
+            .map_err(|error| store_error(&name, error))?;
+            Ok(())
+        })
+    }
+
+    async fn complete(&mut self) -> object_store::Result<PutResult> {
+        let (drive, name) = (&self.drive, &self.name);
+        let result = async {
+            if self.parts == 0 {
+                // Deta can't finish a chunked upload without parts, so an empty file is put at once instead.
+                drive.abort_chunked_upload(name, &self.upload_id).await?;
+                drive.put_file(name, Bytes::new(), None).await?;
+            } else {
+                let ended = drive.end_chunked_upload(name, &self.upload_id).await;
+                drive.notify_put(name, &ended);
+                ended?;
+            }
+            Ok(())
+        }
+        .await;
+        result.map_err(|error| store_error(name, error))?;
+        Ok(PutResult {
+            e_tag: None,
+            version: None,
+        })
+    }
+
+    async fn abort(&mut self) -> object_store::Result<()> {
+        self.drive
+            .abort_chunked_upload(&self.name, &self.upload_id)
+            .await
+            .map_err(|error| store_error(&self.name, error))
+    }
+}
+
+// Returns the prefix of the names of the files within the `prefix` directory, ending with the delimiter.
+fn directory(prefix: Option<&Path>) -> String {
+    match prefix.map(Path::as_ref) {
+        None | Some("") => String::new(),
+        Some(prefix) => format!("{}{}", prefix, DELIMITER),
+    }
+}
+
+// Returns the metadata of a listed file, or `None` if it isn't a valid path or has been deleted in the meantime.
+async fn head_listed(drive: Arc<Drive>, name: String) -> object_store::Result<Option<ObjectMeta>> {
+    let location = match Path::parse(&name) {
+        Ok(location) => location,
+        Err(_) => return Ok(None),
+    };
+    match drive.head_file(&name).await {
+        Ok(metadata) => Ok(metadata.map(|metadata| object_meta(location, &metadata))),
+        Err(error) => Err(store_error(&name, error)),
+    }
+}
+
+fn object_meta(location: Path, metadata: &super::models::FileMetadata) -> ObjectMeta {
+    // The date is sent in the format of HTTP headers, e.g. `Wed, 21 Oct 2015 07:28:00 GMT`.
+    let last_modified = metadata
+        .last_modified
+        .as_deref()
+        .and_then(|date| DateTime::parse_from_rfc2822(date).ok())
+        .map(|date| date.with_timezone(&Utc))
+        .unwrap_or_default();
+    ObjectMeta {
+        location,
+        last_modified,
+        size: metadata.content_length.unwrap_or_default(),
+        e_tag: None,
+        version: None,
+    }
+}
+
+fn content_type_attributes(content_type: Option<String>) -> Attributes {
+    let mut attributes = Attributes::new();
+    if let Some(content_type) = content_type {
+        attributes.insert(Attribute::ContentType, content_type.into());
+    }
+    attributes
+}
+
+fn not_found(name: &str) -> object_store::Error {
+    object_store::Error::NotFound {
+        path: name.to_owned(),
+        source: "The file doesn't exist".into(),
+    }
+}
+
+fn store_error(name: &str, error: Error) -> object_store::Error {
+    if error.is_not_found() {
+        return object_store::Error::NotFound {
+            path: name.to_owned(),
+            source: Box::new(error),
+        };
+    }
+    object_store::Error::Generic {
+        store: STORE,
+        source: Box::new(error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::FakeDrive;
+    use object_store::integration;
+    use object_store::WriteMultipart;
+
+    fn store(fake: &FakeDrive) -> DriveObjectStore {
+        DriveObjectStore::new(fake.drive())
+    }
+
+    #[tokio::test]
+    async fn conformance() {
+        let fake = FakeDrive::new();
+        let store = store(&fake);
+
+        integration::list_uses_directories_correctly(&store).await;
+        integration::list_with_delimiter(&store).await;
+        integration::stream_get(&store).await;
+        integration::multipart_out_of_order(&store).await;
+        let error = integration::get_nonexistent_object(&store, None)
+            .await
+            .unwrap_err();
+        assert!(matches!(error, object_store::Error::NotFound { .. }));
+        assert_eq!(fake.pending_uploads(), 0);
+    }
+
+    #[tokio::test]
+    async fn get_ranges_with_size_of_file() {
+        let fake = FakeDrive::new();
+        let store = store(&fake);
+        let location = Path::from("test_dir/test_file.json");
+        let data = Bytes::from("arbitrary data");
+        store.put(&location, data.clone().into()).await.unwrap();
+
+        assert_eq!(store.get_range(&location, 3..7).await.unwrap(), "itra");
+        let cases = [
+            (GetRange::Bounded(2..5), 2..5, "bit"),
+            (GetRange::Bounded(2..100), 2..14, "bitrary data"),
+            (GetRange::Offset(3), 3..14, "itrary data"),
+            (GetRange::Suffix(2), 12..14, "ta"),
+            (GetRange::Suffix(100), 0..14, "arbitrary data"),
+        ];
+        for (range, expected_range, expected) in cases {
+            let options = GetOptions {
+                range: Some(range),
+                ..Default::default()
+            };
+            let result = store.get_opts(&location, options).await.unwrap();
+            assert_eq!(result.meta.size, 14);
+            assert_eq!(result.range, expected_range);
+            assert_eq!(result.bytes().await.unwrap(), expected);
+        }
+        store.get_range(&location, 200..300).await.unwrap_err();
+
+        let ranges = store.get_ranges(&location, &[0..1, 2..3, 0..5]).await;
+        assert_eq!(ranges.unwrap(), ["a", "b", "arbit"]);
+        assert_eq!(store.head(&location).await.unwrap().size, 14);
+    }
+
+    #[tokio::test]
+    async fn put_modes_and_empty_files() {
+        let fake = FakeDrive::new();
+        let store = store(&fake);
+        let location = Path::from("tables/a.csv");
+        let create = PutOptions {
+            mode: PutMode::Create,
+            attributes: Attributes::from_iter([(Attribute::ContentType, "text/csv")]),
+            ..Default::default()
+        };
+
+        store
+            .put_opts(&location, "a,b".into(), create.clone())
+            .await
+            .unwrap();
+        let error = store.put_opts(&location, "c,d".into(), create).await;
+        assert!(matches!(
+            error,
+            Err(object_store::Error::AlreadyExists { .. })
+        ));
+        assert_eq!(fake.files()["tables/a.csv"], b"a,b");
+        assert_eq!(fake.content_type("tables/a.csv").unwrap(), "text/csv");
+        let result = store.get(&location).await.unwrap();
+        assert_eq!(
+            result
+                .attributes
+                .get(&Attribute::ContentType)
+                .unwrap()
+                .as_ref(),
+            "text/csv"
+        );
+
+        // A multipart upload without parts puts an empty file.
+        let empty = Path::from("tables/empty.csv");
+        WriteMultipart::new(store.put_multipart(&empty).await.unwrap())
+            .finish()
+            .await
+            .unwrap();
+        assert_eq!(store.head(&empty).await.unwrap().size, 0);
+        assert_eq!(fake.pending_uploads(), 0);
+    }
+
+    #[tokio::test]
+    async fn unsupported_operations() {
+        let fake = FakeDrive::new();
+        let store = store(&fake);
+        let (from, to) = (Path::from("a"), Path::from("b"));
+        store.put(&from, "data".into()).await.unwrap();
+
+        for result in [
+            store.copy(&from, &to).await,
+            store.rename(&from, &to).await,
+            store.copy_if_not_exists(&from, &to).await,
+            store.rename_if_not_exists(&from, &to).await,
+        ] {
+            assert!(matches!(result, Err(object_store::Error::NotImplemented)));
+        }
+        assert_eq!(fake.files().len(), 1);
+        assert_eq!(store.to_string(), "DetaDrive(fake_drive)");
+    }
+}
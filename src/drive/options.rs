@@ -0,0 +1,237 @@
+//! Options customizing the behavior of drive operations.
+
+use crate::constants;
+use crate::error::{Error, Result};
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+/// Options for [`Drive::put_file_with`](super::Drive::put_file_with).
+///
+/// ```
+/// use deta_rust::drive::PutFileOptions;
+///
+/// let options = PutFileOptions {
+///     content_type: Some("image/png".into()),
+///     chunk_size: 1024 * 1024 * 5,
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone)]
+pub struct PutFileOptions {
+    /// Content type of the file. Deta ignores it for chunked uploads.
+    pub content_type: Option<String>,
+    /// Size of the chunks used for chunked uploading.
+    /// It must be between 5MB and 10MB (the limits set by deta). Defaults to 10MB.
+    pub chunk_size: usize,
+    /// Files greater than this size are uploaded in chunks.
+    /// It must not exceed 10MB, which is the maximum size of a single request payload. Defaults to 10MB.
+    pub chunked_threshold: usize,
+    /// Maximum number of chunks uploaded at the same time. Defaults to 1.
+    pub concurrency: usize,
+    /// Uses chunked uploading regardless of the file size (a small file is sent as a single part).
+    /// Empty files are still uploaded with a single put. Defaults to `false`.
+    pub force_chunked: bool,
+    /// After the upload, compares the size of the stored file with the number of uploaded bytes,
+    /// using the file metadata (see [`Drive::head_file`](super::Drive::head_file)).
+    /// A mismatch is reported as a [`Kind::VerificationFailed`](crate::error::Kind::VerificationFailed) error.
+    /// Defaults to `false`.
+    pub verify: bool,
+    /// Allows the verification to download the whole file and count its bytes,
+    /// when the metadata doesn't contain the size or the HEAD request isn't allowed. Any other failure
+    /// of the HEAD request is returned as it is. Has no effect without [`verify`](PutFileOptions::verify).
+    /// Defaults to `false`.
+    pub deep_verify: bool,
+    /// Maximum time of each request of the upload (including initializing and ending a chunked upload).
+    /// Defaults to `None`, which means no limit.
+    pub part_timeout: Option<Duration>,
+    /// Maximum time of the whole upload, counted from the moment it starts,
+    /// including the time spent waiting for the content of a stream or a reader.
+    /// Defaults to `None`, which means no limit.
+    ///
+    /// When any of the limits is exceeded, the chunked upload is aborted
+    /// and the returned error states which limit was hit and how many parts were completed.
+    pub deadline: Option<Duration>,
+    /// Number of times a part of a chunked upload is sent again after a failure that is [`retryable`](crate::error::Error::is_retryable).
+    /// Defaults to 0.
+    pub part_retries: usize,
+    /// Token allowing to cancel the upload. Cancellation is checked before each request
+    /// of a chunked upload, and the cancelled upload is aborted. The returned error
+    /// (see [`Error::is_cancelled`](crate::error::Error::is_cancelled)) contains the number of uploaded parts.
+    /// Defaults to `None`.
+    pub cancellation: Option<CancellationToken>,
+    /// Retries a single put rejected with the 413 status (the actual limit of the server may be lower than 10MB,
+    /// e.g. behind a proxy) as a chunked upload of the same data, using the minimum chunk size of 5MB.
+    /// Set to `false` to get the [`PayloadTooLarge`](crate::error::ResponseStatusKind::PayloadTooLarge) error instead.
+    /// Defaults to `true`.
+    pub chunked_fallback: bool,
+    /// Compresses the content before uploading (see [`Compression`](super::Compression)).
+    /// Defaults to [`Compression::None`](super::Compression::None).
+    ///
+    /// Available with the `compression` feature.
+    #[cfg(feature = "compression")]
+    pub compression: super::Compression,
+}
+
+impl Default for PutFileOptions {
+    fn default() -> Self {
+        Self {
+            content_type: None,
+            chunk_size: constants::MAX_DATA_CHUNK_SIZE,
+            chunked_threshold: constants::MAX_DATA_CHUNK_SIZE,
+            concurrency: 1,
+            force_chunked: false,
+            verify: false,
+            deep_verify: false,
+            part_timeout: None,
+            deadline: None,
+            part_retries: 0,
+            cancellation: None,
+            chunked_fallback: true,
+            #[cfg(feature = "compression")]
+            compression: super::Compression::None,
+        }
+    }
+}
+
+impl PutFileOptions {
+    pub(crate) fn validate(&self) -> Result<()> {
+        if self.chunk_size < constants::MIN_DATA_CHUNK_SIZE
+            || self.chunk_size > constants::MAX_DATA_CHUNK_SIZE
+        {
+            return Err(Error::from_invalid_argument(format!(
+                "Chunk size must be between {} and {} bytes, got {}",
+                constants::MIN_DATA_CHUNK_SIZE,
+                constants::MAX_DATA_CHUNK_SIZE,
+                self.chunk_size
+            )));
+        }
+
+        if self.chunked_threshold > constants::MAX_DATA_CHUNK_SIZE {
+            return Err(Error::from_invalid_argument(format!(
+                "Chunked upload threshold must not exceed {} bytes, got {}",
+                constants::MAX_DATA_CHUNK_SIZE,
+                self.chunked_threshold
+            )));
+        }
+
+        if self.concurrency == 0 {
+            return Err(Error::from_invalid_argument(
+                "Concurrency must be greater than 0".into(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Options for [`Drive::get_file_as_buffer_with`](super::Drive::get_file_as_buffer_with)
+/// and [`Drive::get_file_as_stream_with`](super::Drive::get_file_as_stream_with).
+///
+/// Available with the `compression` feature.
+#[cfg(feature = "compression")]
+#[derive(Debug, Clone, Default)]
+pub struct GetFileOptions {
+    /// Decompresses files stored with [`Compression::Gzip`](super::Compression::Gzip),
+    /// recognized by the `.gz` suffix of the name. Other files are returned unchanged.
+    /// Defaults to `false`.
+    pub decompress: bool,
+}
+
+/// Options for [`Drive::append_to_file_with`](super::Drive::append_to_file_with).
+#[derive(Debug, Clone, Default)]
+pub struct AppendOptions {
+    /// Maximum size of the file after appending. If it would be exceeded,
+    /// an error is returned and the file is left unchanged. Defaults to `None`, which means no limit.
+    pub max_size: Option<u64>,
+}
+
+/// Options for [`Drive::put_from_url`](super::Drive::put_from_url).
+#[derive(Debug, Clone, Default)]
+pub struct PutFromUrlOptions {
+    /// Maximum size of the source. The upload is aborted once the source turns out to be greater.
+    /// Defaults to `None`, which means no limit.
+    pub max_bytes: Option<u64>,
+    /// Options used to upload the file. If the content type is not set,
+    /// the one sent by the source is used.
+    pub put_options: PutFileOptions,
+}
+
+/// Options for [`Drive::download_to_writer_with`](super::Drive::download_to_writer_with)
+/// and [`Drive::download_to_file_with`](super::Drive::download_to_file_with).
+#[derive(Debug, Clone)]
+pub struct DownloadOptions {
+    /// Maximum number of times the download is resumed after the connection is interrupted.
+    /// The download continues from the first missing byte, using the `Range` header.
+    /// Defaults to 3.
+    pub resume_attempts: usize,
+}
+
+impl Default for DownloadOptions {
+    fn default() -> Self {
+        Self { resume_attempts: 3 }
+    }
+}
+
+/// Options for [`Drive::download_prefix_to_dir_with`](super::Drive::download_prefix_to_dir_with).
+#[derive(Debug, Clone, Default)]
+pub struct DownloadDirOptions {
+    /// Skips files for which a local file of the same size already exists.
+    /// The size is compared with the `Content-Length` header of a HEAD request, and the content
+    /// is only downloaded if the sizes differ.
+    /// Defaults to `false`.
+    pub skip_same_size: bool,
+}
+
+/// Options for [`Drive::upload_dir`](super::Drive::upload_dir).
+#[derive(Debug, Clone, Default)]
+pub struct UploadDirOptions {
+    /// Glob patterns of the ignored files and directories.
+    /// Patterns containing `/` are matched against the path relative to the uploaded directory,
+    /// other patterns are matched against every single component of the path (e.g. `*.tmp` or `.git`).
+    /// Supported wildcards: `*` (any characters except `/`), `**` (any characters) and `?` (single character).
+    pub ignore: Vec<String>,
+    /// Follows symbolic links instead of skipping them. Defaults to `false`.
+    pub follow_symlinks: bool,
+    /// Options used to upload each file. If the content type is not set,
+    /// it's guessed from the file extension.
+    pub put_options: PutFileOptions,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MB: usize = 1024 * 1024;
+
+    #[test]
+    fn validate_for_default_options() {
+        assert!(PutFileOptions::default().validate().is_ok());
+    }
+
+    #[test]
+    fn validate_for_invalid_options() {
+        let cases = [
+            PutFileOptions {
+                chunk_size: 4 * MB,
+                ..Default::default()
+            },
+            PutFileOptions {
+                chunk_size: 11 * MB,
+                ..Default::default()
+            },
+            PutFileOptions {
+                chunked_threshold: 10 * MB + 1,
+                ..Default::default()
+            },
+            PutFileOptions {
+                concurrency: 0,
+                ..Default::default()
+            },
+        ];
+
+        for options in cases {
+            let error = options.validate().unwrap_err();
+            assert!(error.is_invalid_argument(), "{:?}", options);
+        }
+    }
+}
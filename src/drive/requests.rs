@@ -1,17 +1,20 @@
 use crate::error::Result;
-use crate::utils::send_request;
+use crate::transport::Transport;
+use crate::utils::send_request_via;
 use serde_json::json;
 
 pub async fn put_file_request(
+    transport: &dyn Transport,
     base_url: &str,
     x_api_key: &str,
     file_name: &str,
-    data: Vec<u8>,
+    data: bytes::Bytes,
     content_type: Option<&str>,
 ) -> Result<reqwest::Response> {
     let mut request = reqwest::Client::new()
         .post(format!("{}/files", base_url))
         .query(&[("name", file_name)])
+        .header("Content-Length", data.len())
         .body(data)
         .header("X-Api-Key", x_api_key);
 
@@ -19,23 +22,55 @@ pub async fn put_file_request(
         request = request.header("Content-Type", content_type);
     }
 
-    send_request(request).await
+    send_request_via(transport, request).await
 }
 
 pub async fn get_file_request(
+    transport: &dyn Transport,
+    base_url: &str,
+    x_api_key: &str,
+    file_name: &str,
+) -> Result<reqwest::Response> {
+    let request = reqwest::Client::new()
+        .get(format!("{}/files/download", base_url))
+        .query(&[("name", file_name)])
+        .header("X-Api-Key", x_api_key);
+
+    send_request_via(transport, request).await
+}
+
+pub async fn get_file_range_request(
+    transport: &dyn Transport,
     base_url: &str,
     x_api_key: &str,
     file_name: &str,
+    range: &str,
 ) -> Result<reqwest::Response> {
     let request = reqwest::Client::new()
         .get(format!("{}/files/download", base_url))
         .query(&[("name", file_name)])
+        .header("X-Api-Key", x_api_key)
+        .header(reqwest::header::RANGE, range);
+
+    send_request_via(transport, request).await
+}
+
+pub async fn head_file_request(
+    transport: &dyn Transport,
+    base_url: &str,
+    x_api_key: &str,
+    file_name: &str,
+) -> Result<reqwest::Response> {
+    let request = reqwest::Client::new()
+        .head(format!("{}/files/download", base_url))
+        .query(&[("name", file_name)])
         .header("X-Api-Key", x_api_key);
 
-    send_request(request).await
+    send_request_via(transport, request).await
 }
 
 pub async fn list_files_request(
+    transport: &dyn Transport,
     base_url: &str,
     x_api_key: &str,
     limit: Option<u32>,
@@ -59,10 +94,11 @@ pub async fn list_files_request(
 
     request = request.query(&query_params);
 
-    send_request(request).await
+    send_request_via(transport, request).await
 }
 
 pub async fn delete_files_request(
+    transport: &dyn Transport,
     base_url: &str,
     x_api_key: &str,
     names: &[String],
@@ -71,10 +107,11 @@ pub async fn delete_files_request(
         .delete(format!("{}/files", base_url))
         .header("X-Api-Key", x_api_key)
         .json(&json!({ "names": names }));
-    send_request(request).await
+    send_request_via(transport, request).await
 }
 
 pub async fn initialize_chunked_upload_request(
+    transport: &dyn Transport,
     base_url: &str,
     x_api_key: &str,
     name: &str,
@@ -84,10 +121,11 @@ pub async fn initialize_chunked_upload_request(
         .query(&[("name", name)])
         .header("X-Api-Key", x_api_key);
 
-    send_request(request).await
+    send_request_via(transport, request).await
 }
 
 pub async fn upload_chunk_request(
+    transport: &dyn Transport,
     base_url: &str,
     x_api_key: &str,
     name: &str,
@@ -100,10 +138,11 @@ pub async fn upload_chunk_request(
         .query(&[("name", name), ("part", &part.to_string())])
         .header("X-Api-Key", x_api_key)
         .body(data);
-    send_request(request).await
+    send_request_via(transport, request).await
 }
 
 pub async fn abort_chunked_upload_request(
+    transport: &dyn Transport,
     base_url: &str,
     x_api_key: &str,
     name: &str,
@@ -114,10 +153,11 @@ pub async fn abort_chunked_upload_request(
         .query(&[("name", name)])
         .header("X-Api-Key", x_api_key);
 
-    send_request(request).await
+    send_request_via(transport, request).await
 }
 
 pub async fn end_chunked_upload_request(
+    transport: &dyn Transport,
     base_url: &str,
     x_api_key: &str,
     name: &str,
@@ -127,5 +167,5 @@ pub async fn end_chunked_upload_request(
         .patch(format!("{}/uploads/{}", base_url, upload_id))
         .query(&[("name", name)])
         .header("X-Api-Key", x_api_key);
-    send_request(request).await
+    send_request_via(transport, request).await
 }
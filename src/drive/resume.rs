@@ -0,0 +1,299 @@
+use super::{requests, DownloadOptions, Drive};
+use crate::error::{Error, Result};
+use futures::{Stream, StreamExt};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+impl Drive {
+    // Writes the downloaded file into the `writer`. When receiving the content fails, the download is resumed
+    // from the first missing byte with a `Range` request. If the server ignores the range,
+    // the content is received from the beginning again and the already written bytes are skipped.
+    // Returns the number of written bytes.
+    pub(super) async fn write_resumable<W>(
+        &self,
+        name: &str,
+        response: reqwest::Response,
+        writer: &mut W,
+        options: &DownloadOptions,
+    ) -> Result<u64>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let expected = response.content_length();
+
+        let mut written = 0;
+        let mut resumes = 0;
+        let mut next: Result<(reqwest::Response, u64)> = Ok((response, 0));
+        loop {
+            let error = match next {
+                Ok((response, skip)) => {
+                    let stream = self.body_stream(response);
+                    match write_body(stream, writer, &mut written, skip).await? {
+                        Ok(()) => break,
+                        Err(error) => error,
+                    }
+                }
+                Err(error) if error.get_kind().is_connection() || error.is_timeout() => error,
+                Err(error) => return Err(error),
+            };
+
+            if resumes == options.resume_attempts {
+                return Err(error);
+            }
+            resumes += 1;
+            next = self.resume_response(name, written).await;
+        }
+
+        writer.flush().await?;
+        match expected {
+            Some(expected) if expected != written => Err(Error::from_other(
+                format!(
+                    "Downloaded {} bytes of '{}', but its size is {} bytes",
+                    written, name, expected
+                ),
+                None,
+            )),
+            _ => Ok(written),
+        }
+    }
+
+    // Requests the content starting at `offset`. Returns the response along with the number of its leading bytes
+    // that have already been written, which is the whole `offset` if the server sends the complete content.
+    async fn resume_response(&self, name: &str, offset: u64) -> Result<(reqwest::Response, u64)> {
+        let response = requests::get_file_range_request(
+            self.transport.as_ref(),
+            &self.base_url,
+            &self.x_api_key,
+            name,
+            &format!("bytes={}-", offset),
+        )
+        .await?;
+        if response.status() != reqwest::StatusCode::PARTIAL_CONTENT {
+            return Ok((response, offset));
+        }
+
+        match range_start(response.headers()) {
+            Some(start) if start <= offset => Ok((response, offset - start)),
+            _ => Err(Error::from_other(
+                format!(
+                    "Cannot resume download of '{}' at byte {}, the server sent an unexpected range",
+                    name, offset
+                ),
+                None,
+            )),
+        }
+    }
+}
+
+// Writes the response body into the `writer`, skipping its first `skip` bytes.
+// A failure of writing is returned as the outer error, a failure of receiving the body as the inner one,
+// so that only the latter can be resumed.
+async fn write_body<S, W>(
+    stream: S,
+    writer: &mut W,
+    written: &mut u64,
+    mut skip: u64,
+) -> Result<Result<()>>
+where
+    S: Stream<Item = Result<bytes::Bytes>>,
+    W: AsyncWrite + Unpin,
+{
+    futures::pin_mut!(stream);
+    while let Some(chunk) = stream.next().await {
+        let mut chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(error) => return Ok(Err(error)),
+        };
+        if skip > 0 {
+            let skipped = skip.min(chunk.len() as u64);
+            chunk = chunk.slice(skipped as usize..);
+            skip -= skipped;
+        }
+        writer.write_all(&chunk).await?;
+        *written += chunk.len() as u64;
+    }
+    Ok(Ok(()))
+}
+
+// Reads the first byte position from the `Content-Range` header, e.g. `bytes 100-999/1000`.
+fn range_start(headers: &reqwest::header::HeaderMap) -> Option<u64> {
+    headers
+        .get(reqwest::header::CONTENT_RANGE)?
+        .to_str()
+        .ok()?
+        .strip_prefix("bytes ")?
+        .split('-')
+        .next()?
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+
+    const CONTENT: &[u8] = b"0123456789";
+
+    // Serves the raw `responses` to the subsequent connections, closing each connection after the response,
+    // which cuts the body short if it's shorter than declared. Returns the base url and the received request heads.
+    async fn serve(responses: Vec<Vec<u8>>) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let requests = Arc::new(Mutex::new(vec![]));
+        let received = requests.clone();
+
+        tokio::spawn(async move {
+            for response in responses {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut head = vec![];
+                while !head.ends_with(b"\r\n\r\n") {
+                    let mut byte = [0];
+                    if socket.read(&mut byte).await.unwrap() == 0 {
+                        break;
+                    }
+                    head.push(byte[0]);
+                }
+                received
+                    .lock()
+                    .unwrap()
+                    .push(String::from_utf8(head).unwrap().to_lowercase());
+                socket.write_all(&response).await.unwrap();
+                socket.shutdown().await.unwrap();
+            }
+        });
+
+        (format!("http://{}", address), requests)
+    }
+
+    fn response(status: &str, headers: &str, body: &[u8]) -> Vec<u8> {
+        let mut response = format!(
+            "HTTP/1.1 {}\r\nconnection: close\r\n{}\r\n",
+            status, headers
+        )
+        .into_bytes();
+        response.extend_from_slice(body);
+        response
+    }
+
+    fn cut_response() -> Vec<u8> {
+        response("200 OK", "content-length: 10\r\n", &CONTENT[..4])
+    }
+
+    async fn download(base_url: &str, resume_attempts: usize) -> Result<Option<u64>> {
+        let drive = Drive::with_base_url(base_url);
+        let options = DownloadOptions { resume_attempts };
+        let mut data = vec![];
+        let written = drive
+            .download_to_writer_with("file.dat", &mut data, options)
+            .await?;
+        assert_eq!(data, CONTENT);
+        Ok(written)
+    }
+
+    #[tokio::test]
+    async fn download_resumed_with_range() {
+        let (base_url, requests) = serve(vec![
+            cut_response(),
+            response(
+                "206 Partial Content",
+                "content-length: 6\r\ncontent-range: bytes 4-9/10\r\n",
+                &CONTENT[4..],
+            ),
+        ])
+        .await;
+
+        assert_eq!(download(&base_url, 3).await.unwrap(), Some(10));
+        let requests = requests.lock().unwrap();
+        assert!(!requests[0].contains("range:"));
+        assert!(requests[1].contains("range: bytes=4-"));
+    }
+
+    #[tokio::test]
+    async fn download_restarted_when_range_is_ignored() {
+        let (base_url, _) = serve(vec![
+            cut_response(),
+            response("200 OK", "content-length: 10\r\n", CONTENT),
+        ])
+        .await;
+
+        assert_eq!(download(&base_url, 3).await.unwrap(), Some(10));
+    }
+
+    #[tokio::test]
+    async fn download_resumed_many_times() {
+        let (base_url, requests) = serve(vec![
+            cut_response(),
+            response(
+                "206 Partial Content",
+                "content-length: 6\r\ncontent-range: bytes 4-9/10\r\n",
+                &CONTENT[4..7],
+            ),
+            response(
+                "206 Partial Content",
+                "content-length: 3\r\ncontent-range: bytes 7-9/10\r\n",
+                &CONTENT[7..],
+            ),
+        ])
+        .await;
+
+        assert_eq!(download(&base_url, 2).await.unwrap(), Some(10));
+        assert!(requests.lock().unwrap()[2].contains("range: bytes=7-"));
+    }
+
+    #[tokio::test]
+    async fn download_fails_after_resume_attempts() {
+        let (base_url, _) = serve(vec![cut_response()]).await;
+
+        let drive = Drive::with_base_url(&base_url);
+        let options = DownloadOptions { resume_attempts: 0 };
+        let mut data = vec![];
+        let result = drive
+            .download_to_writer_with("file.dat", &mut data, options)
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(data, &CONTENT[..4]);
+    }
+
+    #[tokio::test]
+    async fn download_fails_for_unexpected_range() {
+        let (base_url, _) = serve(vec![
+            cut_response(),
+            response(
+                "206 Partial Content",
+                "content-length: 4\r\ncontent-range: bytes 6-9/10\r\n",
+                &CONTENT[6..],
+            ),
+        ])
+        .await;
+
+        let drive = Drive::with_base_url(&base_url);
+        let mut data = vec![];
+        let error = drive
+            .download_to_writer("file.dat", &mut data)
+            .await
+            .unwrap_err();
+
+        assert!(error.to_string().contains("unexpected range"));
+    }
+
+    #[test]
+    fn range_start_for_headers() {
+        let mut headers = reqwest::header::HeaderMap::new();
+        assert_eq!(range_start(&headers), None);
+
+        headers.insert(
+            reqwest::header::CONTENT_RANGE,
+            "bytes 100-999/1000".parse().unwrap(),
+        );
+        assert_eq!(range_start(&headers), Some(100));
+
+        headers.insert(
+            reqwest::header::CONTENT_RANGE,
+            "bytes */1000".parse().unwrap(),
+        );
+        assert_eq!(range_start(&headers), None);
+    }
+}
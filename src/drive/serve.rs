@@ -0,0 +1,184 @@
+// The parts of serving the files shared by the web framework integrations: the headers passed on to the client
+// and the status of the response for an error. The frameworks use the types of the `http` crate, as reqwest does.
+
+use crate::error::{Error, Result};
+use reqwest::header::{self, HeaderMap, HeaderName, HeaderValue};
+use reqwest::StatusCode;
+
+// The headers of the download response passed on to the client.
+const FORWARDED_HEADERS: [HeaderName; 4] = [
+    header::CONTENT_TYPE,
+    header::CONTENT_LENGTH,
+    header::LAST_MODIFIED,
+    header::CONTENT_RANGE,
+];
+
+// Returns the value of the `Range` header of the request, to be passed on to deta.
+pub(super) fn range(value: Option<&HeaderValue>) -> Result<Option<&str>> {
+    value
+        .map(|value| {
+            value.to_str().map_err(|_| {
+                Error::from_invalid_argument("The Range header isn't valid text".to_owned())
+            })
+        })
+        .transpose()
+}
+
+// Returns the headers of the download response to send to the client,
+// with the generic content type if deta didn't send any.
+pub(super) fn file_headers(headers: &HeaderMap) -> Vec<(HeaderName, HeaderValue)> {
+    let mut forwarded: Vec<_> = FORWARDED_HEADERS
+        .into_iter()
+        .filter_map(|name| {
+            let value = headers.get(&name)?.clone();
+            Some((name, value))
+        })
+        .collect();
+    if !headers.contains_key(header::CONTENT_TYPE) {
+        forwarded.push((
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/octet-stream"),
+        ));
+    }
+    forwarded
+}
+
+// Returns the `Content-Disposition` header making browsers save the file under the last segment of its `name`.
+// A name outside of printable ASCII is sent in the `filename*` parameter (RFC 6266),
+// with a fallback for older clients in which such characters are replaced with `_`.
+// Only the actix integration offers attachments.
+#[cfg(feature = "actix")]
+pub(super) fn attachment(name: &str) -> (HeaderName, HeaderValue) {
+    let file_name = name.rsplit('/').next().unwrap_or(name);
+    let fallback: String = file_name
+        .chars()
+        .map(|c| {
+            if c == ' ' || (c.is_ascii_graphic() && c != '"' && c != '\\') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+
+    let mut value = format!("attachment; filename=\"{}\"", fallback);
+    if fallback != file_name {
+        value.push_str("; filename*=UTF-8''");
+        for byte in file_name.bytes() {
+            if byte.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&byte) {
+                value.push(byte as char);
+            } else {
+                value.push_str(&format!("%{:02X}", byte));
+            }
+        }
+    }
+    let value = HeaderValue::from_str(&value).expect("The value consists of printable ASCII");
+    (header::CONTENT_DISPOSITION, value)
+}
+
+// Returns the status of the response for the `error`. Any failure other than a bad request from the client
+// is reported as a failure of the upstream server.
+pub(super) fn error_status(error: &Error) -> StatusCode {
+    if error.is_invalid_argument() {
+        StatusCode::BAD_REQUEST
+    } else if error.is_not_found() {
+        StatusCode::NOT_FOUND
+    } else if error.status() == Some(StatusCode::RANGE_NOT_SATISFIABLE.as_u16()) {
+        StatusCode::RANGE_NOT_SATISFIABLE
+    } else {
+        StatusCode::BAD_GATEWAY
+    }
+}
+
+// Returns the body of a response without a file, the reason phrase of the `status`,
+// so that no details of the failure are revealed.
+pub(super) fn status_message(status: StatusCode) -> &'static str {
+    status.canonical_reason().unwrap_or_default()
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+    use crate::transport::{Transport, TransportFuture};
+    use std::sync::{Arc, Mutex};
+
+    pub const CONTENT: &[u8] = b"0123456789";
+
+    // Serves `avatar.png` and its `bytes=2-5` range, responds with 404 to `missing.png`
+    // and with 500 to any other file. Records the `Range` header of every request.
+    #[derive(Clone, Default)]
+    pub struct Storage {
+        pub ranges: Arc<Mutex<Vec<Option<String>>>>,
+    }
+
+    impl Transport for Storage {
+        fn execute(&self, request: reqwest::Request) -> TransportFuture<'_> {
+            let name = request.url().query_pairs().next().unwrap().1.into_owned();
+            let range = request
+                .headers()
+                .get(header::RANGE)
+                .map(|range| range.to_str().unwrap().to_owned());
+            self.ranges.lock().unwrap().push(range.clone());
+
+            let builder = http::Response::builder();
+            let response = match (name.as_str(), range.as_deref()) {
+                ("avatar.png", None) => builder
+                    .header(header::CONTENT_TYPE, "image/png")
+                    .header(header::CONTENT_LENGTH, CONTENT.len())
+                    .header(header::LAST_MODIFIED, "Wed, 01 May 2024 12:30:00 GMT")
+                    .body(CONTENT.to_vec()),
+                ("avatar.png", Some("bytes=2-5")) => builder
+                    .status(206)
+                    .header(header::CONTENT_TYPE, "image/png")
+                    .header(header::CONTENT_LENGTH, 4)
+                    .header(header::CONTENT_RANGE, "bytes 2-5/10")
+                    .body(CONTENT[2..6].to_vec()),
+                ("avatar.png", Some(_)) => builder.status(416).body(vec![]),
+                ("missing.png", _) => builder.status(404).body(b"{}".to_vec()),
+                _ => builder
+                    .status(500)
+                    .body(br#"{"errors":["Internal error at 10.0.0.1"]}"#.to_vec()),
+            };
+            let response = reqwest::Response::from(response.unwrap());
+            Box::pin(async move { Ok(response) })
+        }
+    }
+
+    #[test]
+    fn file_headers_with_default_content_type() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONTENT_LENGTH, HeaderValue::from_static("10"));
+        headers.insert(header::ETAG, HeaderValue::from_static("\"abc\""));
+
+        let forwarded = file_headers(&headers);
+        assert_eq!(
+            forwarded,
+            vec![
+                (header::CONTENT_LENGTH, HeaderValue::from_static("10")),
+                (
+                    header::CONTENT_TYPE,
+                    HeaderValue::from_static("application/octet-stream")
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "actix")]
+    fn attachment_for_names() {
+        let value = |name| attachment(name).1.to_str().unwrap().to_owned();
+
+        assert_eq!(
+            value("avatars/user 1.png"),
+            r#"attachment; filename="user 1.png""#
+        );
+        assert_eq!(
+            value(r#"say "hi".txt"#),
+            r#"attachment; filename="say _hi_.txt"; filename*=UTF-8''say%20%22hi%22.txt"#
+        );
+        assert_eq!(
+            value("zdjęcie.jpg"),
+            r#"attachment; filename="zdj_cie.jpg"; filename*=UTF-8''zdj%C4%99cie.jpg"#
+        );
+    }
+}
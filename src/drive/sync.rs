@@ -0,0 +1,555 @@
+//! Synchronization of drive files with local directories.
+
+use super::{write_stream, DownloadDirOptions, Drive, UploadDirOptions};
+use crate::error::{Error, Result};
+use crate::utils;
+use futures::StreamExt;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// Summary of the synchronization between a drive and a local directory.
+#[derive(Debug, Default)]
+pub struct SyncStats {
+    /// Names of the transferred files.
+    pub transferred: Vec<String>,
+    /// Names of the skipped files, e.g. already up to date or symbolic links.
+    pub skipped: Vec<String>,
+    /// Names of the files that could not be transferred, along with the cause.
+    pub failed: Vec<(String, Error)>,
+}
+
+enum SyncOutcome {
+    Transferred,
+    Skipped,
+}
+
+impl Drive {
+    /// Downloads all files whose names start with `prefix` into the `dir` directory.
+    /// The `prefix` is stripped from the names, and the rest is mapped to a path inside `dir`,
+    /// with `/` separating the subdirectories, e.g. `site/css/main.css` downloaded with
+    /// the `site/` prefix lands at `dir/css/main.css`. Missing directories are created.
+    ///
+    /// Names which would escape `dir` (containing `..` segments) are rejected.
+    /// A failed download doesn't interrupt the operation, it's reported in the
+    /// [`SyncStats::failed`](SyncStats::failed) field instead.
+    /// Files are downloaded concurrently, within the limit set by [`DetaClient::with_concurrency`](crate::DetaClient::with_concurrency).
+    pub async fn download_prefix_to_dir(
+        &self,
+        prefix: &str,
+        dir: impl AsRef<Path>,
+    ) -> Result<SyncStats> {
+        self.download_prefix_to_dir_with(prefix, dir, DownloadDirOptions::default())
+            .await
+    }
+
+    /// Works like [`Drive::download_prefix_to_dir`](Drive::download_prefix_to_dir),
+    /// but allows to customize the behavior with [`DownloadDirOptions`](DownloadDirOptions).
+    pub async fn download_prefix_to_dir_with(
+        &self,
+        prefix: &str,
+        dir: impl AsRef<Path>,
+        options: DownloadDirOptions,
+    ) -> Result<SyncStats> {
+        let dir = dir.as_ref();
+        let names = self.list_all_files(Some(prefix)).await?.names;
+
+        let mut outcomes = futures::stream::iter(names)
+            .map(|name| {
+                let options = &options;
+                async move {
+                    let _permit = self.limiter.acquire().await;
+                    let outcome = self.download_into_dir(&name, prefix, dir, options).await;
+                    (name, outcome)
+                }
+            })
+            .buffer_unordered(self.limiter.limit());
+
+        let mut stats = SyncStats::default();
+        while let Some((name, outcome)) = outcomes.next().await {
+            match outcome {
+                Ok(SyncOutcome::Transferred) => stats.transferred.push(name),
+                Ok(SyncOutcome::Skipped) => stats.skipped.push(name),
+                Err(error) => stats.failed.push((name, error)),
+            }
+        }
+
+        Ok(stats)
+    }
+
+    async fn download_into_dir(
+        &self,
+        name: &str,
+        prefix: &str,
+        dir: &Path,
+        options: &DownloadDirOptions,
+    ) -> Result<SyncOutcome> {
+        let path = local_path(dir, name, prefix)?;
+        let disappeared =
+            || Error::from_other(format!("File '{}' disappeared during download", name), None);
+
+        // The sizes are compared with a HEAD request, so the content is only requested when it's needed.
+        if options.skip_same_size {
+            if let Ok(local) = tokio::fs::metadata(&path).await {
+                let remote = self.head_file(name).await?.ok_or_else(disappeared)?;
+                if remote.content_length == Some(local.len()) {
+                    return Ok(SyncOutcome::Skipped);
+                }
+            }
+        }
+
+        let response = self
+            .get_file_response(name)
+            .await?
+            .ok_or_else(disappeared)?;
+
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let mut file = tokio::fs::File::create(&path).await?;
+        write_stream(self.body_stream(response), &mut file).await?;
+        Ok(SyncOutcome::Transferred)
+    }
+}
+
+impl Drive {
+    /// Uploads all files from the `dir` directory and its subdirectories.
+    /// Each file is stored under its path relative to `dir`, with `/` separators,
+    /// preceded by `prefix` (a `/` is added after a non-empty prefix if missing),
+    /// e.g. `dir/css/main.css` uploaded with the `site` prefix is stored as `site/css/main.css`.
+    ///
+    /// Content types are guessed from the file extensions, and files are streamed,
+    /// so big files are uploaded in chunks without being loaded whole into memory.
+    /// Empty directories are ignored, since drive has no concept of directories.
+    ///
+    /// A failed upload doesn't interrupt the operation, it's reported in the
+    /// [`SyncStats::failed`](SyncStats::failed) field instead.
+    /// Files are uploaded concurrently, within the limit set by [`DetaClient::with_concurrency`](crate::DetaClient::with_concurrency).
+    pub async fn upload_dir(
+        &self,
+        dir: impl AsRef<Path>,
+        prefix: &str,
+        options: UploadDirOptions,
+    ) -> Result<SyncStats> {
+        let mut stats = SyncStats::default();
+        let files = collect_files(dir.as_ref(), prefix, &options, &mut stats).await?;
+
+        let mut outcomes = futures::stream::iter(files)
+            .map(|(path, name)| {
+                let options = &options;
+                async move {
+                    let _permit = self.limiter.acquire().await;
+                    let outcome = self.upload_from_dir(&path, &name, options).await;
+                    (name, outcome)
+                }
+            })
+            .buffer_unordered(self.limiter.limit());
+
+        while let Some((name, outcome)) = outcomes.next().await {
+            match outcome {
+                Ok(()) => stats.transferred.push(name),
+                Err(error) => stats.failed.push((name, error)),
+            }
+        }
+
+        Ok(stats)
+    }
+
+    async fn upload_from_dir(
+        &self,
+        path: &Path,
+        name: &str,
+        options: &UploadDirOptions,
+    ) -> Result<()> {
+        let mut put_options = options.put_options.clone();
+        if put_options.content_type.is_none() {
+            put_options.content_type = mime_guess::from_path(path)
+                .first()
+                .map(|mime| mime.to_string());
+        }
+        let file = tokio::fs::File::open(path).await?;
+        self.put_file_from_reader(name, file, put_options).await?;
+        Ok(())
+    }
+}
+
+// Walks through the directory tree and returns the files to upload, along with their names in the drive.
+// Skipped symbolic links and entries that cannot be processed are recorded in the `stats`.
+async fn collect_files(
+    dir: &Path,
+    prefix: &str,
+    options: &UploadDirOptions,
+    stats: &mut SyncStats,
+) -> Result<Vec<(PathBuf, String)>> {
+    let mut files = vec![];
+    // Canonical paths of the visited directories, which prevent loops when following symbolic links.
+    let mut visited = HashSet::new();
+    visited.insert(tokio::fs::canonicalize(dir).await?);
+    let mut pending = vec![(dir.to_path_buf(), String::new())];
+
+    while let Some((dir_path, dir_relative_path)) = pending.pop() {
+        let mut entries = tokio::fs::read_dir(&dir_path).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            let relative_path = if dir_relative_path.is_empty() {
+                file_name.clone()
+            } else {
+                format!("{}/{}", dir_relative_path, file_name)
+            };
+
+            if is_ignored(&relative_path, &file_name, &options.ignore) {
+                continue;
+            }
+
+            let name = drive_name(prefix, &relative_path);
+            if entry.file_name().to_str().is_none() {
+                let error = Error::from_invalid_argument(format!(
+                    "Path '{}' is not valid UTF-8",
+                    relative_path
+                ));
+                stats.failed.push((name, error));
+                continue;
+            }
+
+            let entry_path = entry.path();
+            let mut file_type = entry.file_type().await?;
+            if file_type.is_symlink() {
+                if !options.follow_symlinks {
+                    stats.skipped.push(name);
+                    continue;
+                }
+                match tokio::fs::metadata(&entry_path).await {
+                    Ok(metadata) => file_type = metadata.file_type(),
+                    Err(error) => {
+                        stats.failed.push((name, error.into()));
+                        continue;
+                    }
+                }
+            }
+
+            if file_type.is_dir() {
+                if visited.insert(tokio::fs::canonicalize(&entry_path).await?) {
+                    pending.push((entry_path, relative_path));
+                }
+            } else if file_type.is_file() {
+                files.push((entry_path, name));
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+// Checks the ignore patterns. Patterns with `/` are matched against the whole relative path,
+// others against the last component only (parent directories are checked when entering them).
+fn is_ignored(relative_path: &str, file_name: &str, patterns: &[String]) -> bool {
+    patterns.iter().any(|pattern| {
+        if pattern.contains('/') {
+            utils::glob_match(pattern, relative_path)
+        } else {
+            utils::glob_match(pattern, file_name)
+        }
+    })
+}
+
+fn drive_name(prefix: &str, relative_path: &str) -> String {
+    if prefix.is_empty() || prefix.ends_with('/') {
+        format!("{}{}", prefix, relative_path)
+    } else {
+        format!("{}/{}", prefix, relative_path)
+    }
+}
+
+// Maps the file name, without the prefix, to a path inside the `dir`.
+fn local_path(dir: &Path, name: &str, prefix: &str) -> Result<PathBuf> {
+    let relative_name = name
+        .strip_prefix(prefix)
+        .unwrap_or(name)
+        .trim_start_matches('/');
+
+    let segments: Vec<&str> = relative_name
+        .split('/')
+        .filter(|segment| !segment.is_empty() && *segment != ".")
+        .collect();
+
+    if segments.is_empty() || segments.contains(&"..") {
+        return Err(Error::from_invalid_argument(format!(
+            "File name '{}' cannot be mapped to a path inside the directory",
+            name
+        )));
+    }
+
+    Ok(segments
+        .into_iter()
+        .fold(dir.to_path_buf(), |path, segment| path.join(segment)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[test]
+    fn local_path_for_valid_names() {
+        let dir = Path::new("public");
+        let cases = [
+            ("site/index.html", "site/", "public/index.html"),
+            ("site/css/main.css", "site/", "public/css/main.css"),
+            ("site/css/main.css", "site", "public/css/main.css"),
+            ("site//css/./main.css", "", "public/site/css/main.css"),
+        ];
+
+        for (name, prefix, expected) in cases {
+            assert_eq!(local_path(dir, name, prefix).unwrap(), Path::new(expected));
+        }
+    }
+
+    #[test]
+    fn local_path_for_invalid_names() {
+        let dir = Path::new("public");
+        let cases = [
+            ("site/../secret", "site/"),
+            ("site/a/../../secret", "site/"),
+            ("../secret", ""),
+            ("site/", "site/"),
+        ];
+
+        for (name, prefix) in cases {
+            let error = local_path(dir, name, prefix).unwrap_err();
+            assert!(error.is_invalid_argument(), "{}", name);
+        }
+    }
+
+    async fn mount_file(server: &MockServer, name: &str, content: &str) {
+        Mock::given(method("GET"))
+            .and(path("/files/download"))
+            .and(query_param("name", name))
+            .respond_with(ResponseTemplate::new(200).set_body_string(content))
+            .mount(server)
+            .await;
+        Mock::given(method("HEAD"))
+            .and(path("/files/download"))
+            .and(query_param("name", name))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("Content-Length", content.len().to_string().as_str()),
+            )
+            .mount(server)
+            .await;
+    }
+
+    #[test]
+    fn is_ignored_for_patterns() {
+        let patterns = vec!["*.tmp".to_owned(), "build/**".to_owned(), ".git".to_owned()];
+        assert!(is_ignored("notes.tmp", "notes.tmp", &patterns));
+        assert!(is_ignored("docs/notes.tmp", "notes.tmp", &patterns));
+        assert!(is_ignored("build/out/app.js", "app.js", &patterns));
+        assert!(is_ignored(".git", ".git", &patterns));
+        assert!(!is_ignored("src/build.rs", "build.rs", &patterns));
+        assert!(!is_ignored("notes.txt", "notes.txt", &patterns));
+    }
+
+    #[test]
+    fn drive_name_for_prefixes() {
+        assert_eq!(drive_name("", "a/b.txt"), "a/b.txt");
+        assert_eq!(drive_name("site", "a/b.txt"), "site/a/b.txt");
+        assert_eq!(drive_name("site/", "a/b.txt"), "site/a/b.txt");
+    }
+
+    #[tokio::test]
+    async fn upload_dir() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/files"))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!({
+                "name": "file",
+                "project_id": "project",
+                "drive_name": "drive"
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/uploads"))
+            .respond_with(
+                ResponseTemplate::new(201).set_body_json(json!({ "upload_id": "upload_id" })),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/uploads/upload_id/parts"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        Mock::given(method("PATCH"))
+            .and(path("/uploads/upload_id"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "name": "file",
+                "upload_id": "upload_id",
+                "project_id": "project",
+                "drive_name": "drive"
+            })))
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("css/fonts")).unwrap();
+        std::fs::create_dir_all(dir.path().join("empty")).unwrap();
+        std::fs::write(dir.path().join("index.html"), "<html></html>").unwrap();
+        std::fs::write(dir.path().join("draft.tmp"), "draft").unwrap();
+        std::fs::write(dir.path().join("css/main.css"), "body {}").unwrap();
+        std::fs::write(dir.path().join("css/fonts/font.woff"), vec![0; 2048]).unwrap();
+
+        let drive = Drive::with_base_url(&server.uri());
+        let options = UploadDirOptions {
+            ignore: vec!["*.tmp".into()],
+            put_options: crate::drive::PutFileOptions {
+                chunked_threshold: 1024,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut stats = drive.upload_dir(dir.path(), "site", options).await.unwrap();
+        stats.transferred.sort_unstable();
+
+        assert_eq!(
+            stats.transferred,
+            vec![
+                "site/css/fonts/font.woff",
+                "site/css/main.css",
+                "site/index.html"
+            ]
+        );
+        assert!(stats.failed.is_empty());
+
+        let requests = server.received_requests().await.unwrap();
+        let css_request = requests
+            .iter()
+            .find(|request| request.url.query() == Some("name=site%2Fcss%2Fmain.css"))
+            .unwrap();
+        assert_eq!(css_request.headers[&"content-type".into()], "text/css");
+        let chunked_uploads = requests
+            .iter()
+            .filter(|request| request.url.path() == "/uploads")
+            .count();
+        assert_eq!(chunked_uploads, 1);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn upload_dir_skips_symlinks() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/files"))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!({
+                "name": "file",
+                "project_id": "project",
+                "drive_name": "drive"
+            })))
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("file.txt"), "content").unwrap();
+        std::os::unix::fs::symlink(dir.path().join("file.txt"), dir.path().join("link.txt"))
+            .unwrap();
+
+        let drive = Drive::with_base_url(&server.uri());
+        let stats = drive
+            .upload_dir(dir.path(), "", UploadDirOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(stats.transferred, vec!["file.txt"]);
+        assert_eq!(stats.skipped, vec!["link.txt"]);
+
+        let options = UploadDirOptions {
+            follow_symlinks: true,
+            ..Default::default()
+        };
+        let mut stats = drive.upload_dir(dir.path(), "", options).await.unwrap();
+        stats.transferred.sort_unstable();
+        assert_eq!(stats.transferred, vec!["file.txt", "link.txt"]);
+    }
+
+    #[tokio::test]
+    async fn download_prefix_to_dir() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/files"))
+            .and(query_param("prefix", "site/"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "names": ["site/index.html", "site/css/main.css", "site/../escape.txt"]
+            })))
+            .mount(&server)
+            .await;
+        mount_file(&server, "site/index.html", "<html></html>").await;
+        mount_file(&server, "site/css/main.css", "body {}").await;
+        mount_file(&server, "site/../escape.txt", "escape").await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("public");
+        let drive = Drive::with_base_url(&server.uri());
+        let stats = drive
+            .download_prefix_to_dir("site/", &target)
+            .await
+            .unwrap();
+
+        assert_eq!(stats.transferred.len(), 2);
+        assert_eq!(stats.failed.len(), 1);
+        assert_eq!(stats.failed[0].0, "site/../escape.txt");
+        assert!(!dir.path().join("escape.txt").exists());
+
+        let index = std::fs::read_to_string(target.join("index.html")).unwrap();
+        assert_eq!(index, "<html></html>");
+        let css = std::fs::read_to_string(target.join("css").join("main.css")).unwrap();
+        assert_eq!(css, "body {}");
+    }
+
+    #[tokio::test]
+    async fn download_prefix_to_dir_skipping_same_size() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/files"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "names": ["site/same.txt", "site/changed.txt"]
+            })))
+            .mount(&server)
+            .await;
+        mount_file(&server, "site/same.txt", "1234").await;
+        mount_file(&server, "site/changed.txt", "123456").await;
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("same.txt"), "abcd").unwrap();
+        std::fs::write(dir.path().join("changed.txt"), "abcd").unwrap();
+
+        let drive = Drive::with_base_url(&server.uri());
+        let options = DownloadDirOptions {
+            skip_same_size: true,
+        };
+        let stats = drive
+            .download_prefix_to_dir_with("site/", dir.path(), options)
+            .await
+            .unwrap();
+
+        assert_eq!(stats.skipped, vec!["site/same.txt"]);
+        assert_eq!(stats.transferred, vec!["site/changed.txt"]);
+        let same = std::fs::read_to_string(dir.path().join("same.txt")).unwrap();
+        assert_eq!(same, "abcd");
+        let changed = std::fs::read_to_string(dir.path().join("changed.txt")).unwrap();
+        assert_eq!(changed, "123456");
+
+        // The content of the skipped file is never requested.
+        let downloads: Vec<_> = server
+            .received_requests()
+            .await
+            .unwrap()
+            .into_iter()
+            .filter(|request| {
+                request.method == wiremock::http::Method::Get
+                    && request.url.path() == "/files/download"
+            })
+            .filter_map(|request| request.url.query().map(str::to_owned))
+            .collect();
+        assert_eq!(downloads, vec!["name=site%2Fchanged.txt"]);
+    }
+}
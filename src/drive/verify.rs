@@ -0,0 +1,204 @@
+use super::{Drive, PutFileOptions};
+use crate::error::{Error, Result};
+use futures::StreamExt;
+
+impl Drive {
+    // Verifies the size of the uploaded file, if requested in the options.
+    pub(super) async fn verify_if_requested(
+        &self,
+        name: &str,
+        expected: u64,
+        options: &PutFileOptions,
+    ) -> Result<()> {
+        if !options.verify {
+            return Ok(());
+        }
+
+        let actual = match self.head_file(name).await {
+            Ok(Some(metadata)) if metadata.content_length.is_some() => metadata.content_length,
+            // Only missing metadata, or a HEAD request the server doesn't allow, falls back to the download.
+            // Other failures, e.g. an authorization or server error, are returned as they are.
+            Ok(_) if options.deep_verify => self.count_file_bytes(name).await?,
+            Err(error) if error.status() == Some(405) && options.deep_verify => {
+                self.count_file_bytes(name).await?
+            }
+            Ok(None) => Some(0),
+            Ok(Some(_)) => None,
+            Err(error) => return Err(error),
+        };
+
+        match actual {
+            Some(actual) if actual == expected => Ok(()),
+            Some(actual) => Err(Error::from_failed_verification(name, expected, actual)),
+            None => Err(Error::from_other(
+                format!(
+                    "Cannot verify the size of '{}', the metadata doesn't contain it",
+                    name
+                ),
+                None,
+            )),
+        }
+    }
+
+    // Downloads the whole file, counting its bytes. Returns zero if the file doesn't exist.
+    async fn count_file_bytes(&self, name: &str) -> Result<Option<u64>> {
+        let mut size = 0;
+        if let Some(stream) = self.get_file_as_stream(name).await? {
+            futures::pin_mut!(stream);
+            while let Some(chunk) = stream.next().await.transpose()? {
+                size += chunk.len() as u64;
+            }
+        }
+        Ok(Some(size))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    async fn mount_put(server: &MockServer) {
+        Mock::given(method("POST"))
+            .and(path("/files"))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!({
+                "name": "file.dat",
+                "project_id": "project",
+                "drive_name": "drive"
+            })))
+            .mount(server)
+            .await;
+    }
+
+    async fn mount_head(server: &MockServer, content_length: Option<usize>) {
+        let mut response = ResponseTemplate::new(200);
+        if let Some(content_length) = content_length {
+            response =
+                response.insert_header("Content-Length", content_length.to_string().as_str());
+        }
+        Mock::given(method("HEAD"))
+            .and(path("/files/download"))
+            .respond_with(response)
+            .mount(server)
+            .await;
+    }
+
+    fn verify_options(deep_verify: bool) -> PutFileOptions {
+        PutFileOptions {
+            verify: true,
+            deep_verify,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn verify_for_matching_size() {
+        let server = MockServer::start().await;
+        mount_put(&server).await;
+        mount_head(&server, Some(4)).await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        drive
+            .put_file_with("file.dat", vec![1, 2, 3, 4], verify_options(false))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn verify_for_mismatched_size() {
+        let server = MockServer::start().await;
+        mount_put(&server).await;
+        mount_head(&server, Some(3)).await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let error = drive
+            .put_file_with("file.dat", vec![1, 2, 3, 4], verify_options(false))
+            .await
+            .unwrap_err();
+
+        assert!(error.is_verification_failed());
+        assert!(matches!(
+            error.get_kind(),
+            crate::error::Kind::VerificationFailed {
+                expected: 4,
+                actual: 3,
+                ..
+            }
+        ));
+    }
+
+    #[tokio::test]
+    async fn verify_without_metadata() {
+        let server = MockServer::start().await;
+        mount_put(&server).await;
+        Mock::given(method("HEAD"))
+            .and(path("/files/download"))
+            .respond_with(ResponseTemplate::new(405))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/files/download"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(vec![1, 2, 3, 4]))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let error = drive
+            .put_file_with("file.dat", vec![1, 2, 3, 4], verify_options(false))
+            .await
+            .unwrap_err();
+        assert!(!error.is_verification_failed());
+
+        // The expensive download happens only when explicitly allowed.
+        drive
+            .put_file_with("file.dat", vec![1, 2, 3, 4], verify_options(true))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn deep_verify_for_failed_head() {
+        let server = MockServer::start().await;
+        mount_put(&server).await;
+        Mock::given(method("HEAD"))
+            .and(path("/files/download"))
+            .respond_with(ResponseTemplate::new(500))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/files/download"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(vec![1, 2, 3, 4]))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let error = drive
+            .put_file_with("file.dat", vec![1, 2, 3, 4], verify_options(true))
+            .await
+            .unwrap_err();
+        assert_eq!(error.status(), Some(500));
+    }
+
+    #[tokio::test]
+    async fn deep_verify_for_mismatched_size() {
+        let server = MockServer::start().await;
+        mount_put(&server).await;
+        mount_head(&server, None).await;
+        Mock::given(method("GET"))
+            .and(path("/files/download"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(vec![1, 2]))
+            .mount(&server)
+            .await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let error = drive
+            .put_file_from_reader("file.dat", &[1u8, 2, 3, 4][..], verify_options(true))
+            .await
+            .unwrap_err();
+        assert!(error.is_verification_failed());
+    }
+}
@@ -0,0 +1,178 @@
+use super::Drive;
+use crate::error::Result;
+use futures::Stream;
+use std::collections::{HashSet, VecDeque};
+use std::time::Duration;
+
+/// Change in the drive detected by [`Drive::watch_prefix`](Drive::watch_prefix).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DriveEvent {
+    /// A file with the given name has appeared.
+    Added(String),
+    /// A file with the given name has disappeared.
+    Removed(String),
+}
+
+// State of the polling stream.
+struct Watch<'a> {
+    drive: &'a Drive,
+    prefix: String,
+    interval: Duration,
+    started: bool,
+    snapshot: Option<HashSet<String>>,
+    pending: VecDeque<DriveEvent>,
+}
+
+impl Drive {
+    /// Watches the files matching the `prefix`, by listing them every `interval`
+    /// and comparing the names with the previous listing.
+    /// Files existing at the first listing are treated as the starting point and produce no events.
+    ///
+    /// A failed listing is yielded as an error item, and polling continues on the next tick.
+    /// Polling stops when the stream is dropped.
+    ///
+    /// **NOTE:** Only names are compared, so overwriting an existing file produces no event.
+    pub fn watch_prefix(
+        &self,
+        prefix: &str,
+        interval: Duration,
+    ) -> impl Stream<Item = Result<DriveEvent>> + '_ {
+        let watch = Watch {
+            drive: self,
+            prefix: prefix.to_owned(),
+            interval,
+            started: false,
+            snapshot: None,
+            pending: VecDeque::new(),
+        };
+
+        futures::stream::unfold(watch, |mut watch| async move {
+            loop {
+                if let Some(event) = watch.pending.pop_front() {
+                    return Some((Ok(event), watch));
+                }
+
+                if watch.started {
+                    tokio::time::sleep(watch.interval).await;
+                }
+                watch.started = true;
+
+                let names = match watch.drive.list_all_files(Some(&watch.prefix)).await {
+                    Ok(files) => files.names.into_iter().collect::<HashSet<_>>(),
+                    Err(error) => return Some((Err(error), watch)),
+                };
+
+                if let Some(previous) = &watch.snapshot {
+                    watch.pending.extend(diff_names(previous, &names));
+                }
+                watch.snapshot = Some(names);
+            }
+        })
+    }
+}
+
+// Returns the events turning the `previous` set of names into the `current` one.
+// Events are sorted by name, additions first, so that the output is deterministic.
+fn diff_names(previous: &HashSet<String>, current: &HashSet<String>) -> Vec<DriveEvent> {
+    let mut added: Vec<&String> = current.difference(previous).collect();
+    let mut removed: Vec<&String> = previous.difference(current).collect();
+    added.sort_unstable();
+    removed.sort_unstable();
+
+    added
+        .into_iter()
+        .map(|name| DriveEvent::Added(name.clone()))
+        .chain(
+            removed
+                .into_iter()
+                .map(|name| DriveEvent::Removed(name.clone())),
+        )
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+    use serde_json::json;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn names(names: &[&str]) -> HashSet<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn diff_names_for_cases() {
+        let cases = [
+            (names(&[]), names(&[]), vec![]),
+            (names(&["a"]), names(&["a"]), vec![]),
+            (
+                names(&[]),
+                names(&["b", "a"]),
+                vec![DriveEvent::Added("a".into()), DriveEvent::Added("b".into())],
+            ),
+            (
+                names(&["a", "b"]),
+                names(&[]),
+                vec![
+                    DriveEvent::Removed("a".into()),
+                    DriveEvent::Removed("b".into()),
+                ],
+            ),
+            (
+                names(&["a", "b"]),
+                names(&["b", "c"]),
+                vec![
+                    DriveEvent::Added("c".into()),
+                    DriveEvent::Removed("a".into()),
+                ],
+            ),
+        ];
+
+        for (previous, current, expected) in cases {
+            assert_eq!(diff_names(&previous, &current), expected);
+        }
+    }
+
+    // Mounts the listing response used once, after the previously mounted ones are used up.
+    async fn mount_listing(server: &MockServer, response: ResponseTemplate) {
+        Mock::given(method("GET"))
+            .and(path("/files"))
+            .and(query_param("prefix", "incoming/"))
+            .respond_with(response)
+            .up_to_n_times(1)
+            .mount(server)
+            .await;
+    }
+
+    fn listing(names: &[&str]) -> ResponseTemplate {
+        ResponseTemplate::new(200).set_body_json(json!({ "names": names }))
+    }
+
+    #[tokio::test]
+    async fn watch_prefix() {
+        let server = MockServer::start().await;
+        mount_listing(&server, listing(&["incoming/a"])).await;
+        mount_listing(&server, listing(&["incoming/a", "incoming/b"])).await;
+        mount_listing(&server, ResponseTemplate::new(500)).await;
+        mount_listing(&server, listing(&["incoming/b"])).await;
+
+        let drive = Drive::with_base_url(&server.uri());
+        let events: Vec<Result<DriveEvent>> = drive
+            .watch_prefix("incoming/", Duration::from_millis(10))
+            .take(3)
+            .collect()
+            .await;
+
+        assert_eq!(
+            events[0].as_ref().unwrap(),
+            &DriveEvent::Added("incoming/b".into())
+        );
+        assert!(events[1].is_err());
+        assert_eq!(
+            events[2].as_ref().unwrap(),
+            &DriveEvent::Removed("incoming/a".into())
+        );
+    }
+}
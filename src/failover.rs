@@ -0,0 +1,524 @@
+//! Failover across several deta projects, e.g. a primary project and a standby one.
+
+#[cfg(feature = "base")]
+use crate::database::Database;
+#[cfg(feature = "drive")]
+use crate::drive::Drive;
+use crate::error::{Error, Result};
+use crate::transport::{HttpTransport, Transport, TransportFuture};
+use crate::DetaClient;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Rules of switching between the projects of a [`MultiClient`](MultiClient).
+#[derive(Debug, Clone, Copy)]
+pub struct FailoverPolicy {
+    /// Number of consecutive failures after which the project is skipped. Defaults to 1.
+    pub failure_threshold: u32,
+    /// How long the project is skipped for, before it's tried first again. Defaults to 30 seconds.
+    pub cooldown: Duration,
+}
+
+impl Default for FailoverPolicy {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 1,
+            cooldown: Duration::from_secs(30),
+        }
+    }
+}
+
+/// The project which served a request, passed to the callback set with [`MultiClient::on_served`](MultiClient::on_served).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Served {
+    /// Position of the client of the project in the list given to [`MultiClient::new`](MultiClient::new).
+    pub index: usize,
+    /// Id of the project.
+    pub project_id: String,
+    /// Whether the request has been sent to other projects first, which failed.
+    pub failed_over: bool,
+}
+
+type ServedCallback = Arc<dyn Fn(&Served) + Send + Sync>;
+
+/// Hands out [`Database`](Database) and [`Drive`](Drive) handles sending each request to the first available project,
+/// in the order of the clients given to [`MultiClient::new`](MultiClient::new).
+///
+/// A request failing with a connection error, a timeout or a 5xx response is sent again to the next project.
+/// Other errors, e.g. a missing item or an invalid request, are returned without failover.
+/// A project failing [`failure_threshold`](FailoverPolicy::failure_threshold) times in a row is skipped
+/// for the [`cooldown`](FailoverPolicy::cooldown), so the requests don't alternate between the projects,
+/// and it's tried first again afterwards. If all projects are skipped, they are tried anyway, in order.
+///
+/// The projects aren't synchronized in any way: each request fails over on its own,
+/// so the data written during an outage is spread between them, and operations made of several requests
+/// (e.g. chunked uploads, or fetching pages with `last`) may fail when the project changes in the middle.
+/// Requests with streamed bodies, e.g. uploads from readers, can't be sent again and are never failed over.
+///
+/// ```no_run
+/// use deta_rust::{failover::{FailoverPolicy, MultiClient}, DetaClient};
+///
+/// # fn main() -> deta_rust::error::Result<()> {
+/// let clients = vec![DetaClient::new("primary_key"), DetaClient::new("standby_key")];
+/// let client = MultiClient::new(clients, FailoverPolicy::default())?.on_served(|served| {
+///     if served.failed_over {
+///         println!("Served by {}", served.project_id);
+///     }
+/// });
+/// # #[cfg(feature = "base")]
+/// let users = client.database("users")?;
+/// # #[cfg(feature = "drive")]
+/// let photos = client.drive("photos")?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct MultiClient {
+    clients: Vec<DetaClient>,
+    policy: FailoverPolicy,
+    inner: Arc<dyn Transport>,
+    on_served: Option<ServedCallback>,
+    health: Arc<Vec<Mutex<Health>>>,
+}
+
+#[derive(Debug, Default)]
+struct Health {
+    failures: u32,
+    skipped_until: Option<Instant>,
+}
+
+impl MultiClient {
+    /// Creates a client failing over between the projects of the `clients`, the first one being the primary.
+    /// Fails with an invalid argument error if no client is given.
+    pub fn new(clients: Vec<DetaClient>, policy: FailoverPolicy) -> Result<Self> {
+        if clients.is_empty() {
+            return Err(Error::from_invalid_argument(
+                "At least one client is required".to_owned(),
+            ));
+        }
+        let health = clients.iter().map(|_| Mutex::default()).collect();
+        Ok(Self {
+            clients,
+            policy,
+            inner: Arc::new(HttpTransport::default()),
+            on_served: None,
+            health: Arc::new(health),
+        })
+    }
+
+    /// Sends the requests through the `transport` instead of the network, see [`Transport`](Transport).
+    /// Affects the handles created afterwards.
+    pub fn with_transport(mut self, transport: Arc<dyn Transport>) -> Self {
+        self.inner = transport;
+        self
+    }
+
+    /// Calls `callback` with the project which served each request.
+    /// Affects the handles created afterwards.
+    pub fn on_served<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&Served) + Send + Sync + 'static,
+    {
+        self.on_served = Some(Arc::new(callback));
+        self
+    }
+
+    /// Returns the client of the primary project.
+    pub fn primary(&self) -> &DetaClient {
+        &self.clients[0]
+    }
+
+    /// Checks whether the project of the client at the `index` isn't being skipped after failures.
+    /// Returns `false` for an index out of range.
+    pub fn is_available(&self, index: usize) -> bool {
+        self.health
+            .get(index)
+            .is_some_and(|health| lock(health).is_available(Instant::now()))
+    }
+
+    /// Creates a handle of the base `name`, failing over between the projects.
+    /// Fails with an invalid argument error if the name is blank (see [`Database::new`](Database::new)).
+    ///
+    /// Available with the `base` feature.
+    #[cfg(feature = "base")]
+    pub fn database(&self, name: &str) -> Result<Database> {
+        Ok(Database::new(self.primary(), name)?.with_transport(self.transport()))
+    }
+
+    /// Creates a handle of the drive `name`, failing over between the projects.
+    /// Uploads and downloads are limited by the settings of the primary client, e.g. its concurrency.
+    /// Fails with an invalid argument error if the name isn't a valid drive name (see [`Drive::new`](Drive::new)).
+    ///
+    /// Available with the `drive` feature.
+    #[cfg(feature = "drive")]
+    pub fn drive(&self, name: &str) -> Result<Drive> {
+        Ok(Drive::new(self.primary(), name)?.with_transport(self.transport()))
+    }
+
+    fn transport(&self) -> Arc<dyn Transport> {
+        Arc::new(FailoverTransport {
+            backends: self
+                .clients
+                .iter()
+                .map(|client| Backend {
+                    project_id: client.project_id().to_owned(),
+                    api_key: client.api_key().to_owned(),
+                })
+                .collect(),
+            policy: self.policy,
+            inner: self.inner.clone(),
+            on_served: self.on_served.clone(),
+            health: self.health.clone(),
+        })
+    }
+}
+
+struct Backend {
+    project_id: String,
+    api_key: String,
+}
+
+// Sends the requests built for the primary project to the available projects.
+// The health is shared by all the handles of the client.
+struct FailoverTransport {
+    backends: Vec<Backend>,
+    policy: FailoverPolicy,
+    inner: Arc<dyn Transport>,
+    on_served: Option<ServedCallback>,
+    health: Arc<Vec<Mutex<Health>>>,
+}
+
+impl Transport for FailoverTransport {
+    fn execute(&self, request: reqwest::Request) -> TransportFuture<'_> {
+        Box::pin(async move {
+            let order = self.order();
+            let last = order.len() - 1;
+            let mut request = Some(request);
+            for (position, index) in order.into_iter().enumerate() {
+                let attempt = request
+                    .take()
+                    .expect("The request is kept for the next project");
+                // A copy is kept for the next project, unless the body is streamed and can't be cloned.
+                if position < last {
+                    request = attempt.try_clone();
+                }
+                let result = self.inner.execute(self.retarget(attempt, index)).await;
+
+                let failed = match &result {
+                    Ok(response) => response.status().is_server_error(),
+                    Err(error) => error.is_retryable(),
+                };
+                self.record(index, failed);
+                if !failed || request.is_none() {
+                    if result.is_ok() {
+                        self.served(index, position > 0);
+                    }
+                    return result;
+                }
+            }
+            unreachable!("The last project returns the result")
+        })
+    }
+}
+
+impl FailoverTransport {
+    // The available projects in order, followed by the skipped ones.
+    fn order(&self) -> Vec<usize> {
+        let now = Instant::now();
+        let (mut available, skipped): (Vec<usize>, Vec<usize>) = (0..self.backends.len())
+            .partition(|&index| lock(&self.health[index]).is_available(now));
+        available.extend(skipped);
+        available
+    }
+
+    // Points the request built for the primary project at the project of the backend at the `index`.
+    fn retarget(&self, mut request: reqwest::Request, index: usize) -> reqwest::Request {
+        let backend = &self.backends[index];
+        let primary = &self.backends[0];
+        if index == 0 {
+            return request;
+        }
+        let path = request.url().path().to_owned();
+        let prefix = format!("/v1/{}/", primary.project_id);
+        if let Some(rest) = path.strip_prefix(&prefix) {
+            request
+                .url_mut()
+                .set_path(&format!("/v1/{}/{}", backend.project_id, rest));
+        }
+        if let Ok(api_key) = reqwest::header::HeaderValue::from_str(&backend.api_key) {
+            request.headers_mut().insert("x-api-key", api_key);
+        }
+        request
+    }
+
+    fn record(&self, index: usize, failed: bool) {
+        let mut health = lock(&self.health[index]);
+        if !failed {
+            *health = Health::default();
+            return;
+        }
+        health.failures += 1;
+        if health.failures >= self.policy.failure_threshold {
+            health.skipped_until = Some(Instant::now() + self.policy.cooldown);
+        }
+    }
+
+    fn served(&self, index: usize, failed_over: bool) {
+        if let Some(on_served) = &self.on_served {
+            on_served(&Served {
+                index,
+                project_id: self.backends[index].project_id.clone(),
+                failed_over,
+            });
+        }
+    }
+}
+
+impl Health {
+    fn is_available(&self, now: Instant) -> bool {
+        self.skipped_until.is_none_or(|until| now >= until)
+    }
+}
+
+fn lock(health: &Mutex<Health>) -> std::sync::MutexGuard<'_, Health> {
+    health
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(all(test, feature = "base"))]
+mod tests {
+    use super::*;
+    use crate::testing::MockTransport;
+    use serde_json::{json, Value};
+
+    // Routes the requests to the mock of their project, failing with a timeout for the projects which are down.
+    #[derive(Clone, Default)]
+    struct Projects {
+        primary: MockTransport,
+        standby: MockTransport,
+        down: Arc<Mutex<Vec<&'static str>>>,
+        // The project and the api key of every request.
+        sent: Arc<Mutex<Vec<(String, String)>>>,
+    }
+
+    impl Projects {
+        fn set_down(&self, projects: &[&'static str]) {
+            *self.down.lock().unwrap() = projects.to_vec();
+        }
+
+        fn take_sent(&self) -> Vec<String> {
+            let sent = std::mem::take(&mut *self.sent.lock().unwrap());
+            sent.into_iter()
+                .map(|(project, api_key)| {
+                    assert_eq!(api_key, format!("{}_key", project));
+                    project
+                })
+                .collect()
+        }
+    }
+
+    impl Transport for Projects {
+        fn execute(&self, request: reqwest::Request) -> TransportFuture<'_> {
+            let project = request.url().path_segments().unwrap().nth(1).unwrap();
+            let project = project.to_owned();
+            let api_key = request.headers()["x-api-key"].to_str().unwrap().to_owned();
+            self.sent.lock().unwrap().push((project.clone(), api_key));
+            if self.down.lock().unwrap().contains(&project.as_str()) {
+                return Box::pin(async { Err(Error::from_timeout("Timed out".to_owned())) });
+            }
+            match project.as_str() {
+                "primary" => self.primary.execute(request),
+                _ => self.standby.execute(request),
+            }
+        }
+    }
+
+    fn setup(policy: FailoverPolicy) -> (Projects, MultiClient, Arc<Mutex<Vec<Served>>>) {
+        let projects = Projects::default();
+        projects
+            .primary
+            .respond(
+                "GET",
+                "/items/a",
+                200,
+                json!({ "key": "a", "from": "primary" }),
+            )
+            .respond("GET", "/items/missing", 404, json!({ "key": "missing" }));
+        projects.standby.respond(
+            "GET",
+            "/items/a",
+            200,
+            json!({ "key": "a", "from": "standby" }),
+        );
+        let served = Arc::new(Mutex::new(Vec::new()));
+        let log = served.clone();
+        let clients = vec![
+            DetaClient::new("primary_key"),
+            DetaClient::new("standby_key"),
+        ];
+        let client = MultiClient::new(clients, policy)
+            .unwrap()
+            .with_transport(Arc::new(projects.clone()))
+            .on_served(move |served| log.lock().unwrap().push(served.clone()));
+        (projects, client, served)
+    }
+
+    async fn get_from(database: &Database, key: &str) -> Result<Option<String>> {
+        let item: Option<Value> = database.get_item(key).await?;
+        Ok(item.map(|item| item["from"].as_str().unwrap().to_owned()))
+    }
+
+    #[test]
+    fn new_requires_client() {
+        let error = MultiClient::new(Vec::new(), FailoverPolicy::default())
+            .err()
+            .unwrap();
+        assert!(error.is_invalid_argument());
+    }
+
+    #[tokio::test]
+    async fn primary_serves_requests() {
+        let (projects, client, served) = setup(FailoverPolicy::default());
+        let database = client.database("users").unwrap();
+
+        assert_eq!(
+            get_from(&database, "a").await.unwrap().as_deref(),
+            Some("primary")
+        );
+        assert_eq!(projects.take_sent(), ["primary"]);
+        assert_eq!(
+            served.lock().unwrap()[..],
+            [Served {
+                index: 0,
+                project_id: "primary".to_owned(),
+                failed_over: false
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn connection_failure_fails_over() {
+        let (projects, client, served) = setup(FailoverPolicy::default());
+        projects.set_down(&["primary"]);
+
+        let from = get_from(&client.database("users").unwrap(), "a")
+            .await
+            .unwrap();
+
+        assert_eq!(from.as_deref(), Some("standby"));
+        assert_eq!(projects.take_sent(), ["primary", "standby"]);
+        assert!(projects.standby.requests()[0]
+            .url
+            .starts_with("https://database.deta.sh/v1/standby/users/items/a"));
+        let served = served.lock().unwrap();
+        assert_eq!((served[0].index, served[0].failed_over), (1, true));
+        assert!(!client.is_available(0));
+        assert!(client.is_available(1));
+    }
+
+    #[tokio::test]
+    async fn server_error_fails_over() {
+        let (projects, client, _) = setup(FailoverPolicy::default());
+        projects
+            .primary
+            .respond("GET", "/items/b", 503, json!({ "errors": ["Unavailable"] }));
+        projects.standby.respond(
+            "GET",
+            "/items/b",
+            200,
+            json!({ "key": "b", "from": "standby" }),
+        );
+
+        let from = get_from(&client.database("users").unwrap(), "b")
+            .await
+            .unwrap();
+
+        assert_eq!(from.as_deref(), Some("standby"));
+        assert_eq!(projects.take_sent(), ["primary", "standby"]);
+    }
+
+    #[tokio::test]
+    async fn client_error_does_not_fail_over() {
+        let (projects, client, _) = setup(FailoverPolicy::default());
+        projects
+            .primary
+            .respond("GET", "/items/c", 400, json!({ "errors": ["Bad request"] }));
+        let database = client.database("users").unwrap();
+
+        assert!(get_from(&database, "missing").await.unwrap().is_none());
+        assert!(get_from(&database, "c").await.unwrap_err().is_bad_request());
+        assert_eq!(projects.take_sent(), ["primary", "primary"]);
+        assert!(client.is_available(0));
+    }
+
+    #[tokio::test]
+    async fn skipped_primary_is_tried_first_after_cooldown() {
+        let policy = FailoverPolicy {
+            cooldown: Duration::from_millis(50),
+            ..Default::default()
+        };
+        let (projects, client, _) = setup(policy);
+        let database = client.database("users").unwrap();
+        projects.set_down(&["primary"]);
+        get_from(&database, "a").await.unwrap();
+        projects.take_sent();
+
+        // The primary is skipped, even though it has recovered.
+        projects.set_down(&[]);
+        assert_eq!(
+            get_from(&database, "a").await.unwrap().as_deref(),
+            Some("standby")
+        );
+        assert_eq!(projects.take_sent(), ["standby"]);
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert!(client.is_available(0));
+        assert_eq!(
+            get_from(&database, "a").await.unwrap().as_deref(),
+            Some("primary")
+        );
+        assert_eq!(projects.take_sent(), ["primary"]);
+    }
+
+    #[tokio::test]
+    async fn project_is_skipped_after_threshold() {
+        let policy = FailoverPolicy {
+            failure_threshold: 2,
+            ..Default::default()
+        };
+        let (projects, client, _) = setup(policy);
+        let database = client.database("users").unwrap();
+        projects.set_down(&["primary"]);
+
+        get_from(&database, "a").await.unwrap();
+        assert!(client.is_available(0));
+        get_from(&database, "a").await.unwrap();
+        assert!(!client.is_available(0));
+        assert_eq!(
+            projects.take_sent(),
+            ["primary", "standby", "primary", "standby"]
+        );
+
+        // The health is shared by all handles of the client.
+        get_from(&client.database("orders").unwrap(), "a")
+            .await
+            .unwrap();
+        assert_eq!(projects.take_sent(), ["standby"]);
+    }
+
+    #[tokio::test]
+    async fn all_projects_down_returns_last_error() {
+        let (projects, client, served) = setup(FailoverPolicy::default());
+        projects.set_down(&["primary", "standby"]);
+        let database = client.database("users").unwrap();
+
+        assert!(get_from(&database, "a").await.unwrap_err().is_timeout());
+        // Skipped projects are tried anyway, in order.
+        assert!(get_from(&database, "a").await.unwrap_err().is_timeout());
+        assert_eq!(
+            projects.take_sent(),
+            ["primary", "standby", "primary", "standby"]
+        );
+        assert!(served.lock().unwrap().is_empty());
+    }
+}
@@ -1,17 +1,51 @@
 //! The [Deta-Rust](https://github.com/JakubesP/deta-rust) is a simple unofficial [Deta](https://www.deta.sh/) SDK for Rust lang.
-//! 
+//!
 //! You can see [examples](https://github.com/JakubesP/deta-rust/tree/main/examples) to get you started more quickly.
+//! The commonly used types can be imported at once from the [`prelude`](prelude).
 //!
 //! Have fun 😀
 
+#[cfg(any(feature = "base", feature = "drive"))]
+pub mod batch;
+#[cfg(feature = "blob")]
+pub mod blob;
+#[cfg(feature = "base")]
+pub mod cache;
 mod constants;
+#[cfg(feature = "base")]
+pub mod counter;
+#[cfg(feature = "base")]
 pub mod database;
 mod deta_client;
+#[cfg(feature = "drive")]
 pub mod drive;
 pub mod error;
+#[cfg(any(feature = "base", feature = "drive"))]
+pub mod failover;
+#[cfg(feature = "base")]
+pub mod lock;
+#[cfg(any(feature = "base", feature = "drive"))]
+pub mod mutation;
+#[cfg(all(feature = "base", feature = "drive"))]
+pub mod offload;
+pub mod ops;
+pub mod prelude;
+#[cfg(feature = "base")]
+pub mod queue;
+#[cfg(feature = "chrono")]
+pub mod serde_helpers;
+#[cfg(feature = "sessions")]
+pub mod sessions;
+#[cfg(any(test, feature = "test-util"))]
+pub mod testing;
+pub mod transport;
 mod utils;
 pub use deta_client::DetaClient;
+#[cfg(feature = "drive")]
+pub use deta_client::TransferLimit;
 
 // Re-exports
+/// The HTTP method of a raw request, e.g. with `Database::raw_request`.
+pub use reqwest::Method;
 pub use serde;
 pub use serde_json;
@@ -0,0 +1,387 @@
+//! A lock shared by processes on different machines, kept in a base.
+//!
+//! [`Lock::acquire`](Lock::acquire) inserts an item named after the lock. Inserting an existing key fails
+//! with a conflict, so only one process holds the lock at a time, e.g. to run a nightly job once
+//! even though it's scheduled on every machine. The lock expires after the given time to live,
+//! so a process which crashed doesn't hold it forever; the holder [renews](LockGuard::renew) it
+//! while it works, and [releases](LockGuard::release) it when done.
+//!
+//! ```no_run
+//! use deta_rust::lock::Lock;
+//! use deta_rust::prelude::*;
+//! use std::time::Duration;
+//!
+//! # async fn run_nightly_job() {}
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() -> Result<()> {
+//! let client = DetaClient::new("project_key");
+//! let locks = Database::new(&client, "locks")?;
+//!
+//! if let Some(guard) = Lock::acquire(locks, "nightly_job", Duration::from_secs(600)).await? {
+//!     run_nightly_job().await;
+//!     guard.release().await?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! # Expiry
+//!
+//! The lock item holds the time of its expiry, and the `__expires` field, so deta deletes it by itself.
+//! Deta doesn't delete it right away though, so an expired lock found by `acquire` is stolen: the lock
+//! is deleted and inserted again. Two processes stealing the same expired lock would both succeed that way,
+//! so the thief first inserts a marker item keyed by the lock and its holder; only one of them inserts it,
+//! the other one doesn't get the lock.
+//!
+//! # Caveats
+//!
+//! - The expiry is set by the clock of the holder and checked by the clock of the process trying to acquire
+//!   the lock. If the clocks differ, the lock is stolen earlier or later by the difference.
+//!   Choose a time to live much longer than the expected difference, and renew the lock well before
+//!   it expires, e.g. after a third of the time to live.
+//! - Deta has no conditional updates or deletes, so [`LockGuard::renew`](LockGuard::renew) and
+//!   [`LockGuard::release`](LockGuard::release) check the holder before changing the item. If the lock
+//!   expires and is stolen right between the check and the change, renewing extends the lock of the thief,
+//!   and releasing deletes it. Renewing on time avoids it.
+//! - A lock doesn't stop a holder which has lost it from working. Make the protected work safe to repeat,
+//!   or check [`LockGuard::renew`](LockGuard::renew) between its steps.
+//!
+//! The base should be used only by the locks, with names which are valid keys of the items.
+//!
+//! Available with the `base` feature.
+
+use crate::database::updates::{Action, Updates};
+use crate::error::{Error, Result};
+use crate::ops::DatabaseOps;
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const HOLDER_FIELD: &str = "holder";
+const EXPIRES_AT_FIELD: &str = "expires_at";
+const EXPIRES_FIELD: &str = "__expires";
+// How long the marker of a stolen lock is kept, longer than any steal takes.
+const STEAL_MARKER_TTL: Duration = Duration::from_secs(3600);
+
+/// A lock shared by processes on different machines (see the [module documentation](self)).
+pub struct Lock;
+
+impl Lock {
+    /// Acquires the lock `name`, held for the `ttl` unless it's renewed.
+    /// Returns `None` if another process holds the lock.
+    pub async fn acquire(
+        database: impl DatabaseOps + 'static,
+        name: &str,
+        ttl: Duration,
+    ) -> Result<Option<LockGuard>> {
+        let database: Arc<dyn DatabaseOps> = Arc::new(database);
+        let holder = holder_id();
+
+        // The second attempt follows a lock released or stolen in the meantime.
+        for _ in 0..2 {
+            let expires_at = now_millis() + ttl.as_millis() as u64;
+            match database
+                .insert_item_value(lock_item(name, &holder, expires_at))
+                .await
+            {
+                Ok(_) => {
+                    return Ok(Some(LockGuard {
+                        database,
+                        name: name.to_owned(),
+                        holder,
+                        ttl,
+                        expires_at,
+                        released: false,
+                    }))
+                }
+                Err(error) if error.is_conflict() => {}
+                Err(error) => return Err(error),
+            }
+
+            let current = match database.get_item_value(name).await? {
+                Some(current) => Held::parse(&current)?,
+                None => continue,
+            };
+            if current.expires_at > now_millis() {
+                return Ok(None);
+            }
+
+            let marker = json!({
+                "key": format!("{}.stolen.{}", name, current.holder),
+                EXPIRES_FIELD: expires_field(now_millis() + STEAL_MARKER_TTL.as_millis() as u64),
+            });
+            match database.insert_item_value(marker).await {
+                Ok(_) => {}
+                Err(error) if error.is_conflict() => return Ok(None),
+                Err(error) => return Err(error),
+            }
+            database.delete_item(name).await?;
+        }
+        Ok(None)
+    }
+}
+
+/// The held lock, returned by [`Lock::acquire`](Lock::acquire).
+///
+/// Dropping the guard without calling [`LockGuard::release`](LockGuard::release) releases the lock
+/// in the background, on a best-effort basis, if it's dropped within a tokio runtime.
+/// Otherwise the lock is held until it expires.
+pub struct LockGuard {
+    database: Arc<dyn DatabaseOps>,
+    name: String,
+    holder: String,
+    ttl: Duration,
+    expires_at: u64,
+    released: bool,
+}
+
+impl LockGuard {
+    /// Returns the name of the lock.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the unique id of this holder of the lock, stored in the lock item.
+    pub fn holder(&self) -> &str {
+        &self.holder
+    }
+
+    /// Returns the time at which the lock expires, unless it's renewed.
+    pub fn expires_at(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_millis(self.expires_at)
+    }
+
+    /// Extends the lock by the time to live given to [`Lock::acquire`](Lock::acquire), counted from now.
+    /// Returns `false` if the lock has been lost, as it expired and another process has acquired it.
+    pub async fn renew(&mut self) -> Result<bool> {
+        if !self.is_held().await? {
+            return Ok(false);
+        }
+        let expires_at = now_millis() + self.ttl.as_millis() as u64;
+        let updates = Updates::init()
+            .add(EXPIRES_AT_FIELD, Action::set(expires_at))
+            .add(EXPIRES_FIELD, Action::set(expires_field(expires_at)));
+        let renewed = self.database.update_item(&self.name, updates).await?;
+        if renewed.is_none() {
+            return Ok(false);
+        }
+        self.expires_at = expires_at;
+        Ok(true)
+    }
+
+    /// Releases the lock, so that other processes can acquire it right away.
+    /// Does nothing if the lock has been lost.
+    pub async fn release(mut self) -> Result<()> {
+        self.released = true;
+        release(self.database.as_ref(), &self.name, &self.holder).await
+    }
+
+    async fn is_held(&self) -> Result<bool> {
+        is_held(self.database.as_ref(), &self.name, &self.holder).await
+    }
+}
+
+impl Drop for LockGuard {
+    fn drop(&mut self) {
+        if self.released {
+            return;
+        }
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            let database = self.database.clone();
+            let name = std::mem::take(&mut self.name);
+            let holder = std::mem::take(&mut self.holder);
+            runtime.spawn(async move {
+                let _ = release(database.as_ref(), &name, &holder).await;
+            });
+        }
+    }
+}
+
+// The fields of the lock item read by the other processes.
+struct Held {
+    holder: String,
+    expires_at: u64,
+}
+
+impl Held {
+    fn parse(item: &Value) -> Result<Self> {
+        let holder = item.get(HOLDER_FIELD).and_then(Value::as_str);
+        let expires_at = item.get(EXPIRES_AT_FIELD).and_then(Value::as_f64);
+        match (holder, expires_at) {
+            (Some(holder), Some(expires_at)) => Ok(Self {
+                holder: holder.to_owned(),
+                expires_at: expires_at as u64,
+            }),
+            _ => Err(Error::from_failed_deserialization(Some(item.to_string()))),
+        }
+    }
+}
+
+async fn is_held(database: &dyn DatabaseOps, name: &str, holder: &str) -> Result<bool> {
+    match database.get_item_value(name).await? {
+        Some(item) => Ok(Held::parse(&item)?.holder == holder),
+        None => Ok(false),
+    }
+}
+
+async fn release(database: &dyn DatabaseOps, name: &str, holder: &str) -> Result<()> {
+    if is_held(database, name, holder).await? {
+        database.delete_item(name).await?;
+    }
+    Ok(())
+}
+
+fn lock_item(name: &str, holder: &str, expires_at: u64) -> Value {
+    json!({
+        "key": name,
+        HOLDER_FIELD: holder,
+        EXPIRES_AT_FIELD: expires_at,
+        EXPIRES_FIELD: expires_field(expires_at),
+    })
+}
+
+// The `__expires` field in seconds, rounded up so that deta never deletes a lock which hasn't expired.
+fn expires_field(expires_at: u64) -> u64 {
+    expires_at.div_ceil(1000)
+}
+
+// A unique id of the holder: the time of the acquisition, the process and a counter within it.
+fn holder_id() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    format!(
+        "{:x}-{:x}-{:x}",
+        nanos,
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::FakeBase;
+
+    const MINUTE: Duration = Duration::from_secs(60);
+
+    #[tokio::test]
+    async fn lock_is_held_by_one_process() {
+        let base = FakeBase::new();
+
+        let guard = Lock::acquire(base.clone(), "job", MINUTE)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(Lock::acquire(base.clone(), "job", MINUTE)
+            .await
+            .unwrap()
+            .is_none());
+        // Other locks are independent.
+        let other = Lock::acquire(base.clone(), "other", MINUTE).await.unwrap();
+        assert!(other.is_some());
+
+        let item = &base.items()["job"];
+        assert_eq!(item[HOLDER_FIELD], guard.holder());
+        assert!(
+            item[EXPIRES_FIELD].as_u64().unwrap() * 1000
+                >= item[EXPIRES_AT_FIELD].as_u64().unwrap()
+        );
+
+        guard.release().await.unwrap();
+        let next = Lock::acquire(base.clone(), "job", MINUTE).await.unwrap();
+        assert!(next.is_some());
+    }
+
+    #[tokio::test]
+    async fn expired_lock_is_stolen() {
+        let base = FakeBase::new();
+        let mut expired = Lock::acquire(base.clone(), "job", Duration::ZERO)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let thief = Lock::acquire(base.clone(), "job", MINUTE)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(base.items()["job"][HOLDER_FIELD], thief.holder());
+        assert!(base
+            .items()
+            .contains_key(&format!("job.stolen.{}", expired.holder())));
+
+        // The previous holder finds out it has lost the lock, and doesn't release the new one.
+        assert!(!expired.renew().await.unwrap());
+        expired.release().await.unwrap();
+        assert_eq!(base.items()["job"][HOLDER_FIELD], thief.holder());
+    }
+
+    #[tokio::test]
+    async fn expired_lock_is_stolen_once() {
+        let base = FakeBase::new();
+        let expired = Lock::acquire(base.clone(), "job", Duration::ZERO)
+            .await
+            .unwrap()
+            .unwrap();
+        // Another process is stealing the lock at the same time.
+        base.insert_item_value(json!({ "key": format!("job.stolen.{}", expired.holder()) }))
+            .await
+            .unwrap();
+
+        assert!(Lock::acquire(base.clone(), "job", MINUTE)
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(base.items()["job"][HOLDER_FIELD], expired.holder());
+    }
+
+    #[tokio::test]
+    async fn renew_extends_lock() {
+        let base = FakeBase::new();
+        let mut guard = Lock::acquire(base.clone(), "job", MINUTE)
+            .await
+            .unwrap()
+            .unwrap();
+        let acquired_until = guard.expires_at();
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(guard.renew().await.unwrap());
+
+        assert!(guard.expires_at() > acquired_until);
+        let item = &base.items()["job"];
+        let expires_at = item[EXPIRES_AT_FIELD].as_u64().unwrap();
+        assert_eq!(
+            UNIX_EPOCH + Duration::from_millis(expires_at),
+            guard.expires_at()
+        );
+        assert_eq!(item[EXPIRES_FIELD], expires_at.div_ceil(1000));
+
+        base.delete_item("job").await.unwrap();
+        assert!(!guard.renew().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn dropped_guard_releases_lock() {
+        let base = FakeBase::new();
+        let guard = Lock::acquire(base.clone(), "job", MINUTE).await.unwrap();
+        drop(guard);
+
+        for _ in 0..100 {
+            if base.items().is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(base.items().is_empty());
+    }
+}
@@ -0,0 +1,155 @@
+//! Observers of the changes made through a [`Database`](crate::database::Database) or a [`Drive`](crate::drive::Drive),
+//! e.g. to invalidate the caches and search indexes derived from the data.
+//!
+//! An observer is registered with `on_mutation` and called with a [`MutationEvent`](MutationEvent)
+//! once the request of a mutating method completes, whether it succeeded or failed.
+//! The operations rejected before sending a request, e.g. because of an invalid key, aren't reported.
+//!
+#![cfg_attr(feature = "base", doc = "```no_run")]
+#![cfg_attr(not(feature = "base"), doc = "```ignore")]
+//! use deta_rust::mutation::MutationEvent;
+//! use deta_rust::prelude::*;
+//! use std::sync::Arc;
+//!
+//! # fn main() -> Result<()> {
+//! let client = DetaClient::new("project_key");
+//! let database = Database::new(&client, "users")?.on_mutation(Arc::new(|event: MutationEvent| {
+//!     if event.is_success() {
+//!         println!("{:?} of {:?}", event.kind, event.keys);
+//!     }
+//! }));
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! A drive reports its uploads and deletions the same way, with the names of the files as the keys:
+//!
+#![cfg_attr(feature = "drive", doc = "```no_run")]
+#![cfg_attr(not(feature = "drive"), doc = "```ignore")]
+//! use deta_rust::mutation::{MutationEvent, MutationKind};
+//! use deta_rust::prelude::*;
+//! use std::sync::Arc;
+//!
+//! # fn main() -> Result<()> {
+//! let client = DetaClient::new("project_key");
+//! let drive = Drive::new(&client, "photos")?.on_mutation(Arc::new(|event: MutationEvent| {
+//!     if event.kind == MutationKind::Delete {
+//!         println!("Deleted {:?}, failed {:?}", event.keys, event.failed_keys);
+//!     }
+//! }));
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! # Delivery
+//!
+//! Observers can't fail or hold up the operation. Within a tokio runtime they're called on its blocking threads,
+//! so the events may arrive after the method returns, and the events of concurrent operations in any order.
+//! Outside of a runtime they're called before the method returns. A panicking observer is ignored either way.
+
+use crate::error::{Error, Result};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+
+/// A function called with the [`MutationEvent`](MutationEvent) of every change.
+pub type MutationObserver = Arc<dyn Fn(MutationEvent) + Send + Sync>;
+
+/// The kind of the change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MutationKind {
+    /// Items put into a base, or files uploaded to a drive.
+    Put,
+    /// An item inserted into a base.
+    Insert,
+    /// An item updated in a base.
+    Update,
+    /// Items or files deleted.
+    Delete,
+}
+
+/// A change made by one call of a mutating method, see the [module documentation](self).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MutationEvent {
+    pub kind: MutationKind,
+    /// Keys of the changed items, including the keys generated by deta, or names of the changed files.
+    /// If the operation failed, the keys it was meant to change, as far as they're known before sending.
+    pub keys: Vec<String>,
+    /// Keys of the items or names of the files which deta reported as failed, while the rest were changed.
+    pub failed_keys: Vec<String>,
+    /// Message of the error the operation failed with.
+    pub error: Option<String>,
+}
+
+impl MutationEvent {
+    /// Checks whether the operation succeeded for all the keys.
+    pub fn is_success(&self) -> bool {
+        self.error.is_none() && self.failed_keys.is_empty()
+    }
+
+    pub(crate) fn new<T>(
+        kind: MutationKind,
+        keys: Vec<String>,
+        result: Result<&T, &Error>,
+    ) -> Self {
+        match result {
+            Ok(_) => Self::succeeded(kind, keys, vec![]),
+            Err(error) => Self::failed(kind, keys, error),
+        }
+    }
+
+    pub(crate) fn succeeded(
+        kind: MutationKind,
+        keys: Vec<String>,
+        failed_keys: Vec<String>,
+    ) -> Self {
+        Self {
+            kind,
+            keys,
+            failed_keys,
+            error: None,
+        }
+    }
+
+    pub(crate) fn failed(kind: MutationKind, keys: Vec<String>, error: &Error) -> Self {
+        Self {
+            kind,
+            keys,
+            failed_keys: vec![],
+            error: Some(error.to_string()),
+        }
+    }
+}
+
+// The observers registered on a base or a drive.
+#[derive(Default)]
+pub(crate) struct Observers(Vec<MutationObserver>);
+
+impl Observers {
+    pub(crate) fn push(&mut self, observer: MutationObserver) {
+        self.0.push(observer);
+    }
+
+    // Reports the outcome of an operation. Only the failures of the sent requests are reported,
+    // the invalid arguments are rejected before sending. The event is built only if anyone observes it.
+    pub(crate) fn notify<T>(
+        &self,
+        result: &Result<T>,
+        event: impl FnOnce(Result<&T, &Error>) -> MutationEvent,
+    ) {
+        if self.0.is_empty() || matches!(result, Err(error) if error.is_invalid_argument()) {
+            return;
+        }
+        let event = event(result.as_ref());
+        let runtime = tokio::runtime::Handle::try_current().ok();
+        for observer in &self.0 {
+            let (observer, event) = (observer.clone(), event.clone());
+            match &runtime {
+                // A panic only ends the task, whose handle is dropped.
+                Some(runtime) => drop(runtime.spawn_blocking(move || observer(event))),
+                None => {
+                    let _ = panic::catch_unwind(AssertUnwindSafe(|| observer(event)));
+                }
+            }
+        }
+    }
+}
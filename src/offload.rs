@@ -0,0 +1,664 @@
+//! Storing the oversized fields of the items in a drive.
+//!
+//! Deta base rejects items larger than about 400KB. An [`OffloadingDatabase`](OffloadingDatabase) wraps a base
+//! and a drive paired with it: the fields listed in [`OffloadConfig::fields`](OffloadConfig::fields)
+//! which are larger than the threshold are uploaded to the drive, and the item keeps only a reference to them.
+//! When the item is read, the references are replaced with the stored fields again.
+//!
+//! ```no_run
+//! use deta_rust::offload::{OffloadConfig, OffloadingDatabase};
+//! use deta_rust::prelude::*;
+//! use serde_json::json;
+//!
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() -> Result<()> {
+//! let client = DetaClient::new("project_key");
+//! let articles = OffloadingDatabase::new(
+//!     Database::new(&client, "articles")?,
+//!     Drive::new(&client, "article_bodies")?,
+//!     OffloadConfig {
+//!         fields: vec!["body".into()],
+//!         ..Default::default()
+//!     },
+//! );
+//!
+//! let body = "Lorem ipsum ".repeat(100_000);
+//! articles.put_items(&[json!({ "key": "a1", "title": "Long", "body": body })]).await?;
+//! let article: Option<serde_json::Value> = articles.get_item("a1").await?;
+//! assert_eq!(article.unwrap()["body"], body.as_str());
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The wrapper implements [`DatabaseOps`](DatabaseOps), so it can replace the database wherever
+//! `Arc<dyn DatabaseOps>` is used, and it has the typed methods of [`DatabaseOpsExt`](crate::ops::DatabaseOpsExt).
+//!
+//! # Reference format
+//!
+//! An offloaded field is replaced with an object with the single `__deta_rust_ref` field,
+//! holding `drive://` followed by the name of the file in the paired drive:
+//!
+//! ```json
+//! { "key": "a1", "title": "Long", "body": { "__deta_rust_ref": "drive://offloaded/a1/body/5f0e0cbf8a3c1a77" } }
+//! ```
+//!
+//! The file contains the value of the field serialized as JSON, with the `application/json` content type,
+//! so a string field is stored as a JSON string. Other clients read the item by downloading the file
+//! and parsing it in place of the reference.
+//!
+//! The file is named `{prefix}{key}/{field}/{hash}`, where the key and the field have `%` and `/`
+//! escaped as `%25` and `%2F`, and the hash is the 64-bit FNV-1a hash of the content in hex.
+//! Equal content gets the same name, so a new version of a field never overwrites the file
+//! referenced by the stored item before the item itself is replaced.
+//!
+//! # Leftover files
+//!
+//! After an item is written, the files under its name which it doesn't reference anymore are deleted,
+//! e.g. the previous version of a field, or the files uploaded for a write which failed.
+//! This costs listing the files of the item, so every written item takes an extra drive request.
+//! If the cleanup fails, the write still succeeds and the files are deleted by the next write of the item,
+//! or with [`OffloadingDatabase::remove_orphans`](OffloadingDatabase::remove_orphans).
+//! Deleting an item deletes all its files, and a failure to do so is returned as the error.
+//!
+//! # Limitations
+//!
+//! - Only top-level fields are offloaded, and only of the items with a key, as the key is a part of the file names.
+//!   Putting an item without a key but with an oversized field fails with an invalid argument error.
+//! - Updates are sent as they are: a field set by [`update_item`](DatabaseOps::update_item) isn't offloaded,
+//!   and changing a nested field of an offloaded one breaks its reference.
+//!   Deleting or setting an offloaded field removes its file afterwards.
+//! - Queries see the references rather than the offloaded values.
+
+use crate::constants;
+use crate::database::models::{DeleteItem, FetchItems, PutItems, UpdateItem};
+use crate::database::query::Query;
+use crate::database::updates::Updates;
+use crate::error::{Error, Result};
+use crate::ops::{DatabaseOps, DriveOps, OpsFuture};
+use serde_json::{Map, Value};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+const REFERENCE_FIELD: &str = "__deta_rust_ref";
+const REFERENCE_SCHEME: &str = "drive://";
+
+/// Configuration of an [`OffloadingDatabase`](OffloadingDatabase).
+#[derive(Debug, Clone)]
+pub struct OffloadConfig {
+    /// Fields larger than this number of bytes, serialized as JSON, are stored in the drive.
+    /// Defaults to 64KB.
+    pub threshold: usize,
+    /// Names of the top-level fields which may be stored in the drive. Defaults to none.
+    pub fields: Vec<String>,
+    /// Prefix of the names of the files in the drive. Defaults to `offloaded/`.
+    pub prefix: String,
+    /// Replaces the references with the stored fields when the items are read.
+    /// With `false`, the items are returned with the references, to be resolved when needed
+    /// with [`OffloadingDatabase::resolve`](OffloadingDatabase::resolve). Defaults to `true`.
+    pub resolve_on_read: bool,
+}
+
+impl Default for OffloadConfig {
+    fn default() -> Self {
+        Self {
+            threshold: 64 * 1024,
+            fields: vec![],
+            prefix: "offloaded/".to_owned(),
+            resolve_on_read: true,
+        }
+    }
+}
+
+/// A base storing the oversized fields of its items in a drive (see the [module documentation](self)).
+pub struct OffloadingDatabase {
+    database: Arc<dyn DatabaseOps>,
+    drive: Arc<dyn DriveOps>,
+    config: OffloadConfig,
+}
+
+impl OffloadingDatabase {
+    /// Creates the wrapper storing the fields of the items of the `database` in the `drive`.
+    pub fn new(
+        database: impl DatabaseOps + 'static,
+        drive: impl DriveOps + 'static,
+        config: OffloadConfig,
+    ) -> Self {
+        Self {
+            database: Arc::new(database),
+            drive: Arc::new(drive),
+            config,
+        }
+    }
+
+    /// Replaces the references in the `item` with the fields stored in the drive.
+    /// Needed only with [`OffloadConfig::resolve_on_read`](OffloadConfig::resolve_on_read) set to `false`.
+    /// Fails if a referenced file doesn't exist.
+    pub async fn resolve(&self, mut item: Value) -> Result<Value> {
+        let fields = match item.as_object_mut() {
+            Some(fields) => fields,
+            None => return Ok(item),
+        };
+        for (field, value) in fields.iter_mut() {
+            let name = match reference(value) {
+                Some(name) => name.to_owned(),
+                None => continue,
+            };
+            match self.drive.get_file_as_buffer(&name).await? {
+                Some(data) => *value = serde_json::from_slice(&data)?,
+                None => {
+                    return Err(Error::from_other(
+                        format!(
+                            "The file '{}' holding the field '{}' doesn't exist",
+                            name, field
+                        ),
+                        None,
+                    ))
+                }
+            }
+        }
+        Ok(item)
+    }
+
+    /// Checks whether the `value` of a field is a reference to the file holding it.
+    pub fn is_reference(value: &Value) -> bool {
+        reference(value).is_some()
+    }
+
+    /// Deletes the files of the item with the `key` which the stored item doesn't reference,
+    /// e.g. left after a failed cleanup. All the files are deleted if the item doesn't exist.
+    pub async fn remove_orphans(&self, key: &str) -> Result<()> {
+        let stored = self.database.get_item_value(key).await?;
+        self.delete_unreferenced(key, stored.as_ref()).await
+    }
+
+    // Uploads the oversized fields of the `item` and replaces them with the references.
+    // Returns the original values by the names of their files.
+    async fn offload(&self, item: &mut Value) -> Result<HashMap<String, Value>> {
+        let mut offloaded = HashMap::new();
+        let fields = match item.as_object_mut() {
+            Some(fields) => fields,
+            None => return Ok(offloaded),
+        };
+        let key = fields.get("key").and_then(Value::as_str).map(str::to_owned);
+
+        for field in &self.config.fields {
+            let value = match fields.get_mut(field) {
+                Some(value) if !Self::is_reference(value) => value,
+                _ => continue,
+            };
+            let data = serde_json::to_vec(value)?;
+            if data.len() <= self.config.threshold {
+                continue;
+            }
+            let key = key.as_deref().ok_or_else(|| {
+                Error::from_invalid_argument(format!(
+                    "The field '{}' is too large to be stored in the item, and it can be offloaded only for an item with a key",
+                    field
+                ))
+            })?;
+
+            let name = format!(
+                "{}{}/{:016x}",
+                self.key_prefix(key),
+                escape(field),
+                content_hash(&data)
+            );
+            self.drive
+                .put_file_bytes(&name, data.into(), Some(constants::JSON_CONTENT_TYPE))
+                .await?;
+            let original = std::mem::replace(value, reference_value(&name));
+            offloaded.insert(name, original);
+        }
+        Ok(offloaded)
+    }
+
+    // Deletes the files of the item with the `key` other than the ones referenced by the `item`.
+    async fn delete_unreferenced(&self, key: &str, item: Option<&Value>) -> Result<()> {
+        let referenced: HashSet<&str> = item.map(references).unwrap_or_default();
+        let prefix = self.key_prefix(key);
+
+        let mut unreferenced = vec![];
+        let mut last = None;
+        loop {
+            let files = self
+                .drive
+                .list_files(None, Some(&prefix), last.as_deref())
+                .await?;
+            let names = files.names.iter();
+            unreferenced.extend(
+                names
+                    .filter(|name| !referenced.contains(name.as_str()))
+                    .cloned(),
+            );
+            match files.next_cursor() {
+                Some(cursor) => last = Some(cursor.as_str().to_owned()),
+                None => break,
+            }
+        }
+
+        for names in unreferenced.chunks(constants::MAX_DELETE_BATCH_SIZE) {
+            self.drive.delete_files(names).await?;
+        }
+        Ok(())
+    }
+
+    // Deletes the leftover files of the written item. A failure is left for the next write.
+    async fn clean_up(&self, key: &str, item: Option<&Value>) {
+        let _ = match item {
+            Some(item) => self.delete_unreferenced(key, Some(item)).await,
+            None => self.remove_orphans(key).await,
+        };
+    }
+
+    async fn resolve_on_read(&self, item: Value) -> Result<Value> {
+        if self.config.resolve_on_read {
+            self.resolve(item).await
+        } else {
+            Ok(item)
+        }
+    }
+
+    // The common beginning of the names of the files of the item, ending with `/`.
+    fn key_prefix(&self, key: &str) -> String {
+        format!("{}{}/", self.config.prefix, escape(key))
+    }
+}
+
+impl DatabaseOps for OffloadingDatabase {
+    fn put_item_values(&self, mut items: Vec<Value>) -> OpsFuture<'_, PutItems<Value>> {
+        Box::pin(async move {
+            let mut offloaded = HashMap::new();
+            let mut result = Ok(());
+            for item in &mut items {
+                match self.offload(item).await {
+                    Ok(values) => offloaded.extend(values),
+                    Err(error) => {
+                        result = Err(error);
+                        break;
+                    }
+                }
+            }
+            let result = match result {
+                Ok(()) => self.database.put_item_values(items.clone()).await,
+                Err(error) => Err(error),
+            };
+
+            match result {
+                Ok(mut put) => {
+                    for item in &mut put.processed.items {
+                        if let Some(key) = item_key(item) {
+                            self.clean_up(&key, Some(item)).await;
+                        }
+                        restore(item, &offloaded);
+                    }
+                    let failed = put.failed.iter_mut().flat_map(|failed| &mut failed.items);
+                    for item in failed {
+                        if let Some(key) = item_key(item) {
+                            self.clean_up(&key, None).await;
+                        }
+                        restore(item, &offloaded);
+                    }
+                    Ok(put)
+                }
+                Err(error) => {
+                    // Nothing is known to be written, only the uploaded files may be left.
+                    let keys: HashSet<String> = items
+                        .iter()
+                        .filter(|item| !references(item).is_empty())
+                        .filter_map(item_key)
+                        .collect();
+                    for key in keys {
+                        self.clean_up(&key, None).await;
+                    }
+                    Err(error)
+                }
+            }
+        })
+    }
+
+    fn get_item_value<'a>(&'a self, key: &'a str) -> OpsFuture<'a, Option<Value>> {
+        Box::pin(async move {
+            match self.database.get_item_value(key).await? {
+                Some(item) => Ok(Some(self.resolve_on_read(item).await?)),
+                None => Ok(None),
+            }
+        })
+    }
+
+    fn insert_item_value(&self, mut item: Value) -> OpsFuture<'_, Value> {
+        Box::pin(async move {
+            let offloaded = self.offload(&mut item).await?;
+            match self.database.insert_item_value(item.clone()).await {
+                Ok(mut inserted) => {
+                    restore(&mut inserted, &offloaded);
+                    Ok(inserted)
+                }
+                Err(error) => {
+                    // The existing item may reference a file with the same content, so it's checked.
+                    if let Some(key) = item_key(&item).filter(|_| !offloaded.is_empty()) {
+                        self.clean_up(&key, None).await;
+                    }
+                    Err(error)
+                }
+            }
+        })
+    }
+
+    fn delete_item<'a>(&'a self, key: &'a str) -> OpsFuture<'a, DeleteItem> {
+        Box::pin(async move {
+            let deleted = self.database.delete_item(key).await?;
+            self.delete_unreferenced(key, None).await?;
+            Ok(deleted)
+        })
+    }
+
+    fn fetch_item_values<'a>(
+        &'a self,
+        limit: Option<u32>,
+        last: Option<&'a str>,
+        query: Option<Query>,
+    ) -> OpsFuture<'a, FetchItems<Value>> {
+        Box::pin(async move {
+            let fetched = self.database.fetch_item_values(limit, last, query).await?;
+            let mut items = Vec::with_capacity(fetched.items.len());
+            for item in fetched.items {
+                items.push(self.resolve_on_read(item).await?);
+            }
+            Ok(FetchItems {
+                paging: fetched.paging,
+                items,
+            })
+        })
+    }
+
+    fn update_item<'a>(
+        &'a self,
+        key: &'a str,
+        updates: Updates,
+    ) -> OpsFuture<'a, Option<UpdateItem>> {
+        Box::pin(async move {
+            // A changed offloaded field may leave its file unreferenced.
+            let offloaded_field_changed = updates.attributes().any(|attribute| {
+                self.config
+                    .fields
+                    .iter()
+                    .any(|offloaded| offloaded == attribute.root())
+            });
+            let updated = self.database.update_item(key, updates).await?;
+            if offloaded_field_changed && updated.is_some() {
+                self.clean_up(key, None).await;
+            }
+            Ok(updated)
+        })
+    }
+}
+
+// Returns the name of the file if the `value` is a reference.
+fn reference(value: &Value) -> Option<&str> {
+    let fields = value.as_object()?;
+    if fields.len() != 1 {
+        return None;
+    }
+    fields
+        .get(REFERENCE_FIELD)?
+        .as_str()?
+        .strip_prefix(REFERENCE_SCHEME)
+}
+
+fn reference_value(name: &str) -> Value {
+    let mut fields = Map::new();
+    fields.insert(
+        REFERENCE_FIELD.to_owned(),
+        Value::String(format!("{}{}", REFERENCE_SCHEME, name)),
+    );
+    Value::Object(fields)
+}
+
+// Returns the names of the files referenced by the top-level fields of the `item`.
+fn references(item: &Value) -> HashSet<&str> {
+    match item.as_object() {
+        Some(fields) => fields.values().filter_map(reference).collect(),
+        None => HashSet::new(),
+    }
+}
+
+// Replaces the references to the files uploaded by this call with the original values,
+// so the items returned by deta look like the ones sent.
+fn restore(item: &mut Value, offloaded: &HashMap<String, Value>) {
+    if let Some(fields) = item.as_object_mut() {
+        for value in fields.values_mut() {
+            if let Some(original) = reference(value).and_then(|name| offloaded.get(name)) {
+                *value = original.clone();
+            }
+        }
+    }
+}
+
+fn item_key(item: &Value) -> Option<String> {
+    item.get("key").and_then(Value::as_str).map(str::to_owned)
+}
+
+// Escapes the characters which would make the names of the files ambiguous.
+fn escape(segment: &str) -> String {
+    segment.replace('%', "%25").replace('/', "%2F")
+}
+
+// FNV-1a, which is stable across Rust versions, unlike the hasher of the standard library.
+fn content_hash(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::updates::Action;
+    use crate::ops::DatabaseOpsExt;
+    use crate::testing::{FakeBase, FakeDrive};
+    use serde_json::json;
+
+    fn setup(config: OffloadConfig) -> (FakeBase, FakeDrive, OffloadingDatabase) {
+        let base = FakeBase::new();
+        let drive = FakeDrive::new();
+        let config = OffloadConfig {
+            threshold: 16,
+            fields: vec!["body".into(), "blob".into()],
+            ..config
+        };
+        let database = OffloadingDatabase::new(base.clone(), drive.clone(), config);
+        (base, drive, database)
+    }
+
+    fn article(key: &str, body: &str) -> Value {
+        json!({ "key": key, "title": "A very long title of the article", "body": body })
+    }
+
+    fn file_names(drive: &FakeDrive) -> Vec<String> {
+        drive.files().into_keys().collect()
+    }
+
+    #[tokio::test]
+    async fn oversized_fields_round_trip() {
+        let (base, drive, database) = setup(OffloadConfig::default());
+        let body = "x".repeat(100);
+        let blob: Vec<u8> = (0..=255).collect();
+        let item = json!({ "key": "a1", "title": "Short", "body": body, "blob": blob });
+
+        let put = database.put_item_values(vec![item.clone()]).await.unwrap();
+        assert_eq!(put.processed.items, vec![item.clone()]);
+
+        let stored = &base.items()["a1"];
+        assert_eq!(stored["title"], "Short");
+        let name = reference(&stored["body"]).unwrap();
+        assert!(name.starts_with("offloaded/a1/body/"), "{}", name);
+        assert_eq!(
+            drive.files()[name],
+            serde_json::to_vec(&body).unwrap(),
+            "the field is stored as JSON"
+        );
+        assert_eq!(drive.content_type(name).unwrap(), "application/json");
+        assert!(OffloadingDatabase::is_reference(&stored["blob"]));
+
+        assert_eq!(
+            database.get_item_value("a1").await.unwrap(),
+            Some(item.clone())
+        );
+        let fetched = database
+            .fetch_items::<Value>(None, None, None)
+            .await
+            .unwrap();
+        assert_eq!(fetched.items, vec![item.clone()]);
+
+        let inserted = database
+            .insert_item_value(article("b", &body))
+            .await
+            .unwrap();
+        assert_eq!(inserted, article("b", &body));
+        assert_eq!(file_names(&drive).len(), 3);
+    }
+
+    #[tokio::test]
+    async fn small_and_unlisted_fields_stay_in_item() {
+        let (base, drive, database) = setup(OffloadConfig::default());
+        let item = json!({ "key": "a", "body": "short", "title": "x".repeat(100) });
+
+        database.put_item_values(vec![item.clone()]).await.unwrap();
+        assert_eq!(base.items()["a"], item);
+        assert!(drive.files().is_empty());
+    }
+
+    #[tokio::test]
+    async fn lazy_reading_returns_references() {
+        let (_, _, database) = setup(OffloadConfig {
+            resolve_on_read: false,
+            ..Default::default()
+        });
+        let item = article("a", &"x".repeat(100));
+        database.put_item_values(vec![item.clone()]).await.unwrap();
+
+        let read = database.get_item_value("a").await.unwrap().unwrap();
+        assert!(OffloadingDatabase::is_reference(&read["body"]));
+        assert_eq!(database.resolve(read).await.unwrap(), item);
+    }
+
+    #[tokio::test]
+    async fn item_without_key_is_rejected() {
+        let (base, drive, database) = setup(OffloadConfig::default());
+        let error = database
+            .put_item_values(vec![json!({ "body": "x".repeat(100) })])
+            .await
+            .unwrap_err();
+
+        assert!(error.is_invalid_argument());
+        assert!(base.items().is_empty());
+        assert!(drive.files().is_empty());
+    }
+
+    #[tokio::test]
+    async fn replaced_fields_leave_no_files() {
+        let (_, drive, database) = setup(OffloadConfig::default());
+        database
+            .put_item_values(vec![
+                article("a", &"x".repeat(100)),
+                article("a/b", &"z".repeat(100)),
+            ])
+            .await
+            .unwrap();
+        let first = file_names(&drive);
+
+        database
+            .put_item_values(vec![article("a", &"y".repeat(100))])
+            .await
+            .unwrap();
+        let second = file_names(&drive);
+        assert_eq!(second.len(), 2);
+        assert_ne!(first, second);
+        assert!(second
+            .iter()
+            .any(|name| name.starts_with("offloaded/a%2Fb/")));
+
+        database
+            .put_item_values(vec![article("a", "short")])
+            .await
+            .unwrap();
+        assert_eq!(file_names(&drive).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn delete_removes_files() {
+        let (base, drive, database) = setup(OffloadConfig::default());
+        database
+            .put_item_values(vec![
+                article("a", &"x".repeat(100)),
+                article("b", &"x".repeat(100)),
+            ])
+            .await
+            .unwrap();
+
+        database.delete_item("a").await.unwrap();
+        assert!(!base.items().contains_key("a"));
+        let names = file_names(&drive);
+        assert_eq!(names.len(), 1);
+        assert!(names[0].starts_with("offloaded/b/"));
+    }
+
+    #[tokio::test]
+    async fn failed_insert_keeps_files_of_existing_item() {
+        let (base, drive, database) = setup(OffloadConfig::default());
+        let existing = article("a", &"x".repeat(100));
+        database.insert_item_value(existing.clone()).await.unwrap();
+        let names = file_names(&drive);
+
+        // Both a new and the same content, the latter sharing the file of the existing item.
+        for body in ["y".repeat(100), "x".repeat(100)] {
+            let error = database
+                .insert_item_value(article("a", &body))
+                .await
+                .unwrap_err();
+            assert!(error.is_conflict());
+            assert_eq!(file_names(&drive), names);
+        }
+        assert_eq!(database.get_item_value("a").await.unwrap(), Some(existing));
+        assert!(OffloadingDatabase::is_reference(&base.items()["a"]["body"]));
+    }
+
+    #[tokio::test]
+    async fn update_of_offloaded_field_removes_its_file() {
+        let (_, drive, database) = setup(OffloadConfig::default());
+        database
+            .put_item_values(vec![article("a", &"x".repeat(100))])
+            .await
+            .unwrap();
+
+        let updates = Updates::init().add("title", Action::set("New"));
+        database.update_item("a", updates).await.unwrap();
+        assert_eq!(file_names(&drive).len(), 1);
+
+        let updates = Updates::init().add("body", Action::set("short"));
+        database.update_item("a", updates).await.unwrap();
+        assert!(drive.files().is_empty());
+        assert_eq!(
+            database.get_item_value("a").await.unwrap().unwrap()["body"],
+            "short"
+        );
+    }
+
+    #[tokio::test]
+    async fn remove_orphans_deletes_unreferenced_files() {
+        let (_, drive, database) = setup(OffloadConfig::default());
+        database
+            .put_item_values(vec![article("a", &"x".repeat(100))])
+            .await
+            .unwrap();
+        let names = file_names(&drive);
+        drive
+            .drive()
+            .put_file("offloaded/a/body/0000000000000000", "\"left\"", None)
+            .await
+            .unwrap();
+
+        database.remove_orphans("a").await.unwrap();
+        assert_eq!(file_names(&drive), names);
+    }
+}
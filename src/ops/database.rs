@@ -0,0 +1,265 @@
+use super::OpsFuture;
+use crate::database::models::{DeleteItem, FetchItems, PutItems, UpdateItem};
+use crate::database::query::Query;
+use crate::database::updates::Updates;
+use crate::database::Database;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::sync::Arc;
+
+/// The core methods of [`Database`](Database) with the items as [`serde_json::Value`],
+/// so the trait can be used as `dyn DatabaseOps`. The typed methods are provided by
+/// [`DatabaseOpsExt`](DatabaseOpsExt), implemented for every `DatabaseOps`.
+///
+/// The methods behave like their counterparts on [`Database`](Database).
+pub trait DatabaseOps: Send + Sync {
+    /// Works like [`Database::put_items`](Database::put_items).
+    fn put_item_values(&self, items: Vec<Value>) -> OpsFuture<'_, PutItems<Value>>;
+
+    /// Works like [`Database::get_item`](Database::get_item).
+    fn get_item_value<'a>(&'a self, key: &'a str) -> OpsFuture<'a, Option<Value>>;
+
+    /// Works like [`Database::insert_item`](Database::insert_item).
+    fn insert_item_value(&self, item: Value) -> OpsFuture<'_, Value>;
+
+    /// Works like [`Database::delete_item`](Database::delete_item).
+    fn delete_item<'a>(&'a self, key: &'a str) -> OpsFuture<'a, DeleteItem>;
+
+    /// Works like [`Database::fetch_items`](Database::fetch_items).
+    fn fetch_item_values<'a>(
+        &'a self,
+        limit: Option<u32>,
+        last: Option<&'a str>,
+        query: Option<Query>,
+    ) -> OpsFuture<'a, FetchItems<Value>>;
+
+    /// Works like [`Database::update_item`](Database::update_item).
+    fn update_item<'a>(
+        &'a self,
+        key: &'a str,
+        updates: Updates,
+    ) -> OpsFuture<'a, Option<UpdateItem>>;
+}
+
+/// The typed methods of [`Database`](Database) built on top of [`DatabaseOps`](DatabaseOps),
+/// implemented for every `DatabaseOps`, including `dyn DatabaseOps`.
+/// The items are converted from and to [`serde_json::Value`]; a failed conversion is a deserialization error.
+pub trait DatabaseOpsExt: DatabaseOps {
+    /// Works like [`Database::put_items`](Database::put_items).
+    fn put_items<'a, T>(&'a self, items: &[T]) -> OpsFuture<'a, PutItems<T>>
+    where
+        T: DeserializeOwned + Serialize + Send + 'a,
+    {
+        let values: serde_json::Result<Vec<Value>> =
+            items.iter().map(serde_json::to_value).collect();
+        Box::pin(async move {
+            let result = self.put_item_values(values?).await?;
+            from_value(serde_json::to_value(result)?)
+        })
+    }
+
+    /// Works like [`Database::get_item`](Database::get_item).
+    fn get_item<'a, T>(&'a self, key: &'a str) -> OpsFuture<'a, Option<T>>
+    where
+        T: DeserializeOwned + Send + 'a,
+    {
+        Box::pin(async move {
+            match self.get_item_value(key).await? {
+                Some(value) => Ok(Some(from_value(value)?)),
+                None => Ok(None),
+            }
+        })
+    }
+
+    /// Works like [`Database::insert_item`](Database::insert_item).
+    fn insert_item<'a, T>(&'a self, item: &T) -> OpsFuture<'a, T>
+    where
+        T: DeserializeOwned + Serialize + Send + 'a,
+    {
+        let value = serde_json::to_value(item);
+        Box::pin(async move { from_value(self.insert_item_value(value?).await?) })
+    }
+
+    /// Works like [`Database::fetch_items`](Database::fetch_items).
+    fn fetch_items<'a, T>(
+        &'a self,
+        limit: Option<u32>,
+        last: Option<&'a str>,
+        query: Option<Query>,
+    ) -> OpsFuture<'a, FetchItems<T>>
+    where
+        T: DeserializeOwned + Send + 'a,
+    {
+        Box::pin(async move {
+            let fetched = self.fetch_item_values(limit, last, query).await?;
+            let items = fetched
+                .items
+                .into_iter()
+                .map(from_value)
+                .collect::<crate::error::Result<_>>()?;
+            Ok(FetchItems {
+                paging: fetched.paging,
+                items,
+            })
+        })
+    }
+}
+
+impl<D: DatabaseOps + ?Sized> DatabaseOpsExt for D {}
+
+fn from_value<T: DeserializeOwned>(value: Value) -> crate::error::Result<T> {
+    Ok(serde_json::from_value(value)?)
+}
+
+impl DatabaseOps for Database {
+    fn put_item_values(&self, items: Vec<Value>) -> OpsFuture<'_, PutItems<Value>> {
+        Box::pin(async move { Database::put_items(self, &items).await })
+    }
+
+    fn get_item_value<'a>(&'a self, key: &'a str) -> OpsFuture<'a, Option<Value>> {
+        Box::pin(Database::get_item(self, key))
+    }
+
+    fn insert_item_value(&self, item: Value) -> OpsFuture<'_, Value> {
+        Box::pin(async move { Database::insert_item(self, &item).await })
+    }
+
+    fn delete_item<'a>(&'a self, key: &'a str) -> OpsFuture<'a, DeleteItem> {
+        Box::pin(Database::delete_item(self, key))
+    }
+
+    fn fetch_item_values<'a>(
+        &'a self,
+        limit: Option<u32>,
+        last: Option<&'a str>,
+        query: Option<Query>,
+    ) -> OpsFuture<'a, FetchItems<Value>> {
+        Box::pin(Database::fetch_items(self, limit, last, query))
+    }
+
+    fn update_item<'a>(
+        &'a self,
+        key: &'a str,
+        updates: Updates,
+    ) -> OpsFuture<'a, Option<UpdateItem>> {
+        Box::pin(Database::update_item(self, key, updates))
+    }
+}
+
+impl<D: DatabaseOps + ?Sized> DatabaseOps for Arc<D> {
+    fn put_item_values(&self, items: Vec<Value>) -> OpsFuture<'_, PutItems<Value>> {
+        (**self).put_item_values(items)
+    }
+
+    fn get_item_value<'a>(&'a self, key: &'a str) -> OpsFuture<'a, Option<Value>> {
+        (**self).get_item_value(key)
+    }
+
+    fn insert_item_value(&self, item: Value) -> OpsFuture<'_, Value> {
+        (**self).insert_item_value(item)
+    }
+
+    fn delete_item<'a>(&'a self, key: &'a str) -> OpsFuture<'a, DeleteItem> {
+        (**self).delete_item(key)
+    }
+
+    fn fetch_item_values<'a>(
+        &'a self,
+        limit: Option<u32>,
+        last: Option<&'a str>,
+        query: Option<Query>,
+    ) -> OpsFuture<'a, FetchItems<Value>> {
+        (**self).fetch_item_values(limit, last, query)
+    }
+
+    fn update_item<'a>(
+        &'a self,
+        key: &'a str,
+        updates: Updates,
+    ) -> OpsFuture<'a, Option<UpdateItem>> {
+        (**self).update_item(key, updates)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::query::Condition;
+    use crate::database::updates::Action;
+    use crate::testing::FakeBase;
+    use serde::Deserialize;
+    use serde_json::json;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct User {
+        key: String,
+        age: u32,
+    }
+
+    fn user(key: &str, age: u32) -> User {
+        User {
+            key: key.into(),
+            age,
+        }
+    }
+
+    // Runs the same calls against the database and the fake itself.
+    async fn check_typed_methods(users: Arc<dyn DatabaseOps>) {
+        let put = users
+            .put_items(&[user("anna", 30), user("tom", 12)])
+            .await
+            .unwrap();
+        assert_eq!(put.processed.items, [user("anna", 30), user("tom", 12)]);
+
+        let inserted = users.insert_item(&user("eve", 20)).await.unwrap();
+        assert_eq!(inserted, user("eve", 20));
+        assert!(users
+            .insert_item(&user("eve", 21))
+            .await
+            .unwrap_err()
+            .is_conflict());
+
+        let anna: Option<User> = users.get_item("anna").await.unwrap();
+        assert_eq!(anna, Some(user("anna", 30)));
+        let missing: Option<User> = users.get_item("missing").await.unwrap();
+        assert!(missing.is_none());
+
+        let query = Query::init().on("age", Condition::greater_than_or_equal(18));
+        let adults = users
+            .fetch_items::<User>(None, None, Some(query))
+            .await
+            .unwrap();
+        assert_eq!(adults.items, [user("anna", 30), user("eve", 20)]);
+
+        let updates = Updates::init().add("age", Action::increment(1));
+        users.update_item("tom", updates).await.unwrap();
+        users.delete_item("anna").await.unwrap();
+        let remaining = users.fetch_items::<User>(None, None, None).await.unwrap();
+        assert_eq!(remaining.items, [user("eve", 20), user("tom", 13)]);
+    }
+
+    #[tokio::test]
+    async fn database_implements_ops() {
+        let fake = FakeBase::new();
+        check_typed_methods(Arc::new(fake.database())).await;
+    }
+
+    #[tokio::test]
+    async fn fake_base_implements_ops() {
+        check_typed_methods(Arc::new(FakeBase::new())).await;
+    }
+
+    #[tokio::test]
+    async fn failed_conversion_is_deserialization_error() {
+        let fake = FakeBase::new();
+        let users: Arc<dyn DatabaseOps> = Arc::new(fake.clone());
+        users
+            .put_item_values(vec![json!({ "key": "anna", "age": "thirty" })])
+            .await
+            .unwrap();
+
+        let error = users.get_item::<User>("anna").await.unwrap_err();
+        assert!(error.is_body_deserialization());
+    }
+}
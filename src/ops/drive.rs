@@ -0,0 +1,223 @@
+use super::OpsFuture;
+use crate::constants;
+use crate::drive::models::{DeleteFiles, FileMetadata, ListFiles};
+use crate::drive::{Drive, PutFileResult};
+use crate::error::Error;
+use crate::utils;
+use bytes::Bytes;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::sync::Arc;
+
+/// The core methods of [`Drive`](Drive) without generic parameters, so the trait can be used as `dyn DriveOps`.
+/// The typed methods are provided by [`DriveOpsExt`](DriveOpsExt), implemented for every `DriveOps`.
+///
+/// The methods behave like their counterparts on [`Drive`](Drive).
+pub trait DriveOps: Send + Sync {
+    /// Works like [`Drive::put_file`](Drive::put_file).
+    fn put_file_bytes<'a>(
+        &'a self,
+        name: &'a str,
+        data: Bytes,
+        content_type: Option<&'a str>,
+    ) -> OpsFuture<'a, PutFileResult>;
+
+    /// Works like [`Drive::get_file_as_buffer`](Drive::get_file_as_buffer).
+    fn get_file_as_buffer<'a>(&'a self, name: &'a str) -> OpsFuture<'a, Option<Bytes>>;
+
+    /// Works like [`Drive::head_file`](Drive::head_file).
+    fn head_file<'a>(&'a self, name: &'a str) -> OpsFuture<'a, Option<FileMetadata>>;
+
+    /// Works like [`Drive::list_files`](Drive::list_files).
+    fn list_files<'a>(
+        &'a self,
+        limit: Option<u32>,
+        prefix: Option<&'a str>,
+        last_name: Option<&'a str>,
+    ) -> OpsFuture<'a, ListFiles>;
+
+    /// Works like [`Drive::delete_files`](Drive::delete_files).
+    fn delete_files<'a>(&'a self, names: &'a [String]) -> OpsFuture<'a, DeleteFiles>;
+}
+
+/// The typed methods of [`Drive`](Drive) built on top of [`DriveOps`](DriveOps),
+/// implemented for every `DriveOps`, including `dyn DriveOps`.
+pub trait DriveOpsExt: DriveOps {
+    /// Works like [`Drive::put_file`](Drive::put_file).
+    fn put_file<'a>(
+        &'a self,
+        name: &'a str,
+        data: impl Into<Bytes>,
+        content_type: Option<&'a str>,
+    ) -> OpsFuture<'a, PutFileResult> {
+        self.put_file_bytes(name, data.into(), content_type)
+    }
+
+    /// Works like [`Drive::get_file_as_u8_vec`](Drive::get_file_as_u8_vec).
+    fn get_file_as_u8_vec<'a>(&'a self, name: &'a str) -> OpsFuture<'a, Option<Vec<u8>>> {
+        Box::pin(async move {
+            let bytes = self.get_file_as_buffer(name).await?;
+            Ok(bytes.map(|bytes| bytes.to_vec()))
+        })
+    }
+
+    /// Works like [`Drive::put_text`](Drive::put_text).
+    fn put_text<'a>(&'a self, name: &'a str, text: &str) -> OpsFuture<'a, PutFileResult> {
+        let data = Bytes::copy_from_slice(text.as_bytes());
+        self.put_file_bytes(name, data, Some(constants::TEXT_CONTENT_TYPE))
+    }
+
+    /// Works like [`Drive::get_text`](Drive::get_text).
+    fn get_text<'a>(&'a self, name: &'a str) -> OpsFuture<'a, Option<String>> {
+        Box::pin(async move {
+            match self.get_file_as_buffer(name).await? {
+                Some(bytes) => String::from_utf8(bytes.to_vec())
+                    .map(Some)
+                    .map_err(|error| Error::from_invalid_utf8(name, error)),
+                None => Ok(None),
+            }
+        })
+    }
+
+    /// Works like [`Drive::put_json`](Drive::put_json).
+    fn put_json<'a, T>(&'a self, name: &'a str, value: &T) -> OpsFuture<'a, PutFileResult>
+    where
+        T: Serialize,
+    {
+        let data = serde_json::to_vec(value);
+        Box::pin(async move {
+            self.put_file_bytes(name, data?.into(), Some(constants::JSON_CONTENT_TYPE))
+                .await
+        })
+    }
+
+    /// Works like [`Drive::get_json`](Drive::get_json).
+    fn get_json<'a, T>(&'a self, name: &'a str) -> OpsFuture<'a, Option<T>>
+    where
+        T: DeserializeOwned + Send + 'a,
+    {
+        Box::pin(async move {
+            match self.get_file_as_buffer(name).await? {
+                Some(bytes) => Ok(Some(utils::parse_raw_data(&bytes)?)),
+                None => Ok(None),
+            }
+        })
+    }
+}
+
+impl<D: DriveOps + ?Sized> DriveOpsExt for D {}
+
+impl DriveOps for Drive {
+    fn put_file_bytes<'a>(
+        &'a self,
+        name: &'a str,
+        data: Bytes,
+        content_type: Option<&'a str>,
+    ) -> OpsFuture<'a, PutFileResult> {
+        Box::pin(Drive::put_file(self, name, data, content_type))
+    }
+
+    fn get_file_as_buffer<'a>(&'a self, name: &'a str) -> OpsFuture<'a, Option<Bytes>> {
+        Box::pin(Drive::get_file_as_buffer(self, name))
+    }
+
+    fn head_file<'a>(&'a self, name: &'a str) -> OpsFuture<'a, Option<FileMetadata>> {
+        Box::pin(Drive::head_file(self, name))
+    }
+
+    fn list_files<'a>(
+        &'a self,
+        limit: Option<u32>,
+        prefix: Option<&'a str>,
+        last_name: Option<&'a str>,
+    ) -> OpsFuture<'a, ListFiles> {
+        Box::pin(Drive::list_files(self, limit, prefix, last_name))
+    }
+
+    fn delete_files<'a>(&'a self, names: &'a [String]) -> OpsFuture<'a, DeleteFiles> {
+        Box::pin(Drive::delete_files(self, names))
+    }
+}
+
+impl<D: DriveOps + ?Sized> DriveOps for Arc<D> {
+    fn put_file_bytes<'a>(
+        &'a self,
+        name: &'a str,
+        data: Bytes,
+        content_type: Option<&'a str>,
+    ) -> OpsFuture<'a, PutFileResult> {
+        (**self).put_file_bytes(name, data, content_type)
+    }
+
+    fn get_file_as_buffer<'a>(&'a self, name: &'a str) -> OpsFuture<'a, Option<Bytes>> {
+        (**self).get_file_as_buffer(name)
+    }
+
+    fn head_file<'a>(&'a self, name: &'a str) -> OpsFuture<'a, Option<FileMetadata>> {
+        (**self).head_file(name)
+    }
+
+    fn list_files<'a>(
+        &'a self,
+        limit: Option<u32>,
+        prefix: Option<&'a str>,
+        last_name: Option<&'a str>,
+    ) -> OpsFuture<'a, ListFiles> {
+        (**self).list_files(limit, prefix, last_name)
+    }
+
+    fn delete_files<'a>(&'a self, names: &'a [String]) -> OpsFuture<'a, DeleteFiles> {
+        (**self).delete_files(names)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::FakeDrive;
+    use serde_json::{json, Value};
+
+    async fn check_typed_methods(files: Arc<dyn DriveOps>) {
+        files.put_file("a.bin", vec![0, 159, 146], None).await.unwrap();
+        files.put_text("b.txt", "Zażółć").await.unwrap();
+        files.put_json("c.json", &json!({ "a": 1 })).await.unwrap();
+
+        let data = files.get_file_as_u8_vec("a.bin").await.unwrap();
+        assert_eq!(data, Some(vec![0, 159, 146]));
+        assert_eq!(
+            files.get_text("b.txt").await.unwrap().as_deref(),
+            Some("Zażółć")
+        );
+        let value: Option<Value> = files.get_json("c.json").await.unwrap();
+        assert_eq!(value, Some(json!({ "a": 1 })));
+        assert!(files.get_text("a.bin").await.unwrap_err().is_invalid_utf8());
+        assert!(files.get_text("missing.txt").await.unwrap().is_none());
+
+        let metadata = files.head_file("b.txt").await.unwrap().unwrap();
+        assert_eq!(
+            metadata.content_type.as_deref(),
+            Some(constants::TEXT_CONTENT_TYPE)
+        );
+
+        let listed = files.list_files(None, None, None).await.unwrap();
+        assert_eq!(listed.names, ["a.bin", "b.txt", "c.json"]);
+        files.delete_files(&listed.names).await.unwrap();
+        assert!(files
+            .list_files(None, None, None)
+            .await
+            .unwrap()
+            .names
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn drive_implements_ops() {
+        let fake = FakeDrive::new();
+        check_typed_methods(Arc::new(fake.drive())).await;
+    }
+
+    #[tokio::test]
+    async fn fake_drive_implements_ops() {
+        check_typed_methods(Arc::new(FakeDrive::new())).await;
+    }
+}
@@ -0,0 +1,55 @@
+//! Object-safe traits implemented by the services, to depend on them through `Arc<dyn ...>`
+//! and substitute fakes or decorators (e.g. caching or metrics) without changing the code using them.
+//!
+//! - [`DatabaseOps`](DatabaseOps) covers the core methods of [`Database`](crate::database::Database)
+//!   with items as [`serde_json::Value`], and [`DatabaseOpsExt`](DatabaseOpsExt) adds their typed versions.
+//! - [`DriveOps`](DriveOps) covers the core methods of [`Drive`](crate::drive::Drive),
+//!   and [`DriveOpsExt`](DriveOpsExt) adds the typed ones.
+//!
+//! The traits are implemented by the services, by the fakes from the `testing` module
+//! and by `Arc` of any implementation, so `Arc<dyn DatabaseOps>` can be passed wherever an implementation is expected. The inherent methods of the services stay the primary API;
+//! the extension methods have the same names and behavior, so the calls look the same.
+//!
+//! The helpers built on top of the services, e.g. the cache, the lock, the queue or the blob store,
+//! take any implementation of these traits, so they work the same on a shared `Arc<dyn DatabaseOps>` or on the fakes in tests.
+//!
+#![cfg_attr(feature = "base", doc = "```")]
+#![cfg_attr(not(feature = "base"), doc = "```ignore")]
+//! use deta_rust::{ops::{DatabaseOps, DatabaseOpsExt}, DetaClient};
+//! use deta_rust::database::Database;
+//! use std::sync::Arc;
+//!
+//! #[derive(serde::Deserialize)]
+//! struct User {
+//!     name: String,
+//! }
+//!
+//! async fn user_name(users: &dyn DatabaseOps, key: &str) -> deta_rust::error::Result<Option<String>> {
+//!     let user: Option<User> = users.get_item(key).await?;
+//!     Ok(user.map(|user| user.name))
+//! }
+//!
+//! # fn main() -> deta_rust::error::Result<()> {
+//! let client = DetaClient::new("project_key");
+//! let users: Arc<dyn DatabaseOps> = Arc::new(Database::new(&client, "users")?);
+//! let _future = user_name(users.as_ref(), "user_1");
+//! # Ok(())
+//! # }
+//! ```
+
+#[cfg(feature = "base")]
+mod database;
+#[cfg(feature = "drive")]
+mod drive;
+
+#[cfg(feature = "base")]
+pub use database::{DatabaseOps, DatabaseOpsExt};
+#[cfg(feature = "drive")]
+pub use drive::{DriveOps, DriveOpsExt};
+
+use crate::error::Result;
+use std::future::Future;
+use std::pin::Pin;
+
+/// The future returned by the methods of the traits.
+pub type OpsFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T>> + Send + 'a>>;
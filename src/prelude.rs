@@ -0,0 +1,38 @@
+//! The commonly used types, to be imported at once:
+//!
+//! ```
+//! use deta_rust::prelude::*;
+//!
+//! # fn run() -> Result<()> {
+//! let client = DetaClient::new("project_key");
+//! # #[cfg(feature = "base")]
+//! # {
+//! let database = Database::new(&client, "users")?;
+//! let query = Query::init().on("age", Condition::greater_than(18));
+//! # }
+//! # #[cfg(feature = "drive")]
+//! let drive = Drive::new(&client, "photos")?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The models of the responses and the request internals are left out, import them from their modules.
+
+pub use crate::error::{Error, Result};
+pub use crate::DetaClient;
+
+#[cfg(feature = "base")]
+pub use crate::database::{
+    query::{Condition, Query},
+    updates::{Action, Updates},
+    Database, Key,
+};
+#[cfg(feature = "base")]
+pub use crate::ops::{DatabaseOps, DatabaseOpsExt};
+
+#[cfg(feature = "drive")]
+pub use crate::drive::{Drive, PutFileOptions, PutFileResult};
+#[cfg(feature = "drive")]
+pub use crate::ops::{DriveOps, DriveOpsExt};
+#[cfg(feature = "drive")]
+pub use crate::TransferLimit;
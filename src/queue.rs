@@ -0,0 +1,562 @@
+//! A work queue kept in a base.
+//!
+//! Jobs are pushed as items of a base dedicated to the queue. Workers [`pull`](Queue::pull) them,
+//! which leases each job for the [visibility timeout](QueueConfig::visibility_timeout): until it passes,
+//! the job isn't given to other workers. The worker then [completes](Lease::complete) the job, deleting it,
+//! or [fails](Lease::fail) it, releasing it to be pulled again. A job which has been pulled
+//! [`max_attempts`](QueueConfig::max_attempts) times without being completed is moved to the dead letters,
+//! see [`Queue::dead_letters`](Queue::dead_letters).
+//!
+//! ```no_run
+//! use deta_rust::prelude::*;
+//! use deta_rust::queue::{Queue, QueueConfig};
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Email {
+//!     to: String,
+//!     subject: String,
+//! }
+//!
+//! # async fn send(email: &Email) -> std::result::Result<(), ()> { Ok(()) }
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() -> Result<()> {
+//! let client = DetaClient::new("project_key");
+//! let queue: Queue<Email> = Queue::new(Database::new(&client, "emails")?, QueueConfig::default());
+//!
+//! queue
+//!     .push(&Email { to: "anna@example.com".into(), subject: "Welcome".into() })
+//!     .await?;
+//!
+//! for lease in queue.pull(10).await? {
+//!     match send(lease.payload()).await {
+//!         Ok(()) => lease.complete().await?,
+//!         Err(()) => lease.fail().await?,
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! # Delivery guarantees
+//!
+//! The queue delivers every job **at least once**, and in no particular order. A job may be processed twice:
+//! - if the worker doesn't complete it within the visibility timeout, the job is given to another worker,
+//!   while the first one may still complete it,
+//! - if completing the job fails after it has been processed, e.g. because of a network error.
+//!
+//! The jobs should therefore be idempotent, or record their effects in a way which can be checked before
+//! repeating them. An attempt is counted when the job is pulled, so the attempts of a worker which crashed
+//! or timed out count towards `max_attempts` as well.
+//!
+//! # Claiming
+//!
+//! Deta has no conditional updates, so two workers reading the same released job can't both be stopped
+//! from leasing it by the update itself. Instead, a worker claims the job by inserting a lock item keyed by
+//! the job and its attempt number, e.g. `{key}.lock.2`: inserting an existing key fails with a conflict,
+//! so only one of the workers claims each attempt and the other one skips the job.
+//! The lock of the previous attempt is deleted when the next one is claimed, and the last one
+//! when the job is completed or dead-lettered.
+//!
+//! The base holds the jobs along with the locks, so it shouldn't be used for anything else.
+//!
+//! Available with the `base` feature.
+
+use crate::database::query::{Condition, Query};
+use crate::database::updates::{Action, Updates};
+use crate::error::{Error, Result};
+use crate::ops::DatabaseOps;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const STATUS_FIELD: &str = "status";
+const PAYLOAD_FIELD: &str = "payload";
+const ATTEMPTS_FIELD: &str = "attempts";
+const LOCKED_UNTIL_FIELD: &str = "locked_until";
+const PENDING_STATUS: &str = "pending";
+const DEAD_STATUS: &str = "dead";
+
+/// Configuration of a [`Queue`](Queue).
+#[derive(Debug, Clone)]
+pub struct QueueConfig {
+    /// Time for which a pulled job isn't given to other workers. Defaults to 30 seconds.
+    pub visibility_timeout: Duration,
+    /// Number of times a job is pulled before it's moved to the dead letters. Defaults to 5.
+    pub max_attempts: u32,
+}
+
+impl Default for QueueConfig {
+    fn default() -> Self {
+        Self {
+            visibility_timeout: Duration::from_secs(30),
+            max_attempts: 5,
+        }
+    }
+}
+
+/// A work queue of jobs with a payload of type `T` (see the [module documentation](self)).
+pub struct Queue<T> {
+    database: Arc<dyn DatabaseOps>,
+    config: QueueConfig,
+    payload: PhantomData<fn() -> T>,
+}
+
+/// A job leased by a worker with [`Queue::pull`](Queue::pull).
+/// It should be either [completed](Lease::complete) or [failed](Lease::fail) within the visibility timeout.
+pub struct Lease<T> {
+    key: String,
+    attempt: u32,
+    payload: T,
+    database: Arc<dyn DatabaseOps>,
+    max_attempts: u32,
+}
+
+/// A job moved to the dead letters after its last attempt, see [`Queue::dead_letters`](Queue::dead_letters).
+/// The payload is kept as JSON, as it may be the reason of the failures.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeadLetter {
+    /// Key of the job.
+    pub key: String,
+    /// Payload of the job.
+    pub payload: Value,
+    /// Number of the attempts made.
+    pub attempts: u32,
+}
+
+impl<T> Queue<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    /// Creates the queue of the jobs kept in the `database`.
+    pub fn new(database: impl DatabaseOps + 'static, config: QueueConfig) -> Self {
+        Self {
+            database: Arc::new(database),
+            config,
+            payload: PhantomData,
+        }
+    }
+
+    /// Adds a job with the `payload` to the queue. Returns the key of the job, generated by deta.
+    pub async fn push(&self, payload: &T) -> Result<String> {
+        let item = json!({
+            PAYLOAD_FIELD: serde_json::to_value(payload)?,
+            STATUS_FIELD: PENDING_STATUS,
+            ATTEMPTS_FIELD: 0,
+            LOCKED_UNTIL_FIELD: 0,
+        });
+        let stored = self.database.insert_item_value(item).await?;
+        match stored.get("key").and_then(Value::as_str) {
+            Some(key) => Ok(key.to_owned()),
+            None => Err(Error::from_failed_deserialization(Some(stored.to_string()))),
+        }
+    }
+
+    /// Leases up to `limit` jobs which aren't leased by other workers.
+    /// Returns fewer jobs if there aren't enough of them, or if other workers claim them at the same time.
+    ///
+    /// A job pulled for the last time without being completed, e.g. because the worker crashed,
+    /// is moved to the dead letters instead of being returned. So is a job whose payload
+    /// can't be deserialized as `T`, as no attempt could process it.
+    pub async fn pull(&self, limit: usize) -> Result<Vec<Lease<T>>> {
+        let now = now_millis();
+        let mut leases = vec![];
+        let mut last: Option<String> = None;
+        while leases.len() < limit {
+            let query = Query::init()
+                .on(STATUS_FIELD, Condition::equal(PENDING_STATUS))
+                .on(
+                    LOCKED_UNTIL_FIELD,
+                    Condition::less_than_or_equal(now as f64),
+                );
+            let page = self
+                .database
+                .fetch_item_values(None, last.as_deref(), Some(query))
+                .await?;
+            for item in page.items {
+                if leases.len() == limit {
+                    break;
+                }
+                if let Some(lease) = self.claim(item, now).await? {
+                    leases.push(lease);
+                }
+            }
+            match page.paging.last {
+                Some(next) => last = Some(next),
+                None => break,
+            }
+        }
+        Ok(leases)
+    }
+
+    /// Returns the jobs moved to the dead letters.
+    pub async fn dead_letters(&self) -> Result<Vec<DeadLetter>> {
+        let mut dead_letters = vec![];
+        let mut last: Option<String> = None;
+        loop {
+            let query = Query::init().on(STATUS_FIELD, Condition::equal(DEAD_STATUS));
+            let page = self
+                .database
+                .fetch_item_values(None, last.as_deref(), Some(query))
+                .await?;
+            for item in page.items {
+                let job = Job::parse(&item)?;
+                dead_letters.push(DeadLetter {
+                    key: job.key,
+                    payload: item.get(PAYLOAD_FIELD).cloned().unwrap_or(Value::Null),
+                    attempts: job.attempts,
+                });
+            }
+            match page.paging.last {
+                Some(next) => last = Some(next),
+                None => break,
+            }
+        }
+        Ok(dead_letters)
+    }
+
+    /// Moves the dead letter with the `key` back to the queue, with no attempts made.
+    /// Fails with a not found error if the job doesn't exist.
+    pub async fn requeue(&self, key: &str) -> Result<()> {
+        let updates = Updates::init()
+            .add(STATUS_FIELD, Action::set(PENDING_STATUS))
+            .add(ATTEMPTS_FIELD, Action::set(0))
+            .add(LOCKED_UNTIL_FIELD, Action::set(0));
+        match self.database.update_item(key, updates).await? {
+            Some(_) => Ok(()),
+            None => Err(Error::from_missing_item()),
+        }
+    }
+
+    // Claims the next attempt of the job, see the module documentation.
+    // Returns `None` if another worker has claimed it, or if the job has been dead-lettered.
+    async fn claim(&self, item: Value, now: u64) -> Result<Option<Lease<T>>> {
+        let job = Job::parse(&item)?;
+        if job.attempts >= self.config.max_attempts {
+            dead_letter(self.database.as_ref(), &job.key, job.attempts).await?;
+            return Ok(None);
+        }
+        let payload = item.get(PAYLOAD_FIELD).cloned().unwrap_or(Value::Null);
+        let payload = match serde_json::from_value(payload) {
+            Ok(payload) => payload,
+            Err(_) => {
+                dead_letter(self.database.as_ref(), &job.key, job.attempts).await?;
+                return Ok(None);
+            }
+        };
+
+        let lock = json!({ "key": lock_key(&job.key, job.attempts) });
+        match self.database.insert_item_value(lock).await {
+            Ok(_) => {}
+            Err(error) if error.is_conflict() => return Ok(None),
+            Err(error) => return Err(error),
+        }
+
+        let locked_until = now + self.config.visibility_timeout.as_millis() as u64;
+        let updates = Updates::init()
+            .add(LOCKED_UNTIL_FIELD, Action::set(locked_until))
+            .add(ATTEMPTS_FIELD, Action::increment(1));
+        let claimed = self.database.update_item(&job.key, updates).await?;
+        // Completed by the worker of the previous attempt in the meantime.
+        if claimed.is_none() {
+            delete_lock(self.database.as_ref(), &job.key, job.attempts).await;
+            return Ok(None);
+        }
+        if job.attempts > 0 {
+            delete_lock(self.database.as_ref(), &job.key, job.attempts - 1).await;
+        }
+
+        Ok(Some(Lease {
+            key: job.key,
+            attempt: job.attempts + 1,
+            payload,
+            database: self.database.clone(),
+            max_attempts: self.config.max_attempts,
+        }))
+    }
+}
+
+impl<T> Lease<T> {
+    /// Returns the key of the job.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Returns the number of the attempt, starting from 1.
+    pub fn attempt(&self) -> u32 {
+        self.attempt
+    }
+
+    /// Returns the payload of the job.
+    pub fn payload(&self) -> &T {
+        &self.payload
+    }
+
+    /// Consumes the lease, returning the payload of the job.
+    /// The job is pulled again once the visibility timeout passes.
+    pub fn into_payload(self) -> T {
+        self.payload
+    }
+
+    /// Removes the processed job from the queue.
+    pub async fn complete(self) -> Result<()> {
+        self.database.delete_item(&self.key).await?;
+        delete_lock(self.database.as_ref(), &self.key, self.attempt - 1).await;
+        Ok(())
+    }
+
+    /// Releases the job to be pulled again right away, or moves it to the dead letters
+    /// if it was the last attempt. Does nothing if the lease has expired and the job
+    /// has been pulled again or completed by another worker.
+    pub async fn fail(self) -> Result<()> {
+        let item = match self.database.get_item_value(&self.key).await? {
+            Some(item) => item,
+            None => return Ok(()),
+        };
+        let job = Job::parse(&item)?;
+        if job.attempts != self.attempt || job.status != PENDING_STATUS {
+            return Ok(());
+        }
+
+        if self.attempt >= self.max_attempts {
+            dead_letter(self.database.as_ref(), &self.key, self.attempt).await
+        } else {
+            let updates = Updates::init().add(LOCKED_UNTIL_FIELD, Action::set(0));
+            self.database.update_item(&self.key, updates).await?;
+            Ok(())
+        }
+    }
+}
+
+// The fields of a job item read by the queue.
+struct Job {
+    key: String,
+    status: String,
+    attempts: u32,
+}
+
+impl Job {
+    fn parse(item: &Value) -> Result<Self> {
+        let key = item.get("key").and_then(Value::as_str);
+        let status = item.get(STATUS_FIELD).and_then(Value::as_str);
+        // Incremented numbers are stored by deta as floats.
+        let attempts = item.get(ATTEMPTS_FIELD).and_then(Value::as_f64);
+        match (key, status, attempts) {
+            (Some(key), Some(status), Some(attempts)) => Ok(Self {
+                key: key.to_owned(),
+                status: status.to_owned(),
+                attempts: attempts as u32,
+            }),
+            _ => Err(Error::from_failed_deserialization(Some(item.to_string()))),
+        }
+    }
+}
+
+async fn dead_letter(database: &dyn DatabaseOps, key: &str, attempts: u32) -> Result<()> {
+    let updates = Updates::init().add(STATUS_FIELD, Action::set(DEAD_STATUS));
+    database.update_item(key, updates).await?;
+    if attempts > 0 {
+        delete_lock(database, key, attempts - 1).await;
+    }
+    Ok(())
+}
+
+fn lock_key(key: &str, attempt: u32) -> String {
+    format!("{}.lock.{}", key, attempt)
+}
+
+// Deletes the lock of the attempt counted from 0. A lock left behind only takes space,
+// as the attempt it claims is over, so a failure is ignored.
+async fn delete_lock(database: &dyn DatabaseOps, key: &str, attempt: u32) {
+    let _ = database.delete_item(&lock_key(key, attempt)).await;
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::FakeBase;
+    use serde::Deserialize;
+
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    struct Task {
+        id: u32,
+    }
+
+    fn queue(base: &FakeBase, visibility_timeout: Duration, max_attempts: u32) -> Queue<Task> {
+        Queue::new(
+            base.clone(),
+            QueueConfig {
+                visibility_timeout,
+                max_attempts,
+            },
+        )
+    }
+
+    fn ids(leases: &[Lease<Task>]) -> Vec<u32> {
+        leases.iter().map(|lease| lease.payload().id).collect()
+    }
+
+    // Keys of the lock items left in the base.
+    fn locks(base: &FakeBase) -> Vec<String> {
+        base.items()
+            .into_keys()
+            .filter(|key| key.contains(".lock."))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn push_pull_and_complete() {
+        let base = FakeBase::new();
+        let queue = queue(&base, Duration::from_secs(60), 3);
+        for id in 1..=3 {
+            queue.push(&Task { id }).await.unwrap();
+        }
+
+        let leases = queue.pull(2).await.unwrap();
+        assert_eq!(ids(&leases), vec![1, 2]);
+        assert!(leases.iter().all(|lease| lease.attempt() == 1));
+        // The leased jobs are skipped until the visibility timeout passes.
+        assert_eq!(ids(&queue.pull(5).await.unwrap()), vec![3]);
+        assert!(queue.pull(5).await.unwrap().is_empty());
+
+        for lease in leases {
+            lease.complete().await.unwrap();
+        }
+        let remaining: Vec<_> = base.items().into_keys().collect();
+        assert_eq!(remaining.len(), 2);
+        assert_eq!(locks(&base).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn failed_job_is_released() {
+        let base = FakeBase::new();
+        let queue = queue(&base, Duration::from_secs(60), 3);
+        queue.push(&Task { id: 1 }).await.unwrap();
+
+        let lease = queue.pull(1).await.unwrap().pop().unwrap();
+        lease.fail().await.unwrap();
+
+        let lease = queue.pull(1).await.unwrap().pop().unwrap();
+        assert_eq!(lease.attempt(), 2);
+        assert_eq!(lease.payload(), &Task { id: 1 });
+        // Only the lock of the current attempt is kept.
+        assert_eq!(locks(&base), vec![format!("{}.lock.1", lease.key())]);
+
+        lease.complete().await.unwrap();
+        assert!(base.items().is_empty());
+    }
+
+    #[tokio::test]
+    async fn expired_lease_is_pulled_again() {
+        let base = FakeBase::new();
+        let queue = queue(&base, Duration::ZERO, 3);
+        queue.push(&Task { id: 1 }).await.unwrap();
+
+        let first = queue.pull(1).await.unwrap().pop().unwrap();
+        let second = queue.pull(1).await.unwrap().pop().unwrap();
+        assert_eq!(second.attempt(), 2);
+
+        // The failure of the expired lease doesn't release the job leased again.
+        first.fail().await.unwrap();
+        let item = &base.items()[second.key()];
+        assert_eq!(item[ATTEMPTS_FIELD], 2.0);
+        assert_eq!(item[STATUS_FIELD], PENDING_STATUS);
+
+        second.complete().await.unwrap();
+        assert!(base.items().is_empty());
+    }
+
+    #[tokio::test]
+    async fn job_is_dead_lettered_after_max_attempts() {
+        let base = FakeBase::new();
+        let queue = queue(&base, Duration::ZERO, 2);
+        let key = queue.push(&Task { id: 1 }).await.unwrap();
+        queue.push(&Task { id: 2 }).await.unwrap();
+
+        for attempt in 1..=2 {
+            let leases = queue.pull(2).await.unwrap();
+            assert_eq!(ids(&leases), vec![1, 2]);
+            assert!(leases.iter().all(|lease| lease.attempt() == attempt));
+            for lease in leases {
+                if lease.payload().id == 1 {
+                    lease.fail().await.unwrap();
+                }
+            }
+        }
+
+        // The first job failed its last attempt, the second one timed out on it.
+        assert!(queue.pull(2).await.unwrap().is_empty());
+        let mut dead_letters = queue.dead_letters().await.unwrap();
+        dead_letters.sort_by_key(|dead| dead.payload["id"].as_u64());
+        assert_eq!(dead_letters.len(), 2);
+        assert_eq!(dead_letters[0].key, key);
+        assert_eq!(dead_letters[0].payload, json!({ "id": 1 }));
+        assert_eq!(dead_letters[0].attempts, 2);
+        assert!(locks(&base).is_empty());
+
+        queue.requeue(&key).await.unwrap();
+        let lease = queue.pull(2).await.unwrap().pop().unwrap();
+        assert_eq!(lease.key(), key);
+        assert_eq!(lease.attempt(), 1);
+        assert_eq!(queue.dead_letters().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn undeserializable_payload_is_dead_lettered() {
+        let base = FakeBase::new();
+        let raw: Queue<Value> = Queue::new(base.clone(), QueueConfig::default());
+        raw.push(&json!({ "unexpected": true })).await.unwrap();
+        let queue = queue(&base, Duration::from_secs(60), 3);
+        queue.push(&Task { id: 1 }).await.unwrap();
+
+        assert_eq!(ids(&queue.pull(5).await.unwrap()), vec![1]);
+        let dead_letters = queue.dead_letters().await.unwrap();
+        assert_eq!(dead_letters[0].payload, json!({ "unexpected": true }));
+        assert_eq!(dead_letters[0].attempts, 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_workers_claim_each_job_once() {
+        let base = FakeBase::new();
+        let producer = queue(&base, Duration::from_secs(60), 3);
+        for id in 0..10 {
+            producer.push(&Task { id }).await.unwrap();
+        }
+
+        let workers = (0..3).map(|_| {
+            let queue = queue(&base, Duration::from_secs(60), 3);
+            tokio::spawn(async move { ids(&queue.pull(10).await.unwrap()) })
+        });
+        let mut claimed = vec![];
+        for worker in workers {
+            claimed.extend(worker.await.unwrap());
+        }
+
+        claimed.sort_unstable();
+        assert_eq!(claimed, (0..10).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn claimed_attempt_is_skipped() {
+        let base = FakeBase::new();
+        let queue = queue(&base, Duration::from_secs(60), 3);
+        let key = queue.push(&Task { id: 1 }).await.unwrap();
+        // Another worker has inserted the lock of the first attempt, but not leased the job yet.
+        base.insert_item_value(json!({ "key": lock_key(&key, 0) }))
+            .await
+            .unwrap();
+
+        assert!(queue.pull(1).await.unwrap().is_empty());
+        assert_eq!(base.items()[&key][ATTEMPTS_FIELD], 0);
+    }
+}
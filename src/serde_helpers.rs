@@ -0,0 +1,317 @@
+//! Serde modules storing [`chrono::DateTime<Utc>`](chrono::DateTime) fields in a form deta base can query,
+//! to be used with `#[serde(with = "...")]`:
+//!
+//! ```
+//! use chrono::{DateTime, Utc};
+//! use deta_rust::serde_helpers::{rfc3339, ts_millis};
+//!
+//! #[derive(serde::Serialize, serde::Deserialize)]
+//! struct Post {
+//!     key: String,
+//!     #[serde(with = "ts_millis")]
+//!     created_at: DateTime<Utc>,
+//!     #[serde(with = "rfc3339::option", default)]
+//!     published_at: Option<DateTime<Utc>>,
+//! }
+//! ```
+//!
+//! The encoding decides which [`Condition`](crate::database::query::Condition) operators work on the field:
+//!
+//! | Module                     | Stored as                              | Operators                                           |
+//! |----------------------------|----------------------------------------|-----------------------------------------------------|
+//! | [`ts_millis`](ts_millis)   | number of milliseconds since the epoch | all, including `less_than`, `greater_than`, `range` |
+//! | [`ts_seconds`](ts_seconds) | number of seconds since the epoch      | all, including `less_than`, `greater_than`, `range` |
+//! | [`rfc3339`](rfc3339)       | text, e.g. `2024-05-01T12:30:00.000Z`  | `equal`, `not_equal`, `prefix`, `contains`          |
+//!
+//! Deta compares only numbers by order, so the numeric encodings are the ones to use for time ranges,
+//! e.g. with [`Condition::after_millis`](crate::database::query::Condition::after_millis).
+//! The text is always written in UTC with milliseconds, so a prefix selects a period,
+//! e.g. `Condition::prefix("2024-05")` matches May 2024.
+//! `ts_seconds` drops the fraction of a second.
+//!
+//! Every module has an `option` submodule for `Option<DateTime<Utc>>` fields, which stores `None` as `null`.
+//! Add `#[serde(default)]` to accept items without the field.
+//!
+//! Available with the `chrono` feature.
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serializer};
+
+/// Stores the time as the number of milliseconds since the Unix epoch.
+pub mod ts_millis {
+    use super::*;
+
+    /// Returns the stored value of the `time`, e.g. to build a condition.
+    pub fn encode(time: &DateTime<Utc>) -> i64 {
+        time.timestamp_millis()
+    }
+
+    pub fn serialize<S: Serializer>(
+        time: &DateTime<Utc>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i64(encode(time))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<DateTime<Utc>, D::Error> {
+        let millis = whole_number::<D>(f64::deserialize(deserializer)?)?;
+        DateTime::from_timestamp_millis(millis)
+            .ok_or_else(|| D::Error::custom(format!("Timestamp {} is out of range", millis)))
+    }
+
+    /// The same encoding for `Option<DateTime<Utc>>` fields.
+    pub mod option {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(
+            time: &Option<DateTime<Utc>>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            match time {
+                Some(time) => super::serialize(time, serializer),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Option<DateTime<Utc>>, D::Error> {
+            Option::<Millis>::deserialize(deserializer).map(|time| time.map(|Millis(time)| time))
+        }
+
+        #[derive(Deserialize)]
+        struct Millis(#[serde(with = "super")] DateTime<Utc>);
+    }
+}
+
+/// Stores the time as the number of whole seconds since the Unix epoch.
+pub mod ts_seconds {
+    use super::*;
+
+    /// Returns the stored value of the `time`, e.g. to build a condition.
+    pub fn encode(time: &DateTime<Utc>) -> i64 {
+        time.timestamp()
+    }
+
+    pub fn serialize<S: Serializer>(
+        time: &DateTime<Utc>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i64(encode(time))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<DateTime<Utc>, D::Error> {
+        let seconds = whole_number::<D>(f64::deserialize(deserializer)?)?;
+        DateTime::from_timestamp(seconds, 0)
+            .ok_or_else(|| D::Error::custom(format!("Timestamp {} is out of range", seconds)))
+    }
+
+    /// The same encoding for `Option<DateTime<Utc>>` fields.
+    pub mod option {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(
+            time: &Option<DateTime<Utc>>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            match time {
+                Some(time) => super::serialize(time, serializer),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Option<DateTime<Utc>>, D::Error> {
+            Option::<Seconds>::deserialize(deserializer).map(|time| time.map(|Seconds(time)| time))
+        }
+
+        #[derive(Deserialize)]
+        struct Seconds(#[serde(with = "super")] DateTime<Utc>);
+    }
+}
+
+/// Stores the time as RFC 3339 text in UTC with milliseconds, e.g. `2024-05-01T12:30:00.000Z`.
+/// Reading accepts any RFC 3339 offset.
+pub mod rfc3339 {
+    use super::*;
+
+    /// Returns the stored value of the `time`, e.g. to build a condition.
+    pub fn encode(time: &DateTime<Utc>) -> String {
+        time.to_rfc3339_opts(SecondsFormat::Millis, true)
+    }
+
+    pub fn serialize<S: Serializer>(
+        time: &DateTime<Utc>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&encode(time))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<DateTime<Utc>, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        DateTime::parse_from_rfc3339(&text)
+            .map(|time| time.with_timezone(&Utc))
+            .map_err(|error| {
+                D::Error::custom(format!("Invalid RFC 3339 time '{}': {}", text, error))
+            })
+    }
+
+    /// The same encoding for `Option<DateTime<Utc>>` fields.
+    pub mod option {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(
+            time: &Option<DateTime<Utc>>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            match time {
+                Some(time) => super::serialize(time, serializer),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Option<DateTime<Utc>>, D::Error> {
+            Option::<Text>::deserialize(deserializer).map(|time| time.map(|Text(time)| time))
+        }
+
+        #[derive(Deserialize)]
+        struct Text(#[serde(with = "super")] DateTime<Utc>);
+    }
+}
+
+// Deta may return whole numbers written as floats, e.g. after an increment.
+fn whole_number<'de, D: Deserializer<'de>>(number: f64) -> Result<i64, D::Error> {
+    if number.fract() == 0.0 && number.abs() < i64::MAX as f64 {
+        Ok(number as i64)
+    } else {
+        Err(D::Error::custom(format!(
+            "Timestamp {} isn't a whole number",
+            number
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use serde::Serialize;
+    use serde_json::json;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Event {
+        key: String,
+        #[serde(with = "ts_millis")]
+        created_at: DateTime<Utc>,
+        #[serde(with = "ts_seconds")]
+        day: DateTime<Utc>,
+        #[serde(with = "rfc3339")]
+        updated_at: DateTime<Utc>,
+        #[serde(with = "rfc3339::option", default)]
+        published_at: Option<DateTime<Utc>>,
+        #[serde(with = "ts_millis::option", default)]
+        deleted_at: Option<DateTime<Utc>>,
+    }
+
+    fn time() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 5, 1, 12, 30, 0).unwrap() + chrono::Duration::milliseconds(250)
+    }
+
+    fn event() -> Event {
+        Event {
+            key: "a".into(),
+            created_at: time(),
+            day: Utc.with_ymd_and_hms(2024, 5, 1, 0, 0, 0).unwrap(),
+            updated_at: time(),
+            published_at: Some(time()),
+            deleted_at: None,
+        }
+    }
+
+    #[cfg(feature = "base")]
+    #[tokio::test]
+    async fn round_trip_through_put_and_get() {
+        use crate::database::Database;
+        use crate::testing::MockTransport;
+        use std::sync::Arc;
+
+        let transport = MockTransport::new();
+        let database = Database::new(&crate::DetaClient::new("project_key"), "events")
+            .unwrap()
+            .with_transport(Arc::new(transport.clone()));
+        transport.respond(
+            "PUT",
+            "/items",
+            207,
+            json!({ "processed": { "items": [] } }),
+        );
+        database.put_items(&[event()]).await.unwrap();
+
+        let sent = transport.requests()[0].json().unwrap();
+        let stored = &sent["items"][0];
+        assert_eq!(
+            stored,
+            &json!({
+                "key": "a",
+                "created_at": 1714566600250_i64,
+                "day": 1714521600,
+                "updated_at": "2024-05-01T12:30:00.250Z",
+                "published_at": "2024-05-01T12:30:00.250Z",
+                "deleted_at": null
+            })
+        );
+
+        transport.respond("GET", "/items/a", 200, stored.clone());
+        let fetched: Option<Event> = database.get_item("a").await.unwrap();
+        assert_eq!(fetched, Some(event()));
+    }
+
+    #[test]
+    fn whole_floats_and_missing_options_are_accepted() {
+        let event: Event = serde_json::from_value(json!({
+            "key": "a",
+            "created_at": 1714566600250.0,
+            "day": 1714521600.0,
+            "updated_at": "2024-05-01T14:30:00.250+02:00"
+        }))
+        .unwrap();
+
+        assert_eq!(event.created_at, time());
+        assert_eq!(event.updated_at, time());
+        assert!(event.published_at.is_none());
+        assert!(event.deleted_at.is_none());
+    }
+
+    #[test]
+    fn invalid_values_are_rejected() {
+        let base =
+            json!({ "key": "a", "created_at": 1, "day": 1, "updated_at": "2024-05-01T12:30:00Z" });
+        for (field, value) in [
+            ("created_at", json!(1.5)),
+            ("created_at", json!("1714566600250")),
+            ("day", json!(1e300)),
+            ("updated_at", json!("2024-05-01 12:30")),
+        ] {
+            let mut item = base.clone();
+            item[field] = value;
+            assert!(serde_json::from_value::<Event>(item).is_err(), "{}", field);
+        }
+    }
+
+    #[test]
+    fn encode_returns_stored_values() {
+        assert_eq!(ts_seconds::encode(&time()), 1714566600);
+        assert_eq!(ts_millis::encode(&time()), 1714566600250);
+        assert_eq!(rfc3339::encode(&time()), "2024-05-01T12:30:00.250Z");
+    }
+}
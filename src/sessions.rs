@@ -0,0 +1,226 @@
+//! A session store for [`tower-sessions`](https://docs.rs/tower-sessions), keeping the sessions in a base.
+//!
+//! [`DetaSessionStore`](DetaSessionStore) implements the `SessionStore` trait of `tower-sessions` 0.14,
+//! so it can be passed to its `SessionManagerLayer` in an `axum` 0.8 application.
+//! See the `axum_sessions` example for a complete application with a login and a logout.
+//!
+//! ```no_run
+//! use deta_rust::prelude::*;
+//! use deta_rust::sessions::DetaSessionStore;
+//! # use tower_sessions::SessionManagerLayer;
+//!
+//! # fn main() -> Result<()> {
+//! let client = DetaClient::new("project_key");
+//! let store = DetaSessionStore::new(Database::new(&client, "sessions")?);
+//! let layer = SessionManagerLayer::new(store);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! # Items
+//!
+//! A session is stored as an item under its id, with the session data in the `data` field
+//! and the time of its expiry in the `__expires` field, in seconds since the Unix epoch, rounded up.
+//! Deta deletes the item by itself once it expires, so the abandoned sessions don't pile up in the base.
+//! Deta doesn't delete it right away though, so an expired session is loaded as a missing one.
+//!
+//! Available with the `sessions` feature.
+
+use crate::error::Error;
+use crate::ops::DatabaseOps;
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use time::OffsetDateTime;
+use tower_sessions_core::session::{Id, Record};
+use tower_sessions_core::session_store::{self, SessionStore};
+
+const DATA_FIELD: &str = "data";
+const EXPIRES_FIELD: &str = "__expires";
+
+/// A `tower-sessions` session store kept in a base (see the [module documentation](self)).
+#[derive(Clone)]
+pub struct DetaSessionStore {
+    database: Arc<dyn DatabaseOps>,
+}
+
+impl DetaSessionStore {
+    /// Creates the store of the sessions kept in the `database`.
+    pub fn new(database: impl DatabaseOps + 'static) -> Self {
+        Self {
+            database: Arc::new(database),
+        }
+    }
+}
+
+impl fmt::Debug for DetaSessionStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DetaSessionStore").finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl SessionStore for DetaSessionStore {
+    // Inserts the record, so a session is never created over another one with the same id.
+    async fn create(&self, record: &mut Record) -> session_store::Result<()> {
+        loop {
+            match self.database.insert_item_value(to_item(record)?).await {
+                Err(error) if error.is_conflict() => record.id = Id::default(),
+                result => return result.map(|_| ()).map_err(backend_error),
+            }
+        }
+    }
+
+    async fn save(&self, record: &Record) -> session_store::Result<()> {
+        let result = self
+            .database
+            .put_item_values(vec![to_item(record)?])
+            .await
+            .map_err(backend_error)?;
+        if result.has_failures() {
+            return Err(session_store::Error::Backend(format!(
+                "Deta failed to store the session '{}'",
+                record.id
+            )));
+        }
+        Ok(())
+    }
+
+    async fn load(&self, id: &Id) -> session_store::Result<Option<Record>> {
+        let item = self
+            .database
+            .get_item_value(&id.to_string())
+            .await
+            .map_err(backend_error)?;
+        match item {
+            Some(item) => from_item(*id, item),
+            None => Ok(None),
+        }
+    }
+
+    async fn delete(&self, id: &Id) -> session_store::Result<()> {
+        self.database
+            .delete_item(&id.to_string())
+            .await
+            .map_err(backend_error)?;
+        Ok(())
+    }
+}
+
+fn to_item(record: &Record) -> session_store::Result<Value> {
+    let expires =
+        record.expiry_date.unix_timestamp() + i64::from(record.expiry_date.nanosecond() > 0);
+    let data = serde_json::to_value(&record.data)
+        .map_err(|error| session_store::Error::Encode(error.to_string()))?;
+    Ok(json!({ "key": record.id.to_string(), DATA_FIELD: data, EXPIRES_FIELD: expires }))
+}
+
+// Returns `None` for an expired session, which deta hasn't deleted yet.
+fn from_item(id: Id, mut item: Value) -> session_store::Result<Option<Record>> {
+    let decode_error =
+        |item: &Value| session_store::Error::Decode(format!("Invalid session item: {}", item));
+    let expiry_date = item
+        .get(EXPIRES_FIELD)
+        .and_then(Value::as_f64)
+        .and_then(|expires| OffsetDateTime::from_unix_timestamp(expires as i64).ok())
+        .ok_or_else(|| decode_error(&item))?;
+    if expiry_date <= OffsetDateTime::now_utc() {
+        return Ok(None);
+    }
+    let data: HashMap<String, Value> = match item.get_mut(DATA_FIELD).map(Value::take) {
+        Some(data) => serde_json::from_value(data)
+            .map_err(|error| session_store::Error::Decode(error.to_string()))?,
+        None => return Err(decode_error(&item)),
+    };
+    Ok(Some(Record {
+        id,
+        data,
+        expiry_date,
+    }))
+}
+
+fn backend_error(error: Error) -> session_store::Error {
+    session_store::Error::Backend(error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::FakeBase;
+    use time::Duration;
+
+    fn record(expiry_date: OffsetDateTime) -> Record {
+        Record {
+            id: Id::default(),
+            data: HashMap::from([("user".to_owned(), json!("anna"))]),
+            expiry_date,
+        }
+    }
+
+    #[tokio::test]
+    async fn sessions_lifecycle() {
+        let base = FakeBase::new();
+        let store = DetaSessionStore::new(base.clone());
+        let expiry_date = OffsetDateTime::from_unix_timestamp(4_000_000_000).unwrap();
+        let mut session = record(expiry_date);
+
+        store.create(&mut session).await.unwrap();
+        let key = session.id.to_string();
+        assert_eq!(
+            base.items()[&key],
+            json!({ "key": key, "data": { "user": "anna" }, "__expires": 4_000_000_000u64 })
+        );
+        assert_eq!(
+            store.load(&session.id).await.unwrap(),
+            Some(session.clone())
+        );
+
+        session.data.insert("visits".to_owned(), json!(2));
+        store.save(&session).await.unwrap();
+        let loaded = store.load(&session.id).await.unwrap().unwrap();
+        assert_eq!(loaded.data["visits"], 2);
+
+        store.delete(&session.id).await.unwrap();
+        assert_eq!(store.load(&session.id).await.unwrap(), None);
+        assert!(base.items().is_empty());
+    }
+
+    #[tokio::test]
+    async fn create_never_replaces_session() {
+        let base = FakeBase::new();
+        let store = DetaSessionStore::new(base.clone());
+        let expiry_date = OffsetDateTime::now_utc() + Duration::hours(1);
+        let existing = record(expiry_date);
+        store.save(&existing).await.unwrap();
+
+        let mut session = record(expiry_date);
+        session.id = existing.id;
+        session.data.insert("user".to_owned(), json!("tom"));
+        store.create(&mut session).await.unwrap();
+
+        assert_ne!(session.id, existing.id);
+        assert_eq!(base.items().len(), 2);
+        let loaded = store.load(&existing.id).await.unwrap().unwrap();
+        assert_eq!(loaded.data["user"], "anna");
+    }
+
+    #[tokio::test]
+    async fn expired_session_is_missing() {
+        let base = FakeBase::new();
+        let store = DetaSessionStore::new(base.clone());
+        let session = record(OffsetDateTime::now_utc() - Duration::seconds(5));
+        store.save(&session).await.unwrap();
+
+        assert_eq!(store.load(&session.id).await.unwrap(), None);
+        // The expiry is rounded up to a whole second.
+        let session =
+            record(OffsetDateTime::from_unix_timestamp_nanos(4_000_000_000_500_000_000).unwrap());
+        store.save(&session).await.unwrap();
+        assert_eq!(
+            base.items()[&session.id.to_string()][EXPIRES_FIELD],
+            4_000_000_001u64
+        );
+    }
+}
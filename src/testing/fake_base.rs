@@ -0,0 +1,893 @@
+//! In-memory fake of deta base.
+
+use super::json_response;
+use crate::constants;
+use crate::database::models::{DeleteItem, FetchItems, PutItems, UpdateItem};
+use crate::database::query::Query;
+use crate::database::updates::{apply_action, Action, Updates};
+use crate::database::Database;
+use crate::ops::{DatabaseOps, OpsFuture};
+use crate::transport::{Transport, TransportFuture};
+use crate::DetaClient;
+use serde_json::{json, Map, Value};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+const DEFAULT_FETCH_LIMIT: usize = 1000;
+
+/// A [`Transport`](Transport) keeping the items in memory and answering the requests of
+/// [`Database`](Database) the way deta base does, so the code built on top of the database
+/// can be tested without network access and credentials.
+/// Clones share the items. The fake also implements [`DatabaseOps`](DatabaseOps),
+/// so it can be passed directly as `Arc<dyn DatabaseOps>`.
+///
+/// The fake follows the documented behavior of the service:
+/// - missing keys are generated, and non-object items are stored under the `value` field,
+/// - inserting an item with an existing key fails with `409 Conflict`,
+/// - queries support all [`Condition`](crate::database::query::Condition) operators
+///   and nested fields written with dots, e.g. `profile.age`,
+/// - fetched items are sorted by key, descending with [`Order::Desc`](crate::database::Order::Desc),
+///   and paginated with `last`,
+/// - updates support all [`Action`](crate::database::updates::Action) kinds.
+///
+/// Unlike the service, the fetch limit is applied to the matching items rather than the scanned ones,
+/// so a page is never shorter than the limit if more matching items exist.
+///
+/// ```
+/// use deta_rust::{database::query::{Condition, Query}, testing::FakeBase};
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let fake = FakeBase::new();
+/// let database = fake.database();
+/// database
+///     .put_items(&[
+///         serde_json::json!({ "key": "user_1", "age": 30 }),
+///         serde_json::json!({ "key": "user_2", "age": 17 }),
+///     ])
+///     .await
+///     .unwrap();
+///
+/// let query = Query::init().on("age", Condition::greater_than_or_equal(18));
+/// let adults = database
+///     .fetch_items::<serde_json::Value>(None, None, Some(query))
+///     .await
+///     .unwrap();
+///
+/// assert_eq!(adults.items, vec![serde_json::json!({ "key": "user_1", "age": 30 })]);
+/// assert_eq!(fake.items().len(), 2);
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct FakeBase {
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    items: BTreeMap<String, Value>,
+    generated_keys: u64,
+}
+
+// The status and the body of a response.
+type Reply = (u16, Value);
+
+impl FakeBase {
+    /// Creates an empty base.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a [`Database`](Database) sending its requests to this base.
+    pub fn database(&self) -> Database {
+        let client = DetaClient::new("fake_project_key");
+        Database::new(&client, "fake_base")
+            .expect("The fake base name is valid")
+            .with_transport(Arc::new(self.clone()))
+    }
+
+    /// Returns a snapshot of the stored items by key.
+    pub fn items(&self) -> BTreeMap<String, Value> {
+        self.lock().items.clone()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .expect("The fake base lock is never poisoned")
+    }
+
+    fn handle(&self, request: &reqwest::Request) -> Reply {
+        let body = request
+            .body()
+            .and_then(reqwest::Body::as_bytes)
+            .and_then(|bytes| serde_json::from_slice(bytes).ok())
+            .unwrap_or(Value::Null);
+        let segments: Vec<&str> = request
+            .url()
+            .path_segments()
+            .map(|segments| segments.collect())
+            .unwrap_or_default();
+
+        let mut state = self.lock();
+        match (request.method().as_str(), segments.as_slice()) {
+            ("PUT", [.., "items"]) => state.put(&body),
+            ("POST", [.., "items"]) => state.insert(&body),
+            ("POST", [.., "query"]) => state.query(&body),
+            ("GET", [.., "items", key]) => state.get(&decode_segment(key)),
+            ("DELETE", [.., "items", key]) => state.delete(&decode_segment(key)),
+            ("PATCH", [.., "items", key]) => state.update(&decode_segment(key), &body),
+            _ => error_reply(404, "Not found"),
+        }
+    }
+}
+
+impl Transport for FakeBase {
+    fn execute(&self, request: reqwest::Request) -> TransportFuture<'_> {
+        let (status, body) = self.handle(&request);
+        Box::pin(async move { Ok(json_response(status, &body)) })
+    }
+}
+
+// Every method goes through the database, so the items are processed exactly like the requests.
+impl DatabaseOps for FakeBase {
+    fn put_item_values(&self, items: Vec<Value>) -> OpsFuture<'_, PutItems<Value>> {
+        let database = self.database();
+        Box::pin(async move { database.put_items(&items).await })
+    }
+
+    fn get_item_value<'a>(&'a self, key: &'a str) -> OpsFuture<'a, Option<Value>> {
+        let database = self.database();
+        Box::pin(async move { database.get_item(key).await })
+    }
+
+    fn insert_item_value(&self, item: Value) -> OpsFuture<'_, Value> {
+        let database = self.database();
+        Box::pin(async move { database.insert_item(&item).await })
+    }
+
+    fn delete_item<'a>(&'a self, key: &'a str) -> OpsFuture<'a, DeleteItem> {
+        let database = self.database();
+        Box::pin(async move { database.delete_item(key).await })
+    }
+
+    fn fetch_item_values<'a>(
+        &'a self,
+        limit: Option<u32>,
+        last: Option<&'a str>,
+        query: Option<Query>,
+    ) -> OpsFuture<'a, FetchItems<Value>> {
+        let database = self.database();
+        Box::pin(async move { database.fetch_items(limit, last, query).await })
+    }
+
+    fn update_item<'a>(
+        &'a self,
+        key: &'a str,
+        updates: Updates,
+    ) -> OpsFuture<'a, Option<UpdateItem>> {
+        let database = self.database();
+        Box::pin(async move { database.update_item(key, updates).await })
+    }
+}
+
+impl State {
+    fn put(&mut self, body: &Value) -> Reply {
+        let items = match body.get("items").and_then(Value::as_array) {
+            Some(items) => items,
+            None => return error_reply(400, "Items must be an array"),
+        };
+        if items.len() > constants::MAX_PUT_BATCH_SIZE {
+            return error_reply(400, "At most 25 items can be put at once");
+        }
+
+        let mut processed = vec![];
+        let mut failed = vec![];
+        for item in items {
+            match self.prepare(item.clone()) {
+                Some(prepared) => {
+                    self.items.insert(item_key(&prepared), prepared.clone());
+                    processed.push(prepared);
+                }
+                None => failed.push(item.clone()),
+            }
+        }
+
+        let mut reply = json!({ "processed": { "items": processed } });
+        if !failed.is_empty() {
+            reply["failed"] = json!({ "items": failed });
+        }
+        (207, reply)
+    }
+
+    fn insert(&mut self, body: &Value) -> Reply {
+        let item = match body.get("item") {
+            Some(item) => item.clone(),
+            None => return error_reply(400, "Item is missing"),
+        };
+        let item = match self.prepare(item) {
+            Some(item) => item,
+            None => return error_reply(400, "Key must be a string"),
+        };
+
+        let key = item_key(&item);
+        if self.items.contains_key(&key) {
+            return error_reply(409, "Key already exists");
+        }
+        self.items.insert(key, item.clone());
+        (201, item)
+    }
+
+    fn get(&self, key: &str) -> Reply {
+        match self.items.get(key) {
+            Some(item) => (200, item.clone()),
+            None => (404, json!({ "key": key })),
+        }
+    }
+
+    fn delete(&mut self, key: &str) -> Reply {
+        self.items.remove(key);
+        (200, json!({ "key": key }))
+    }
+
+    fn update(&mut self, key: &str, body: &Value) -> Reply {
+        let mut item = match self.items.get(key) {
+            Some(item) => item.clone(),
+            None => return error_reply(404, "Key not found"),
+        };
+        if let Err(message) = apply_updates(&mut item, body) {
+            return error_reply(400, &message);
+        }
+        self.items.insert(key.to_owned(), item);
+
+        let mut reply = body.clone();
+        reply["key"] = key.into();
+        (200, reply)
+    }
+
+    fn query(&self, body: &Value) -> Reply {
+        let limit = match body.get("limit").and_then(Value::as_u64) {
+            Some(limit) if limit > 0 => limit as usize,
+            _ => DEFAULT_FETCH_LIMIT,
+        };
+        let last = body.get("last").and_then(Value::as_str);
+        let query = match parse_query(body.get("query").unwrap_or(&Value::Null)) {
+            Ok(query) => query,
+            Err(message) => return error_reply(400, &message),
+        };
+
+        let descending = body.get("sort").and_then(Value::as_str) == Some("desc");
+
+        let items: Box<dyn Iterator<Item = (&String, &Value)>> = if descending {
+            Box::new(self.items.iter().rev())
+        } else {
+            Box::new(self.items.iter())
+        };
+        let mut matching = items
+            .filter(|(key, _)| {
+                last.is_none_or(|last| {
+                    if descending {
+                        key.as_str() < last
+                    } else {
+                        key.as_str() > last
+                    }
+                })
+            })
+            .filter(|(_, item)| {
+                query.is_empty()
+                    || query.iter().any(|conditions| {
+                        conditions.iter().all(|condition| condition.matches(item))
+                    })
+            })
+            .map(|(_, item)| item);
+        let items: Vec<Value> = matching.by_ref().take(limit).cloned().collect();
+
+        let mut paging = json!({ "size": items.len() });
+        if matching.next().is_some() {
+            if let Some(item) = items.last() {
+                paging["last"] = item_key(item).into();
+            }
+        }
+        (200, json!({ "paging": paging, "items": items }))
+    }
+
+    // Turns the sent item into the stored one, wrapping a non-object in the `value` field
+    // and generating a missing key. Returns `None` if the key isn't a string.
+    fn prepare(&mut self, item: Value) -> Option<Value> {
+        let mut item = match item {
+            Value::Object(item) => item,
+            value => {
+                let mut item = Map::new();
+                item.insert("value".into(), value);
+                item
+            }
+        };
+
+        match item.get("key") {
+            Some(Value::String(key)) if !key.is_empty() => {}
+            None | Some(Value::String(_)) => {
+                let key = self.generate_key();
+                item.insert("key".into(), key.into());
+            }
+            Some(_) => return None,
+        }
+        Some(Value::Object(item))
+    }
+
+    // Generates keys in order of creation, so fetching returns the items in the order they were put.
+    fn generate_key(&mut self) -> String {
+        loop {
+            self.generated_keys += 1;
+            let key = format!("{:012}", self.generated_keys);
+            if !self.items.contains_key(&key) {
+                return key;
+            }
+        }
+    }
+}
+
+fn error_reply(status: u16, message: &str) -> Reply {
+    (status, json!({ "errors": [message] }))
+}
+
+fn item_key(item: &Value) -> String {
+    item["key"].as_str().unwrap_or_default().to_owned()
+}
+
+// Decodes the percent-encoded path segment, e.g. `user%201` into `user 1`.
+fn decode_segment(segment: &str) -> String {
+    let bytes = segment.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = match bytes.get(i + 1..i + 3) {
+            Some(hex) if bytes[i] == b'%' => std::str::from_utf8(hex)
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+            _ => None,
+        };
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operator {
+    Equal,
+    NotEqual,
+    LessThan,
+    GreaterThan,
+    LessThanOrEqual,
+    GreaterThanOrEqual,
+    Prefix,
+    Range,
+    Contains,
+    NotContains,
+}
+
+impl Operator {
+    fn from_postfix(postfix: &str) -> Option<Self> {
+        let operator = match postfix {
+            "ne" => Self::NotEqual,
+            "lt" => Self::LessThan,
+            "gt" => Self::GreaterThan,
+            "lte" => Self::LessThanOrEqual,
+            "gte" => Self::GreaterThanOrEqual,
+            "pfx" => Self::Prefix,
+            "r" => Self::Range,
+            "contains" => Self::Contains,
+            "not_contains" => Self::NotContains,
+            _ => return None,
+        };
+        Some(operator)
+    }
+}
+
+#[derive(Debug)]
+struct QueryCondition {
+    path: String,
+    operator: Operator,
+    value: Value,
+}
+
+impl QueryCondition {
+    fn matches(&self, item: &Value) -> bool {
+        let field = get_path(item, &self.path);
+        let compared = |accept: fn(Ordering) -> bool| {
+            field
+                .and_then(|field| compare(field, &self.value))
+                .is_some_and(accept)
+        };
+
+        match self.operator {
+            Operator::Equal => field.is_some_and(|field| equal(field, &self.value)),
+            Operator::NotEqual => !field.is_some_and(|field| equal(field, &self.value)),
+            Operator::LessThan => compared(Ordering::is_lt),
+            Operator::GreaterThan => compared(Ordering::is_gt),
+            Operator::LessThanOrEqual => compared(Ordering::is_le),
+            Operator::GreaterThanOrEqual => compared(Ordering::is_ge),
+            Operator::Prefix => match (field.and_then(Value::as_str), self.value.as_str()) {
+                (Some(field), Some(prefix)) => field.starts_with(prefix),
+                _ => false,
+            },
+            Operator::Range => match (field, self.value.as_array().map(Vec::as_slice)) {
+                (Some(field), Some([start, end])) => {
+                    compare(field, start).is_some_and(Ordering::is_ge)
+                        && compare(field, end).is_some_and(Ordering::is_le)
+                }
+                _ => false,
+            },
+            Operator::Contains => field.is_some_and(|field| contains(field, &self.value)),
+            Operator::NotContains => !field.is_some_and(|field| contains(field, &self.value)),
+        }
+    }
+}
+
+// Parses the rendered query: a list of objects, which are alternatives of their conditions.
+fn parse_query(query: &Value) -> Result<Vec<Vec<QueryCondition>>, String> {
+    let alternatives = match query {
+        Value::Null => return Ok(vec![]),
+        Value::Array(alternatives) => alternatives,
+        _ => return Err("Query must be an array".into()),
+    };
+
+    alternatives
+        .iter()
+        .map(|conditions| {
+            let conditions = conditions
+                .as_object()
+                .ok_or_else(|| "Query must be an array of objects".to_owned())?;
+            conditions
+                .iter()
+                .map(|(key, value)| {
+                    let (path, operator) = match key.split_once('?') {
+                        Some((path, postfix)) => match Operator::from_postfix(postfix) {
+                            Some(operator) => (path, operator),
+                            None => return Err(format!("Unknown query operator '{}'", postfix)),
+                        },
+                        None => (key.as_str(), Operator::Equal),
+                    };
+                    Ok(QueryCondition {
+                        path: path.to_owned(),
+                        operator,
+                        value: value.clone(),
+                    })
+                })
+                .collect()
+        })
+        .collect()
+}
+
+// Compares numbers by value, so that `10` equals `10.0`, and other values structurally.
+fn equal(a: &Value, b: &Value) -> bool {
+    compare(a, b).map_or(a == b, Ordering::is_eq)
+}
+
+fn compare(a: &Value, b: &Value) -> Option<Ordering> {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64()?.partial_cmp(&b.as_f64()?),
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        _ => None,
+    }
+}
+
+// A string contains a substring, an array contains an element.
+fn contains(field: &Value, value: &Value) -> bool {
+    match (field, value) {
+        (Value::String(field), Value::String(value)) => field.contains(value.as_str()),
+        (Value::Array(elements), value) => elements.iter().any(|element| equal(element, value)),
+        _ => false,
+    }
+}
+
+fn get_path<'a>(item: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.')
+        .try_fold(item, |value, field| value.get(field))
+}
+
+// Applies the rendered updates: `set`, `increment`, `append`, `prepend` and `delete` sections,
+// any of which can be null.
+fn apply_updates(item: &mut Value, updates: &Value) -> Result<(), String> {
+    let section = |name: &str| updates.get(name).and_then(Value::as_object);
+    let touched = ["set", "increment", "append", "prepend"]
+        .iter()
+        .filter_map(|name| section(name))
+        .flat_map(Map::keys)
+        .map(String::as_str)
+        .chain(
+            updates
+                .get("delete")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str),
+        );
+    for path in touched {
+        if path == "key" || path.starts_with("key.") {
+            return Err("The key cannot be updated".into());
+        }
+    }
+
+    for (path, value) in section("set").into_iter().flatten() {
+        apply_action(item, &fields(path), Action::Set(value.clone()))?;
+    }
+
+    for (path, by) in section("increment").into_iter().flatten() {
+        let by = by
+            .as_f64()
+            .ok_or_else(|| format!("Cannot increment '{}' by {}", path, by))?;
+        apply_action(item, &fields(path), Action::Increment(by))?;
+    }
+
+    for (name, prepend) in [("append", false), ("prepend", true)] {
+        for (path, values) in section(name).into_iter().flatten() {
+            let values = values
+                .as_array()
+                .ok_or_else(|| format!("Values to {} to '{}' must be an array", name, path))?
+                .clone();
+            let action = if prepend {
+                Action::Prepend(values)
+            } else {
+                Action::Append(values)
+            };
+            apply_action(item, &fields(path), action)?;
+        }
+    }
+
+    for path in updates
+        .get("delete")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+    {
+        apply_action(item, &fields(path), Action::Delete)?;
+    }
+    Ok(())
+}
+
+// Splits the path of an updated attribute, as deta reads every dot as nesting.
+fn fields(path: &str) -> Vec<&str> {
+    path.split('.').collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{
+        query::{Condition, Query},
+        updates::{Action, Attr, Updates},
+        FetchOptions, Order,
+    };
+    use crate::error::ResponseStatusKind;
+
+    async fn seeded() -> (FakeBase, Database) {
+        let fake = FakeBase::new();
+        let database = fake.database();
+        database
+            .put_items(&[
+                json!({ "key": "a", "name": "Anna", "age": 30, "tags": ["admin"], "profile": { "city": "Oslo" } }),
+                json!({ "key": "b", "name": "Bob", "age": 17.5, "tags": [] }),
+                json!({ "key": "c", "name": "Cecil", "age": -4 }),
+            ])
+            .await
+            .unwrap();
+        (fake, database)
+    }
+
+    async fn keys(database: &Database, query: Query) -> Vec<String> {
+        database
+            .fetch_items::<Value>(None, None, Some(query))
+            .await
+            .unwrap()
+            .items
+            .iter()
+            .map(item_key)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn put_generates_keys_and_wraps_values() {
+        let fake = FakeBase::new();
+        let result = fake
+            .database()
+            .put_items(&[json!({ "name": "Anna" }), json!(7), json!({ "key": 1 })])
+            .await
+            .unwrap();
+
+        let processed = result.processed.items;
+        assert_eq!(
+            processed[0],
+            json!({ "key": "000000000001", "name": "Anna" })
+        );
+        assert_eq!(processed[1], json!({ "key": "000000000002", "value": 7 }));
+        assert_eq!(result.failed.unwrap().items, vec![json!({ "key": 1 })]);
+        assert_eq!(fake.items().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn put_rejects_too_many_items() {
+        let items: Vec<Value> = (0..26).map(|i| json!({ "value": i })).collect();
+        let error = FakeBase::new()
+            .database()
+            .put_items(&items)
+            .await
+            .unwrap_err();
+        assert_eq!(error.status_kind(), Some(&ResponseStatusKind::BadRequest));
+    }
+
+    #[tokio::test]
+    async fn insert_conflicts_with_existing_key() {
+        let (fake, database) = seeded().await;
+        let error = database
+            .insert_item(&json!({ "key": "a", "name": "Other" }))
+            .await
+            .unwrap_err();
+
+        assert_eq!(error.status_kind(), Some(&ResponseStatusKind::Conflict));
+        assert_eq!(fake.items()["a"]["name"], "Anna");
+    }
+
+    #[tokio::test]
+    async fn get_and_delete_item() {
+        let (fake, database) = seeded().await;
+        let item: Option<Value> = database.get_item("a").await.unwrap();
+        assert_eq!(item.unwrap()["name"], "Anna");
+
+        database.delete_item("a").await.unwrap();
+        database.delete_item("missing").await.unwrap();
+        assert!(database.get_item::<Value>("a").await.unwrap().is_none());
+        assert_eq!(fake.items().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn get_item_with_encoded_key() {
+        let fake = FakeBase::new();
+        let database = fake.database();
+        database
+            .put_items(&[json!({ "key": "user 1" })])
+            .await
+            .unwrap();
+        assert!(database
+            .get_item::<Value>("user 1")
+            .await
+            .unwrap()
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn query_with_all_operators() {
+        let (_, database) = seeded().await;
+        let cases = [
+            (Query::init().on("name", Condition::equal("Bob")), vec!["b"]),
+            (Query::init().on("age", Condition::equal(30.0)), vec!["a"]),
+            (
+                Query::init().on("name", Condition::not_equal("Bob")),
+                vec!["a", "c"],
+            ),
+            (
+                Query::init().on("age", Condition::less_than(17.5)),
+                vec!["c"],
+            ),
+            (
+                Query::init().on("age", Condition::greater_than(17.5)),
+                vec!["a"],
+            ),
+            (
+                Query::init().on("age", Condition::less_than_or_equal(17.5)),
+                vec!["b", "c"],
+            ),
+            (
+                Query::init().on("age", Condition::greater_than_or_equal(17.5)),
+                vec!["a", "b"],
+            ),
+            (Query::init().on("name", Condition::prefix("Ce")), vec!["c"]),
+            (
+                Query::init().on("age", Condition::range(-4.0, 17.5)),
+                vec!["b", "c"],
+            ),
+            (
+                Query::init().on("name", Condition::contains("o")),
+                vec!["b"],
+            ),
+            (
+                Query::init().on("tags", Condition::contains("admin")),
+                vec!["a"],
+            ),
+            (
+                Query::init().on("name", Condition::not_contains("o")),
+                vec!["a", "c"],
+            ),
+            (
+                Query::init().on("profile.city", Condition::equal("Oslo")),
+                vec!["a"],
+            ),
+            (
+                Query::init()
+                    .on("age", Condition::greater_than(0))
+                    .on("name", Condition::prefix("B")),
+                vec!["b"],
+            ),
+            (
+                Query::init()
+                    .on("name", Condition::equal("Anna"))
+                    .either()
+                    .on("age", Condition::less_than(0)),
+                vec!["a", "c"],
+            ),
+        ];
+
+        for (query, expected) in cases {
+            assert_eq!(keys(&database, query).await, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn query_rejects_unknown_operator() {
+        let fake = FakeBase::new();
+        let reply = fake
+            .lock()
+            .query(&json!({ "query": [{ "age?between": 1 }] }));
+        assert_eq!(reply.0, 400);
+    }
+
+    #[tokio::test]
+    async fn fetch_paginates_with_last() {
+        let (_, database) = seeded().await;
+        let first = database
+            .fetch_items::<Value>(Some(2), None, None)
+            .await
+            .unwrap();
+        assert_eq!(first.items.len(), 2);
+        let last = first.paging.last.clone().unwrap();
+        assert_eq!(last, "b");
+
+        let second = database
+            .fetch_items::<Value>(Some(2), Some(&last), None)
+            .await
+            .unwrap();
+        assert_eq!(second.items.iter().map(item_key).collect::<Vec<_>>(), ["c"]);
+        assert!(second.paging.last.is_none());
+    }
+
+    #[tokio::test]
+    async fn fetch_paginates_in_descending_order() {
+        let (_, database) = seeded().await;
+        let options = |last: Option<String>| FetchOptions {
+            limit: Some(2),
+            last,
+            order: Some(Order::Desc),
+            ..Default::default()
+        };
+
+        let first = database
+            .fetch_items_with::<Value>(options(None))
+            .await
+            .unwrap();
+        assert_eq!(
+            first.items.iter().map(item_key).collect::<Vec<_>>(),
+            ["c", "b"]
+        );
+
+        let second = database
+            .fetch_items_with::<Value>(options(first.paging.last))
+            .await
+            .unwrap();
+        assert_eq!(second.items.iter().map(item_key).collect::<Vec<_>>(), ["a"]);
+        assert!(second.paging.last.is_none());
+    }
+
+    #[tokio::test]
+    async fn update_applies_all_actions() {
+        let (fake, database) = seeded().await;
+        let updates = Updates::init()
+            .add("profile.city", Action::set("Bergen"))
+            .add("age", Action::increment(1))
+            .add("visits", Action::increment(2.5))
+            .add("tags", Action::append("editor"))
+            .add("history", Action::prepend_many(&["x", "y"]))
+            .add("name", Action::delete());
+        let result = database.update_item("a", updates).await.unwrap().unwrap();
+        assert_eq!(result.key, "a");
+
+        let item = &fake.items()["a"];
+        assert_eq!(item["profile"]["city"], "Bergen");
+        assert_eq!(item["age"], 31);
+        assert_eq!(item["visits"], 2.5);
+        assert_eq!(item["tags"], json!(["admin", "editor"]));
+        assert_eq!(item["history"], json!(["x", "y"]));
+        assert!(item.get("name").is_none());
+    }
+
+    #[tokio::test]
+    async fn update_skips_missing_item_and_fails_for_invalid_actions() {
+        let (fake, database) = seeded().await;
+        let result = database
+            .update_item("missing", Updates::init().add("age", Action::increment(1)))
+            .await
+            .unwrap();
+        assert!(result.is_none());
+        assert!(!fake.items().contains_key("missing"));
+
+        let error = database
+            .update_item("a", Updates::init().add("name", Action::increment(1)))
+            .await
+            .unwrap_err();
+        assert_eq!(error.status_kind(), Some(&ResponseStatusKind::BadRequest));
+        assert_eq!(fake.items()["a"]["name"], "Anna");
+    }
+
+    #[tokio::test]
+    async fn update_fields_with_dots_in_names() {
+        let (fake, database) = seeded().await;
+        database
+            .put_items(&[json!({
+                "key": "c",
+                "config": { "v1": { "enabled": false } },
+                "config.v1": { "enabled": false },
+                "hits.total": 1,
+                "tags.old": ["a"],
+            })])
+            .await
+            .unwrap();
+        let updates = Updates::init()
+            .add(
+                Attr::literal("config.v1").field("enabled"),
+                Action::set(true),
+            )
+            .add(Attr::literal("hits.total"), Action::increment(2))
+            .add(Attr::literal("tags.old"), Action::delete())
+            .add("profile.city", Action::set("Oslo"));
+        let result = database.update_item("c", updates).await.unwrap().unwrap();
+        assert_eq!(result.key, "c");
+        assert_eq!(result.set.unwrap()["config.v1.enabled"], true);
+
+        assert_eq!(
+            fake.items()["c"],
+            json!({
+                "key": "c",
+                "config": { "v1": { "enabled": false } },
+                "config.v1": { "enabled": true },
+                "hits.total": 3,
+                "profile": { "city": "Oslo" },
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn update_fields_with_dots_fails_like_service() {
+        let (fake, database) = seeded().await;
+        let result = database
+            .update_item(
+                "missing",
+                Updates::init().add(Attr::literal("a.b"), Action::set(1)),
+            )
+            .await
+            .unwrap();
+        assert!(result.is_none());
+
+        let updates = Updates::init()
+            .add(Attr::literal("a.b"), Action::set(1))
+            .add("name", Action::increment(1));
+        let error = database.update_item("a", updates).await.unwrap_err();
+        assert!(error.is_invalid_argument());
+        assert!(fake.items()["a"].get("a.b").is_none());
+    }
+
+    #[test]
+    fn decode_segment_for_escapes() {
+        assert_eq!(decode_segment("user%201"), "user 1");
+        assert_eq!(decode_segment("%C5%BC%"), "ż%");
+        assert_eq!(decode_segment("plain"), "plain");
+    }
+}
@@ -0,0 +1,610 @@
+//! In-memory fake of deta drive.
+
+use super::json_response;
+use crate::constants;
+use crate::drive::models::{DeleteFiles, FileMetadata, ListFiles};
+use crate::drive::{Drive, PutFileResult};
+use crate::ops::{DriveOps, OpsFuture};
+use crate::transport::{Transport, TransportFuture};
+use crate::DetaClient;
+use reqwest::header::{CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, RANGE};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+const DEFAULT_LIST_LIMIT: usize = 1000;
+const DEFAULT_CONTENT_TYPE: &str = "application/octet-stream";
+
+/// A [`Transport`](Transport) keeping the files in memory and answering the requests of
+/// [`Drive`](Drive) the way deta drive does, so the code uploading and downloading files
+/// can be tested without network access and credentials.
+/// Clones share the files. The fake also implements [`DriveOps`](DriveOps),
+/// so it can be passed directly as `Arc<dyn DriveOps>`.
+///
+/// The fake reproduces the behavior of the service, including its quirks:
+/// - a single upload larger than 10MB fails with `413 Payload Too Large`, so bigger files go through
+///   the chunked upload, whose parts must have between 5MB and 10MB (except the last one),
+/// - names are listed in lexicographic order, paginated with `last`, and the last page has no paging,
+/// - deleting reports every name as deleted, including the names of files that didn't exist,
+/// - downloads support the `Range` header with a single range, e.g. `bytes=100-` or `bytes=100-199`,
+///   so interrupted downloads can be resumed.
+///
+/// ```
+/// use deta_rust::testing::FakeDrive;
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let fake = FakeDrive::new();
+/// let drive = fake.drive();
+/// drive.put_file("notes/today.txt", b"hello".to_vec(), None).await.unwrap();
+///
+/// let data = drive.get_file_as_u8_vec("notes/today.txt").await.unwrap();
+/// assert_eq!(data.as_deref(), Some(&b"hello"[..]));
+/// assert_eq!(fake.files()["notes/today.txt"], b"hello");
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct FakeDrive {
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    files: BTreeMap<String, StoredFile>,
+    uploads: HashMap<String, Upload>,
+    started_uploads: u64,
+}
+
+#[derive(Debug)]
+struct StoredFile {
+    data: Vec<u8>,
+    content_type: String,
+}
+
+#[derive(Debug)]
+struct Upload {
+    name: String,
+    parts: BTreeMap<usize, Vec<u8>>,
+}
+
+// The parsed request: the path after the drive name, the query parameters and the body.
+struct FakeRequest<'a> {
+    method: &'a str,
+    path: Vec<&'a str>,
+    params: HashMap<String, String>,
+    headers: &'a reqwest::header::HeaderMap,
+    body: &'a [u8],
+    project_id: &'a str,
+    drive_name: &'a str,
+}
+
+impl<'a> FakeRequest<'a> {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params.get(name).map(String::as_str)
+    }
+
+    fn header(&self, name: reqwest::header::HeaderName) -> Option<&str> {
+        self.headers.get(name)?.to_str().ok()
+    }
+
+    fn json(&self) -> Value {
+        serde_json::from_slice(self.body).unwrap_or(Value::Null)
+    }
+
+    // Returns the body describing the file, sent by deta after uploading it.
+    fn file_reply(&self, name: &str) -> Value {
+        json!({ "name": name, "project_id": self.project_id, "drive_name": self.drive_name })
+    }
+}
+
+impl FakeDrive {
+    /// Creates an empty drive.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a [`Drive`](Drive) sending its requests to this drive.
+    pub fn drive(&self) -> Drive {
+        let client = DetaClient::new("fake_project_key");
+        Drive::new(&client, "fake_drive")
+            .expect("The fake drive name is valid")
+            .with_transport(Arc::new(self.clone()))
+    }
+
+    /// Returns a snapshot of the stored files by name.
+    pub fn files(&self) -> BTreeMap<String, Vec<u8>> {
+        self.lock()
+            .files
+            .iter()
+            .map(|(name, file)| (name.clone(), file.data.clone()))
+            .collect()
+    }
+
+    /// Returns the content type the file has been uploaded with, or `None` if the file doesn't exist.
+    pub fn content_type(&self, name: &str) -> Option<String> {
+        Some(self.lock().files.get(name)?.content_type.clone())
+    }
+
+    /// Returns the number of chunked uploads which have been started, but neither finished nor aborted.
+    pub fn pending_uploads(&self) -> usize {
+        self.lock().uploads.len()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .expect("The fake drive lock is never poisoned")
+    }
+
+    fn handle(&self, request: &reqwest::Request) -> reqwest::Response {
+        let segments: Vec<&str> = request
+            .url()
+            .path_segments()
+            .map(|segments| segments.collect())
+            .unwrap_or_default();
+        // The path starts with the API version, the project id and the drive name.
+        let (project_id, drive_name, path) = match segments.as_slice() {
+            [_, project_id, drive_name, path @ ..] => (*project_id, *drive_name, path.to_vec()),
+            _ => return error_response(404, "Not found"),
+        };
+        let request = FakeRequest {
+            method: request.method().as_str(),
+            path,
+            params: request.url().query_pairs().into_owned().collect(),
+            headers: request.headers(),
+            body: request
+                .body()
+                .and_then(reqwest::Body::as_bytes)
+                .unwrap_or_default(),
+            project_id,
+            drive_name,
+        };
+
+        let mut state = self.lock();
+        match (request.method, request.path.as_slice()) {
+            ("POST", ["files"]) => state.put(&request),
+            ("GET", ["files"]) => state.list(&request),
+            ("DELETE", ["files"]) => state.delete(&request),
+            ("GET" | "HEAD", ["files", "download"]) => state.download(&request),
+            ("POST", ["uploads"]) => state.start_upload(&request),
+            ("POST", ["uploads", id, "parts"]) => state.upload_part(id, &request),
+            ("PATCH", ["uploads", id]) => state.end_upload(id, &request),
+            ("DELETE", ["uploads", id]) => state.abort_upload(id, &request),
+            _ => error_response(404, "Not found"),
+        }
+    }
+}
+
+impl Transport for FakeDrive {
+    fn execute(&self, request: reqwest::Request) -> TransportFuture<'_> {
+        let response = self.handle(&request);
+        Box::pin(async move { Ok(response) })
+    }
+}
+
+// Every method goes through the drive, so the files are processed exactly like the requests.
+impl DriveOps for FakeDrive {
+    fn put_file_bytes<'a>(
+        &'a self,
+        name: &'a str,
+        data: bytes::Bytes,
+        content_type: Option<&'a str>,
+    ) -> OpsFuture<'a, PutFileResult> {
+        let drive = self.drive();
+        Box::pin(async move { drive.put_file(name, data, content_type).await })
+    }
+
+    fn get_file_as_buffer<'a>(&'a self, name: &'a str) -> OpsFuture<'a, Option<bytes::Bytes>> {
+        let drive = self.drive();
+        Box::pin(async move { drive.get_file_as_buffer(name).await })
+    }
+
+    fn head_file<'a>(&'a self, name: &'a str) -> OpsFuture<'a, Option<FileMetadata>> {
+        let drive = self.drive();
+        Box::pin(async move { drive.head_file(name).await })
+    }
+
+    fn list_files<'a>(
+        &'a self,
+        limit: Option<u32>,
+        prefix: Option<&'a str>,
+        last_name: Option<&'a str>,
+    ) -> OpsFuture<'a, ListFiles> {
+        let drive = self.drive();
+        Box::pin(async move { drive.list_files(limit, prefix, last_name).await })
+    }
+
+    fn delete_files<'a>(&'a self, names: &'a [String]) -> OpsFuture<'a, DeleteFiles> {
+        let drive = self.drive();
+        Box::pin(async move { drive.delete_files(names).await })
+    }
+}
+
+impl State {
+    fn put(&mut self, request: &FakeRequest) -> reqwest::Response {
+        let name = match request.param("name") {
+            Some(name) if !name.is_empty() => name,
+            _ => return error_response(400, "Name is missing"),
+        };
+        if request.body.len() > constants::MAX_DATA_CHUNK_SIZE {
+            return error_response(413, "Request entity too large");
+        }
+
+        let content_type = request
+            .header(CONTENT_TYPE)
+            .unwrap_or(DEFAULT_CONTENT_TYPE)
+            .to_owned();
+        self.files.insert(
+            name.to_owned(),
+            StoredFile {
+                data: request.body.to_vec(),
+                content_type,
+            },
+        );
+        json_response(201, &request.file_reply(name))
+    }
+
+    fn download(&self, request: &FakeRequest) -> reqwest::Response {
+        let file = match request.param("name").and_then(|name| self.files.get(name)) {
+            Some(file) => file,
+            None => return error_response(404, "File not found"),
+        };
+
+        let size = file.data.len();
+        // Either `bytes=start-` or `bytes=start-end`, with the end inclusive and cut to the size.
+        let range = request
+            .header(RANGE)
+            .and_then(|range| range.strip_prefix("bytes="))
+            .and_then(|range| range.split_once('-'))
+            .and_then(|(start, end)| {
+                let start = start.parse::<usize>().ok()?;
+                match end {
+                    "" => Some((start, size)),
+                    end => Some((
+                        start,
+                        end.parse::<usize>().ok()?.saturating_add(1).min(size),
+                    )),
+                }
+            });
+        let (status, content) = match range {
+            Some((start, end)) if start < end => (206, &file.data[start..end]),
+            Some(_) => return error_response(416, "Range not satisfiable"),
+            None => (200, &file.data[..]),
+        };
+
+        let mut response = http::Response::builder()
+            .status(status)
+            .header(CONTENT_TYPE, &file.content_type)
+            .header(CONTENT_LENGTH, content.len());
+        if let Some((start, end)) = range {
+            let range = format!("bytes {}-{}/{}", start, end - 1, size);
+            response = response.header(CONTENT_RANGE, range);
+        }
+        // A HEAD response only declares the length of the content.
+        let body = match request.method {
+            "HEAD" => vec![],
+            _ => content.to_vec(),
+        };
+        reqwest::Response::from(response.body(body).expect("The fake response is valid"))
+    }
+
+    fn list(&self, request: &FakeRequest) -> reqwest::Response {
+        let limit = match request.param("limit").map(str::parse::<usize>) {
+            None => DEFAULT_LIST_LIMIT,
+            Some(Ok(limit)) if limit > 0 => limit,
+            Some(_) => return error_response(400, "Limit must be a positive number"),
+        };
+        let prefix = request.param("prefix").unwrap_or_default();
+        let last = request.param("last");
+
+        let mut names = self
+            .files
+            .keys()
+            .filter(|name| name.starts_with(prefix))
+            .filter(|name| last.is_none_or(|last| name.as_str() > last));
+        let page: Vec<&String> = names.by_ref().take(limit).collect();
+
+        let mut reply = json!({ "names": page });
+        // The paging is sent only when there are more names to list.
+        if names.next().is_some() {
+            reply["paging"] = json!({ "size": page.len(), "last": page.last() });
+        }
+        json_response(200, &reply)
+    }
+
+    fn delete(&mut self, request: &FakeRequest) -> reqwest::Response {
+        let body = request.json();
+        let names: Vec<&str> = match body.get("names").and_then(Value::as_array) {
+            Some(names) => names.iter().filter_map(Value::as_str).collect(),
+            None => return error_response(400, "Names must be an array"),
+        };
+        if names.len() > constants::MAX_DELETE_BATCH_SIZE {
+            return error_response(400, "At most 1000 files can be deleted at once");
+        }
+
+        for name in &names {
+            self.files.remove(*name);
+        }
+        json_response(200, &json!({ "deleted": names }))
+    }
+
+    fn start_upload(&mut self, request: &FakeRequest) -> reqwest::Response {
+        let name = match request.param("name") {
+            Some(name) if !name.is_empty() => name,
+            _ => return error_response(400, "Name is missing"),
+        };
+
+        self.started_uploads += 1;
+        let upload_id = format!("upload_{}", self.started_uploads);
+        self.uploads.insert(
+            upload_id.clone(),
+            Upload {
+                name: name.to_owned(),
+                parts: BTreeMap::new(),
+            },
+        );
+        let mut reply = request.file_reply(name);
+        reply["upload_id"] = upload_id.into();
+        json_response(202, &reply)
+    }
+
+    fn upload_part(&mut self, upload_id: &str, request: &FakeRequest) -> reqwest::Response {
+        let part = match request.param("part").map(str::parse::<usize>) {
+            Some(Ok(part)) if part > 0 => part,
+            _ => return error_response(400, "Part must be a positive number"),
+        };
+        let upload = match self.upload(upload_id, request) {
+            Some(upload) => upload,
+            None => return error_response(404, "Upload not found"),
+        };
+        if request.body.len() > constants::MAX_DATA_CHUNK_SIZE {
+            return error_response(413, "Request entity too large");
+        }
+
+        upload.parts.insert(part, request.body.to_vec());
+        let mut reply = request.file_reply(&upload.name);
+        reply["upload_id"] = upload_id.into();
+        reply["part"] = part.into();
+        json_response(200, &reply)
+    }
+
+    fn end_upload(&mut self, upload_id: &str, request: &FakeRequest) -> reqwest::Response {
+        let upload = match self.upload(upload_id, request) {
+            Some(upload) => upload,
+            None => return error_response(404, "Upload not found"),
+        };
+
+        let parts = upload.parts.len();
+        if parts == 0 {
+            return error_response(400, "No parts have been uploaded");
+        }
+        for (index, (part, data)) in upload.parts.iter().enumerate() {
+            if *part != index + 1 {
+                return error_response(400, &format!("Part {} is missing", index + 1));
+            }
+            if index + 1 < parts && data.len() < constants::MIN_DATA_CHUNK_SIZE {
+                return error_response(400, &format!("Part {} is smaller than 5MB", part));
+            }
+        }
+
+        let upload = self.uploads.remove(upload_id).expect("The upload exists");
+        let data = upload.parts.into_values().flatten().collect();
+        self.files.insert(
+            upload.name.clone(),
+            StoredFile {
+                data,
+                content_type: DEFAULT_CONTENT_TYPE.to_owned(),
+            },
+        );
+        let mut reply = request.file_reply(&upload.name);
+        reply["upload_id"] = upload_id.into();
+        json_response(200, &reply)
+    }
+
+    fn abort_upload(&mut self, upload_id: &str, request: &FakeRequest) -> reqwest::Response {
+        let name = match self.upload(upload_id, request) {
+            Some(upload) => upload.name.clone(),
+            None => return error_response(404, "Upload not found"),
+        };
+        self.uploads.remove(upload_id);
+
+        let mut reply = request.file_reply(&name);
+        reply["upload_id"] = upload_id.into();
+        json_response(200, &reply)
+    }
+
+    // Returns the started upload of the file named in the request.
+    fn upload(&mut self, upload_id: &str, request: &FakeRequest) -> Option<&mut Upload> {
+        self.uploads
+            .get_mut(upload_id)
+            .filter(|upload| request.param("name") == Some(upload.name.as_str()))
+    }
+}
+
+fn error_response(status: u16, message: &str) -> reqwest::Response {
+    json_response(status, &json!({ "errors": [message] }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::drive::PutFileOptions;
+
+    const MB: usize = 1024 * 1024;
+
+    async fn seeded() -> (FakeDrive, Drive) {
+        let fake = FakeDrive::new();
+        let drive = fake.drive();
+        for name in ["b.txt", "a.txt", "docs/c.txt", "docs/a.txt"] {
+            drive
+                .put_file(name, name.as_bytes().to_vec(), Some("text/plain"))
+                .await
+                .unwrap();
+        }
+        (fake, drive)
+    }
+
+    #[tokio::test]
+    async fn put_and_get_file() {
+        let (fake, drive) = seeded().await;
+        let data = drive.get_file_as_u8_vec("docs/c.txt").await.unwrap();
+        assert_eq!(data, Some(b"docs/c.txt".to_vec()));
+        assert!(drive
+            .get_file_as_u8_vec("missing.txt")
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(fake.content_type("a.txt").as_deref(), Some("text/plain"));
+    }
+
+    #[tokio::test]
+    async fn head_file_returns_metadata() {
+        let (_, drive) = seeded().await;
+        let metadata = drive.head_file("a.txt").await.unwrap().unwrap();
+        assert_eq!(metadata.content_length, Some(5));
+        assert_eq!(metadata.content_type.as_deref(), Some("text/plain"));
+        assert!(drive.head_file("missing.txt").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn put_large_file_in_chunks() {
+        let fake = FakeDrive::new();
+        let data: Vec<u8> = (0..11 * MB).map(|i| i as u8).collect();
+        let result = fake
+            .drive()
+            .put_file("big.dat", data.clone(), None)
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            result,
+            crate::drive::PutFileResult::ChunkedUpload(_)
+        ));
+        assert_eq!(fake.files()["big.dat"], data);
+        assert_eq!(fake.pending_uploads(), 0);
+    }
+
+    #[tokio::test]
+    async fn single_put_over_limit_is_rejected() {
+        let fake = FakeDrive::new();
+        let options = PutFileOptions {
+            chunked_fallback: false,
+            ..Default::default()
+        };
+        let reply = fake.lock().put(&FakeRequest {
+            method: "POST",
+            path: vec!["files"],
+            params: [("name".to_owned(), "big.dat".to_owned())].into(),
+            headers: &reqwest::header::HeaderMap::new(),
+            body: &vec![0; 10 * MB + 1],
+            project_id: "fake",
+            drive_name: "fake_drive",
+        });
+        assert_eq!(reply.status(), 413);
+
+        // Files at the threshold are still uploaded with a single request.
+        fake.drive()
+            .put_file_with("limit.dat", vec![0; 10 * MB], options)
+            .await
+            .unwrap();
+        assert_eq!(fake.files()["limit.dat"].len(), 10 * MB);
+    }
+
+    #[tokio::test]
+    async fn end_upload_rejects_small_parts() {
+        let fake = FakeDrive::new();
+        let drive = fake.drive();
+        let options = PutFileOptions {
+            force_chunked: true,
+            chunk_size: 5 * MB,
+            ..Default::default()
+        };
+        drive
+            .put_file_with("parts.dat", vec![1; 6 * MB], options)
+            .await
+            .unwrap();
+        assert_eq!(fake.files()["parts.dat"].len(), 6 * MB);
+
+        let mut state = fake.lock();
+        state.uploads.insert(
+            "small".into(),
+            Upload {
+                name: "small.dat".into(),
+                parts: [(1, vec![0; 10]), (2, vec![0; 10])].into(),
+            },
+        );
+        let request = FakeRequest {
+            method: "PATCH",
+            path: vec!["uploads", "small"],
+            params: [("name".to_owned(), "small.dat".to_owned())].into(),
+            headers: &reqwest::header::HeaderMap::new(),
+            body: &[],
+            project_id: "fake",
+            drive_name: "fake_drive",
+        };
+        assert_eq!(state.end_upload("small", &request).status(), 400);
+    }
+
+    #[tokio::test]
+    async fn list_in_lexicographic_order() {
+        let (_, drive) = seeded().await;
+        let files = drive.list_files(None, None, None).await.unwrap();
+        assert_eq!(files.names, ["a.txt", "b.txt", "docs/a.txt", "docs/c.txt"]);
+        assert!(files.paging.is_none());
+
+        let files = drive.list_files(None, Some("docs/"), None).await.unwrap();
+        assert_eq!(files.names, ["docs/a.txt", "docs/c.txt"]);
+    }
+
+    #[tokio::test]
+    async fn list_paginated_with_last() {
+        let (_, drive) = seeded().await;
+        let first = drive.list_files(Some(3), None, None).await.unwrap();
+        assert_eq!(first.names, ["a.txt", "b.txt", "docs/a.txt"]);
+        let paging = first.paging.unwrap();
+        assert_eq!(paging.size, 3);
+        assert_eq!(paging.last.as_deref(), Some("docs/a.txt"));
+
+        let second = drive
+            .list_files(Some(3), None, Some("docs/a.txt"))
+            .await
+            .unwrap();
+        assert_eq!(second.names, ["docs/c.txt"]);
+        assert!(second.paging.is_none());
+    }
+
+    #[tokio::test]
+    async fn delete_reports_missing_names_as_deleted() {
+        let (fake, drive) = seeded().await;
+        let result = drive
+            .delete_files(&["a.txt".into(), "missing.txt".into()])
+            .await
+            .unwrap();
+
+        assert_eq!(result.deleted, ["a.txt", "missing.txt"]);
+        assert!(result.all_succeeded());
+        assert!(!fake.files().contains_key("a.txt"));
+        assert_eq!(fake.files().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn download_resumes_from_range() {
+        let (fake, _) = seeded().await;
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert(RANGE, "bytes=5-".parse().unwrap());
+        let request = FakeRequest {
+            method: "GET",
+            path: vec!["files", "download"],
+            params: [("name".to_owned(), "docs/c.txt".to_owned())].into(),
+            headers: &headers,
+            body: &[],
+            project_id: "fake",
+            drive_name: "fake_drive",
+        };
+
+        let response = fake.lock().download(&request);
+        assert_eq!(response.status(), 206);
+        assert_eq!(response.headers()[CONTENT_RANGE], "bytes 5-9/10");
+        assert_eq!(response.bytes().await.unwrap(), &b"c.txt"[..]);
+    }
+}
@@ -0,0 +1,249 @@
+//! Mocked transport returning scripted responses.
+
+use super::raw_response;
+use crate::error::Error;
+use crate::transport::{Transport, TransportFuture};
+use std::sync::{Arc, Mutex};
+
+/// A [`Transport`](Transport) recording the sent requests and returning the scripted responses.
+/// Clones share the responses and the recorded requests, so a clone can be passed to the service
+/// and the original used for assertions.
+///
+/// A request without a matching response fails with an error, rather than getting a 404 response,
+/// so that a missing script isn't mistaken for a missing item.
+///
+/// ```
+/// use deta_rust::{database::Database, testing::MockTransport, DetaClient};
+/// use std::sync::Arc;
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let transport = MockTransport::new();
+/// transport.respond("GET", "/items/user_1", 200, serde_json::json!({ "key": "user_1", "age": 30 }));
+///
+/// let client = DetaClient::new("project_key");
+/// let database = Database::new(&client, "users").unwrap().with_transport(Arc::new(transport.clone()));
+/// let user: Option<serde_json::Value> = database.get_item("user_1").await.unwrap();
+///
+/// assert_eq!(user.unwrap()["age"], 30);
+/// let requests = transport.requests();
+/// assert_eq!(requests[0].method, "GET");
+/// assert!(requests[0].url.ends_with("/users/items/user_1"));
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MockTransport {
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    responses: Vec<MockResponse>,
+    requests: Vec<RecordedRequest>,
+}
+
+#[derive(Debug)]
+struct MockResponse {
+    method: String,
+    path: String,
+    status: u16,
+    content_type: String,
+    body: Vec<u8>,
+}
+
+/// A request sent through the [`MockTransport`](MockTransport).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedRequest {
+    /// HTTP method, e.g. `GET`.
+    pub method: String,
+    /// The whole url, including the query.
+    pub url: String,
+    /// The headers with lowercase names, except for the `x-api-key` header carrying the project key.
+    pub headers: Vec<(String, String)>,
+    /// The body, if any.
+    pub body: Option<Vec<u8>>,
+}
+
+impl RecordedRequest {
+    /// Returns the value of the header. The name is case-insensitive.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns the body deserialized from JSON, or `None` if there's no body or it isn't JSON.
+    pub fn json(&self) -> Option<serde_json::Value> {
+        serde_json::from_slice(self.body.as_deref()?).ok()
+    }
+}
+
+impl MockTransport {
+    /// Creates a transport without any scripted responses.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Responds with the `status` and the JSON `body` to the requests with the `method`
+    /// whose url path ends with the `path`, e.g. `/items/user_1`.
+    /// If many responses match the request, the first registered one is used.
+    pub fn respond(&self, method: &str, path: &str, status: u16, body: serde_json::Value) -> &Self {
+        self.respond_raw(
+            method,
+            path,
+            status,
+            "application/json",
+            body.to_string().into_bytes(),
+        )
+    }
+
+    /// Works like [`MockTransport::respond`](MockTransport::respond), but with a raw body of the given content type,
+    /// e.g. a file content or an HTML error page.
+    pub fn respond_raw(
+        &self,
+        method: &str,
+        path: &str,
+        status: u16,
+        content_type: &str,
+        body: impl Into<Vec<u8>>,
+    ) -> &Self {
+        self.lock().responses.push(MockResponse {
+            method: method.to_uppercase(),
+            path: path.to_owned(),
+            status,
+            content_type: content_type.to_owned(),
+            body: body.into(),
+        });
+        self
+    }
+
+    /// Returns the requests sent so far, in the order they were sent.
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.lock().requests.clone()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .expect("The mock transport lock is never poisoned")
+    }
+
+    fn response(&self, request: reqwest::Request) -> Result<reqwest::Response, Error> {
+        let recorded = RecordedRequest {
+            method: request.method().to_string(),
+            url: request.url().to_string(),
+            headers: request
+                .headers()
+                .iter()
+                .filter(|(name, _)| name.as_str() != "x-api-key")
+                .filter_map(|(name, value)| {
+                    Some((name.as_str().to_owned(), value.to_str().ok()?.to_owned()))
+                })
+                .collect(),
+            body: request
+                .body()
+                .and_then(reqwest::Body::as_bytes)
+                .map(<[u8]>::to_vec),
+        };
+
+        let mut state = self.lock();
+        let response = state
+            .responses
+            .iter()
+            .find(|response| {
+                response.method == recorded.method && request.url().path().ends_with(&response.path)
+            })
+            .map(|response| {
+                raw_response(
+                    response.status,
+                    &response.content_type,
+                    response.body.clone(),
+                )
+            });
+        let message = format!(
+            "No mocked response for '{} {}'",
+            recorded.method, recorded.url
+        );
+        state.requests.push(recorded);
+
+        response.ok_or_else(|| Error::from_other(message, None))
+    }
+}
+
+impl Transport for MockTransport {
+    fn execute(&self, request: reqwest::Request) -> TransportFuture<'_> {
+        let response = self.response(request);
+        Box::pin(async move { response })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn request(method: reqwest::Method, url: &str) -> reqwest::RequestBuilder {
+        reqwest::Client::new()
+            .request(method, url)
+            .header("X-Api-Key", "secret_key")
+    }
+
+    #[tokio::test]
+    async fn execute_returns_scripted_response() {
+        let transport = MockTransport::new();
+        transport
+            .respond("GET", "/items/a", 200, json!({ "key": "a" }))
+            .respond("GET", "/items/b", 404, json!({ "key": "b" }));
+
+        let response = transport
+            .execute(
+                request(reqwest::Method::GET, "http://deta/db/items/b")
+                    .build()
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), 404);
+        assert_eq!(response.text().await.unwrap(), r#"{"key":"b"}"#);
+    }
+
+    #[tokio::test]
+    async fn execute_records_requests() {
+        let transport = MockTransport::new();
+        transport.respond("PUT", "/items", 200, json!({}));
+
+        let request = request(reqwest::Method::PUT, "http://deta/db/items?a=1")
+            .json(&json!({ "items": [1] }))
+            .build()
+            .unwrap();
+        transport.execute(request).await.unwrap();
+
+        let requests = transport.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, "PUT");
+        assert_eq!(requests[0].url, "http://deta/db/items?a=1");
+        assert_eq!(requests[0].header("Content-Type"), Some("application/json"));
+        assert_eq!(requests[0].header("x-api-key"), None);
+        assert_eq!(requests[0].json(), Some(json!({ "items": [1] })));
+    }
+
+    #[tokio::test]
+    async fn execute_fails_without_scripted_response() {
+        let transport = MockTransport::new();
+        transport.respond("GET", "/items/a", 200, json!({}));
+
+        let error = transport
+            .execute(
+                request(reqwest::Method::DELETE, "http://deta/db/items/a")
+                    .build()
+                    .unwrap(),
+            )
+            .await
+            .unwrap_err();
+
+        assert!(error.to_string().contains("No mocked response for 'DELETE"));
+        assert_eq!(transport.requests().len(), 1);
+    }
+}
@@ -0,0 +1,47 @@
+//! Utilities for testing code using the SDK without network access and credentials:
+//!
+//! - [`MockTransport`](MockTransport) records the sent requests and returns scripted responses,
+//!   to verify what is sent to deta.
+//! - [`FakeBase`](FakeBase) keeps the items in memory and behaves like deta base,
+//!   to test the application logic built on top of it.
+//! - [`FakeDrive`](FakeDrive) does the same for the files of deta drive.
+//! - [`RecordingTransport`](RecordingTransport) records real interactions to a fixture file,
+//!   replayed offline by [`ReplayTransport`](ReplayTransport).
+//! - [`TempBase`](TempBase) and [`TempDrive`](TempDrive) give tests against the real API
+//!   their own base and drive, emptied afterwards.
+//!
+//! Available with the `test-util` feature.
+
+#[cfg(feature = "base")]
+mod fake_base;
+#[cfg(feature = "drive")]
+mod fake_drive;
+mod mock;
+mod replay;
+#[cfg(any(feature = "base", feature = "drive"))]
+mod temp;
+
+#[cfg(feature = "base")]
+pub use fake_base::FakeBase;
+#[cfg(feature = "drive")]
+pub use fake_drive::FakeDrive;
+pub use mock::{MockTransport, RecordedRequest};
+pub use replay::{RecordingTransport, ReplayTransport};
+#[cfg(feature = "base")]
+pub use temp::TempBase;
+#[cfg(feature = "drive")]
+pub use temp::TempDrive;
+
+#[cfg(any(feature = "base", feature = "drive"))]
+fn json_response(status: u16, body: &serde_json::Value) -> reqwest::Response {
+    raw_response(status, "application/json", body.to_string().into_bytes())
+}
+
+fn raw_response(status: u16, content_type: &str, body: Vec<u8>) -> reqwest::Response {
+    let response = http::Response::builder()
+        .status(status)
+        .header(reqwest::header::CONTENT_TYPE, content_type)
+        .body(body)
+        .expect("The fake response is valid");
+    reqwest::Response::from(response)
+}
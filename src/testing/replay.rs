@@ -0,0 +1,543 @@
+//! Recording of real interactions to fixture files and their offline replay.
+
+use crate::error::{Error, Result};
+use crate::transport::{HttpTransport, Transport, TransportFuture};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+const FORMAT_VERSION: u32 = 1;
+const REDACTED: &str = "[REDACTED]";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Fixture {
+    version: u32,
+    interactions: Vec<Interaction>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Interaction {
+    request: RequestRecord,
+    response: ResponseRecord,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct RequestRecord {
+    method: String,
+    path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    query: Option<String>,
+    #[serde(default)]
+    body: Option<Body>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ResponseRecord {
+    status: u16,
+    #[serde(default)]
+    headers: Vec<(String, String)>,
+    #[serde(default)]
+    body: Option<Body>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Body {
+    Json(Value),
+    Text(String),
+    Bytes(Vec<u8>),
+}
+
+impl Body {
+    // Keeps the body as JSON if it's declared as JSON and parses, otherwise as text or bytes.
+    fn from_bytes(bytes: &[u8], is_json: bool) -> Option<Self> {
+        if bytes.is_empty() {
+            return None;
+        }
+        if is_json {
+            if let Ok(value) = serde_json::from_slice(bytes) {
+                return Some(Body::Json(value));
+            }
+        }
+        match std::str::from_utf8(bytes) {
+            Ok(text) => Some(Body::Text(text.to_owned())),
+            Err(_) => Some(Body::Bytes(bytes.to_vec())),
+        }
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        match self {
+            Body::Json(value) => value.to_string().into_bytes(),
+            Body::Text(text) => text.into_bytes(),
+            Body::Bytes(bytes) => bytes,
+        }
+    }
+}
+
+impl RequestRecord {
+    fn new(request: &reqwest::Request) -> Self {
+        let is_json = is_json(request.headers());
+        Self {
+            method: request.method().to_string(),
+            path: service_path(request.url().path()).to_owned(),
+            query: request.url().query().map(str::to_owned),
+            body: request
+                .body()
+                .and_then(reqwest::Body::as_bytes)
+                .and_then(|bytes| Body::from_bytes(bytes, is_json)),
+        }
+    }
+
+    fn describe(&self) -> String {
+        match &self.query {
+            Some(query) => format!("{} {}?{}", self.method, self.path, query),
+            None => format!("{} {}", self.method, self.path),
+        }
+    }
+}
+
+// Strips the `/v1/{project_id}` prefix of the url path.
+fn service_path(path: &str) -> &str {
+    path.strip_prefix("/v1/")
+        .and_then(|rest| rest.find('/').map(|index| &rest[index..]))
+        .unwrap_or(path)
+}
+
+fn is_json(headers: &reqwest::header::HeaderMap) -> bool {
+    headers
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.contains("json"))
+}
+
+/// A [`Transport`](Transport) sending the requests with another transport, by default
+/// [`HttpTransport`](HttpTransport), and writing the interactions to a fixture file,
+/// to be replayed offline with [`ReplayTransport`](ReplayTransport).
+///
+/// The file is created with the first request and rewritten after every response,
+/// so it's complete even if the test fails halfway. A failure to write it fails the request.
+/// The project key is never written to the file.
+///
+/// ```no_run
+/// use deta_rust::{database::Database, testing::RecordingTransport, DetaClient};
+/// use std::sync::Arc;
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> deta_rust::error::Result<()> {
+/// let transport = RecordingTransport::new("tests/fixtures/users.json");
+/// let client = DetaClient::new("project_key");
+/// let database = Database::new(&client, "users")?.with_transport(Arc::new(transport));
+/// database.put_items(&[serde_json::json!({ "key": "user_1" })]).await?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Fixture format
+///
+/// A fixture is a JSON file with the format `version` and the `interactions` in the order they happened:
+///
+/// ```json
+/// {
+///   "version": 1,
+///   "interactions": [
+///     {
+///       "request": {
+///         "method": "GET",
+///         "path": "/photos/files",
+///         "query": "limit=10",
+///         "body": null
+///       },
+///       "response": {
+///         "status": 200,
+///         "headers": [["content-type", "application/json"]],
+///         "body": { "json": { "names": ["a.txt"] } }
+///       }
+///     }
+///   ]
+/// }
+/// ```
+///
+/// - `path` is the url path after the project id, so it starts with the base or drive name.
+///   It stays percent-encoded, e.g. `/users/items/user%201`.
+/// - `query` is the raw query of the url, omitted if there's none.
+/// - A body is `null` if there's none, `{ "json": value }` for a JSON body, `{ "text": "..." }` for other UTF-8
+///   content and `{ "bytes": [...] }` for binary content. Streamed request bodies aren't recorded and are `null`.
+/// - The response `headers` are the name-value pairs with lowercase names.
+///
+/// The request headers aren't recorded, so the project key isn't written with the `x-api-key` header,
+/// and any other occurrence of it, e.g. in a body, is replaced with `[REDACTED]`.
+/// The project id is left out of the paths, so the fixture can be replayed with any project key.
+///
+/// Changes of the format increase the `version`. Fixtures of an unsupported version fail to load.
+#[derive(Clone)]
+pub struct RecordingTransport {
+    path: PathBuf,
+    transport: Arc<dyn Transport>,
+    interactions: Arc<Mutex<Vec<Interaction>>>,
+}
+
+impl RecordingTransport {
+    /// Creates a transport recording to the fixture file at the `path`, overwritten if it exists.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            transport: Arc::new(HttpTransport::default()),
+            interactions: Arc::default(),
+        }
+    }
+
+    /// Sends the requests with the `transport` instead of [`HttpTransport`](HttpTransport).
+    pub fn with_transport(mut self, transport: Arc<dyn Transport>) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Returns the path of the fixture file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    async fn record(&self, request: reqwest::Request) -> Result<reqwest::Response> {
+        let api_key = request
+            .headers()
+            .get("x-api-key")
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_owned);
+        let request_record = RequestRecord::new(&request);
+
+        let response = self.transport.execute(request).await?;
+        let status = response.status().as_u16();
+        let is_json = is_json(response.headers());
+        let headers: Vec<(String, String)> = response
+            .headers()
+            .iter()
+            .filter_map(|(name, value)| {
+                Some((name.as_str().to_owned(), value.to_str().ok()?.to_owned()))
+            })
+            .collect();
+        let bytes = response.bytes().await?;
+
+        let interaction = Interaction {
+            request: request_record,
+            response: ResponseRecord {
+                status,
+                headers: headers.clone(),
+                body: Body::from_bytes(&bytes, is_json),
+            },
+        };
+        self.write(interaction, api_key.as_deref())?;
+
+        Ok(build_response(status, &headers, bytes.to_vec()))
+    }
+
+    fn write(&self, interaction: Interaction, api_key: Option<&str>) -> Result<()> {
+        let mut interactions = self
+            .interactions
+            .lock()
+            .expect("The recording lock is never poisoned");
+        interactions.push(interaction);
+        let fixture = Fixture {
+            version: FORMAT_VERSION,
+            interactions: interactions.clone(),
+        };
+        let mut json = serde_json::to_string_pretty(&fixture)?;
+        if let Some(api_key) = api_key.filter(|key| !key.is_empty()) {
+            json = json.replace(api_key, REDACTED);
+        }
+        std::fs::write(&self.path, json)?;
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for RecordingTransport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RecordingTransport")
+            .field("path", &self.path)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Transport for RecordingTransport {
+    fn execute(&self, request: reqwest::Request) -> TransportFuture<'_> {
+        Box::pin(self.record(request))
+    }
+}
+
+/// A [`Transport`](Transport) answering the requests with the responses from a fixture file
+/// written by [`RecordingTransport`](RecordingTransport#fixture-format), so real interactions can be tested offline.
+/// Clones share the fixture.
+///
+/// A request is matched with a recorded one by the method, the path, the query and the body,
+/// where JSON bodies are compared as values. Every recorded interaction is replayed once, in the recorded order,
+/// so repeated requests get the responses in the order they were recorded.
+/// A request without a matching interaction fails with an error naming it, rather than getting any response.
+/// Use [`ReplayTransport::assert_replayed_all`](ReplayTransport::assert_replayed_all) to make sure
+/// that no recorded request is missing.
+///
+/// ```no_run
+/// use deta_rust::{database::Database, testing::ReplayTransport, DetaClient};
+/// use std::sync::Arc;
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> deta_rust::error::Result<()> {
+/// let transport = ReplayTransport::from_file("tests/fixtures/users.json")?;
+/// let client = DetaClient::new("project_key");
+/// let database = Database::new(&client, "users")?.with_transport(Arc::new(transport.clone()));
+/// database.put_items(&[serde_json::json!({ "key": "user_1" })]).await?;
+/// transport.assert_replayed_all();
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ReplayTransport {
+    state: Arc<Mutex<ReplayState>>,
+}
+
+#[derive(Debug)]
+struct ReplayState {
+    interactions: Vec<Interaction>,
+    replayed: Vec<bool>,
+}
+
+impl ReplayTransport {
+    /// Loads the fixture file at the `path`.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let json = std::fs::read_to_string(path)?;
+        Self::from_json(&json)
+    }
+
+    /// Loads the fixture from its JSON content.
+    /// Fails if it isn't a fixture or its format version isn't supported.
+    pub fn from_json(json: &str) -> Result<Self> {
+        let fixture: Fixture = serde_json::from_str(json)?;
+        if fixture.version != FORMAT_VERSION {
+            return Err(Error::from_invalid_argument(format!(
+                "Unsupported fixture version {}, expected {}",
+                fixture.version, FORMAT_VERSION
+            )));
+        }
+        Ok(Self {
+            state: Arc::new(Mutex::new(ReplayState {
+                replayed: vec![false; fixture.interactions.len()],
+                interactions: fixture.interactions,
+            })),
+        })
+    }
+
+    /// Returns the number of the recorded interactions not replayed yet.
+    pub fn remaining(&self) -> usize {
+        self.lock().replayed.iter().filter(|&&done| !done).count()
+    }
+
+    /// Panics if some recorded interactions haven't been replayed, listing their requests.
+    pub fn assert_replayed_all(&self) {
+        let state = self.lock();
+        let remaining: Vec<String> = state
+            .interactions
+            .iter()
+            .zip(&state.replayed)
+            .filter(|(_, &done)| !done)
+            .map(|(interaction, _)| interaction.request.describe())
+            .collect();
+        assert!(
+            remaining.is_empty(),
+            "Recorded requests not replayed: {:?}",
+            remaining
+        );
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, ReplayState> {
+        self.state
+            .lock()
+            .expect("The replay lock is never poisoned")
+    }
+
+    fn response(&self, request: reqwest::Request) -> Result<reqwest::Response> {
+        let record = RequestRecord::new(&request);
+        let mut state = self.lock();
+        let ReplayState {
+            interactions,
+            replayed,
+        } = &mut *state;
+        let index = interactions
+            .iter()
+            .zip(replayed.iter())
+            .position(|(interaction, &done)| !done && interaction.request == record)
+            .ok_or_else(|| {
+                Error::from_other(
+                    format!("No recorded interaction for '{}'", record.describe()),
+                    None,
+                )
+            })?;
+        replayed[index] = true;
+
+        let response = interactions[index].response.clone();
+        let body = response.body.map(Body::into_bytes).unwrap_or_default();
+        Ok(build_response(response.status, &response.headers, body))
+    }
+}
+
+impl Transport for ReplayTransport {
+    fn execute(&self, request: reqwest::Request) -> TransportFuture<'_> {
+        let response = self.response(request);
+        Box::pin(async move { response })
+    }
+}
+
+// Builds the response with all the recorded headers, except for the content length
+// which may differ after the body is serialized again.
+fn build_response(status: u16, headers: &[(String, String)], body: Vec<u8>) -> reqwest::Response {
+    let content_type = headers
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+        .map_or("application/octet-stream", |(_, value)| value.as_str());
+    let mut response = http::Response::builder()
+        .status(status)
+        .header(reqwest::header::CONTENT_TYPE, content_type)
+        .body(body)
+        .expect("The replayed response is valid");
+    for (name, value) in headers {
+        if name.eq_ignore_ascii_case("content-type") || name.eq_ignore_ascii_case("content-length")
+        {
+            continue;
+        }
+        if let (Ok(name), Ok(value)) = (
+            http::header::HeaderName::from_bytes(name.as_bytes()),
+            http::header::HeaderValue::from_str(value),
+        ) {
+            response.headers_mut().append(name, value);
+        }
+    }
+    reqwest::Response::from(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockTransport;
+    use serde_json::json;
+
+    fn request(method: reqwest::Method, url: &str) -> reqwest::RequestBuilder {
+        reqwest::Client::new()
+            .request(method, url)
+            .header("X-Api-Key", "project_secret")
+    }
+
+    #[test]
+    fn service_path_strips_project_id() {
+        assert_eq!(service_path("/v1/project/users/items/a"), "/users/items/a");
+        assert_eq!(service_path("/v1/project"), "/v1/project");
+        assert_eq!(service_path("/other"), "/other");
+    }
+
+    #[tokio::test]
+    async fn recorded_interactions_are_replayed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fixture.json");
+        let mock = MockTransport::new();
+        mock.respond(
+            "GET",
+            "/items/a",
+            200,
+            json!({ "key": "a", "owner": "project_secret" }),
+        )
+        .respond_raw(
+            "GET",
+            "/files/download",
+            200,
+            "image/png",
+            vec![0, 159, 146],
+        );
+        let recording = RecordingTransport::new(&path).with_transport(Arc::new(mock));
+
+        let get_item = request(
+            reqwest::Method::GET,
+            "https://database.deta.sh/v1/project/users/items/a",
+        );
+        recording.execute(get_item.build().unwrap()).await.unwrap();
+        let download = request(
+            reqwest::Method::GET,
+            "https://drive.deta.sh/v1/project/photos/files/download?name=a.png",
+        );
+        let response = recording.execute(download.build().unwrap()).await.unwrap();
+        assert_eq!(response.bytes().await.unwrap().as_ref(), [0, 159, 146]);
+
+        let fixture = std::fs::read_to_string(&path).unwrap();
+        assert!(!fixture.contains("project_secret"));
+        assert!(fixture.contains(REDACTED));
+
+        let replay = ReplayTransport::from_json(&fixture).unwrap();
+        let download = request(
+            reqwest::Method::GET,
+            "https://drive.deta.sh/v1/other_project/photos/files/download?name=a.png",
+        );
+        let response = replay.execute(download.build().unwrap()).await.unwrap();
+        assert_eq!(response.headers()["content-type"], "image/png");
+        assert_eq!(response.bytes().await.unwrap().as_ref(), [0, 159, 146]);
+        assert_eq!(replay.remaining(), 1);
+
+        let get_item = request(
+            reqwest::Method::GET,
+            "https://database.deta.sh/v1/project/users/items/a",
+        );
+        let response = replay.execute(get_item.build().unwrap()).await.unwrap();
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body, json!({ "key": "a", "owner": REDACTED }));
+        replay.assert_replayed_all();
+    }
+
+    #[tokio::test]
+    async fn requests_are_matched_by_body() {
+        let fixture = json!({
+            "version": 1,
+            "interactions": [
+                {
+                    "request": { "method": "POST", "path": "/users/items", "body": { "json": { "item": { "key": "a" } } } },
+                    "response": { "status": 201, "headers": [["content-type", "application/json"]], "body": { "json": { "key": "a" } } }
+                },
+                {
+                    "request": { "method": "POST", "path": "/users/items", "body": { "json": { "item": { "key": "a" } } } },
+                    "response": { "status": 409, "headers": [["content-type", "application/json"]], "body": { "json": { "errors": ["Conflict"] } } }
+                }
+            ]
+        });
+        let replay = ReplayTransport::from_json(&fixture.to_string()).unwrap();
+        let insert = |key: &str| {
+            request(
+                reqwest::Method::POST,
+                "https://database.deta.sh/v1/project/users/items",
+            )
+            .json(&json!({ "item": { "key": key } }))
+            .build()
+            .unwrap()
+        };
+
+        let error = replay.execute(insert("b")).await.unwrap_err();
+        assert!(error.to_string().contains("POST /users/items"));
+        assert_eq!(replay.execute(insert("a")).await.unwrap().status(), 201);
+        assert_eq!(replay.execute(insert("a")).await.unwrap().status(), 409);
+        assert!(replay.execute(insert("a")).await.is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "GET /users/items/a")]
+    fn assert_replayed_all_lists_remaining_requests() {
+        let fixture = json!({
+            "version": 1,
+            "interactions": [{
+                "request": { "method": "GET", "path": "/users/items/a" },
+                "response": { "status": 404 }
+            }]
+        });
+        let replay = ReplayTransport::from_json(&fixture.to_string()).unwrap();
+        replay.assert_replayed_all();
+    }
+
+    #[test]
+    fn unsupported_version_is_rejected() {
+        let error =
+            ReplayTransport::from_json(r#"{ "version": 2, "interactions": [] }"#).unwrap_err();
+        assert!(error.is_invalid_argument());
+    }
+}
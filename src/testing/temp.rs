@@ -0,0 +1,242 @@
+//! Temporary bases and drives for tests against the real API.
+
+#[cfg(feature = "base")]
+use crate::database::Database;
+#[cfg(feature = "drive")]
+use crate::drive::Drive;
+use crate::error::Result;
+use crate::DetaClient;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const DEFAULT_PREFIX: &str = "temp";
+
+/// A base with a unique name, emptied when it's no longer needed, so that tests can run in parallel
+/// and don't leave their items behind. Deta creates the base with the first write.
+///
+/// The items are deleted by [`TempBase::close`](TempBase::close). A base that hasn't been closed,
+/// e.g. because the test panicked, is emptied on drop: the cleanup runs on a separate thread
+/// and the drop blocks until it finishes. Cleanup failures on drop are ignored.
+///
+/// ```no_run
+/// use deta_rust::{testing::TempBase, DetaClient};
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> deta_rust::error::Result<()> {
+/// let client = DetaClient::new("project_key");
+/// let base = TempBase::new(&client);
+/// base.database().put_items(&[serde_json::json!({ "key": "a" })]).await?;
+/// base.close().await
+/// # }
+/// ```
+///
+/// Available with the `test-util` feature.
+#[cfg(feature = "base")]
+pub struct TempBase {
+    api_key: String,
+    name: String,
+    database: Database,
+    closed: bool,
+}
+
+#[cfg(feature = "base")]
+impl TempBase {
+    /// Creates a base named `temp_` followed by a unique suffix.
+    pub fn new(client: &DetaClient) -> Self {
+        Self::with_prefix(client, DEFAULT_PREFIX)
+    }
+
+    /// Creates a base named with the `prefix`, `_` and a unique suffix.
+    pub fn with_prefix(client: &DetaClient, prefix: &str) -> Self {
+        let name = unique_name(prefix);
+        Self {
+            api_key: client.api_key().to_owned(),
+            database: Database::new(client, &name).expect("The generated name is never blank"),
+            name,
+            closed: false,
+        }
+    }
+
+    /// Returns the generated name of the base.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the database working with the base.
+    pub fn database(&self) -> &Database {
+        &self.database
+    }
+
+    /// Deletes all items of the base. If it fails, the cleanup is tried again on drop.
+    pub async fn close(mut self) -> Result<()> {
+        let result = empty_base(&self.database).await;
+        self.closed = result.is_ok();
+        result
+    }
+}
+
+#[cfg(feature = "base")]
+impl Drop for TempBase {
+    fn drop(&mut self) {
+        if self.closed {
+            return;
+        }
+        let client = DetaClient::new(&self.api_key);
+        if let Ok(database) = Database::new(&client, &self.name) {
+            clean_up_blocking(async move { empty_base(&database).await });
+        }
+    }
+}
+
+/// A drive with a unique name, emptied when it's no longer needed, so that tests can run in parallel
+/// and don't leave their files behind. Deta creates the drive with the first upload.
+///
+/// The files are deleted by [`TempDrive::close`](TempDrive::close). A drive that hasn't been closed,
+/// e.g. because the test panicked, is emptied on drop: the cleanup runs on a separate thread
+/// and the drop blocks until it finishes. Cleanup failures on drop are ignored.
+///
+/// Available with the `test-util` and `drive` features.
+#[cfg(feature = "drive")]
+pub struct TempDrive {
+    api_key: String,
+    name: String,
+    drive: Drive,
+    closed: bool,
+}
+
+#[cfg(feature = "drive")]
+impl TempDrive {
+    /// Creates a drive named `temp_` followed by a unique suffix.
+    pub fn new(client: &DetaClient) -> Self {
+        Self::with_prefix(client, DEFAULT_PREFIX).expect("The default prefix is a valid drive name")
+    }
+
+    /// Creates a drive named with the `prefix`, `_` and a unique suffix.
+    /// Fails with an invalid argument error if the name isn't a valid drive name (see [`Drive::new`](Drive::new)).
+    pub fn with_prefix(client: &DetaClient, prefix: &str) -> Result<Self> {
+        let name = unique_name(prefix);
+        Ok(Self {
+            api_key: client.api_key().to_owned(),
+            drive: Drive::new(client, &name)?,
+            name,
+            closed: false,
+        })
+    }
+
+    /// Returns the generated name of the drive.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the drive working with the temporary drive.
+    pub fn drive(&self) -> &Drive {
+        &self.drive
+    }
+
+    /// Deletes all files of the drive. If it fails, the cleanup is tried again on drop.
+    pub async fn close(mut self) -> Result<()> {
+        let result = empty_drive(&self.drive).await;
+        self.closed = result.is_ok();
+        result
+    }
+}
+
+#[cfg(feature = "drive")]
+impl Drop for TempDrive {
+    fn drop(&mut self) {
+        if self.closed {
+            return;
+        }
+        let client = DetaClient::new(&self.api_key);
+        if let Ok(drive) = Drive::new(&client, &self.name) {
+            clean_up_blocking(async move { empty_drive(&drive).await });
+        }
+    }
+}
+
+#[cfg(feature = "base")]
+async fn empty_base(database: &Database) -> Result<()> {
+    // Every pass deletes the first page, until no items are left.
+    loop {
+        let page = database
+            .fetch_items::<serde_json::Value>(None, None, None)
+            .await?;
+        if page.items.is_empty() {
+            return Ok(());
+        }
+        for item in &page.items {
+            if let Some(key) = item["key"].as_str() {
+                database.delete_item(key).await?;
+            }
+        }
+    }
+}
+
+#[cfg(feature = "drive")]
+async fn empty_drive(drive: &Drive) -> Result<()> {
+    let result = drive.delete_all().await?;
+    match result.failed_names().count() {
+        0 => Ok(()),
+        failed => Err(crate::error::Error::from_other(
+            format!("Could not delete {} files of the temporary drive", failed),
+            None,
+        )),
+    }
+}
+
+// Runs the cleanup on a separate thread with its own runtime, as the drop can't await,
+// and the runtime of the caller may be shutting down or blocked by the drop itself.
+fn clean_up_blocking<F>(cleanup: F)
+where
+    F: Future<Output = Result<()>> + Send + 'static,
+{
+    let thread = std::thread::spawn(move || {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build();
+        if let Ok(runtime) = runtime {
+            let _ = runtime.block_on(cleanup);
+        }
+    });
+    let _ = thread.join();
+}
+
+// Appends a suffix unique across the processes and the calls, e.g. `temp_17a2f3c4b5d6e7f8_3039_0`.
+fn unique_name(prefix: &str) -> String {
+    static CREATED: AtomicU64 = AtomicU64::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_nanos() as u64)
+        .unwrap_or_default();
+    format!(
+        "{}_{:x}_{:x}_{}",
+        prefix,
+        nanos,
+        std::process::id(),
+        CREATED.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unique_name_differs_between_calls() {
+        let first = unique_name("temp");
+        let second = unique_name("temp");
+        assert!(first.starts_with("temp_"));
+        assert_ne!(first, second);
+    }
+
+    #[cfg(feature = "drive")]
+    #[test]
+    fn temp_drive_rejects_invalid_prefix() {
+        let client = DetaClient::new("project_key");
+        assert!(TempDrive::with_prefix(&client, "with space").is_err());
+        let mut drive = TempDrive::new(&client);
+        assert!(drive.name().len() <= 64);
+        drive.closed = true;
+    }
+}
@@ -0,0 +1,38 @@
+//! Sending of the requests made by the services, replaceable e.g. to test code using the SDK without network access.
+
+use crate::error::Result;
+use std::future::Future;
+use std::pin::Pin;
+
+/// The future returned by [`Transport::execute`](Transport::execute).
+pub type TransportFuture<'a> = Pin<Box<dyn Future<Output = Result<reqwest::Response>> + Send + 'a>>;
+
+/// Sends the requests built by the services and returns the responses.
+/// The services use [`HttpTransport`](HttpTransport) unless another transport is set,
+/// e.g. with [`Database::with_transport`](crate::database::Database::with_transport).
+///
+/// Negative response statuses are handled by the services, so the transport should return them as they are.
+/// A transport failure can be built from [`reqwest::Error`] with `Error::from`.
+pub trait Transport: Send + Sync {
+    /// Sends the `request`.
+    fn execute(&self, request: reqwest::Request) -> TransportFuture<'_>;
+}
+
+/// Sends the requests over the network with a [`reqwest::Client`].
+#[derive(Debug, Clone, Default)]
+pub struct HttpTransport {
+    client: reqwest::Client,
+}
+
+impl HttpTransport {
+    /// Creates a transport sending the requests with the `client`, e.g. one with custom timeouts or a proxy.
+    pub fn new(client: reqwest::Client) -> Self {
+        Self { client }
+    }
+}
+
+impl Transport for HttpTransport {
+    fn execute(&self, request: reqwest::Request) -> TransportFuture<'_> {
+        Box::pin(async move { Ok(self.client.execute(request).await?) })
+    }
+}
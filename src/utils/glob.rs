@@ -0,0 +1,73 @@
+// A lightweight glob matcher. Supported wildcards:
+// `*` matches any sequence of characters except `/`,
+// `**` matches any sequence of characters including `/`,
+// `?` matches a single character except `/`.
+// All other characters, including dots, are matched literally.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    // `matches[i][j]` tells whether `pattern[i..]` matches `text[j..]`. The table is filled from the ends,
+    // so every wildcard is resolved in time proportional to the lengths, with no backtracking.
+    let mut matches = vec![vec![false; text.len() + 1]; pattern.len() + 1];
+    matches[pattern.len()][text.len()] = true;
+    for i in (0..pattern.len()).rev() {
+        for j in (0..=text.len()).rev() {
+            let character = text.get(j);
+            let within_segment = matches!(character, Some(character) if *character != '/');
+            matches[i][j] = match pattern[i] {
+                '*' if pattern.get(i + 1) == Some(&'*') => {
+                    matches[i + 2][j] || (character.is_some() && matches[i][j + 1])
+                }
+                '*' => matches[i + 1][j] || (within_segment && matches[i][j + 1]),
+                '?' => within_segment && matches[i + 1][j + 1],
+                literal => character == Some(&literal) && matches[i + 1][j + 1],
+            };
+        }
+    }
+    matches[0][0]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_match_for_star() {
+        assert!(glob_match("*.jpg", "photo.jpg"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("uploads/*", "uploads/photo.jpg"));
+        assert!(!glob_match("*.jpg", "photo.png"));
+        assert!(!glob_match("*.jpg", "uploads/photo.jpg"));
+    }
+
+    #[test]
+    fn glob_match_for_double_star() {
+        assert!(glob_match("**.jpg", "uploads/2022/photo.jpg"));
+        assert!(glob_match("uploads/**", "uploads/2022/photo.jpg"));
+        assert!(!glob_match("uploads/**", "downloads/photo.jpg"));
+    }
+
+    #[test]
+    fn glob_match_for_question_mark() {
+        assert!(glob_match("file?.txt", "file1.txt"));
+        assert!(!glob_match("file?.txt", "file.txt"));
+        assert!(!glob_match("file?.txt", "file12.txt"));
+        assert!(!glob_match("a?b", "a/b"));
+    }
+
+    #[test]
+    fn glob_match_for_literal_dots() {
+        assert!(glob_match("file.txt", "file.txt"));
+        assert!(!glob_match("file.txt", "file_txt"));
+        assert!(!glob_match("*.tar.gz", "backup.tar_gz"));
+    }
+
+    #[test]
+    fn glob_match_for_many_stars_without_backtracking() {
+        let text = format!("{}b", "a".repeat(200));
+        assert!(!glob_match(&"*a".repeat(30), &text));
+        assert!(!glob_match(&"**a".repeat(30), &text));
+        assert!(glob_match(&format!("{}b", "*a".repeat(30)), &text));
+    }
+}
@@ -0,0 +1,50 @@
+use std::sync::Arc;
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+// Bounds the number of requests performed concurrently by the bulk operations.
+// Clones share the same pool of permits.
+#[derive(Clone, Debug)]
+pub struct Limiter {
+    semaphore: Arc<Semaphore>,
+    limit: usize,
+}
+
+impl Limiter {
+    pub fn new(limit: usize) -> Self {
+        let limit = limit.max(1);
+        Self {
+            semaphore: Arc::new(Semaphore::new(limit)),
+            limit,
+        }
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    pub async fn acquire(&self) -> SemaphorePermit<'_> {
+        self.semaphore
+            .acquire()
+            .await
+            .expect("The limiter semaphore is never closed")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_with_zero_limit() {
+        let limiter = Limiter::new(0);
+        assert_eq!(limiter.limit(), 1);
+    }
+
+    #[tokio::test]
+    async fn clones_share_permits() {
+        let limiter = Limiter::new(1);
+        let cloned = limiter.clone();
+        let _permit = limiter.acquire().await;
+        assert!(cloned.semaphore.try_acquire().is_err());
+    }
+}
@@ -1,5 +1,33 @@
+#[cfg(feature = "drive")]
+mod glob;
+#[cfg(feature = "drive")]
+mod limiter;
+#[cfg(any(feature = "base", feature = "drive"))]
 mod parse;
+#[cfg(any(feature = "base", feature = "drive"))]
 mod request;
+#[cfg(feature = "drive")]
+mod throttle;
+#[cfg(any(feature = "base", feature = "drive"))]
+mod validation;
 
-pub use parse::parse_response_body;
-pub use request::send_request;
+#[cfg(feature = "drive")]
+pub use glob::glob_match;
+#[cfg(feature = "drive")]
+pub use limiter::Limiter;
+#[cfg(feature = "drive")]
+pub use parse::parse_raw_data;
+#[cfg(feature = "base")]
+pub use parse::{parse_borrowed, read_response_body};
+#[cfg(any(feature = "base", feature = "drive"))]
+pub use parse::{parse_raw_json_response, parse_response_body};
+#[cfg(any(feature = "base", feature = "drive"))]
+pub use request::{send_raw_request, send_request_via};
+#[cfg(feature = "drive")]
+pub use throttle::Throttle;
+#[cfg(feature = "base")]
+pub use validation::broken_key_rule;
+#[cfg(any(feature = "base", feature = "drive"))]
+pub use validation::validate_not_blank;
+#[cfg(feature = "drive")]
+pub use validation::{validate_drive_name, validate_file_name};
@@ -1,31 +1,140 @@
+use super::request::{content_type, is_json};
 use crate::error::{Error, Result};
-use serde::de::DeserializeOwned;
+use bytes::Bytes;
+use serde::de::{Deserialize, DeserializeOwned};
 
+// Deserializes the JSON body of a successful response. If it fails for a body which isn't JSON
+// according to its content type, or has no content type at all, the unexpected content type is reported.
+//
+// The body is parsed as received, without decoding it into a `String` first. Only a failure keeps
+// a copy of the body, decoded lossily, so that it's available as the raw response data of the error.
 pub async fn parse_response_body<T>(response: reqwest::Response) -> Result<T>
 where
     T: DeserializeOwned,
 {
-    let raw_response_body = response.text().await.ok();
-    parse_raw_response_text(raw_response_body).await
+    let content_type = content_type(&response);
+    let raw_response_body = response.bytes().await.ok();
+    parse_raw_response_body(raw_response_body)
+        .await
+        .map_err(|error| with_content_type(error, content_type))
 }
 
-async fn parse_raw_response_text<T>(raw_response_text: Option<String>) -> Result<T>
+// Deserializes the JSON body of a successful response to a raw request, an empty body is `null`.
+pub async fn parse_raw_json_response(response: reqwest::Response) -> Result<serde_json::Value> {
+    let content_type = content_type(&response);
+    let raw_response_body = response.bytes().await?;
+    if raw_response_body.is_empty() {
+        return Ok(serde_json::Value::Null);
+    }
+    parse_raw_response_body(Some(raw_response_body))
+        .await
+        .map_err(|error| with_content_type(error, content_type))
+}
+
+// Reads the body of a successful response as received, e.g. to deserialize it later borrowing from it.
+#[cfg(feature = "base")]
+pub async fn read_response_body(response: reqwest::Response) -> Result<Bytes> {
+    response
+        .bytes()
+        .await
+        .map_err(|_| Error::from_failed_deserialization(None))
+}
+
+/// Deserializes JSON data into a model which may borrow from it, e.g. with `&RawValue` or `&str` fields.
+/// On failure, the data is kept as the raw response data, decoded lossily.
+#[cfg(feature = "base")]
+pub fn parse_borrowed<'a, T>(raw_data: &'a [u8]) -> Result<T>
 where
-    T: DeserializeOwned,
+    T: Deserialize<'a>,
 {
-    if raw_response_text.is_none() {
-        return Err(Error::from_failed_deserialization(None));
+    deserialize_with_serde_json(raw_data, || lossy(raw_data))
+}
+
+// Reports a failed deserialization of a body which isn't JSON as the unexpected content type.
+fn with_content_type(error: Error, content_type: Option<String>) -> Error {
+    let is_json = content_type.as_deref().is_some_and(is_json);
+    if !error.is_body_deserialization() || is_json {
+        return error;
+    }
+    let body = error.get_raw_response_data().map(str::to_owned);
+    if body.is_none() {
+        return error;
     }
+    Error::from_unexpected_content_type(content_type, None, body.as_deref(), Some(error.into()))
+}
+
+/// Deserializes JSON data other than a response body (e.g. a downloaded file).
+/// On failure, the data is kept as the raw response data.
+#[cfg(feature = "drive")]
+pub fn parse_raw_data<T>(raw_data: &[u8]) -> Result<T>
+where
+    T: DeserializeOwned,
+{
+    deserialize(raw_data, || lossy(raw_data))
+}
 
-    let raw_response_text = raw_response_text.unwrap();
+async fn parse_raw_response_body<T>(raw_response_body: Option<Bytes>) -> Result<T>
+where
+    T: DeserializeOwned,
+{
+    match raw_response_body {
+        Some(body) => deserialize(&body, || lossy(&body)),
+        None => Err(Error::from_failed_deserialization(None)),
+    }
+}
 
-    let model = serde_json::from_str::<T>(&raw_response_text);
+fn lossy(data: &[u8]) -> String {
+    String::from_utf8_lossy(data).into_owned()
+}
 
-    if let Err(_) = model {
-        return Err(Error::from_failed_deserialization(Some(raw_response_text)));
+// Deserializes the JSON `data` with simd-json. It parses in place, so it works on a copy of the data.
+// On failure the data is deserialized again with serde_json, which reports the error the same way
+// as without the feature.
+#[cfg(all(
+    feature = "simd-json",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+fn deserialize<T, F>(data: &[u8], raw_data: F) -> Result<T>
+where
+    T: DeserializeOwned,
+    F: FnOnce() -> String,
+{
+    let mut buffer = data.to_vec();
+    match simd_json::serde::from_slice(&mut buffer) {
+        Ok(model) => Ok(model),
+        Err(_) => deserialize_with_serde_json(data, raw_data),
     }
+}
 
-    Ok(model.unwrap())
+#[cfg(not(all(
+    feature = "simd-json",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+)))]
+fn deserialize<T, F>(data: &[u8], raw_data: F) -> Result<T>
+where
+    T: DeserializeOwned,
+    F: FnOnce() -> String,
+{
+    deserialize_with_serde_json(data, raw_data)
+}
+
+// Deserializes the JSON `data`, keeping the path of the field which failed and the target type in the error.
+// The `raw_data` is called only on failure, to keep the data in the error.
+fn deserialize_with_serde_json<'a, T, F>(data: &'a [u8], raw_data: F) -> Result<T>
+where
+    T: Deserialize<'a>,
+    F: FnOnce() -> String,
+{
+    let mut deserializer = serde_json::Deserializer::from_slice(data);
+    let model = serde_path_to_error::deserialize(&mut deserializer).map_err(|error| {
+        Error::from_failed_deserialization_at(Some(raw_data()), error, std::any::type_name::<T>())
+    })?;
+
+    // Trailing characters after the JSON value are rejected as well.
+    match deserializer.end() {
+        Ok(()) => Ok(model),
+        Err(_) => Err(Error::from_failed_deserialization(Some(lossy(data)))),
+    }
 }
 
 #[cfg(test)]
@@ -39,40 +148,245 @@ mod tests {
     }
 
     #[tokio::test]
-    pub async fn parse_raw_response_text_for_valid_data() {
+    pub async fn parse_raw_response_body_for_valid_data() {
         let text = r#"{ "data": 10 }"#;
-        let result = parse_raw_response_text::<SampleModel>(Some(text.into())).await;
-        assert!(matches!(result, Ok(_)));
+        let result = parse_raw_response_body::<SampleModel>(Some(text.into())).await;
+        assert!(result.is_ok());
         let model = result.unwrap();
         assert_eq!(model, SampleModel { data: 10 });
     }
 
     #[tokio::test]
-    pub async fn parse_raw_response_text_for_none() {
-        let result = parse_raw_response_text::<SampleModel>(None).await;
-        assert!(matches!(result, Err(_)));
+    pub async fn parse_raw_response_body_for_none() {
+        let result = parse_raw_response_body::<SampleModel>(None).await;
+        assert!(result.is_err());
         let error = result.err().unwrap();
         assert!(error.is_body_deserialization());
         assert_eq!(error.get_raw_response_data(), None);
     }
 
     #[tokio::test]
-    pub async fn parse_raw_response_text_for_incompatible_model() {
+    pub async fn parse_raw_response_body_for_incompatible_model() {
         let text = r#"{ "data": "text data" }"#;
-        let result = parse_raw_response_text::<SampleModel>(Some(text.into())).await;
-        assert!(matches!(result, Err(_)));
+        let result = parse_raw_response_body::<SampleModel>(Some(text.into())).await;
+        assert!(result.is_err());
         let error = result.err().unwrap();
         assert!(error.is_body_deserialization());
-        assert_eq!(error.get_raw_response_data(), Some(text.into()));
+        assert_eq!(error.get_raw_response_data(), Some(text));
+    }
+
+    #[cfg(feature = "drive")]
+    #[test]
+    pub fn parse_raw_data_for_valid_data() {
+        let model = parse_raw_data::<SampleModel>(br#"{ "data": 10 }"#).unwrap();
+        assert_eq!(model, SampleModel { data: 10 });
+    }
+
+    #[cfg(feature = "drive")]
+    #[test]
+    pub fn parse_raw_data_for_invalid_json() {
+        let text = r#"{ "data"; }"#;
+        let error = parse_raw_data::<SampleModel>(text.as_bytes()).unwrap_err();
+        assert!(error.is_body_deserialization());
+        assert_eq!(error.get_raw_response_data(), Some(text));
     }
 
     #[tokio::test]
-    pub async fn parse_raw_response_text_for_invalid_json() {
+    pub async fn parse_raw_response_body_for_invalid_json() {
         let text = r#"{ "data"; }"#;
-        let result = parse_raw_response_text::<SampleModel>(Some(text.into())).await;
-        assert!(matches!(result, Err(_)));
+        let result = parse_raw_response_body::<SampleModel>(Some(text.into())).await;
+        assert!(result.is_err());
         let error = result.err().unwrap();
         assert!(error.is_body_deserialization());
-        assert_eq!(error.get_raw_response_data(), Some(text.into()));
+        assert_eq!(error.get_raw_response_data(), Some(text));
+    }
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct EscapedModel {
+        text: String,
+        numbers: Vec<f64>,
+        nested: Option<Box<EscapedModel>>,
+    }
+
+    #[tokio::test]
+    pub async fn parse_raw_response_body_for_escapes_and_numbers() {
+        let text = r#"{ "text": "a\"b\\c\u00f3\n", "numbers": [1, -2.5, 1e3], "nested": { "text": "zażółć", "numbers": [], "nested": null } }"#;
+        let model = parse_raw_response_body::<EscapedModel>(Some(text.into()))
+            .await
+            .unwrap();
+
+        assert_eq!(model.text, "a\"b\\c\u{f3}\n");
+        assert_eq!(model.numbers, [1.0, -2.5, 1000.0]);
+        assert_eq!(model.nested.unwrap().text, "zażółć");
+    }
+
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct SampleItem {
+        key: String,
+        some_field_2: i32,
+    }
+
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct SampleItems {
+        items: Vec<SampleItem>,
+    }
+
+    #[tokio::test]
+    pub async fn parse_raw_response_body_reports_path_of_mismatched_field() {
+        let mut items: Vec<String> = (0..20)
+            .map(|index| format!(r#"{{ "key": "{}", "some_field_2": {} }}"#, index, index))
+            .collect();
+        items[17] = r#"{ "key": "17", "some_field_2": "text" }"#.into();
+        let text = format!(r#"{{ "items": [{}] }}"#, items.join(", "));
+
+        let error = parse_raw_response_body::<SampleItems>(Some(text.clone().into()))
+            .await
+            .unwrap_err();
+
+        assert!(error.is_body_deserialization());
+        assert_eq!(error.deser_path(), Some("items[17].some_field_2"));
+        assert!(error.deser_type_name().unwrap().ends_with("SampleItems"));
+        assert_eq!(error.get_raw_response_data(), Some(text.as_str()));
+        assert!(error.to_string().contains("items[17].some_field_2"));
+        assert!(error.to_string().contains("SampleItems"));
+        assert!(std::error::Error::source(&error).is_some());
+    }
+
+    #[tokio::test]
+    pub async fn parse_raw_response_body_for_trailing_characters() {
+        let text = r#"{ "data": 10 } x"#;
+        let error = parse_raw_response_body::<SampleModel>(Some(text.into()))
+            .await
+            .unwrap_err();
+        assert!(error.is_body_deserialization());
+        assert_eq!(error.get_raw_response_data(), Some(text));
+    }
+
+    #[tokio::test]
+    pub async fn parse_raw_response_body_for_invalid_utf8() {
+        let body =
+            Bytes::from_static(b"{ \"text\": \"a\xffb\", \"numbers\": [], \"nested\": null }");
+        let error = parse_raw_response_body::<EscapedModel>(Some(body))
+            .await
+            .unwrap_err();
+
+        assert!(error.is_body_deserialization());
+        assert_eq!(
+            error.get_raw_response_data(),
+            Some("{ \"text\": \"a\u{fffd}b\", \"numbers\": [], \"nested\": null }")
+        );
+    }
+
+    #[cfg(feature = "base")]
+    #[test]
+    pub fn parse_borrowed_in_two_phases() {
+        let text = r#"{ "items": [{ "key": "a", "some_field_2": 1 }, { "key": "b\"", "some_field_2": 2 }] }"#;
+        let raw: SampleRawItems = parse_borrowed(text.as_bytes()).unwrap();
+        assert_eq!(raw.items[1].get(), r#"{ "key": "b\"", "some_field_2": 2 }"#);
+
+        let item: SampleItem = parse_borrowed(raw.items[1].get().as_bytes()).unwrap();
+        assert_eq!(item.key, "b\"");
+        let borrowed: BorrowedItem = parse_borrowed(raw.items[0].get().as_bytes()).unwrap();
+        assert_eq!(borrowed.key, "a");
+    }
+
+    #[cfg(feature = "base")]
+    #[test]
+    pub fn parse_borrowed_for_invalid_utf8() {
+        let error = parse_borrowed::<BorrowedItem>(b"{ \"key\": \"\xc3\" }").unwrap_err();
+        assert!(error.is_body_deserialization());
+        assert_eq!(
+            error.get_raw_response_data(),
+            Some("{ \"key\": \"\u{fffd}\" }")
+        );
+    }
+
+    #[cfg(feature = "base")]
+    #[derive(Deserialize)]
+    struct SampleRawItems<'a> {
+        #[serde(borrow)]
+        items: Vec<&'a serde_json::value::RawValue>,
+    }
+
+    #[cfg(feature = "base")]
+    #[derive(Deserialize, Debug)]
+    struct BorrowedItem<'a> {
+        key: &'a str,
+    }
+
+    #[cfg(feature = "drive")]
+    #[test]
+    pub fn parse_raw_data_reports_path_of_mismatched_field() {
+        let text =
+            r#"{ "items": [{ "key": "a", "some_field_2": 1 }, { "key": 2, "some_field_2": 2 }] }"#;
+        let error = parse_raw_data::<SampleItems>(text.as_bytes()).unwrap_err();
+        assert_eq!(error.deser_path(), Some("items[1].key"));
+    }
+
+    async fn parse_mocked_body(response: wiremock::ResponseTemplate) -> Result<SampleModel> {
+        let server = wiremock::MockServer::start().await;
+        wiremock::Mock::given(wiremock::matchers::method("GET"))
+            .respond_with(response)
+            .mount(&server)
+            .await;
+        let response = reqwest::get(server.uri()).await.unwrap();
+        parse_response_body(response).await
+    }
+
+    #[tokio::test]
+    pub async fn parse_response_body_for_html() {
+        let html = "<html><h1>Maintenance</h1></html>";
+        let response = wiremock::ResponseTemplate::new(200).set_body_raw(html, "text/html");
+        let error = parse_mocked_body(response).await.unwrap_err();
+
+        assert!(error.is_unexpected_content_type());
+        assert_eq!(error.get_raw_response_data(), Some(html));
+        assert!(std::error::Error::source(&error).is_some());
+    }
+
+    #[test]
+    pub fn with_content_type_missing() {
+        let error = Error::from_failed_deserialization(Some("Maintenance".into()));
+        let error = with_content_type(error, None);
+
+        assert!(matches!(
+            error.get_kind(),
+            crate::error::Kind::UnexpectedContentType { content_type: None }
+        ));
+        assert_eq!(error.get_raw_response_data(), Some("Maintenance"));
+    }
+
+    #[tokio::test]
+    pub async fn parse_response_body_for_invalid_json() {
+        let response = wiremock::ResponseTemplate::new(200)
+            .set_body_raw(r#"{ "data": "text" }"#, "application/json");
+        let error = parse_mocked_body(response).await.unwrap_err();
+
+        assert!(error.is_body_deserialization());
+        assert_eq!(error.deser_path(), Some("data"));
+    }
+
+    #[tokio::test]
+    pub async fn parse_response_body_for_invalid_utf8() {
+        let response = wiremock::ResponseTemplate::new(200)
+            .set_body_raw(b"{ \"data\": \"\xff\" }".to_vec(), "application/json");
+        let error = parse_mocked_body(response).await.unwrap_err();
+
+        assert!(error.is_body_deserialization());
+        assert_eq!(
+            error.get_raw_response_data(),
+            Some("{ \"data\": \"\u{fffd}\" }")
+        );
+    }
+
+    #[tokio::test]
+    pub async fn parse_response_body_for_valid_json() {
+        let response = wiremock::ResponseTemplate::new(200)
+            .set_body_raw(r#"{ "data": 1 }"#, "application/json");
+        let model = parse_mocked_body(response).await.unwrap();
+
+        assert_eq!(model, SampleModel { data: 1 });
     }
 }
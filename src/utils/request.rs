@@ -1,23 +1,321 @@
 use crate::error::{Error, ErrorResponseData, Result};
+use crate::transport::Transport;
 
-pub async fn send_request(request: reqwest::RequestBuilder) -> Result<reqwest::Response> {
-    let response = request.send().await?;
+// Sends the request through the `transport` instead of the client the request has been built with.
+pub async fn send_request_via(
+    transport: &dyn Transport,
+    request: reqwest::RequestBuilder,
+) -> Result<reqwest::Response> {
+    execute(transport, request.build()?).await
+}
+
+// Sends a request to an endpoint not covered by the services, at the `path` under the `base_url`
+// of the service, with the api key and the JSON `body`.
+pub async fn send_raw_request(
+    transport: &dyn Transport,
+    base_url: &str,
+    x_api_key: &str,
+    method: reqwest::Method,
+    path: &str,
+    body: Option<serde_json::Value>,
+) -> Result<reqwest::Response> {
+    let mut request = reqwest::Client::new()
+        .request(method, raw_request_url(base_url, path)?)
+        .header("X-Api-Key", x_api_key);
+    if let Some(body) = body {
+        request = request.json(&body);
+    }
+    send_request_via(transport, request).await
+}
+
+// Appends the `path` to the `base_url`, rejecting the paths leading outside of it, e.g. with `..` segments.
+// The path is checked after parsing the url, as parsing resolves the encoded dots and backslashes too.
+fn raw_request_url(base_url: &str, path: &str) -> Result<reqwest::Url> {
+    let invalid_path = || Error::from_invalid_argument(format!("Invalid request path '{}'", path));
+    let base = reqwest::Url::parse(&format!("{}/", base_url.trim_end_matches('/')))
+        .map_err(|_| invalid_path())?;
+    let url = base
+        .join(path.trim_start_matches('/'))
+        .map_err(|_| invalid_path())?;
+    if url.origin() != base.origin() || !url.path().starts_with(base.path()) {
+        return Err(Error::from_invalid_argument(format!(
+            "The request path '{}' leads outside of '{}'",
+            path, base_url
+        )));
+    }
+    Ok(url)
+}
+
+async fn execute(
+    transport: &dyn Transport,
+    request: reqwest::Request,
+) -> Result<reqwest::Response> {
+    let method = request.method().clone();
+    let url = request.url().clone();
+
+    let response = transport
+        .execute(request)
+        .await
+        .map_err(|error| error.with_request(&method, &url))?;
     let status = response.status();
 
     if status.is_success() {
         return Ok(response);
     }
 
+    let content_type = content_type(&response);
+    let headers = response.headers().clone();
     let raw_response_body = response.text().await.ok();
+    if content_type
+        .as_deref()
+        .is_some_and(|content_type| !is_json(content_type))
+    {
+        return Err(Error::from_unexpected_content_type(
+            content_type,
+            Some(status),
+            raw_response_body.as_deref(),
+            None,
+        )
+        .with_request(&method, &url)
+        .with_response_headers(&headers));
+    }
+
     let errors: Option<ErrorResponseData> = if let Some(ref raw_response_body) = raw_response_body {
         serde_json::from_str(raw_response_body).ok()
     } else {
         None
     };
 
-    return Err(Error::from_response_data(
-        Some(status),
-        errors,
-        raw_response_body,
-    ));
-}
\ No newline at end of file
+    Err(
+        Error::from_response_data(Some(status), errors, raw_response_body)
+            .with_request(&method, &url)
+            .with_response_headers(&headers),
+    )
+}
+
+// Returns the `Content-Type` header of the response.
+pub(super) fn content_type(response: &reqwest::Response) -> Option<String> {
+    let content_type = response.headers().get(reqwest::header::CONTENT_TYPE)?;
+    content_type.to_str().ok().map(str::to_owned)
+}
+
+// Checks whether the content type denotes JSON, e.g. `application/json` or `application/problem+json`.
+pub(super) fn is_json(content_type: &str) -> bool {
+    content_type.to_lowercase().contains("json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::HttpTransport;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn send_request_records_request() {
+        let server = MockServer::start().await;
+        Mock::given(method("DELETE"))
+            .and(path("/items/a"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&server)
+            .await;
+
+        let request = reqwest::Client::new()
+            .delete(format!("{}/items/a", server.uri()))
+            .header("X-Api-Key", "secret_key")
+            .query(&[("key", "secret_key")]);
+        let error = send_request_via(&HttpTransport::default(), request)
+            .await
+            .unwrap_err();
+
+        let url = format!("{}/items/a", server.uri());
+        assert!(error.is_not_found());
+        assert_eq!(error.method(), Some("DELETE"));
+        assert_eq!(error.url(), Some(url.as_str()));
+        assert!(error.to_string().contains(&format!("'DELETE {}'", url)));
+        assert!(!error.to_string().contains("secret_key"));
+        assert!(!format!("{:?}", error).contains("secret_key"));
+    }
+
+    #[tokio::test]
+    async fn send_request_records_connection_failure() {
+        let request = reqwest::Client::new().get("http://127.0.0.1:1/files?key=secret_key");
+        let error = send_request_via(&HttpTransport::default(), request)
+            .await
+            .unwrap_err();
+
+        assert_eq!(error.method(), Some("GET"));
+        assert_eq!(error.url(), Some("http://127.0.0.1:1/files"));
+        assert!(!error.to_string().contains("secret_key"));
+        assert!(!format!("{:?}", error).contains("secret_key"));
+    }
+
+    #[tokio::test]
+    async fn send_request_for_html_error_page() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(
+                ResponseTemplate::new(502)
+                    .set_body_raw("<html><h1>502 Bad Gateway</h1></html>", "text/html"),
+            )
+            .mount(&server)
+            .await;
+
+        let request = reqwest::Client::new().get(format!("{}/items/a", server.uri()));
+        let error = send_request_via(&HttpTransport::default(), request)
+            .await
+            .unwrap_err();
+
+        assert!(error.is_unexpected_content_type());
+        assert!(matches!(
+            error.get_kind(),
+            crate::error::Kind::UnexpectedContentType { content_type: Some(content_type) }
+                if content_type == "text/html"
+        ));
+        assert_eq!(error.status(), Some(502));
+        assert!(error.is_retryable());
+        assert_eq!(
+            error.get_raw_response_data(),
+            Some("<html><h1>502 Bad Gateway</h1></html>")
+        );
+    }
+
+    #[tokio::test]
+    async fn send_request_for_error_without_content_type() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&server)
+            .await;
+
+        let request = reqwest::Client::new().get(format!("{}/items/a", server.uri()));
+        let error = send_request_via(&HttpTransport::default(), request)
+            .await
+            .unwrap_err();
+
+        assert!(error.is_not_found());
+        assert!(!error.is_unexpected_content_type());
+    }
+
+    #[tokio::test]
+    async fn send_request_for_json_error() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(400).set_body_raw(
+                r#"{"errors":["Bad key"]}"#,
+                "application/json; charset=utf-8",
+            ))
+            .mount(&server)
+            .await;
+
+        let request = reqwest::Client::new().get(format!("{}/items/a", server.uri()));
+        let error = send_request_via(&HttpTransport::default(), request)
+            .await
+            .unwrap_err();
+
+        assert!(error.is_bad_request());
+        assert_eq!(error.server_errors(), Some(&["Bad key".to_owned()][..]));
+    }
+
+    #[tokio::test]
+    async fn send_request_keeps_response_headers() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(
+                ResponseTemplate::new(429)
+                    .insert_header("Retry-After", "5")
+                    .insert_header("X-Request-Id", "req-1")
+                    .insert_header("X-RateLimit-Limit", "100")
+                    .insert_header("X-Other", "other"),
+            )
+            .mount(&server)
+            .await;
+
+        let request = reqwest::Client::new().get(format!("{}/items/a", server.uri()));
+        let error = send_request_via(&HttpTransport::default(), request)
+            .await
+            .unwrap_err();
+
+        assert!(error.is_rate_limited());
+        assert_eq!(error.retry_after(), Some(std::time::Duration::from_secs(5)));
+        assert_eq!(error.request_id(), Some("req-1"));
+        assert_eq!(error.response_header("x-ratelimit-limit"), Some("100"));
+        assert_eq!(error.response_header("x-other"), None);
+    }
+
+    #[tokio::test]
+    async fn send_request_without_response_headers() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(429))
+            .mount(&server)
+            .await;
+
+        let request = reqwest::Client::new().get(format!("{}/items/a", server.uri()));
+        let error = send_request_via(&HttpTransport::default(), request)
+            .await
+            .unwrap_err();
+
+        assert_eq!(error.retry_after(), None);
+        assert_eq!(error.request_id(), None);
+        assert_eq!(error.response_header("x-ratelimit-limit"), None);
+    }
+
+    #[tokio::test]
+    async fn send_request_keeps_headers_of_html_error_page() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(
+                ResponseTemplate::new(503)
+                    .insert_header("Retry-After", "120")
+                    .set_body_raw("<html>Unavailable</html>", "text/html"),
+            )
+            .mount(&server)
+            .await;
+
+        let request = reqwest::Client::new().get(format!("{}/items/a", server.uri()));
+        let error = send_request_via(&HttpTransport::default(), request)
+            .await
+            .unwrap_err();
+
+        assert!(error.is_unexpected_content_type());
+        assert_eq!(
+            error.retry_after(),
+            Some(std::time::Duration::from_secs(120))
+        );
+    }
+
+    #[test]
+    fn raw_request_url_stays_under_base_url() {
+        let base_url = "https://database.deta.sh/v1/project/base";
+        for (path, expected) in [
+            (
+                "items/a",
+                "https://database.deta.sh/v1/project/base/items/a",
+            ),
+            (
+                "/items/a?x=1",
+                "https://database.deta.sh/v1/project/base/items/a?x=1",
+            ),
+            ("", "https://database.deta.sh/v1/project/base/"),
+            ("a/../b", "https://database.deta.sh/v1/project/base/b"),
+            (
+                "//other.example/items",
+                "https://database.deta.sh/v1/project/base/other.example/items",
+            ),
+        ] {
+            assert_eq!(raw_request_url(base_url, path).unwrap().as_str(), expected);
+        }
+        for path in [
+            "..",
+            "../other/items",
+            "items/../../other",
+            "%2e%2e/other",
+            "..\\other",
+            "https://evil.example/items",
+        ] {
+            let error = raw_request_url(base_url, path).unwrap_err();
+            assert!(error.is_invalid_argument(), "{} should be rejected", path);
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use crate::error::Result;
+use futures::{Stream, TryStreamExt};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+
+// Paces transfers to the given number of bytes per second.
+// Clones share the same budget, so that concurrent transfers together don't exceed the limit.
+#[derive(Clone, Debug)]
+pub struct Throttle {
+    bytes_per_second: u64,
+    next_free: Arc<Mutex<Instant>>,
+}
+
+impl Throttle {
+    pub fn new(bytes_per_second: u64) -> Self {
+        Self {
+            bytes_per_second: bytes_per_second.max(1),
+            next_free: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+
+    // Waits until transferring the `bytes` fits within the limit. The time needed to transfer them
+    // is reserved right away, so that concurrent callers queue up one after another.
+    pub async fn consume(&self, bytes: usize) {
+        let duration = Duration::from_secs_f64(bytes as f64 / self.bytes_per_second as f64);
+        let ready_at = {
+            let mut next_free = self
+                .next_free
+                .lock()
+                .expect("The throttle lock is never poisoned");
+            *next_free = (*next_free).max(Instant::now()) + duration;
+            *next_free
+        };
+        tokio::time::sleep_until(ready_at).await;
+    }
+
+    // Delays the chunks of the stream to keep the limit.
+    pub fn pace<S>(self, stream: S) -> impl Stream<Item = Result<bytes::Bytes>>
+    where
+        S: Stream<Item = Result<bytes::Bytes>>,
+    {
+        stream.and_then(move |chunk| {
+            let throttle = self.clone();
+            async move {
+                throttle.consume(chunk.len()).await;
+                Ok(chunk)
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KB: usize = 1024;
+
+    #[tokio::test(start_paused = true)]
+    async fn consume_paces_transfer() {
+        let throttle = Throttle::new(256 * KB as u64);
+        let started = Instant::now();
+        for _ in 0..16 {
+            throttle.consume(64 * KB).await;
+        }
+        assert_eq!(started.elapsed(), Duration::from_secs(4));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn consume_shares_limit_between_clones() {
+        let throttle = Throttle::new(256 * KB as u64);
+        let started = Instant::now();
+        let transfer = |throttle: Throttle| async move {
+            for _ in 0..8 {
+                throttle.consume(64 * KB).await;
+            }
+        };
+        futures::join!(transfer(throttle.clone()), transfer(throttle));
+        assert_eq!(started.elapsed(), Duration::from_secs(4));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn pace_delays_stream() {
+        let chunks = (0..16).map(|_| Ok(bytes::Bytes::from(vec![0; 64 * KB])));
+        let started = Instant::now();
+        let data: Vec<bytes::Bytes> = Throttle::new(256 * KB as u64)
+            .pace(futures::stream::iter(chunks))
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(data.len(), 16);
+        assert_eq!(started.elapsed(), Duration::from_secs(4));
+    }
+}
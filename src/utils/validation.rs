@@ -0,0 +1,239 @@
+//! Client-side validation of the names and keys accepted by deta.
+//! Each rule is a single entry in a table, so it's easy to adjust them when deta changes its requirements.
+
+#[cfg(feature = "base")]
+use crate::constants::MAX_KEY_LENGTH;
+#[cfg(feature = "drive")]
+use crate::constants::{MAX_DRIVE_NAME_LENGTH, MAX_FILE_NAME_LENGTH};
+use crate::error::{Error, Result};
+
+struct Rule {
+    description: &'static str,
+    check: fn(&str) -> bool,
+}
+
+const NOT_BLANK_RULES: &[Rule] = &[
+    Rule {
+        description: "must not be empty",
+        check: |value| !value.is_empty(),
+    },
+    Rule {
+        description: "must not consist only of whitespace",
+        check: |value| !value.trim().is_empty(),
+    },
+];
+
+#[cfg(feature = "base")]
+const KEY_RULES: &[Rule] = &[
+    Rule {
+        description: "must not be empty",
+        check: |key| !key.is_empty(),
+    },
+    Rule {
+        description: "must be at most 1024 bytes long",
+        check: |key| key.len() <= MAX_KEY_LENGTH,
+    },
+    Rule {
+        description: "must not start or end with whitespace",
+        check: |key| key.trim() == key,
+    },
+    Rule {
+        description: "must not contain control characters",
+        check: |key| !key.chars().any(char::is_control),
+    },
+];
+
+#[cfg(feature = "drive")]
+const DRIVE_NAME_RULES: &[Rule] = &[
+    Rule {
+        description: "must not be empty",
+        check: |name| !name.is_empty(),
+    },
+    Rule {
+        description: "must be at most 64 characters long",
+        check: |name| name.chars().count() <= MAX_DRIVE_NAME_LENGTH,
+    },
+    Rule {
+        description: "may contain only ASCII letters, digits, '_' and '-'",
+        check: |name| {
+            name.chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        },
+    },
+];
+
+#[cfg(feature = "drive")]
+const FILE_NAME_RULES: &[Rule] = &[
+    Rule {
+        description: "must not be empty",
+        check: |name| !name.is_empty(),
+    },
+    Rule {
+        description: "must not consist only of whitespace",
+        check: |name| !name.trim().is_empty(),
+    },
+    Rule {
+        description: "must be at most 1024 bytes long",
+        check: |name| name.len() <= MAX_FILE_NAME_LENGTH,
+    },
+    Rule {
+        description: "must not start with '/'",
+        check: |name| !name.starts_with('/'),
+    },
+];
+
+/// Checks the item key, returning the description of the first broken rule.
+#[cfg(feature = "base")]
+pub fn broken_key_rule(key: &str) -> Option<&'static str> {
+    broken_rule(key, KEY_RULES)
+}
+
+/// Checks the drive name, returning an invalid argument error that names the first broken rule.
+#[cfg(feature = "drive")]
+pub fn validate_drive_name(name: &str) -> Result<()> {
+    validate("drive name", name, DRIVE_NAME_RULES)
+}
+
+/// Checks the file name, returning an invalid argument error that names the first broken rule.
+#[cfg(feature = "drive")]
+pub fn validate_file_name(name: &str) -> Result<()> {
+    validate("file name", name, FILE_NAME_RULES)
+}
+
+/// Checks that the value isn't empty or whitespace only, returning an invalid argument error
+/// that names the `parameter`, e.g. `key`.
+pub fn validate_not_blank(parameter: &str, value: &str) -> Result<()> {
+    validate(parameter, value, NOT_BLANK_RULES)
+}
+
+fn validate(subject: &str, value: &str, rules: &[Rule]) -> Result<()> {
+    match broken_rule(value, rules) {
+        Some(rule) => Err(Error::from_invalid_argument(format!(
+            "Invalid {} '{}', it {}",
+            subject, value, rule
+        ))),
+        None => Ok(()),
+    }
+}
+
+fn broken_rule(value: &str, rules: &[Rule]) -> Option<&'static str> {
+    rules
+        .iter()
+        .find(|rule| !(rule.check)(value))
+        .map(|rule| rule.description)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Each case is an input and the description of the broken rule, if any.
+    fn check_cases(validator: fn(&str) -> Result<()>, cases: &[(&str, Option<&str>)]) {
+        for (input, broken_rule) in cases {
+            let result = validator(input);
+            match broken_rule {
+                None => assert!(result.is_ok(), "'{}' should be valid", input),
+                Some(rule) => {
+                    let error = result.expect_err(&format!("'{}' should be invalid", input));
+                    assert!(error.is_invalid_argument());
+                    assert!(
+                        error.to_string().contains(rule),
+                        "'{}' should break the '{}' rule, got: {}",
+                        input,
+                        rule,
+                        error
+                    );
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "drive")]
+    #[test]
+    fn validate_drive_name_for_cases() {
+        let too_long = "a".repeat(MAX_DRIVE_NAME_LENGTH + 1);
+        let longest = "a".repeat(MAX_DRIVE_NAME_LENGTH);
+        check_cases(
+            validate_drive_name,
+            &[
+                ("photos", None),
+                ("my-drive_2", None),
+                (&longest, None),
+                ("", Some("must not be empty")),
+                (&too_long, Some("at most 64 characters")),
+                ("my drive", Some("only ASCII letters")),
+                ("drive/name", Some("only ASCII letters")),
+                ("zdjęcia", Some("only ASCII letters")),
+            ],
+        );
+    }
+
+    #[cfg(feature = "drive")]
+    #[test]
+    fn validate_file_name_for_cases() {
+        let too_long = "a".repeat(MAX_FILE_NAME_LENGTH + 1);
+        let longest = "a".repeat(MAX_FILE_NAME_LENGTH);
+        check_cases(
+            validate_file_name,
+            &[
+                ("file.txt", None),
+                ("dir/sub dir/file.txt", None),
+                ("zdjęcie.jpg", None),
+                (&longest, None),
+                ("", Some("must not be empty")),
+                ("   ", Some("only of whitespace")),
+                ("\t\n", Some("only of whitespace")),
+                (&too_long, Some("at most 1024 bytes")),
+                ("/file.txt", Some("must not start with '/'")),
+            ],
+        );
+    }
+
+    #[test]
+    fn validate_not_blank_for_cases() {
+        check_cases(
+            |value| validate_not_blank("key", value),
+            &[
+                ("user_1", None),
+                (" user_1 ", None),
+                ("", Some("must not be empty")),
+                (" \t\n", Some("only of whitespace")),
+            ],
+        );
+        let error = validate_not_blank("key", "").unwrap_err();
+        assert!(error.to_string().contains("Invalid key"));
+    }
+
+    #[cfg(feature = "base")]
+    #[test]
+    fn broken_key_rule_for_cases() {
+        let too_long = "a".repeat(MAX_KEY_LENGTH + 1);
+        let longest = "a".repeat(MAX_KEY_LENGTH);
+        let cases: &[(&str, Option<&str>)] = &[
+            ("user_1", None),
+            ("user 1", None),
+            ("zażółć", None),
+            ("a/b?c#d", None),
+            (&longest, None),
+            ("", Some("must not be empty")),
+            (&too_long, Some("at most 1024 bytes")),
+            ("user_1\n", Some("start or end with whitespace")),
+            (" user_1", Some("start or end with whitespace")),
+            ("user\t1", Some("control characters")),
+            ("user\u{7f}1", Some("control characters")),
+        ];
+        for (input, rule) in cases {
+            let broken = broken_key_rule(input);
+            match rule {
+                None => assert_eq!(broken, None, "{:?} should be valid", input),
+                Some(rule) => assert!(
+                    broken.is_some_and(|broken| broken.contains(rule)),
+                    "{:?} should break the '{}' rule, got: {:?}",
+                    input,
+                    rule,
+                    broken
+                ),
+            }
+        }
+    }
+}
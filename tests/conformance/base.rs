@@ -0,0 +1,227 @@
+//! Conformance scenarios for deta base, run against the real API and against `deta_rust::testing::FakeBase`
+//! to check that the fake behaves like the service. Each scenario expects an empty base and leaves it empty.
+
+use deta_rust::{
+    database::{
+        query::{Condition, Query},
+        updates::{Action, Updates},
+        Database,
+    },
+    error::ResponseStatusKind,
+    serde_json::{json, Value},
+};
+
+/// Runs all scenarios one after another.
+pub async fn run_all(database: &Database) {
+    put_and_get(database).await;
+    insert_with_existing_key(database).await;
+    fetch_with_query(database).await;
+    fetch_with_pagination(database).await;
+    update(database).await;
+    delete_nonexistent(database).await;
+}
+
+async fn clean(database: &Database) {
+    let items = database
+        .fetch_items::<Value>(None, None, None)
+        .await
+        .expect("Fetch items went wrong during clean() performing");
+    for item in items.items {
+        database
+            .delete_item(item["key"].as_str().unwrap())
+            .await
+            .expect("Delete item went wrong during clean() performing");
+    }
+}
+
+async fn setup(database: &Database) {
+    database
+        .put_items(&[
+            json!({ "key": "conformance_a", "name": "Anna", "age": 30, "tags": ["admin"] }),
+            json!({ "key": "conformance_b", "name": "Bob", "age": 17 }),
+            json!({ "key": "conformance_c", "name": "Cecil", "age": -4 }),
+        ])
+        .await
+        .unwrap();
+}
+
+async fn keys(database: &Database, query: Query) -> Vec<String> {
+    let mut keys: Vec<String> = database
+        .fetch_items::<Value>(None, None, Some(query))
+        .await
+        .unwrap()
+        .items
+        .iter()
+        .map(|item| item["key"].as_str().unwrap().to_owned())
+        .collect();
+    keys.sort();
+    keys
+}
+
+pub async fn put_and_get(database: &Database) {
+    let result = database
+        .put_items(&[
+            json!({ "key": "conformance_a", "age": 30 }),
+            json!({ "age": 17 }),
+        ])
+        .await
+        .unwrap();
+    assert_eq!(result.processed.items.len(), 2);
+    let generated = result.processed.items[1]["key"]
+        .as_str()
+        .unwrap()
+        .to_owned();
+    assert!(!generated.is_empty());
+
+    let item: Option<Value> = database.get_item("conformance_a").await.unwrap();
+    assert_eq!(item, Some(json!({ "key": "conformance_a", "age": 30 })));
+    let item: Option<Value> = database.get_item(&generated).await.unwrap();
+    assert_eq!(item, Some(json!({ "key": generated, "age": 17 })));
+    assert!(database
+        .get_item::<Value>("conformance_missing")
+        .await
+        .unwrap()
+        .is_none());
+
+    clean(database).await;
+}
+
+pub async fn insert_with_existing_key(database: &Database) {
+    database
+        .insert_item(&json!({ "key": "conformance_a", "age": 30 }))
+        .await
+        .unwrap();
+    let error = database
+        .insert_item(&json!({ "key": "conformance_a", "age": 31 }))
+        .await
+        .unwrap_err();
+    assert_eq!(error.status_kind(), Some(&ResponseStatusKind::Conflict));
+
+    let item: Option<Value> = database.get_item("conformance_a").await.unwrap();
+    assert_eq!(item.unwrap()["age"], 30);
+
+    clean(database).await;
+}
+
+pub async fn fetch_with_query(database: &Database) {
+    setup(database).await;
+
+    let cases = [
+        (
+            Query::init().on("name", Condition::equal("Bob")),
+            vec!["conformance_b"],
+        ),
+        (
+            Query::init().on("name", Condition::not_equal("Bob")),
+            vec!["conformance_a", "conformance_c"],
+        ),
+        (
+            Query::init().on("age", Condition::less_than(17)),
+            vec!["conformance_c"],
+        ),
+        (
+            Query::init().on("age", Condition::greater_than(17)),
+            vec!["conformance_a"],
+        ),
+        (
+            Query::init().on("age", Condition::less_than_or_equal(17)),
+            vec!["conformance_b", "conformance_c"],
+        ),
+        (
+            Query::init().on("age", Condition::greater_than_or_equal(17)),
+            vec!["conformance_a", "conformance_b"],
+        ),
+        (
+            Query::init().on("name", Condition::prefix("Ce")),
+            vec!["conformance_c"],
+        ),
+        (
+            Query::init().on("age", Condition::range(-4, 17)),
+            vec!["conformance_b", "conformance_c"],
+        ),
+        (
+            Query::init().on("name", Condition::contains("o")),
+            vec!["conformance_b"],
+        ),
+        (
+            Query::init().on("tags", Condition::contains("admin")),
+            vec!["conformance_a"],
+        ),
+        (
+            Query::init().on("name", Condition::not_contains("o")),
+            vec!["conformance_a", "conformance_c"],
+        ),
+        (
+            Query::init()
+                .on("name", Condition::equal("Anna"))
+                .either()
+                .on("age", Condition::less_than(0)),
+            vec!["conformance_a", "conformance_c"],
+        ),
+    ];
+    for (query, expected) in cases {
+        assert_eq!(keys(database, query).await, expected);
+    }
+
+    clean(database).await;
+}
+
+pub async fn fetch_with_pagination(database: &Database) {
+    setup(database).await;
+
+    let mut fetched = vec![];
+    let mut last = None;
+    loop {
+        let page = database
+            .fetch_items::<Value>(Some(2), last.as_deref(), None)
+            .await
+            .unwrap();
+        assert!(page.items.len() <= 2);
+        fetched.extend(page.items);
+        last = page.paging.last;
+        if last.is_none() {
+            break;
+        }
+    }
+    assert_eq!(fetched.len(), 3);
+
+    clean(database).await;
+}
+
+pub async fn update(database: &Database) {
+    setup(database).await;
+
+    let updates = Updates::init()
+        .add("name", Action::set("Anne"))
+        .add("age", Action::increment(1))
+        .add("tags", Action::append("editor"))
+        .add("history", Action::prepend("created"))
+        .add("unused", Action::delete());
+    let result = database
+        .update_item("conformance_a", updates)
+        .await
+        .unwrap()
+        .expect("The item exists");
+    assert_eq!(result.key, "conformance_a");
+
+    let item: Value = database.get_item("conformance_a").await.unwrap().unwrap();
+    assert_eq!(item["name"], "Anne");
+    assert_eq!(item["age"], 31);
+    assert_eq!(item["tags"], json!(["admin", "editor"]));
+    assert_eq!(item["history"], json!(["created"]));
+
+    let result = database
+        .update_item(
+            "conformance_missing",
+            Updates::init().add("name", Action::set("Nobody")),
+        )
+        .await
+        .unwrap();
+    assert!(result.is_none());
+
+    clean(database).await;
+}
+
+pub async fn delete_nonexistent(database: &Database) {
+    database.delete_item("conformance_missing").await.unwrap();
+}
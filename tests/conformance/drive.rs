@@ -0,0 +1,114 @@
+//! Conformance scenarios for deta drive, run against the real API and against `deta_rust::testing::FakeDrive`
+//! to check that the fake behaves like the service. Each scenario expects an empty drive and leaves it empty.
+
+use deta_rust::drive::Drive;
+
+/// Runs all scenarios one after another.
+pub async fn run_all(drive: &Drive) {
+    put_and_get(drive).await;
+    put_and_get_large_file(drive).await;
+    list_in_order(drive).await;
+    list_with_pagination(drive).await;
+    delete_with_missing_names(drive).await;
+}
+
+async fn clean(drive: &Drive) {
+    let files = drive
+        .list_all_files(None)
+        .await
+        .expect("List files went wrong during clean() performing");
+    if !files.names.is_empty() {
+        drive
+            .delete_files(&files.names)
+            .await
+            .expect("Delete files went wrong during clean() performing");
+    }
+}
+
+async fn setup(drive: &Drive, names: &[&str]) {
+    for name in names {
+        drive
+            .put_file(name, name.as_bytes().to_vec(), None)
+            .await
+            .unwrap();
+    }
+}
+
+pub async fn put_and_get(drive: &Drive) {
+    drive
+        .put_file("conformance.txt", b"data".to_vec(), Some("text/plain"))
+        .await
+        .unwrap();
+
+    let data = drive.get_file_as_u8_vec("conformance.txt").await.unwrap();
+    assert_eq!(data, Some(b"data".to_vec()));
+    let metadata = drive.head_file("conformance.txt").await.unwrap().unwrap();
+    assert_eq!(metadata.content_length, Some(4));
+    assert!(drive
+        .get_file_as_u8_vec("conformance_missing.txt")
+        .await
+        .unwrap()
+        .is_none());
+
+    clean(drive).await;
+}
+
+pub async fn put_and_get_large_file(drive: &Drive) {
+    let data: Vec<u8> = (0..11 * 1024 * 1024).map(|i| (i % 251) as u8).collect(); // 11MB of data
+    drive
+        .put_file("conformance_big.dat", data.clone(), None)
+        .await
+        .unwrap();
+
+    let downloaded = drive
+        .get_file_as_u8_vec("conformance_big.dat")
+        .await
+        .unwrap();
+    assert_eq!(downloaded, Some(data));
+
+    clean(drive).await;
+}
+
+pub async fn list_in_order(drive: &Drive) {
+    setup(drive, &["b.txt", "docs/b.txt", "a.txt", "docs/a.txt"]).await;
+
+    let files = drive.list_files(None, None, None).await.unwrap();
+    assert_eq!(files.names, ["a.txt", "b.txt", "docs/a.txt", "docs/b.txt"]);
+    let files = drive.list_files(None, Some("docs/"), None).await.unwrap();
+    assert_eq!(files.names, ["docs/a.txt", "docs/b.txt"]);
+
+    clean(drive).await;
+}
+
+pub async fn list_with_pagination(drive: &Drive) {
+    setup(drive, &["a.txt", "b.txt", "c.txt"]).await;
+
+    let first = drive.list_files(Some(2), None, None).await.unwrap();
+    assert_eq!(first.names, ["a.txt", "b.txt"]);
+    let last = first.paging.unwrap().last.unwrap();
+    assert_eq!(last, "b.txt");
+
+    let second = drive.list_files(Some(2), None, Some(&last)).await.unwrap();
+    assert_eq!(second.names, ["c.txt"]);
+    assert!(second.paging.is_none());
+
+    clean(drive).await;
+}
+
+pub async fn delete_with_missing_names(drive: &Drive) {
+    setup(drive, &["a.txt", "b.txt"]).await;
+
+    let result = drive
+        .delete_files(&["a.txt".into(), "conformance_missing.txt".into()])
+        .await
+        .unwrap();
+    let mut deleted = result.deleted.clone();
+    deleted.sort();
+    assert_eq!(deleted, ["a.txt", "conformance_missing.txt"]);
+    assert!(result.all_succeeded());
+
+    let files = drive.list_files(None, None, None).await.unwrap();
+    assert_eq!(files.names, ["b.txt"]);
+
+    clean(drive).await;
+}
@@ -1,57 +1,38 @@
 //! The following integration tests are designed to quickly make sure that SDK is compatible with the API.
 
+mod conformance {
+    pub mod base;
+}
+
 use deta_rust::{
     database::{
         models::FetchItems,
         query::{Condition, Query},
         updates::{Action, Updates},
-        Database,
+        Database, FetchOptions, Order,
     },
+    error::ResponseStatusKind,
     serde_json::json,
+    testing::TempBase,
     DetaClient,
 };
 use serde::{Deserialize, Serialize};
-use serial_test::serial;
 
 // ---------- CONFIG ----------
 
-#[macro_use]
-extern crate lazy_static;
-
-fn config() -> Database {
+// Every test works with its own base, emptied at the end, so the tests can run in parallel.
+fn temp_base() -> TempBase {
     dotenv::dotenv().ok();
     let api_key = std::env::var("API_KEY").expect("API_KEY is not provided");
-    let test_db_name = std::env::var("TEST_DB_NAME").expect("TEST_DB_NAME is not provided");
     let client = DetaClient::new(&api_key);
-    Database::new(&client, &test_db_name)
+    TempBase::with_prefix(&client, "deta_rust_test")
 }
 
-const TEST_KEY: &'static str = "123";
-
-lazy_static! {
-    static ref DATABASE: Database = config();
-}
+const TEST_KEY: &str = "123";
 
 // ---------- HELPERS ----------
 
-/// Removes all creted items.
-async fn clean() {
-    let items = DATABASE
-        .fetch_items::<SampleModel>(None, None, None)
-        .await
-        .expect("Fetch items went wrong during clean() performing");
-    let keys: Vec<&String> = items.items.iter().map(|item| &item.key).collect();
-    for key in keys {
-        DATABASE
-            .delete_item(key)
-            .await
-            .expect("Delete item went wrong during clean() performing");
-    }
-}
-
-async fn setup_items() {
-    clean().await;
-
+async fn setup_items(database: &Database) {
     let items = [
         SampleModel {
             key: TEST_KEY.into(),
@@ -70,7 +51,7 @@ async fn setup_items() {
         },
     ];
 
-    DATABASE.put_items(&items).await.unwrap();
+    database.put_items(&items).await.unwrap();
 }
 
 // ---------- MODELS ----------
@@ -87,89 +68,106 @@ struct SampleModel {
 // ---------- TESTS ----------
 
 #[tokio::test]
-#[serial]
 async fn put_items() {
-    setup_items().await;
-    clean().await;
+    let base = temp_base();
+    let database = base.database();
+    setup_items(database).await;
+
+    base.close().await.unwrap();
 }
 
 #[tokio::test]
-#[serial]
 async fn get_item_return_some() {
-    setup_items().await;
-    let res1 = DATABASE.get_item::<SampleModel>(TEST_KEY).await.unwrap();
-    assert!(matches!(res1, Some(_)));
-    clean().await;
+    let base = temp_base();
+    let database = base.database();
+    setup_items(database).await;
+    let res1 = database.get_item::<SampleModel>(TEST_KEY).await.unwrap();
+    assert!(res1.is_some());
+
+    base.close().await.unwrap();
 }
 
 #[tokio::test]
-#[serial]
 async fn get_item_return_none() {
-    let res = DATABASE
+    let base = temp_base();
+    let database = base.database();
+    let res = database
         .get_item::<SampleModel>("nonexistent_key")
         .await
         .unwrap();
-    assert!(matches!(res, None));
+    assert!(res.is_none());
+    base.close().await.unwrap();
 }
 
 #[tokio::test]
-#[serial]
 async fn delete_existent_item() {
-    setup_items().await;
-    DATABASE.delete_item(TEST_KEY).await.unwrap();
-    clean().await;
+    let base = temp_base();
+    let database = base.database();
+    setup_items(database).await;
+    database.delete_item(TEST_KEY).await.unwrap();
+
+    base.close().await.unwrap();
 }
 
 #[tokio::test]
-#[serial]
 async fn delete_non_existent_item() {
-    DATABASE.delete_item("nonexistent_key").await.unwrap();
+    let base = temp_base();
+    let database = base.database();
+    database.delete_item("nonexistent_key").await.unwrap();
+    base.close().await.unwrap();
 }
 
 #[tokio::test]
-#[serial]
 async fn insert_item() {
+    let base = temp_base();
+    let database = base.database();
     let item = SampleModel {
         key: "".into(),
         sample_field: "field_value".into(),
         some_field_2: 0,
     };
-    DATABASE.insert_item(&item).await.unwrap();
-    clean().await;
+    database.insert_item(&item).await.unwrap();
+
+    base.close().await.unwrap();
 }
 
 #[tokio::test]
-#[should_panic(expected = "Error occurred")]
-#[serial]
 async fn insert_item_with_existent_key() {
-    setup_items().await;
+    let base = temp_base();
+    let database = base.database();
+    setup_items(database).await;
     let item = SampleModel {
         key: TEST_KEY.into(),
         sample_field: "field_value".into(),
         some_field_2: 0,
     };
-    DATABASE.insert_item(&item).await.expect("Error occurred");
-    clean().await;
+    let error = database.insert_item(&item).await.unwrap_err();
+    assert_eq!(error.status_kind(), Some(&ResponseStatusKind::Conflict));
+
+    base.close().await.unwrap();
 }
 
 #[tokio::test]
-#[serial]
 async fn fetch_items() {
-    setup_items().await;
-    DATABASE
+    let base = temp_base();
+    let database = base.database();
+    setup_items(database).await;
+    database
         .fetch_items::<SampleModel>(None, None, None)
         .await
         .unwrap();
-    clean().await;
+
+    base.close().await.unwrap();
 }
 
 #[tokio::test]
-#[serial]
 async fn fetch_items_with_query() {
-    setup_items().await;
+    let base = temp_base();
+    let database = base.database();
+    setup_items(database).await;
 
-    async fn make_fetch(query: Query) -> FetchItems<SampleModel> {
-        DATABASE
+    async fn make_fetch(database: &Database, query: Query) -> FetchItems<SampleModel> {
+        database
             .fetch_items::<SampleModel>(None, None, Some(query))
             .await
             .unwrap()
@@ -178,81 +176,153 @@ async fn fetch_items_with_query() {
     // Test for several queries
 
     let query = Query::init().on("sample_field", Condition::equal("field1_val"));
-    assert_eq!(make_fetch(query).await.items.len(), 1);
+    assert_eq!(make_fetch(database, query).await.items.len(), 1);
 
     let query = Query::init().on("some_field_2", Condition::equal(10));
-    assert_eq!(make_fetch(query).await.items.len(), 1);
+    assert_eq!(make_fetch(database, query).await.items.len(), 1);
 
     let query = Query::init().on("sample_field", Condition::not_equal("field1_val"));
-    assert_eq!(make_fetch(query).await.items.len(), 2);
+    assert_eq!(make_fetch(database, query).await.items.len(), 2);
 
     let query = Query::init().on("some_field_2", Condition::greater_than(9));
-    assert_eq!(make_fetch(query).await.items.len(), 1);
+    assert_eq!(make_fetch(database, query).await.items.len(), 1);
 
     let query = Query::init().on("some_field_2", Condition::less_than(5));
-    assert_eq!(make_fetch(query).await.items.len(), 2);
+    assert_eq!(make_fetch(database, query).await.items.len(), 2);
 
     let query = Query::init().on("some_field_2", Condition::greater_than_or_equal(10));
-    assert_eq!(make_fetch(query).await.items.len(), 1);
+    assert_eq!(make_fetch(database, query).await.items.len(), 1);
 
     let query = Query::init().on("some_field_2", Condition::less_than_or_equal(0));
-    assert_eq!(make_fetch(query).await.items.len(), 2);
+    assert_eq!(make_fetch(database, query).await.items.len(), 2);
 
     let query = Query::init().on("sample_field", Condition::prefix("another"));
-    assert_eq!(make_fetch(query).await.items.len(), 1);
+    assert_eq!(make_fetch(database, query).await.items.len(), 1);
 
     let query = Query::init().on("some_field_2", Condition::range(-10, 0));
-    assert_eq!(make_fetch(query).await.items.len(), 2);
+    assert_eq!(make_fetch(database, query).await.items.len(), 2);
 
     let query = Query::init().on("sample_field", Condition::contains("yet"));
-    assert_eq!(make_fetch(query).await.items.len(), 1);
+    assert_eq!(make_fetch(database, query).await.items.len(), 1);
 
     let query = Query::init().on("sample_field", Condition::not_contains("yet"));
-    assert_eq!(make_fetch(query).await.items.len(), 2);
+    assert_eq!(make_fetch(database, query).await.items.len(), 2);
 
     let query = Query::init()
         .on("sample_field", Condition::not_contains("yet"))
         .either()
         .on("some_field_2", Condition::greater_than(-100));
 
-    assert_eq!(make_fetch(query).await.items.len(), 3);
+    assert_eq!(make_fetch(database, query).await.items.len(), 3);
 
-    clean().await;
+    base.close().await.unwrap();
 }
 
 #[tokio::test]
-#[serial]
 async fn fetch_items_with_limit() {
-    setup_items().await;
-    DATABASE
+    let base = temp_base();
+    let database = base.database();
+    setup_items(database).await;
+    database
         .fetch_items::<SampleModel>(Some(1), None, None)
         .await
         .unwrap();
-    clean().await;
+
+    base.close().await.unwrap();
+}
+
+// Not every deployment supports the order, so the test runs only with `DETA_FETCH_ORDER` set.
+#[tokio::test]
+async fn fetch_items_in_descending_order() {
+    if std::env::var_os("DETA_FETCH_ORDER").is_none() {
+        return;
+    }
+    let base = temp_base();
+    let database = base.database();
+    setup_items(database).await;
+
+    let options = FetchOptions {
+        order: Some(Order::Desc),
+        ..Default::default()
+    };
+    let fetched = database
+        .fetch_items_with::<SampleModel>(options)
+        .await
+        .unwrap();
+    let keys: Vec<_> = fetched.items.iter().map(|item| item.key.as_str()).collect();
+    let mut sorted = keys.clone();
+    sorted.sort_unstable_by(|a, b| b.cmp(a));
+    assert_eq!(keys, sorted);
+
+    base.close().await.unwrap();
 }
 
 #[tokio::test]
-#[serial]
 async fn update_item() {
-    setup_items().await;
+    let base = temp_base();
+    let database = base.database();
+    setup_items(database).await;
 
     let updates = Updates::init().add("some_field", Action::set("some_value"));
 
-    let update_result = DATABASE.update_item(TEST_KEY, updates).await.unwrap();
+    let update_result = database
+        .update_item(TEST_KEY, updates)
+        .await
+        .unwrap()
+        .expect("The item exists");
     let result_set_section = &update_result.set.expect("Set section is none");
     assert_eq!(result_set_section, &json!({ "some_field": "some_value" }));
 
-    clean().await;
+    base.close().await.unwrap();
 }
 
 #[tokio::test]
-#[should_panic(expected = "Error occurred")]
-#[serial]
 async fn update_nonexistent_item() {
+    let base = temp_base();
+    let database = base.database();
     let updates = Updates::init().add("some_field", Action::set("some_value"));
 
-    DATABASE
+    let result = database
         .update_item("nonexistent_key", updates)
         .await
-        .expect("Error occurred");
+        .unwrap();
+    assert!(result.is_none());
+    let item: Option<serde_json::Value> = database.get_item("nonexistent_key").await.unwrap();
+    assert!(item.is_none());
+    base.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn keys_with_url_characters() {
+    let base = temp_base();
+    let database = base.database();
+    let key = "a/b?c#d%2F e+ż";
+    database
+        .put_items(&[json!({ "key": key, "value": 1 })])
+        .await
+        .unwrap();
+
+    // The key sent percent-encoded in the URL is decoded by deta into the key of the stored item.
+    let item: Option<serde_json::Value> = database.get_item(key).await.unwrap();
+    assert_eq!(item.unwrap()["key"], key);
+    let updates = Updates::init().add("value", Action::set(2));
+    let updated = database.update_item(key, updates).await.unwrap();
+    assert_eq!(updated.expect("The item exists").key, key);
+    let item: serde_json::Value = database.get_item(key).await.unwrap().unwrap();
+    assert_eq!(item["value"].as_f64(), Some(2.0));
+
+    let deleted = database.delete_item(key).await.unwrap();
+    assert_eq!(deleted.key, key);
+    let item: Option<serde_json::Value> = database.get_item(key).await.unwrap();
+    assert!(item.is_none());
+
+    base.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn conformance() {
+    let base = temp_base();
+    let database = base.database();
+    conformance::base::run_all(database).await;
+    base.close().await.unwrap();
 }
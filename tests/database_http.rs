@@ -0,0 +1,438 @@
+//! The following tests check the exact requests sent by `Database` and the parsing of the responses
+//! against a local mock server, so they need neither credentials nor network access.
+
+mod support {
+    pub mod redirect;
+}
+
+use deta_rust::{
+    database::{
+        query::{Condition, Query},
+        updates::{Action, Updates},
+        Database, FetchOptions, ItemOutcome, Key, Order,
+    },
+    error::ResponseStatusKind,
+    serde_json::{json, Value},
+    DetaClient,
+};
+use std::sync::Arc;
+use support::redirect::Redirect;
+use wiremock::matchers::{body_json, header, method, path};
+use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+// ---------- CONFIG ----------
+
+const ITEMS_PATH: &str = "/v1/project/users/items";
+const QUERY_PATH: &str = "/v1/project/users/query";
+
+async fn setup() -> (MockServer, Database) {
+    let server = MockServer::start().await;
+    let redirect = Redirect::new(&server.uri());
+    let client = DetaClient::new("project_key");
+    let database = Database::new(&client, "users")
+        .unwrap()
+        .with_transport(Arc::new(redirect));
+    (server, database)
+}
+
+// Matches the headers sent with every request.
+fn api_mock(http_method: &str, request_path: &str) -> wiremock::MockBuilder {
+    Mock::given(method(http_method))
+        .and(path(request_path))
+        .and(header("x-api-key", "project_key"))
+}
+
+fn json_body(request: &Request) -> Value {
+    serde_json::from_slice(&request.body).unwrap()
+}
+
+// ---------- TESTS ----------
+
+#[tokio::test]
+async fn put_items_wraps_items() {
+    let (server, database) = setup().await;
+    api_mock("PUT", ITEMS_PATH)
+        .and(header("content-type", "application/json"))
+        .and(body_json(
+            json!({ "items": [{ "key": "a", "age": 1 }, { "age": 2 }] }),
+        ))
+        .respond_with(ResponseTemplate::new(207).set_body_json(json!({
+            "processed": { "items": [{ "key": "a", "age": 1 }] },
+            "failed": { "items": [{ "age": 2 }] }
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let result = database
+        .put_items(&[json!({ "key": "a", "age": 1 }), json!({ "age": 2 })])
+        .await
+        .unwrap();
+
+    assert_eq!(
+        result.processed.items,
+        vec![json!({ "key": "a", "age": 1 })]
+    );
+    assert!(result.has_failures());
+    assert_eq!(result.failed.unwrap().items, vec![json!({ "age": 2 })]);
+}
+
+#[tokio::test]
+async fn put_items_indexed_sends_batches_of_25() {
+    let (server, database) = setup().await;
+    api_mock("PUT", ITEMS_PATH)
+        .respond_with(|request: &Request| {
+            let items = json_body(request)["items"].clone();
+            ResponseTemplate::new(207).set_body_json(json!({ "processed": { "items": items } }))
+        })
+        .expect(2)
+        .mount(&server)
+        .await;
+
+    let items: Vec<Value> = (0..30)
+        .map(|i| json!({ "key": format!("key_{}", i) }))
+        .collect();
+    let outcomes = database.put_items_indexed(&items).await.unwrap();
+
+    assert_eq!(outcomes.len(), 30);
+    assert!(outcomes.iter().all(ItemOutcome::is_stored));
+    let requests = server.received_requests().await.unwrap();
+    let sizes: Vec<usize> = requests
+        .iter()
+        .map(|request| json_body(request)["items"].as_array().unwrap().len())
+        .collect();
+    assert_eq!(sizes, [25, 5]);
+}
+
+#[tokio::test]
+async fn put_raw_returns_keys() {
+    let (server, database) = setup().await;
+    api_mock("PUT", ITEMS_PATH)
+        .and(body_json(json!({ "items": [{ "name": "Anna" }] })))
+        .respond_with(ResponseTemplate::new(207).set_body_json(json!({
+            "processed": { "items": [{ "key": "generated", "name": "Anna" }] }
+        })))
+        .mount(&server)
+        .await;
+
+    let keys = database
+        .put_raw(vec![json!({ "name": "Anna" })])
+        .await
+        .unwrap();
+    assert_eq!(keys, ["generated"]);
+}
+
+#[tokio::test]
+async fn get_item_encodes_key() {
+    let (server, database) = setup().await;
+    api_mock("GET", "/v1/project/users/items/user%201")
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(json!({ "key": "user 1", "age": 30 })),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+    api_mock("GET", "/v1/project/users/items/za%C5%BC%C3%B3%C5%82%C4%87")
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "key": "zażółć" })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let item: Option<Value> = database.get_item("user 1").await.unwrap();
+    assert_eq!(item, Some(json!({ "key": "user 1", "age": 30 })));
+    let item: Option<Value> = database.get_item("zażółć").await.unwrap();
+    assert_eq!(item, Some(json!({ "key": "zażółć" })));
+}
+
+#[tokio::test]
+async fn item_methods_accept_validated_key() {
+    let (server, database) = setup().await;
+    api_mock("GET", "/v1/project/users/items/user_1")
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "key": "user_1" })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    api_mock("PATCH", "/v1/project/users/items/user_1")
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "key": "user_1" })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    api_mock("DELETE", "/v1/project/users/items/user_1")
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "key": "user_1" })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let key = Key::new("user_1").unwrap();
+    let item: Option<Value> = database.get_item(&key).await.unwrap();
+    assert_eq!(item, Some(json!({ "key": "user_1" })));
+    let updates = Updates::init().add("name", Action::set("Anna"));
+    database.update_item(&key, updates).await.unwrap();
+    database.delete_item(key).await.unwrap();
+}
+
+#[tokio::test]
+async fn get_item_returns_none_for_missing_item() {
+    let (server, database) = setup().await;
+    api_mock("GET", "/v1/project/users/items/missing")
+        .respond_with(ResponseTemplate::new(404).set_body_json(json!({ "key": "missing" })))
+        .mount(&server)
+        .await;
+
+    let item: Option<Value> = database.get_item("missing").await.unwrap();
+    assert!(item.is_none());
+}
+
+#[tokio::test]
+async fn delete_item_sends_delete() {
+    let (server, database) = setup().await;
+    api_mock("DELETE", "/v1/project/users/items/a")
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "key": "a" })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let result = database.delete_item("a").await.unwrap();
+    assert_eq!(result.key, "a");
+}
+
+#[tokio::test]
+async fn insert_item_wraps_item() {
+    let (server, database) = setup().await;
+    api_mock("POST", ITEMS_PATH)
+        .and(header("content-type", "application/json"))
+        .and(body_json(json!({ "item": { "key": "a", "age": 1 } })))
+        .respond_with(ResponseTemplate::new(201).set_body_json(json!({ "key": "a", "age": 1 })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let item = database
+        .insert_item(&json!({ "key": "a", "age": 1 }))
+        .await
+        .unwrap();
+    assert_eq!(item, json!({ "key": "a", "age": 1 }));
+}
+
+#[tokio::test]
+async fn insert_item_reports_conflict() {
+    let (server, database) = setup().await;
+    api_mock("POST", ITEMS_PATH)
+        .respond_with(
+            ResponseTemplate::new(409).set_body_json(json!({ "errors": ["Key already exists"] })),
+        )
+        .mount(&server)
+        .await;
+
+    let error = database
+        .insert_item(&json!({ "key": "a" }))
+        .await
+        .unwrap_err();
+    assert_eq!(error.status_kind(), Some(&ResponseStatusKind::Conflict));
+}
+
+#[tokio::test]
+async fn insert_raw_returns_key() {
+    let (server, database) = setup().await;
+    api_mock("POST", ITEMS_PATH)
+        .and(body_json(json!({ "item": { "name": "Anna" } })))
+        .respond_with(
+            ResponseTemplate::new(201).set_body_json(json!({ "key": "generated", "name": "Anna" })),
+        )
+        .mount(&server)
+        .await;
+
+    let (key, item) = database
+        .insert_raw(json!({ "name": "Anna" }))
+        .await
+        .unwrap();
+    assert_eq!(key, "generated");
+    assert_eq!(item["name"], "Anna");
+}
+
+#[tokio::test]
+async fn fetch_items_without_parameters() {
+    let (server, database) = setup().await;
+    api_mock("POST", QUERY_PATH)
+        .and(header("content-type", "application/json"))
+        .and(body_json(
+            json!({ "limit": null, "last": null, "query": null }),
+        ))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "paging": { "size": 1 },
+            "items": [{ "key": "a" }]
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let result = database
+        .fetch_items::<Value>(None, None, None)
+        .await
+        .unwrap();
+    assert_eq!(result.items, vec![json!({ "key": "a" })]);
+    assert_eq!(result.paging.size, 1);
+    assert!(result.paging.last.is_none());
+}
+
+#[tokio::test]
+async fn fetch_items_with_limit_last_and_query() {
+    let (server, database) = setup().await;
+    api_mock("POST", QUERY_PATH)
+        .and(body_json(json!({
+            "limit": 10,
+            "last": "a",
+            "query": [{ "age?gt": 18.0, "name?pfx": "A" }, { "admin": true }]
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "paging": { "size": 1, "last": "b" },
+            "items": [{ "key": "b" }]
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let query = Query::init()
+        .on("age", Condition::greater_than(18))
+        .on("name", Condition::prefix("A"))
+        .either()
+        .on("admin", Condition::equal(true));
+    let result = database
+        .fetch_items::<Value>(Some(10), Some("a"), Some(query))
+        .await
+        .unwrap();
+    assert_eq!(result.paging.last.as_deref(), Some("b"));
+}
+
+#[tokio::test]
+async fn fetch_items_with_order() {
+    let (server, database) = setup().await;
+    api_mock("POST", QUERY_PATH)
+        .and(body_json(json!({
+            "limit": 5,
+            "last": null,
+            "query": null,
+            "sort": "desc"
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "paging": { "size": 1 },
+            "items": [{ "key": "b" }]
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let options = FetchOptions {
+        limit: Some(5),
+        order: Some(Order::Desc),
+        ..Default::default()
+    };
+    let result = database.fetch_items_with::<Value>(options).await.unwrap();
+    assert_eq!(result.items, vec![json!({ "key": "b" })]);
+}
+
+#[tokio::test]
+async fn fetch_items_without_order_sends_no_sort() {
+    let (server, database) = setup().await;
+    api_mock("POST", QUERY_PATH)
+        .and(body_json(
+            json!({ "limit": null, "last": "a", "query": null }),
+        ))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "paging": { "size": 0 },
+            "items": []
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let options = FetchOptions {
+        last: Some("a".into()),
+        ..Default::default()
+    };
+    database.fetch_items_with::<Value>(options).await.unwrap();
+}
+
+#[tokio::test]
+async fn update_item_sends_patch() {
+    let (server, database) = setup().await;
+    let expected_body = json!({
+        "set": { "name": "Anna" },
+        "increment": { "age": 1.0 },
+        "append": { "tags": ["admin"] },
+        "prepend": null,
+        "delete": ["old"]
+    });
+    let mut response_body = expected_body.clone();
+    response_body["key"] = json!("a");
+    api_mock("PATCH", "/v1/project/users/items/a")
+        .and(header("content-type", "application/json"))
+        .and(body_json(expected_body))
+        .respond_with(ResponseTemplate::new(200).set_body_json(response_body))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let updates = Updates::init()
+        .add("name", Action::set("Anna"))
+        .add("age", Action::increment(1))
+        .add("tags", Action::append("admin"))
+        .add("old", Action::delete());
+    let result = database.update_item("a", updates).await.unwrap().unwrap();
+
+    assert_eq!(result.key, "a");
+    assert_eq!(result.set, Some(json!({ "name": "Anna" })));
+    assert!(result.prepend.is_none());
+}
+
+#[tokio::test]
+async fn update_item_returns_none_for_missing_item() {
+    let (server, database) = setup().await;
+    api_mock("PATCH", "/v1/project/users/items/missing")
+        .respond_with(
+            ResponseTemplate::new(404).set_body_json(json!({ "errors": ["Key not found"] })),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let updates = Updates::init().add("name", Action::set("Anna"));
+    let result = database.update_item("missing", updates).await.unwrap();
+    assert!(result.is_none());
+}
+
+#[tokio::test]
+async fn update_item_reports_missing_base() {
+    let (server, database) = setup().await;
+    api_mock("PATCH", "/v1/project/users/items/a")
+        .respond_with(
+            ResponseTemplate::new(404).set_body_json(json!({ "errors": ["Base not found"] })),
+        )
+        .mount(&server)
+        .await;
+
+    let updates = Updates::init().add("name", Action::set("Anna"));
+    let error = database.update_item("a", updates).await.unwrap_err();
+    assert!(error.is_target_not_found());
+    assert_eq!(
+        error.server_errors(),
+        Some(&["Base not found".to_owned()][..])
+    );
+}
+
+#[tokio::test]
+async fn malformed_response_is_reported() {
+    let (server, database) = setup().await;
+    api_mock("GET", "/v1/project/users/items/a")
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "key": 1 })))
+        .mount(&server)
+        .await;
+
+    #[derive(serde::Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Item {
+        key: String,
+    }
+    let error = database.get_item::<Item>("a").await.unwrap_err();
+    assert!(error.is_body_deserialization());
+}
@@ -1,57 +1,36 @@
 //! The following integration tests are designed to quickly make sure that SDK is compatible with the API.
 
-use deta_rust::{drive::Drive, DetaClient};
-use serial_test::serial;
+mod conformance {
+    pub mod drive;
+}
 
-// ---------- CONFIG ----------
+use deta_rust::{drive::Drive, testing::TempDrive, DetaClient};
 
-#[macro_use]
-extern crate lazy_static;
+// ---------- CONFIG ----------
 
-fn config() -> Drive {
+// Every test works with its own drive, emptied at the end, so the tests can run in parallel.
+fn temp_drive() -> TempDrive {
     dotenv::dotenv().ok();
     let api_key = std::env::var("API_KEY").expect("API_KEY is not provided");
-    let test_drive_name =
-        std::env::var("TEST_DRIVE_NAME").expect("TEST_DRIVE_NAME is not provided");
     let client = DetaClient::new(&api_key);
-    Drive::new(&client, &test_drive_name)
+    TempDrive::with_prefix(&client, "deta_rust_test").expect("Invalid drive name")
 }
 
-const FILE_NAME_1: &'static str = "test_file.txt";
-const FILE_NAME_2: &'static str = "another_test_file.txt";
-
-lazy_static! {
-    static ref DRIVE: Drive = config();
-}
+const FILE_NAME_1: &str = "test_file.txt";
+const FILE_NAME_2: &str = "another_test_file.txt";
 
 // ---------- HELPERS ----------
 
-/// Removes all uploaded files.
-async fn clean() {
-    let files = DRIVE
-        .list_files(None, None, None)
-        .await
-        .expect("List files went wrong during clean() performing");
-
-    if files.names.len() > 0 {
-        DRIVE
-            .delete_files(&files.names)
-            .await
-            .expect("Delete files went wrong during clean() performing");
-    }
-}
-
-async fn setup_files() {
-    clean().await;
+async fn setup_files(drive: &Drive) {
     let data_1 = b"data_1".to_vec();
     let data_2 = b"data_2".to_vec();
 
-    DRIVE
+    drive
         .put_file(FILE_NAME_1, data_1, None)
         .await
         .expect("Put file went wrong during setup_file() performing");
 
-    DRIVE
+    drive
         .put_file(FILE_NAME_2, data_2, None)
         .await
         .expect("Put file went wrong during setup_file() performing");
@@ -60,95 +39,229 @@ async fn setup_files() {
 // ---------- TESTS ----------
 
 #[tokio::test]
-#[serial]
 async fn put_file() {
-    setup_files().await;
-    clean().await;
+    let temp = temp_drive();
+    let drive = temp.drive();
+    setup_files(drive).await;
+    temp.close().await.unwrap();
 }
 
 #[tokio::test]
-#[serial]
 async fn put_file_greater_than_10_mb() {
+    let temp = temp_drive();
+    let drive = temp.drive();
     let data = vec![0u8; (1024 * 1024 * 10) + (1024 * 1024)]; // 11MB of data
-    DRIVE.put_file("big_file.dat", data, None).await.unwrap();
-    clean().await;
+    drive.put_file("big_file.dat", data, None).await.unwrap();
+    temp.close().await.unwrap();
 }
 
 #[tokio::test]
-#[serial]
 async fn get_file() {
-    setup_files().await;
+    let temp = temp_drive();
+    let drive = temp.drive();
+    setup_files(drive).await;
     // Testing get_file_as_u8_vec apply also to get_file_as_buffer
-    let data = DRIVE.get_file_as_u8_vec(FILE_NAME_1).await.unwrap();
-    assert!(matches!(data, Some(_)));
-    clean().await;
+    let data = drive.get_file_as_u8_vec(FILE_NAME_1).await.unwrap();
+    assert!(data.is_some());
+    temp.close().await.unwrap();
 }
 
 #[tokio::test]
-#[serial]
 async fn get_nonexistent_file() {
+    let temp = temp_drive();
+    let drive = temp.drive();
     // Testing get_file_as_u8_vec apply also to get_file_as_buffer
-    let data = DRIVE
+    let data = drive
         .get_file_as_u8_vec("nonexistent_file.txt")
         .await
         .unwrap();
-    assert!(matches!(data, None));
-    clean().await;
+    assert!(data.is_none());
+    temp.close().await.unwrap();
 }
 
 #[tokio::test]
-#[serial]
 async fn list_files() {
-    setup_files().await;
-    DRIVE.list_files(None, None, None).await.unwrap();
-    clean().await;
+    let temp = temp_drive();
+    let drive = temp.drive();
+    setup_files(drive).await;
+    drive.list_files(None, None, None).await.unwrap();
+    temp.close().await.unwrap();
 }
 
 #[tokio::test]
-#[serial]
 async fn list_files_with_limit() {
-    setup_files().await;
-    let files = DRIVE.list_files(Some(1), None, None).await.unwrap();
+    let temp = temp_drive();
+    let drive = temp.drive();
+    setup_files(drive).await;
+    let files = drive.list_files(Some(1), None, None).await.unwrap();
     assert_eq!(files.names.len(), 1);
-    clean().await;
+    temp.close().await.unwrap();
 }
 
 #[tokio::test]
-#[serial]
 async fn list_files_with_prefix() {
-    setup_files().await;
-    let files = DRIVE.list_files(None, Some("another"), None).await.unwrap();
+    let temp = temp_drive();
+    let drive = temp.drive();
+    setup_files(drive).await;
+    let files = drive.list_files(None, Some("another"), None).await.unwrap();
     assert_eq!(files.names.len(), 1);
-    clean().await;
+    temp.close().await.unwrap();
 }
 
 #[tokio::test]
-#[serial]
 async fn list_files_from_last_name() {
-    setup_files().await;
+    let temp = temp_drive();
+    let drive = temp.drive();
+    setup_files(drive).await;
 
-    let files = DRIVE.list_files(Some(1), None, None).await.unwrap();
+    let files = drive.list_files(Some(1), None, None).await.unwrap();
     assert_eq!(files.names.len(), 1);
 
     let first_last = &files.paging.unwrap().last.unwrap();
 
-    let next_files = DRIVE
+    let next_files = drive
         .list_files(Some(1), None, Some(first_last))
         .await
         .unwrap();
 
-    assert!(matches!(next_files.paging, None));
+    assert!(next_files.paging.is_none());
 
-    clean().await;
+    temp.close().await.unwrap();
 }
 
 #[tokio::test]
-#[serial]
 async fn delete_files() {
-    setup_files().await;
+    let temp = temp_drive();
+    let drive = temp.drive();
+    setup_files(drive).await;
     let files_to_delete: Vec<String> = vec![FILE_NAME_1.into(), "nonexistent_file.txt".into()];
-    DRIVE.delete_files(&files_to_delete).await.unwrap();
-    let files_after_delete = DRIVE.list_files(None, None, None).await.unwrap();
+    drive.delete_files(&files_to_delete).await.unwrap();
+    let files_after_delete = drive.list_files(None, None, None).await.unwrap();
     assert_eq!(files_after_delete.names.len(), 1);
-    clean().await;
+    temp.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn rename() {
+    let temp = temp_drive();
+    let drive = temp.drive();
+    setup_files(drive).await;
+    drive
+        .rename(FILE_NAME_1, "renamed_file.txt", false)
+        .await
+        .unwrap();
+
+    let old_file = drive.get_file_as_u8_vec(FILE_NAME_1).await.unwrap();
+    assert!(old_file.is_none());
+    let new_file = drive.get_file_as_u8_vec("renamed_file.txt").await.unwrap();
+    assert_eq!(new_file, Some(b"data_1".to_vec()));
+
+    temp.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn rename_file_greater_than_10_mb() {
+    let temp = temp_drive();
+    let drive = temp.drive();
+    let data = vec![7u8; (1024 * 1024 * 10) + (1024 * 1024)]; // 11MB of data
+    drive
+        .put_file("big_file.dat", data.clone(), None)
+        .await
+        .unwrap();
+    drive
+        .rename("big_file.dat", "renamed_big_file.dat", false)
+        .await
+        .unwrap();
+
+    let new_file = drive
+        .get_file_as_u8_vec("renamed_big_file.dat")
+        .await
+        .unwrap();
+    assert_eq!(new_file, Some(data));
+
+    temp.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn rename_without_overwrite() {
+    let temp = temp_drive();
+    let drive = temp.drive();
+    setup_files(drive).await;
+    let result = drive.rename(FILE_NAME_1, FILE_NAME_2, false).await;
+    assert!(result.is_err());
+
+    // Nothing should be changed
+    let data = drive.get_file_as_u8_vec(FILE_NAME_2).await.unwrap();
+    assert_eq!(data, Some(b"data_2".to_vec()));
+
+    temp.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn copy_to() {
+    let temp = temp_drive();
+    let drive = temp.drive();
+    let second_temp = temp_drive();
+    let second_drive = second_temp.drive();
+    setup_files(drive).await;
+
+    let stats = drive.copy_to(second_drive, None, false).await.unwrap();
+    assert_eq!(stats.copied.len(), 2);
+    assert!(stats.failed.is_empty());
+
+    let data = second_drive.get_file_as_u8_vec(FILE_NAME_1).await.unwrap();
+    assert_eq!(data, Some(b"data_1".to_vec()));
+
+    second_temp.close().await.unwrap();
+    temp.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn copy_to_without_overwrite() {
+    let temp = temp_drive();
+    let drive = temp.drive();
+    let second_temp = temp_drive();
+    let second_drive = second_temp.drive();
+    setup_files(drive).await;
+    second_drive
+        .put_file(FILE_NAME_1, b"existing".to_vec(), None)
+        .await
+        .unwrap();
+
+    let progress = std::sync::Mutex::new(vec![]);
+    let stats = drive
+        .copy_to_with_progress(second_drive, None, false, |progress_info| {
+            progress.lock().unwrap().push(progress_info.completed)
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(stats.copied, vec![FILE_NAME_2.to_owned()]);
+    assert_eq!(stats.skipped, vec![FILE_NAME_1.to_owned()]);
+    assert_eq!(progress.into_inner().unwrap(), vec![1, 2]);
+
+    let data = second_drive.get_file_as_u8_vec(FILE_NAME_1).await.unwrap();
+    assert_eq!(data, Some(b"existing".to_vec()));
+
+    second_temp.close().await.unwrap();
+    temp.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn put_and_get_text() {
+    let temp = temp_drive();
+    let drive = temp.drive();
+    let text = "Zażółć gęślą jaźń 🦀";
+    drive.put_text("text_file.txt", text).await.unwrap();
+    let downloaded = drive.get_text("text_file.txt").await.unwrap();
+    assert_eq!(downloaded.as_deref(), Some(text));
+    temp.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn conformance() {
+    let temp = temp_drive();
+    let drive = temp.drive();
+    conformance::drive::run_all(drive).await;
+    temp.close().await.unwrap();
 }
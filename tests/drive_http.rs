@@ -0,0 +1,335 @@
+//! The following tests check the exact requests sent by `Drive` and the parsing of the responses
+//! against a local mock server, so they need neither credentials nor network access.
+//! They also cover failures the live service can't be made to produce on demand.
+
+mod support {
+    pub mod redirect;
+}
+
+use deta_rust::{
+    drive::{Drive, PutFileOptions, PutFileResult},
+    serde_json::json,
+    DetaClient,
+};
+use std::sync::Arc;
+use support::redirect::Redirect;
+use wiremock::matchers::{body_bytes, body_json, header, method, path, query_param};
+use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+// ---------- CONFIG ----------
+
+const FILES_PATH: &str = "/v1/project/photos/files";
+const UPLOADS_PATH: &str = "/v1/project/photos/uploads";
+const UPLOAD_PATH: &str = "/v1/project/photos/uploads/upload_1";
+const PARTS_PATH: &str = "/v1/project/photos/uploads/upload_1/parts";
+const MB: usize = 1024 * 1024;
+
+async fn setup() -> (MockServer, Drive) {
+    let server = MockServer::start().await;
+    let redirect = Redirect::new(&server.uri());
+    let client = DetaClient::new("project_key");
+    let drive = Drive::new(&client, "photos")
+        .unwrap()
+        .with_transport(Arc::new(redirect));
+    (server, drive)
+}
+
+// Matches the headers sent with every request.
+fn api_mock(http_method: &str, request_path: &str) -> wiremock::MockBuilder {
+    Mock::given(method(http_method))
+        .and(path(request_path))
+        .and(header("x-api-key", "project_key"))
+}
+
+fn file_body(name: &str) -> serde_json::Value {
+    json!({ "name": name, "project_id": "project", "drive_name": "photos" })
+}
+
+fn upload_body(name: &str) -> serde_json::Value {
+    let mut body = file_body(name);
+    body["upload_id"] = json!("upload_1");
+    body
+}
+
+// Mounts the start and the end of the chunked upload of `big.dat`.
+async fn mount_chunked_upload(server: &MockServer) {
+    api_mock("POST", UPLOADS_PATH)
+        .and(query_param("name", "big.dat"))
+        .respond_with(ResponseTemplate::new(202).set_body_json(upload_body("big.dat")))
+        .mount(server)
+        .await;
+    api_mock("PATCH", UPLOAD_PATH)
+        .and(query_param("name", "big.dat"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(upload_body("big.dat")))
+        .mount(server)
+        .await;
+}
+
+// Uploads 5MB and 3 bytes in parts of 5MB, so the second part is the last one.
+async fn put_two_parts(drive: &Drive) -> deta_rust::error::Result<PutFileResult> {
+    let options = PutFileOptions {
+        chunk_size: 5 * MB,
+        chunked_threshold: 5 * MB,
+        ..Default::default()
+    };
+    drive
+        .put_file_with("big.dat", vec![7; 5 * MB + 3], options)
+        .await
+}
+
+fn summary(request: &Request) -> String {
+    match request.url.query() {
+        Some(query) => format!("{} {}?{}", request.method, request.url.path(), query),
+        None => format!("{} {}", request.method, request.url.path()),
+    }
+}
+
+// ---------- TESTS ----------
+
+#[tokio::test]
+async fn put_file_sends_single_request() {
+    let (server, drive) = setup().await;
+    api_mock("POST", FILES_PATH)
+        .and(query_param("name", "notes/today.txt"))
+        .and(header("content-type", "text/plain"))
+        .and(header("content-length", "5"))
+        .and(body_bytes(b"hello".to_vec()))
+        .respond_with(ResponseTemplate::new(201).set_body_json(file_body("notes/today.txt")))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let result = drive
+        .put_file("notes/today.txt", b"hello".to_vec(), Some("text/plain"))
+        .await
+        .unwrap();
+
+    match result {
+        PutFileResult::SinglePut(file) => assert_eq!(file.name, "notes/today.txt"),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+}
+
+#[tokio::test]
+async fn put_file_at_threshold_is_single() {
+    let (server, drive) = setup().await;
+    api_mock("POST", FILES_PATH)
+        .respond_with(ResponseTemplate::new(201).set_body_json(file_body("small.dat")))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let options = PutFileOptions {
+        chunked_threshold: 10,
+        ..Default::default()
+    };
+    drive
+        .put_file_with("small.dat", vec![0; 10], options)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn put_file_over_threshold_is_chunked() {
+    let (server, drive) = setup().await;
+    mount_chunked_upload(&server).await;
+    api_mock("POST", PARTS_PATH)
+        .and(query_param("name", "big.dat"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(upload_body("big.dat")))
+        .mount(&server)
+        .await;
+
+    let result = put_two_parts(&drive).await.unwrap();
+
+    assert!(
+        matches!(result, PutFileResult::ChunkedUpload(ref upload) if upload.upload_id == "upload_1")
+    );
+    let requests = server.received_requests().await.unwrap();
+    let summaries: Vec<String> = requests.iter().map(summary).collect();
+    assert_eq!(
+        summaries,
+        [
+            format!("POST {}?name=big.dat", UPLOADS_PATH),
+            format!("POST {}?name=big.dat&part=1", PARTS_PATH),
+            format!("POST {}?name=big.dat&part=2", PARTS_PATH),
+            format!("PATCH {}?name=big.dat", UPLOAD_PATH),
+        ]
+    );
+    assert_eq!(requests[1].body.len(), 5 * MB);
+    assert_eq!(requests[2].body, [7; 3]);
+}
+
+#[tokio::test]
+async fn failed_part_aborts_upload() {
+    let (server, drive) = setup().await;
+    mount_chunked_upload(&server).await;
+    api_mock("POST", PARTS_PATH)
+        .and(query_param("part", "1"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(upload_body("big.dat")))
+        .mount(&server)
+        .await;
+    api_mock("POST", PARTS_PATH)
+        .and(query_param("part", "2"))
+        .respond_with(
+            ResponseTemplate::new(500).set_body_json(json!({ "errors": ["Internal error"] })),
+        )
+        .mount(&server)
+        .await;
+    api_mock("DELETE", UPLOAD_PATH)
+        .and(query_param("name", "big.dat"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(upload_body("big.dat")))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let error = put_two_parts(&drive).await.unwrap_err();
+
+    let context = error.upload_context().unwrap();
+    assert_eq!(context.upload_id, "upload_1");
+    assert_eq!(context.part, Some(2));
+    assert!(error.cleanup_failure().is_none());
+    // The upload is never ended after a failed part.
+    let requests = server.received_requests().await.unwrap();
+    assert!(!requests
+        .iter()
+        .any(|request| summary(request).starts_with("PATCH")));
+}
+
+#[tokio::test]
+async fn failed_abort_is_attached_to_error() {
+    let (server, drive) = setup().await;
+    mount_chunked_upload(&server).await;
+    api_mock("POST", PARTS_PATH)
+        .respond_with(
+            ResponseTemplate::new(500).set_body_json(json!({ "errors": ["Internal error"] })),
+        )
+        .mount(&server)
+        .await;
+    api_mock("DELETE", UPLOAD_PATH)
+        .respond_with(
+            ResponseTemplate::new(503).set_body_json(json!({ "errors": ["Unavailable"] })),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let error = put_two_parts(&drive).await.unwrap_err();
+
+    assert_eq!(error.upload_context().unwrap().part, Some(1));
+    let cleanup = error.cleanup_failure().unwrap();
+    assert_eq!(cleanup.upload_id, "upload_1");
+    assert_eq!(
+        cleanup.error.server_errors(),
+        Some(&["Unavailable".to_owned()][..])
+    );
+}
+
+#[tokio::test]
+async fn list_files_sends_query_params() {
+    let (server, drive) = setup().await;
+    api_mock("GET", FILES_PATH)
+        .and(query_param("limit", "2"))
+        .and(query_param("prefix", "docs/"))
+        .and(query_param("last", "docs/a.txt"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "paging": { "size": 2, "last": "docs/c.txt" },
+            "names": ["docs/b.txt", "docs/c.txt"]
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let files = drive
+        .list_files(Some(2), Some("docs/"), Some("docs/a.txt"))
+        .await
+        .unwrap();
+
+    assert_eq!(files.names, ["docs/b.txt", "docs/c.txt"]);
+    assert_eq!(files.paging.unwrap().last.as_deref(), Some("docs/c.txt"));
+}
+
+#[tokio::test]
+async fn list_files_without_params() {
+    let (server, drive) = setup().await;
+    api_mock("GET", FILES_PATH)
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "names": ["a.txt"] })))
+        .mount(&server)
+        .await;
+
+    let files = drive.list_files(None, None, None).await.unwrap();
+
+    assert_eq!(files.names, ["a.txt"]);
+    assert!(files.paging.is_none());
+    let requests = server.received_requests().await.unwrap();
+    assert_eq!(requests[0].url.query_pairs().count(), 0);
+}
+
+#[tokio::test]
+async fn delete_files_sends_names() {
+    let (server, drive) = setup().await;
+    api_mock("DELETE", FILES_PATH)
+        .and(header("content-type", "application/json"))
+        .and(body_json(json!({ "names": ["a.txt", "b.txt"] })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "deleted": ["a.txt"],
+            "failed": { "b.txt": "Access denied" }
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let result = drive
+        .delete_files(&["a.txt".into(), "b.txt".into()])
+        .await
+        .unwrap();
+
+    assert_eq!(result.deleted, ["a.txt"]);
+    assert_eq!(
+        result.failure_reason("b.txt").as_deref(),
+        Some("Access denied")
+    );
+}
+
+#[tokio::test]
+async fn get_file_returns_none_for_missing_file() {
+    let (server, drive) = setup().await;
+    api_mock("GET", "/v1/project/photos/files/download")
+        .and(query_param("name", "missing.txt"))
+        .respond_with(
+            ResponseTemplate::new(404).set_body_json(json!({ "errors": ["File not found"] })),
+        )
+        .mount(&server)
+        .await;
+
+    let data = drive.get_file_as_u8_vec("missing.txt").await.unwrap();
+    assert!(data.is_none());
+}
+
+#[tokio::test]
+async fn get_file_downloads_content() {
+    let (server, drive) = setup().await;
+    api_mock("GET", "/v1/project/photos/files/download")
+        .and(query_param("name", "a b.txt"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(b"content".to_vec()))
+        .mount(&server)
+        .await;
+
+    let data = drive.get_file_as_u8_vec("a b.txt").await.unwrap();
+    assert_eq!(data.as_deref(), Some(&b"content"[..]));
+}
+
+#[tokio::test]
+async fn html_error_body_is_reported() {
+    let (server, drive) = setup().await;
+    api_mock("GET", "/v1/project/photos/files/download")
+        .respond_with(
+            ResponseTemplate::new(502).set_body_raw("<html>Bad gateway</html>", "text/html"),
+        )
+        .mount(&server)
+        .await;
+
+    let error = drive.get_file_as_u8_vec("a.txt").await.unwrap_err();
+
+    assert!(error.is_unexpected_content_type());
+    assert!(error.is_retryable());
+}
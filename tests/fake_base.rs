@@ -0,0 +1,15 @@
+//! Checks that the fake base from `deta_rust::testing` passes the conformance scenarios
+//! which run against the real API in the `database` tests.
+
+mod conformance {
+    pub mod base;
+}
+
+use deta_rust::testing::FakeBase;
+
+#[tokio::test]
+async fn fake_base_conforms() {
+    let fake = FakeBase::new();
+    conformance::base::run_all(&fake.database()).await;
+    assert!(fake.items().is_empty());
+}
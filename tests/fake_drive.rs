@@ -0,0 +1,16 @@
+//! Checks that the fake drive from `deta_rust::testing` passes the conformance scenarios
+//! which run against the real API in the `drive` tests.
+
+mod conformance {
+    pub mod drive;
+}
+
+use deta_rust::testing::FakeDrive;
+
+#[tokio::test]
+async fn fake_drive_conforms() {
+    let fake = FakeDrive::new();
+    conformance::drive::run_all(&fake.drive()).await;
+    assert!(fake.files().is_empty());
+    assert_eq!(fake.pending_uploads(), 0);
+}
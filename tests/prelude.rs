@@ -0,0 +1,36 @@
+//! Checks that the prelude can be imported with every combination of the features
+//! and brings the commonly used types into scope.
+
+use deta_rust::prelude::*;
+
+// The alias must not get in the way of results with other errors.
+fn other_error() -> Result<(), std::fmt::Error> {
+    Ok(())
+}
+
+#[test]
+fn prelude_is_importable() {
+    let client = DetaClient::new("project_key");
+    assert_eq!(client.project_id(), "project");
+    let result: Result<()> = other_error().map_err(|_| unreachable!());
+    let _: Option<Error> = result.err();
+
+    #[cfg(feature = "base")]
+    {
+        let database: std::sync::Arc<dyn DatabaseOps> =
+            std::sync::Arc::new(Database::new(&client, "users").unwrap());
+        let _pending = database.get_item::<serde_json::Value>("a");
+        let _ = Query::init().on("age", Condition::greater_than(18));
+        let _ = Updates::init().add("age", Action::increment(1));
+    }
+
+    #[cfg(feature = "drive")]
+    {
+        let drive: std::sync::Arc<dyn DriveOps> =
+            std::sync::Arc::new(Drive::new(&client, "photos").unwrap());
+        let _pending = drive.put_text("a.txt", "a");
+        let _ = PutFileOptions::default();
+        let _: Option<PutFileResult> = None;
+        let _ = client.with_transfer_limit(TransferLimit::default());
+    }
+}
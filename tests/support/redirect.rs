@@ -0,0 +1,31 @@
+//! A transport pointing the requests of the services at a local mock server.
+
+use deta_rust::transport::{HttpTransport, Transport, TransportFuture};
+
+/// Sends the requests to the mock server instead of deta, keeping their paths and query params.
+pub struct Redirect {
+    server: reqwest::Url,
+    transport: HttpTransport,
+}
+
+impl Redirect {
+    pub fn new(server_uri: &str) -> Self {
+        Self {
+            server: server_uri.parse().expect("The mock server uri is valid"),
+            transport: HttpTransport::default(),
+        }
+    }
+}
+
+impl Transport for Redirect {
+    fn execute(&self, mut request: reqwest::Request) -> TransportFuture<'_> {
+        let url = request.url_mut();
+        url.set_scheme(self.server.scheme())
+            .expect("The scheme of the mock server is valid");
+        url.set_host(self.server.host_str())
+            .expect("The host of the mock server is valid");
+        url.set_port(self.server.port())
+            .expect("The port of the mock server is valid");
+        self.transport.execute(request)
+    }
+}